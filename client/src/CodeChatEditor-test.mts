@@ -61,31 +61,31 @@ window.CodeChatEditor_test = () => {
     suite("CodeChatEditor.mts", function () {
         suite("codechat_html_to_markdown", function () {
             test("Translate an empty comment", async function () {
-                const db: [DocBlockJSON] = [[0, 0, "", "//", ""]];
+                const db: [DocBlockJSON] = [[0, 0, "", "//", "", ""]];
                 const source = {
                     doc_blocks: db,
                 };
                 await codechat_html_to_markdown(source);
                 assert.deepEqual(source, {
-                    doc_blocks: [[0, 0, "", "//", "\n"]],
+                    doc_blocks: [[0, 0, "", "//", "\n", ""]],
                 });
             });
 
             test("Translate non-breaking space", async function () {
-                const db: [DocBlockJSON] = [[0, 0, "", "//", "&nbsp;"]];
+                const db: [DocBlockJSON] = [[0, 0, "", "//", "&nbsp;", ""]];
                 const source = {
                     doc_blocks: db,
                 };
                 await codechat_html_to_markdown(source);
                 assert.deepEqual(source, {
-                    doc_blocks: [[0, 0, "", "//", "\n"]],
+                    doc_blocks: [[0, 0, "", "//", "\n", ""]],
                 });
             });
 
             test("Translate two empty comments", async function () {
                 const db: DocBlockJSON[] = [
-                    [0, 0, "", "//", ""],
-                    [2, 2, "", "//", ""],
+                    [0, 0, "", "//", "", ""],
+                    [2, 2, "", "//", "", ""],
                 ];
                 const source = {
                     doc_blocks: db,
@@ -93,16 +93,16 @@ window.CodeChatEditor_test = () => {
                 await codechat_html_to_markdown(source);
                 assert.deepEqual(source, {
                     doc_blocks: [
-                        [0, 0, "", "//", "\n"],
-                        [2, 2, "", "//", "\n"],
+                        [0, 0, "", "//", "\n", ""],
+                        [2, 2, "", "//", "\n", ""],
                     ],
                 });
             });
 
             test("Translate unclosed HTML", async function () {
                 const db: DocBlockJSON[] = [
-                    [0, 0, "", "//", "<h1><u>A<u></h1>\n"],
-                    [2, 2, "", "//", "<h2>Ax</h2>"],
+                    [0, 0, "", "//", "<h1><u>A<u></h1>\n", ""],
+                    [2, 2, "", "//", "<h2>Ax</h2>", ""],
                 ];
                 const source = {
                     doc_blocks: db,
@@ -110,8 +110,8 @@ window.CodeChatEditor_test = () => {
                 await codechat_html_to_markdown(source);
                 assert.deepEqual(source, {
                     doc_blocks: [
-                        [0, 0, "", "//", "# <u>A<u></u></u>\n\n<u><u>\n"],
-                        [2, 2, "", "//", "<h2>Ax</h2></u></u>\n"],
+                        [0, 0, "", "//", "# <u>A<u></u></u>\n\n<u><u>\n", ""],
+                        [2, 2, "", "//", "<h2>Ax</h2></u></u>\n", ""],
                     ],
                 });
             });
@@ -43,6 +43,7 @@
 //
 // #### Third-party
 import { EditorView, ViewUpdate } from "@codemirror/view";
+import { Transaction } from "@codemirror/state";
 import prettier from "prettier/esm/standalone.mjs";
 import parserMarkdown from "prettier/esm/parser-markdown.mjs";
 import TurndownService from "./turndown/turndown.browser.es.js";
@@ -50,11 +51,17 @@ import { gfm } from "./turndown/turndown-plugin-gfm.browser.es.js";
 
 // #### Local
 import {
+    CodeMirror_apply_diff,
+    CodeMirror_get_scroll,
+    CodeMirror_get_selection,
+    CodeMirror_set_scroll,
+    CodeMirror_set_selection,
     CodeMirror_load,
     CodeMirror_save,
     addDocBlock,
     updateDocBlock,
 } from "./CodeMirror-integration.mjs";
+import { diff_code_mirror } from "./Diff.mjs";
 import "./EditorComponents.mjs";
 import "./graphviz-webcomponent-setup.mts";
 // This must be imported _after_ the previous setup import, so it's placed here,
@@ -86,8 +93,15 @@ export const page_init = () => {
     on_dom_content_loaded(async () => {
         document
             .getElementById("CodeChat-save-button")!
-            .addEventListener("click", on_save);
+            .addEventListener("click", () => on_save());
         document.body.addEventListener("keydown", on_keydown);
+        document
+            .getElementById("CodeChat-body")!
+            .addEventListener("scroll", () => {
+                if (!is_remote_scroll) {
+                    startPositionTimer();
+                }
+            });
 
         // Intercept links in this document to save before following the link.
         /// @ts-ignore
@@ -103,6 +117,7 @@ export const page_init = () => {
 
         window.CodeChatEditor = {
             open_lp,
+            update,
         };
     });
 };
@@ -127,8 +142,25 @@ const on_dom_content_loaded = (on_load_func: () => void) => {
 // see [SourceFileMetadata](../../server/src/webserver.rs#SourceFileMetadata).
 let current_metadata: {
     mode: string;
+    backlinks?: Backlink[];
 };
 
+// The version of the current file's contents; see
+// [new_version](../../server/src/processing/diff.rs#new_version).
+let current_version = 0;
+
+// The contents this Client last saved and their version, if the Server still
+// has them. Saves send the changes to these contents.
+let last_saved:
+    | {
+          version: number;
+          source: CodeChatForWeb["source"];
+      }
+    | undefined;
+
+// True if the current file contains edits which this Client hasn't yet saved.
+let is_dirty = false;
+
 // True if this is a CodeChat Editor document (not a source file).
 const is_doc_only = () => {
     return current_metadata["mode"] === "markdown";
@@ -157,7 +189,11 @@ export const open_lp = async (
     // A data structure provided by the server, containing the source and
     // associated metadata. See [`AllSource`](#AllSource).
     all_source: CodeChatForWeb,
+    // The version of these contents.
+    version: number,
 ) => {
+    current_version = version;
+    last_saved = undefined;
     // Use
     // [URLSearchParams](https://developer.mozilla.org/en-US/docs/Web/API/URLSearchParams)
     // to parse out the search parameters of this window's URL.
@@ -217,6 +253,8 @@ export const open_lp = async (
         ]);
     }
 
+    show_backlinks(current_metadata.backlinks ?? []);
+
     // <a id="CodeChatEditor_test"></a>If tests should be run, then the
     // [following global variable](CodeChatEditor-test.mts#CodeChatEditor_test)
     // is function that runs them.
@@ -225,7 +263,123 @@ export const open_lp = async (
     }
 };
 
-const save_lp = async () => {
+// Apply an update from the Server to the current file's contents and cursor.
+// If this Client has unsaved edits, save them instead of applying the update;
+// the Server merges these edits with its contents, then sends the merged
+// contents. Return an error message if the update wasn't applied.
+const update = (current_update: UpdateMessageContents) => {
+    const set_cursor = () => {
+        if (current_metadata === undefined || is_doc_only()) {
+            return;
+        }
+        const selection = current_update.cursor_position?.CodeMirror;
+        if (selection !== undefined) {
+            CodeMirror_set_selection(selection);
+        }
+        const scroll_position = current_update.scroll_position?.CodeMirror;
+        if (scroll_position !== undefined) {
+            // Don't send this scroll position back to the Server. Scroll
+            // events are dispatched before the next animation frame.
+            is_remote_scroll = true;
+            CodeMirror_set_scroll(scroll_position);
+            requestAnimationFrame(() => (is_remote_scroll = false));
+        }
+    };
+    const contents = current_update.contents;
+    if (contents === undefined) {
+        set_cursor();
+        return null;
+    }
+    if (is_dirty) {
+        on_save();
+        return "Conflict: the Client has unsaved changes.";
+    }
+    if (contents.Snapshot !== undefined) {
+        open_lp(contents.Snapshot, current_update.version).then(set_cursor);
+        return null;
+    }
+    const err = apply_diff(
+        contents.Diff!,
+        current_update.base_version,
+        current_update.version,
+    );
+    if (err === null) {
+        set_cursor();
+    }
+    return err;
+};
+
+// Apply a diff from the Server to the current file's contents. Return an error
+// message if the diff doesn't apply to the current version; the Server then
+// sends a snapshot instead.
+const apply_diff = (
+    diff: CodeChatForWebDiff,
+    base_version: number | null,
+    version: number,
+) => {
+    if (current_metadata === undefined || base_version !== current_version) {
+        return `Out of sync: no contents for version ${base_version}.`;
+    }
+    if (is_doc_only()) {
+        return "Diffs don't apply to a document.";
+    }
+    const err = CodeMirror_apply_diff(diff.doc, diff.doc_blocks);
+    if (err !== null) {
+        return err;
+    }
+    current_metadata = diff.metadata;
+    show_backlinks(current_metadata.backlinks ?? []);
+    current_version = version;
+    last_saved = undefined;
+    return null;
+};
+
+// Show the hyperlinks which refer to this file, grouped by the heading or
+// anchor they refer to.
+const show_backlinks = (backlinks: Backlink[]) => {
+    const codechat_bottom = document.getElementById("CodeChat-bottom");
+    if (codechat_bottom === null) {
+        return;
+    }
+    codechat_bottom.replaceChildren();
+    if (backlinks.length === 0) {
+        return;
+    }
+    const heading = document.createElement("h2");
+    heading.textContent = "Referenced by";
+    const list = document.createElement("ul");
+    list.id = "CodeChat-backlinks";
+    for (const backlink of backlinks) {
+        const href =
+            encodeURI(backlink.file) +
+            (backlink.source_anchor === null
+                ? ""
+                : `#${encodeURIComponent(backlink.source_anchor)}`);
+        const item = document.createElement("li");
+        const target = document.createElement("code");
+        target.textContent =
+            backlink.anchor === null ? "(this file)" : `#${backlink.anchor}`;
+        const link = document.createElement("a");
+        link.href = href;
+        link.textContent = backlink.file;
+        const context = document.createElement("span");
+        context.className = "CodeChat-backlink-context";
+        context.textContent = `…${backlink.pre_snippet}${backlink.post_snippet}…`;
+        item.append(target, " ← ", link, ": ", context);
+        list.append(item);
+    }
+    codechat_bottom.append(heading, list);
+};
+
+// Return an update containing the current file's contents. If `is_forced`,
+// these contents replace the Server's contents, even if the Server's contents
+// changed since this Client last saved.
+const save_lp = async (is_forced: boolean) => {
+    // Updates may arrive while the contents are converted below; record the
+    // version these edits are based on now.
+    const base_version = current_version;
+    const base = last_saved;
+    is_dirty = false;
     /// @ts-expect-error
     let source: CodeChatForWeb["source"] = {};
     if (is_doc_only()) {
@@ -240,14 +394,36 @@ const save_lp = async () => {
         await codechat_html_to_markdown(source);
     }
 
+    // The Server provides backlinks; don't send them back.
+    const metadata = { mode: current_metadata.mode };
+    // Send only the changes since the last save if the Server has the saved
+    // contents. Documents are always sent in full.
+    const version = Math.random();
     let update: UpdateMessageContents = {
-        contents: {
-            metadata: current_metadata,
-            source,
-        },
-        scroll_position: undefined,
-        cursor_position: undefined,
+        path: undefined,
+        contents:
+            base !== undefined &&
+            base.version === base_version &&
+            !is_forced &&
+            !is_doc_only()
+                ? {
+                      Diff: {
+                          metadata,
+                          ...diff_code_mirror(base.source, source),
+                      },
+                  }
+                : { Snapshot: { metadata, source } },
+        base_version: is_forced ? null : base_version,
+        version,
+        cursor_position: is_doc_only()
+            ? undefined
+            : { CodeMirror: CodeMirror_get_selection() },
+        scroll_position: is_doc_only()
+            ? undefined
+            : { CodeMirror: CodeMirror_get_scroll() },
     };
+    current_version = version;
+    last_saved = { version, source };
     return update;
 };
 
@@ -272,12 +448,38 @@ const on_keydown = (event: KeyboardEvent) => {
     }
 };
 
-// Save CodeChat Editor contents.
-const on_save = async () => {
+// Save CodeChat Editor contents. If `is_forced`, replace the Server's contents
+// with these contents.
+const on_save = async (is_forced = false) => {
     // <a id="save"></a>Save the provided contents back to the filesystem, by
     // sending an update message over the websocket.
     const webSocketComm = parent.window.CodeChatEditorFramework.webSocketComm;
-    webSocketComm.send_message({ Update: await save_lp() });
+    const update = await save_lp(is_forced);
+    webSocketComm.send_message({ Update: update }, (err: string | null) => {
+        if (err === null) {
+            return;
+        }
+        // If these changes conflict with changes made elsewhere, let the user
+        // choose which to keep.
+        if (err.startsWith("Conflict")) {
+            if (
+                window.confirm(
+                    `${err}\n\nSelect OK to replace the file with your ` +
+                        "edits, or Cancel to discard your edits and reload " +
+                        "the file.",
+                )
+            ) {
+                on_save(true);
+            } else {
+                webSocketComm.current_file(new URL(window.location.href));
+            }
+        } else if (update.contents?.Diff !== undefined) {
+            // If the Server couldn't apply these changes, send all the
+            // contents instead.
+            last_saved = undefined;
+            on_save();
+        }
+    });
 };
 
 const codechat_html_to_markdown = async (source: any) => {
@@ -333,6 +535,7 @@ let autosaveEnabled = true;
 
 // Schedule an autosave; call this whenever the document is modified.
 const startAutosaveTimer = () => {
+    is_dirty = true;
     if (!autosaveEnabled) {
         return;
     }
@@ -346,6 +549,42 @@ const startAutosaveTimer = () => {
     autosaveTimeoutId = window.setTimeout(on_save, 1000);
 };
 
+// ### Cursor and scroll synchronization
+//
+// The ID of the timer which sends the cursor and scroll position when it
+// expires.
+let positionTimeoutId: null | number = null;
+
+// True while scrolling to a position provided by the Server.
+let is_remote_scroll = false;
+
+// Schedule sending the cursor and scroll position; call this whenever either
+// changes.
+const startPositionTimer = () => {
+    if (positionTimeoutId !== null) {
+        clearTimeout(positionTimeoutId);
+    }
+    positionTimeoutId = window.setTimeout(send_position, 300);
+};
+
+// Send the cursor and scroll position, without the file's contents.
+const send_position = () => {
+    positionTimeoutId = null;
+    if (current_metadata === undefined || is_doc_only()) {
+        return;
+    }
+    parent.window.CodeChatEditorFramework.webSocketComm.send_message({
+        Update: {
+            path: undefined,
+            contents: undefined,
+            base_version: current_version,
+            version: current_version,
+            cursor_position: { CodeMirror: CodeMirror_get_selection() },
+            scroll_position: { CodeMirror: CodeMirror_get_scroll() },
+        },
+    });
+};
+
 // There doesn't seem to be any tracking of a dirty/clean flag built into
 // CodeMirror v6 (although
 // [v5 does](https://codemirror.net/5/doc/manual.html#isClean)). The best I've
@@ -363,6 +602,13 @@ const autosaveExtension = EditorView.updateListener.of(
     // [ViewUpdate](https://codemirror.net/docs/ref/#view.ViewUpdate) which
     // describes a change being made to the document.
     (v: ViewUpdate) => {
+        // Ignore changes sent by the Server.
+        if (v.transactions.every((tr) => tr.annotation(Transaction.remote))) {
+            return;
+        }
+        if (v.selectionSet) {
+            startPositionTimer();
+        }
         // The
         // [docChanged](https://codemirror.net/docs/ref/#view.ViewUpdate.docChanged)
         // flag is the relevant part of this change description. However, this
@@ -470,7 +716,11 @@ const on_navigate = (navigateEvent: NavigateEvent) => {
 declare global {
     interface Window {
         CodeChatEditor: {
-            open_lp: (all_source: CodeChatForWeb) => Promise<void>;
+            open_lp: (
+                all_source: CodeChatForWeb,
+                version: number,
+            ) => Promise<void>;
+            update: (current_update: UpdateMessageContents) => string | null;
         };
         CodeChatEditor_test: any;
     }
@@ -49,6 +49,13 @@ interface EditorMessageContents {
     Load?: string;
     Result?: [string | null, null];
     RequestClose?: null;
+    Refresh?: string[];
+    Presence?: ClientPresence[];
+}
+
+interface ClientPresence {
+    connection_id: string;
+    name: string;
 }
 
 let webSocketComm: WebSocketComm;
@@ -64,7 +71,7 @@ class WebSocketComm {
         number,
         {
             timer_id: number;
-            callback: () => void;
+            callback: (err: string | null) => void;
         }
     > = {};
     // True when the iframe is loading, so that an `Update` should be postponed
@@ -113,30 +120,36 @@ class WebSocketComm {
                     // Load this data in.
                     const current_update = value as UpdateMessageContents;
                     console.log(
-                        `Update(cursor_position: ${current_update.cursor_position}, scroll_position: ${current_update.scroll_position})`,
+                        `Update(cursor_position: ${JSON.stringify(current_update.cursor_position)}, scroll_position: ${current_update.scroll_position})`,
                     );
 
                     let result = null;
                     const contents = current_update.contents;
-                    if (contents !== null && contents !== undefined) {
+                    const version = current_update.version;
+                    if (contents?.Snapshot !== undefined && this.onloading) {
+                        const snapshot = contents.Snapshot;
                         // If the page is still loading, wait until the load
                         // completed before updating the editable contents.
-                        if (this.onloading) {
-                            root_iframe!.onload = () => {
-                                root_iframe!.contentWindow!.CodeChatEditor.open_lp(
-                                    contents,
-                                );
-                                this.onloading = false;
-                            };
-                        } else {
+                        root_iframe!.onload = () => {
                             root_iframe!.contentWindow!.CodeChatEditor.open_lp(
-                                contents,
+                                snapshot,
+                                version,
                             );
-                        }
+                            this.onloading = false;
+                        };
+                    } else if (this.onloading) {
+                        // A diff applies only to loaded contents; report an
+                        // error so that the Server sends a snapshot. Ignore a
+                        // cursor, which applies only to loaded contents.
+                        result =
+                            contents === undefined
+                                ? null
+                                : "Out of sync: the page is loading.";
                     } else {
-                        // TODO: handle scroll/cursor updates.
-                        result = `Unhandled Update message: ${current_update}`;
-                        console.log(result);
+                        result =
+                            root_iframe!.contentWindow!.CodeChatEditor.update(
+                                current_update,
+                            );
                     }
 
                     this.send_result(id, result);
@@ -156,20 +169,34 @@ class WebSocketComm {
                     this.send_result(id, null);
                     break;
 
+                case "Refresh":
+                    const urls = value as string[];
+                    console.log(`Refresh(${urls})`);
+                    this.refresh(urls);
+                    this.send_result(id, null);
+                    break;
+
+                case "Presence":
+                    const others = value as ClientPresence[];
+                    console.log(`Presence(${JSON.stringify(others)})`);
+                    show_presence(others);
+                    this.send_result(id, null);
+                    break;
+
                 case "Result":
                     // Cancel the timer for this message and remove it from
                     // `pending_messages`.
+                    const [err, _loadFile] = value as [string | null, null];
                     const pending_message = this.pending_messages[id];
                     if (pending_message !== undefined) {
                         const { timer_id, callback } =
                             this.pending_messages[id];
                         clearTimeout(timer_id);
-                        callback();
+                        callback(err);
                         delete this.pending_messages[id];
                     }
 
                     // Report if this was an error.
-                    const [err, _loadFile] = value as [string | null, null];
                     if (err !== null) {
                         console.log(`Error in message ${id}: ${err}.`);
                     }
@@ -194,6 +221,35 @@ class WebSocketComm {
         root_iframe!.onload = () => (this.onloading = false);
     };
 
+    // Reload the provided URLs, whose files changed on disk: the TOC in the
+    // sidebar, and any images showing these files.
+    refresh = (urls: string[]) => {
+        const pathnames = new Set(urls.map(url_pathname));
+        const client_document = root_iframe!.contentDocument;
+        if (client_document === null) {
+            return;
+        }
+        const sidebar_window = (
+            client_document.getElementById(
+                "CodeChat-sidebar",
+            ) as HTMLIFrameElement | null
+        )?.contentWindow;
+        if (
+            sidebar_window &&
+            pathnames.has(url_pathname(sidebar_window.location.href))
+        ) {
+            sidebar_window.location.reload();
+        }
+        for (const img of Array.from(client_document.images)) {
+            if (pathnames.has(url_pathname(img.src))) {
+                // Update the browser's cache, then show the new image.
+                fetch(img.src, { cache: "reload" }).then(() => {
+                    img.src = img.src;
+                });
+            }
+        }
+    };
+
     send = (data: any) => this.ws.send(data);
     close = (...args: any) => this.ws.close(...args);
 
@@ -206,7 +262,8 @@ class WebSocketComm {
     // Send a message expecting a result to the server.
     send_message = (
         message: EditorMessageContents,
-        callback: () => void = () => 0,
+        // Called with the error (if any) the Server's `Result` reports.
+        callback: (err: string | null) => void = () => 0,
     ) => {
         const id = this.ws_id++;
         const jm: EditorMessage = {
@@ -246,6 +303,26 @@ class WebSocketComm {
     };
 }
 
+// Show the other Clients viewing the current file in a small badge at the
+// bottom of the page; hide it when no one else is viewing this file.
+const show_presence = (others: ClientPresence[]) => {
+    let presence = document.getElementById("CodeChat-presence");
+    if (presence === null) {
+        presence = document.createElement("div");
+        presence.id = "CodeChat-presence";
+        document.body.appendChild(presence);
+    }
+    presence.textContent = `Also viewing: ${others
+        .map((client) => client.name)
+        .join(", ")}`;
+    presence.hidden = others.length === 0;
+};
+
+// Return the decoded path of a URL, so that URLs which encode a path
+// differently still compare equal.
+const url_pathname = (url: string) =>
+    decodeURIComponent(new URL(url, window.location.href).pathname);
+
 // The iframe element which composes this page.
 let root_iframe: HTMLIFrameElement | undefined;
 
@@ -264,12 +341,16 @@ export const page_init = (
 ) => {
     testMode = testMode_;
     on_dom_content_loaded(async () => {
+        // Build a websocket address based on the base URL of the current page.
+        // This is the page's URL, unless a `<base>` element provides the
+        // Server's URL (as it does for a page hosted in an IDE's built-in
+        // browser).
+        const base_url = new URL(document.baseURI);
         // If the hosting page uses HTTPS, then use a secure websocket (WSS
         // protocol); otherwise, use an insecure websocket (WS).
-        const protocol = window.location.protocol === "http:" ? "ws:" : "wss:";
-        // Build a websocket address based on the URL of the current page.
+        const protocol = base_url.protocol === "http:" ? "ws:" : "wss:";
         webSocketComm = new WebSocketComm(
-            `${protocol}//${window.location.host}/${ws_pathname}`,
+            `${protocol}//${base_url.host}/${ws_pathname}`,
         );
         root_iframe = document.getElementById(
             "CodeChat-iframe",
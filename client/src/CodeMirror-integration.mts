@@ -71,9 +71,11 @@ import { html } from "@codemirror/lang-html";
 import { java } from "@codemirror/lang-java";
 import { javascript } from "@codemirror/lang-javascript";
 import { json } from "@codemirror/lang-json";
+import { php } from "@codemirror/lang-php";
 import { python } from "@codemirror/lang-python";
 import { rust } from "@codemirror/lang-rust";
 import { Editor, init, tinymce } from "./tinymce-config.mjs";
+import { apply_doc_block_diff, map_doc_blocks } from "./Diff.mjs";
 
 let tinymce_singleton: Editor | undefined;
 
@@ -132,6 +134,7 @@ const docBlockField = StateField.define<DecorationSet>({
                                 effect.value.indent,
                                 effect.value.delimiter,
                                 effect.value.content,
+                                effect.value.style,
                                 null,
                             ),
                             block: true,
@@ -173,6 +176,7 @@ const docBlockField = StateField.define<DecorationSet>({
                                 effect.value.indent,
                                 effect.value.delimiter,
                                 effect.value.content,
+                                effect.value.style,
                                 effect.value.dom,
                             ),
                             block: true,
@@ -185,6 +189,11 @@ const docBlockField = StateField.define<DecorationSet>({
                     ],
                 });
             }
+
+            // Replace all doc blocks.
+            else if (effect.is(setDocBlocks)) {
+                doc_blocks = doc_blocks_from_json(effect.value);
+            }
         return doc_blocks;
     },
 
@@ -201,32 +210,48 @@ const docBlockField = StateField.define<DecorationSet>({
     // This provides a straightforward path to transform the entire editor's
     // contents (including these doc blocks) to JSON, which can then be sent
     // back to the server for reassembly into a source file.
-    toJSON: (value: DecorationSet, state: EditorState) => {
-        let json = [];
-        for (const iter = value.iter(); iter.value !== null; iter.next()) {
-            const w = iter.value.spec.widget;
-            json.push([iter.from, iter.to, w.indent, w.delimiter, w.contents]);
-        }
-        return json;
-    },
+    toJSON: (value: DecorationSet, state: EditorState) =>
+        doc_blocks_to_json(value),
 
     // For loading a file from the server back into the editor, use
     // [fromJSON](https://codemirror.net/docs/ref/#state.StateField^define^config.fromJSON).
-    fromJSON: (json: any, state: EditorState) =>
-        Decoration.set(
-            json.map(([from, to, indent, delimiter, contents]: DocBlockJSON) =>
+    fromJSON: (json: any, state: EditorState) => doc_blocks_from_json(json),
+});
+
+// Transform doc block decorations to JSON.
+const doc_blocks_to_json = (value: DecorationSet) => {
+    let json: DocBlockJSON[] = [];
+    for (const iter = value.iter(); iter.value !== null; iter.next()) {
+        const w = iter.value.spec.widget;
+        json.push([
+            iter.from,
+            iter.to,
+            w.indent,
+            w.delimiter,
+            w.contents,
+            w.style,
+        ]);
+    }
+    return json;
+};
+
+// Transform JSON to doc block decorations.
+const doc_blocks_from_json = (json: DocBlockJSON[]) =>
+    Decoration.set(
+        json.map(
+            ([from, to, indent, delimiter, contents, style]: DocBlockJSON) =>
                 Decoration.replace({
                     widget: new DocBlockWidget(
                         indent,
                         delimiter,
                         contents,
+                        style,
                         null,
                     ),
                     block: true,
                 }).range(from, to),
-            ),
         ),
-});
+    );
 
 // Per the [docs](https://codemirror.net/docs/ref/#state.StateEffect^define),
 // "State effects can be used to represent additional effects associated with a
@@ -239,8 +264,12 @@ export const addDocBlock = StateEffect.define<{
     indent: string;
     delimiter: string;
     content: string;
+    style: string;
 }>({
-    map: ({ from, to, indent, delimiter, content }, change: ChangeDesc) => ({
+    map: (
+        { from, to, indent, delimiter, content, style },
+        change: ChangeDesc,
+    ) => ({
         // Update the location (from/to) of this doc block due to the
         // transaction's changes.
         from: change.mapPos(from),
@@ -248,6 +277,7 @@ export const addDocBlock = StateEffect.define<{
         indent,
         delimiter,
         content,
+        style,
     }),
 });
 
@@ -258,19 +288,28 @@ export const updateDocBlock = StateEffect.define<{
     indent: string;
     delimiter: string;
     content: string;
+    style: string;
     dom: HTMLDivElement;
 }>({
-    map: ({ pos, indent, delimiter, content, dom }, change: ChangeDesc) => ({
+    map: (
+        { pos, indent, delimiter, content, style, dom },
+        change: ChangeDesc,
+    ) => ({
         // Update the position of this doc block due to the transaction's
         // changes.
         pos: change.mapPos(pos),
         indent,
         delimiter,
         content,
+        style,
         dom,
     }),
 });
 
+// Replace all doc blocks, which must already account for the transaction's
+// changes. This applies changes to the doc blocks sent by the Server.
+export const setDocBlocks = StateEffect.define<DocBlockJSON[]>();
+
 // Create a [widget](https://codemirror.net/docs/ref/#view.WidgetType) which
 // contains a doc block.
 class DocBlockWidget extends WidgetType {
@@ -278,6 +317,7 @@ class DocBlockWidget extends WidgetType {
         readonly indent: string,
         readonly delimiter: string,
         readonly contents: string,
+        readonly style: string,
         // Only used in an update to avoid changing an already-modified doc
         // block.
         readonly dom: null | HTMLDivElement,
@@ -291,7 +331,8 @@ class DocBlockWidget extends WidgetType {
         return (
             other.indent == this.indent &&
             other.delimiter == this.delimiter &&
-            other.contents == this.contents
+            other.contents == this.contents &&
+            other.style == this.style
         );
     }
 
@@ -305,7 +346,7 @@ class DocBlockWidget extends WidgetType {
             // pasting whitespace.
             `<div class="CodeChat-doc-indent" contenteditable onpaste="return false" data-delimiter=${JSON.stringify(
                 this.delimiter,
-            )}>${this.indent}</div>` +
+            )} data-style=${JSON.stringify(this.style)}>${this.indent}</div>` +
             // The contents of this doc block.
             `<div class="CodeChat-doc-contents" contenteditable>` +
             this.contents +
@@ -559,6 +600,7 @@ const DocBlockPlugin = ViewPlugin.fromClass(
                 const indent_div = target.childNodes[0] as HTMLDivElement;
                 const indent = indent_div.innerHTML;
                 const delimiter = indent_div.getAttribute("data-delimiter")!;
+                const style = indent_div.getAttribute("data-style")!;
                 const [contents_div, is_tinymce] = get_contents(target);
                 const content = is_tinymce
                     ? tinymce_singleton!.getContent()
@@ -569,6 +611,7 @@ const DocBlockPlugin = ViewPlugin.fromClass(
                         indent,
                         delimiter: delimiter,
                         content: content,
+                        style,
                         dom: target,
                     }),
                 ];
@@ -648,7 +691,11 @@ export const CodeMirror_load = async (
         case "javascript":
             parser = javascript();
             break;
+        case "php":
+            parser = php();
+            break;
         case "python":
+        case "python_docstrings":
             parser = python();
             break;
         case "rust":
@@ -659,12 +706,75 @@ export const CodeMirror_load = async (
             break;
 
         // Languages without a parser.
+        case "assembly":
+            parser = cpp();
+            break;
+        case "cmake":
+            parser = python();
+            break;
+        case "dart":
+            parser = java();
+            break;
+        case "dockerfile":
+            parser = python();
+            break;
+        case "elixir":
+            parser = python();
+            break;
+        case "erlang":
+            parser = python();
+            break;
+        case "fortran":
+            parser = python();
+            break;
+        case "haskell":
+            parser = python();
+            break;
+        case "hcl":
+            parser = cpp();
+            break;
         case "json5":
             parser = json();
             break;
+        case "julia":
+            parser = python();
+            break;
+        case "kotlin":
+            parser = java();
+            break;
+        case "latex":
+            parser = python();
+            break;
+        case "lua":
+            parser = python();
+            break;
+        case "makefile":
+            parser = python();
+            break;
         case "matlab":
             parser = python();
             break;
+        case "nix":
+            parser = python();
+            break;
+        case "ocaml":
+            parser = python();
+            break;
+        case "perl":
+            parser = python();
+            break;
+        case "powershell":
+            parser = python();
+            break;
+        case "r":
+            parser = python();
+            break;
+        case "ruby":
+            parser = python();
+            break;
+        case "scala":
+            parser = java();
+            break;
         case "sql":
             parser = python();
             break;
@@ -683,6 +793,9 @@ export const CodeMirror_load = async (
         case "v":
             parser = javascript();
             break;
+        case "zig":
+            parser = cpp();
+            break;
 
         default:
             parser = javascript();
@@ -722,3 +835,126 @@ export const CodeMirror_save = () => {
 
     return source;
 };
+
+// Return the current selection. The Server measures offsets into the document
+// in characters (code points), not UTF-16 code units. In a doc block, this
+// locates the selection only to the granularity of the doc block.
+export const CodeMirror_get_selection = () => {
+    const doc_block_div = document.activeElement?.closest(".CodeChat-doc");
+    if (doc_block_div) {
+        const from = current_view.posAtDOM(doc_block_div);
+        const index = doc_blocks_to_json(
+            current_view.state.field(docBlockField),
+        ).findIndex(([doc_block_from]) => doc_block_from === from);
+        if (index !== -1) {
+            const position = { DocBlock: { index, line: 0, column: 0 } };
+            return { anchor: position, head: position };
+        }
+    }
+    const { anchor, head } = current_view.state.selection.main;
+    return {
+        anchor: { Code: to_code_points(anchor) },
+        head: { Code: to_code_points(head) },
+    };
+};
+
+// Select the provided selection, scrolling it into view. A location in a doc
+// block selects the beginning of that doc block.
+export const CodeMirror_set_selection = (
+    selection: CursorSelection<CodeMirrorPosition>,
+) => {
+    const doc_blocks = doc_blocks_to_json(
+        current_view.state.field(docBlockField),
+    );
+    const to_pos = (position: CodeMirrorPosition) =>
+        position.Code !== undefined
+            ? from_code_points(position.Code)
+            : doc_blocks[position.DocBlock!.index]?.[0] ?? 0;
+    current_view.dispatch({
+        selection: EditorSelection.single(
+            to_pos(selection.anchor),
+            to_pos(selection.head),
+        ),
+        scrollIntoView: true,
+        annotations: Transaction.remote.of(true),
+    });
+};
+
+// Return the scroll position: the first line of the line of code or doc block
+// at the top of the view, plus the fraction of it scrolled above the top.
+export const CodeMirror_get_scroll = (): LineScrollPosition => {
+    const height =
+        scroll_element().getBoundingClientRect().top - current_view.documentTop;
+    const block = current_view.lineBlockAtHeight(Math.max(height, 0));
+    return {
+        line: current_view.state.doc.lineAt(block.from).number - 1,
+        offset:
+            block.height > 0
+                ? Math.min(Math.max((height - block.top) / block.height, 0), 1)
+                : 0,
+    };
+};
+
+// Scroll to the provided scroll position.
+export const CodeMirror_set_scroll = (scroll_position: LineScrollPosition) => {
+    const doc = current_view.state.doc;
+    const line = doc.line(Math.min(scroll_position.line + 1, doc.lines));
+    const block = current_view.lineBlockAt(line.from);
+    const element = scroll_element();
+    element.scrollTop +=
+        current_view.documentTop +
+        block.top +
+        scroll_position.offset * block.height -
+        element.getBoundingClientRect().top;
+};
+
+// The element which scrolls the document.
+const scroll_element = () => document.getElementById("CodeChat-body")!;
+
+// Convert a position in the document to an offset in characters.
+const to_code_points = (pos: number) =>
+    Array.from(current_view.state.doc.sliceString(0, pos)).length;
+
+// Convert an offset in characters to a position in the document.
+const from_code_points = (offset: number) => {
+    let pos = 0;
+    for (const c of current_view.state.doc.sliceString(0)) {
+        if (offset-- <= 0) {
+            break;
+        }
+        pos += c.length;
+    }
+    return pos;
+};
+
+// Apply the changes to the current document provided by a diff from the
+// Server. Return an error message if they don't apply.
+export const CodeMirror_apply_diff = (
+    doc_diffs: StringDiff[],
+    doc_block_diffs: DocBlockDiff[],
+): string | null => {
+    const doc_blocks = apply_doc_block_diff(
+        map_doc_blocks(
+            doc_blocks_to_json(current_view.state.field(docBlockField)),
+            doc_diffs,
+        ),
+        doc_block_diffs,
+    );
+    if (typeof doc_blocks === "string") {
+        return doc_blocks;
+    }
+    // The positions of each edit refer to the document before any edits are
+    // applied, as CodeMirror expects. CodeMirror rejects edits outside the
+    // document.
+    try {
+        current_view.dispatch({
+            changes: doc_diffs,
+            effects: setDocBlocks.of(doc_blocks),
+            // Mark these as changes made elsewhere, which need no autosave.
+            annotations: Transaction.remote.of(true),
+        });
+    } catch (err) {
+        return `Invalid edit: ${err}.`;
+    }
+    return null;
+};
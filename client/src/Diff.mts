@@ -0,0 +1,179 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+//
+// # `Diff.mts` -- Compute and apply changes to a CodeMirror document
+//
+// This mirrors [diff.rs](../../server/src/processing/diff.rs); the two must
+// agree. A diff consists of edits to the `doc` string, followed by inserts,
+// deletes, and replacements of doc blocks. Before applying the doc block
+// changes, the location of each doc block is moved to account for the edits to
+// `doc`.
+//
+// ## Diffs
+//
+// Return the changes which transform `before` into `after`.
+export const diff_code_mirror = (
+    before: CodeChatForWeb["source"],
+    after: CodeChatForWeb["source"],
+): { doc: StringDiff[]; doc_blocks: DocBlockDiff[] } => {
+    const doc = diff_str(before.doc, after.doc);
+
+    // Compare each doc block, after moving it to account for the edits to
+    // `doc`, with the new doc blocks.
+    const mapped_before = map_doc_blocks(before.doc_blocks, doc);
+    const is_unchanged = (index: number, doc_block: DocBlockJSON) =>
+        mapped_before[index].every(
+            (value, field_index) => value === doc_block[field_index],
+        );
+    // Find the unchanged doc blocks at the beginning and end; everything in
+    // between is replaced, deleted, or inserted.
+    const before_len = before.doc_blocks.length;
+    const after_len = after.doc_blocks.length;
+    let prefix_len = 0;
+    while (
+        prefix_len < Math.min(before_len, after_len) &&
+        is_unchanged(prefix_len, after.doc_blocks[prefix_len])
+    ) {
+        prefix_len++;
+    }
+    let suffix_len = 0;
+    while (
+        suffix_len < Math.min(before_len, after_len) - prefix_len &&
+        is_unchanged(
+            before_len - 1 - suffix_len,
+            after.doc_blocks[after_len - 1 - suffix_len],
+        )
+    ) {
+        suffix_len++;
+    }
+    const before_changed_len = before_len - prefix_len - suffix_len;
+    const after_changed_len = after_len - prefix_len - suffix_len;
+
+    const doc_blocks: DocBlockDiff[] = [];
+    const replaced_len = Math.min(before_changed_len, after_changed_len);
+    let index = prefix_len;
+    for (; index < prefix_len + replaced_len; index++) {
+        doc_blocks.push({ Replace: [index, after.doc_blocks[index]] });
+    }
+    for (let count = after_changed_len; count < before_changed_len; count++) {
+        doc_blocks.push({ Delete: index });
+    }
+    for (; index < prefix_len + after_changed_len; index++) {
+        doc_blocks.push({ Insert: [index, after.doc_blocks[index]] });
+    }
+
+    return { doc, doc_blocks };
+};
+
+// Apply the doc block changes produced by `diff_code_mirror` to `doc_blocks`,
+// whose locations already account for the edits to `doc` (see
+// `map_doc_blocks`). Return an error message if a change doesn't apply.
+export const apply_doc_block_diff = (
+    doc_blocks: DocBlockJSON[],
+    doc_block_diffs: DocBlockDiff[],
+): DocBlockJSON[] | string => {
+    const result = [...doc_blocks];
+    for (const doc_block_diff of doc_block_diffs) {
+        if (
+            doc_block_diff.Insert !== undefined &&
+            doc_block_diff.Insert[0] <= result.length
+        ) {
+            const [index, doc_block] = doc_block_diff.Insert;
+            result.splice(index, 0, doc_block);
+        } else if (
+            doc_block_diff.Delete !== undefined &&
+            doc_block_diff.Delete < result.length
+        ) {
+            result.splice(doc_block_diff.Delete, 1);
+        } else if (
+            doc_block_diff.Replace !== undefined &&
+            doc_block_diff.Replace[0] < result.length
+        ) {
+            result[doc_block_diff.Replace[0]] = doc_block_diff.Replace[1];
+        } else {
+            return `Invalid doc block change ${JSON.stringify(doc_block_diff)}.`;
+        }
+    }
+    return result;
+};
+
+// Move each doc block to account for the edits in `string_diffs`.
+export const map_doc_blocks = (
+    doc_blocks: DocBlockJSON[],
+    string_diffs: StringDiff[],
+): DocBlockJSON[] =>
+    doc_blocks.map(([from, to, indent, delimiter, contents, style]) => [
+        map_position(from, string_diffs),
+        map_position(to, string_diffs),
+        indent,
+        delimiter,
+        contents,
+        style,
+    ]);
+
+// Return the edits which transform `before` into `after`: the characters
+// between their common prefix and their common suffix.
+const diff_str = (before: string, after: string): StringDiff[] => {
+    if (before === after) {
+        return [];
+    }
+    // Like the Server, count characters (code points), not UTF-16 code units.
+    const before_chars = Array.from(before);
+    const after_chars = Array.from(after);
+    let prefix_len = 0;
+    while (
+        prefix_len < Math.min(before_chars.length, after_chars.length) &&
+        before_chars[prefix_len] === after_chars[prefix_len]
+    ) {
+        prefix_len++;
+    }
+    let suffix_len = 0;
+    while (
+        suffix_len <
+            Math.min(before_chars.length, after_chars.length) - prefix_len &&
+        before_chars[before_chars.length - 1 - suffix_len] ===
+            after_chars[after_chars.length - 1 - suffix_len]
+    ) {
+        suffix_len++;
+    }
+    return [
+        {
+            from: prefix_len,
+            to: before_chars.length - suffix_len,
+            insert: after_chars
+                .slice(prefix_len, after_chars.length - suffix_len)
+                .join(""),
+        },
+    ];
+};
+
+// Move `position` to account for the edits in `string_diffs`. A position in
+// text which an edit replaced moves to the start of the edit.
+const map_position = (position: number, string_diffs: StringDiff[]) => {
+    let mapped_position = position;
+    for (const string_diff of string_diffs) {
+        if (position < string_diff.from) {
+            break;
+        }
+        const insert_len = Array.from(string_diff.insert).length;
+        if (position >= string_diff.to) {
+            mapped_position += insert_len - (string_diff.to - string_diff.from);
+        } else {
+            return mapped_position - (position - string_diff.from);
+        }
+    }
+    return mapped_position;
+};
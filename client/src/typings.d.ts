@@ -19,7 +19,7 @@
 // The server passes this to the client to load a file. See
 // [LexedSourceFile](../../server/src/webserver.rs#LexedSourceFile).
 type CodeChatForWeb = {
-    metadata: { mode: string };
+    metadata: { mode: string; backlinks?: Backlink[] };
     source: {
         doc: string;
         doc_blocks: DocBlockJSON[];
@@ -27,6 +27,16 @@ type CodeChatForWeb = {
     };
 };
 
+// A hyperlink which refers to the current file or an anchor in it. See
+// [Backlink](../../server/src/processing.rs#Backlink).
+type Backlink = {
+    anchor: string | null;
+    file: string;
+    source_anchor: string | null;
+    pre_snippet: string;
+    post_snippet: string;
+};
+
 // How a doc block is stored using CodeMirror.
 type DocBlockJSON = [
     // From
@@ -39,14 +49,94 @@ type DocBlockJSON = [
     string,
     // Contents
     string,
+    // Style -- details of how the comment was written, which the Server uses
+    // to save it the same way. Pass this back unchanged.
+    string,
 ];
 
+// The contents of a file sent in an `Update` message: either the entire
+// contents, or the changes to a previous version of them. See
+// [CodeChatForWebUpdate](../../server/src/processing/diff.rs#CodeChatForWebUpdate).
+type CodeChatForWebUpdate = {
+    Snapshot?: CodeChatForWeb;
+    Diff?: CodeChatForWebDiff;
+};
+
+type CodeChatForWebDiff = {
+    metadata: CodeChatForWeb["metadata"];
+    doc: StringDiff[];
+    doc_blocks: DocBlockDiff[];
+};
+
+// Replace the characters from `from` up to `to` with `insert`.
+type StringDiff = {
+    from: number;
+    to: number;
+    insert: string;
+};
+
+// A change to the doc blocks; each index refers to the doc blocks after the
+// preceding changes were applied.
+type DocBlockDiff = {
+    Insert?: [number, DocBlockJSON];
+    Delete?: number;
+    Replace?: [number, DocBlockJSON];
+};
+
 interface UpdateMessageContents {
-    contents: CodeChatForWeb | undefined;
-    cursor_position: number | undefined;
-    scroll_position: number | undefined;
+    path: string | undefined;
+    contents: CodeChatForWebUpdate | undefined;
+    // The version of this file's contents which the sender edited to produce
+    // this update, or `null` to replace the contents regardless of their
+    // version.
+    base_version: number | null;
+    // The version of this file's contents after applying this update.
+    version: number;
+    // The cursor or selection, in source code coordinates when sent to or by
+    // the IDE and in CodeMirror coordinates when sent to or by the Client.
+    cursor_position: CursorPosition | undefined;
+    // The line at the top of the view, in the same coordinates as
+    // `cursor_position`.
+    scroll_position: ScrollPosition | undefined;
 }
 
+// See [position.rs](../../server/src/processing/position.rs).
+type CursorPosition = {
+    Source?: CursorSelection<SourcePosition>;
+    CodeMirror?: CursorSelection<CodeMirrorPosition>;
+};
+
+// A selection; this mirrors `Selection` in the Server, renamed to avoid
+// conflicting with the DOM's `Selection`.
+type CursorSelection<Position> = {
+    anchor: Position;
+    head: Position;
+};
+
+type ScrollPosition = {
+    Source?: LineScrollPosition;
+    CodeMirror?: LineScrollPosition;
+};
+
+// A zero-based line at the top of a view, plus the fraction of this line (in
+// the Client, of this line of code or doc block) scrolled above the top.
+type LineScrollPosition = {
+    line: number;
+    offset: number;
+};
+
+// A zero-based line and column, measured in characters.
+type SourcePosition = {
+    line: number;
+    column: number;
+};
+
+// Either a character offset into the document or a location in a doc block.
+type CodeMirrorPosition = {
+    Code?: number;
+    DocBlock?: { index: number; line: number; column: number };
+};
+
 // These modules keep TypeScript from complaining about missing type definitions
 // for Javascript libraries used in this project. See
 // [CodeChatEditor.mts](CodeChatEditor.mts).
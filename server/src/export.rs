@@ -0,0 +1,564 @@
+/// Copyright (C) 2023 Bryan A. Jones.
+///
+/// This file is part of the CodeChat Editor. The CodeChat Editor is free
+/// software: you can redistribute it and/or modify it under the terms of the
+/// GNU General Public License as published by the Free Software Foundation,
+/// either version 3 of the License, or (at your option) any later version.
+///
+/// The CodeChat Editor is distributed in the hope that it will be useful, but
+/// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+/// or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+/// more details.
+///
+/// You should have received a copy of the GNU General Public License along with
+/// the CodeChat Editor. If not, see
+/// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+///
+/// # `export.rs` -- Export a project to a static HTML site
+///
+/// A project is a directory tree rooted at a `toc.md` file. Exporting it
+/// produces a site which can be published without running the server:
+///
+/// - Each CodeChat Editor file becomes a read-only page named by appending
+///   `.html` to the file's name (so that `foo.py` and `foo.md` don't collide).
+///   Each page contains the rendered doc blocks, the code, and the TOC sidebar.
+/// - All other files (images, data, etc.) are copied unchanged.
+/// - Relative links to CodeChat Editor files are rewritten to refer to their
+///   exported pages.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use log::warn;
+use path_slash::PathExt;
+use regex::{Captures, Regex};
+
+// ### Local
+use crate::processing::{
+    find_project_root, lexer_key, source_to_codechat_for_web, AnchorIndex, CodeChatForWeb,
+    TranslationResults,
+};
+use crate::webserver::escape_html;
+
+// ## Data structures
+//
+// The stylesheet written to the root of the exported site. This is a
+// simplified version of `CodeChatEditor.css` and `CodeChatEditorProject.css`,
+// omitting everything related to editing.
+const EXPORT_CSS_NAME: &str = "CodeChatEditorExport.css";
+const EXPORT_CSS: &str = r#"/* Styles for a static site exported by the CodeChat Editor. */
+:root {
+    --sidebar-width: 15rem;
+}
+
+html {
+    box-sizing: border-box;
+}
+
+*,
+*:before,
+*:after {
+    box-sizing: inherit;
+}
+
+body {
+    display: flex;
+    margin: 0px;
+    background: white;
+}
+
+#CodeChat-sidebar {
+    flex: 0 0 var(--sidebar-width);
+    height: 100vh;
+    overflow: auto;
+    position: sticky;
+    top: 0px;
+    padding: 0.2rem;
+}
+
+#CodeChat-sidebar ol {
+    padding-inline-start: 1.5rem;
+}
+
+#CodeChat-contents {
+    flex-grow: 1;
+    min-width: 0px;
+    padding: 0.2rem;
+}
+
+#CodeChat-filename p {
+    margin: 0px;
+    white-space: nowrap;
+}
+
+.CodeChat-code {
+    margin: 0px;
+    tab-size: 4;
+}
+
+.CodeChat-doc {
+    display: flex;
+    padding: 0px 2px 0px 0px;
+}
+
+.CodeChat-doc-indent {
+    flex: 0 0 auto;
+    white-space: pre;
+    font-family: monospace;
+    tab-size: 4;
+}
+
+.CodeChat-doc-contents {
+    flex-grow: 1;
+    min-width: 0px;
+}
+
+.CodeChat-doc-contents > *:first-child {
+    margin-top: 0px;
+    padding-top: 0px;
+}
+
+.CodeChat-doc-contents > *:last-child {
+    margin-bottom: 0px;
+    padding-bottom: 0px;
+}
+
+.CodeChat-doc-contents table, .CodeChat-doc-contents th, .CodeChat-doc-contents td {
+    border-collapse: collapse;
+    padding-left: 4px;
+    padding-right: 4px;
+    border: 1px solid;
+}
+
+li[data-codechat-number] {
+    list-style-type: none;
+}
+
+[data-codechat-number]::before {
+    content: attr(data-codechat-number) " ";
+}
+
+a[data-codechat-link-text]:empty::before {
+    content: attr(data-codechat-link-text);
+}
+"#;
+
+lazy_static! {
+    // Find links in the HTML produced by the Markdown translator, which always
+    // double-quotes attribute values.
+    static ref LINK_ATTRIBUTE: Regex = Regex::new(r#"\b(href|src)="([^"]*)""#).unwrap();
+    // A URL with a scheme (`http:`, `mailto:`, etc.) is absolute.
+    static ref URL_SCHEME: Regex = Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:").unwrap();
+}
+
+// ## Code
+//
+// Export the project containing `project_path` (a file or directory) to a
+// static site in `output_path`. Return a summary of the export.
+pub fn export_project(project_path: &Path, output_path: &Path) -> Result<String, String> {
+    let root = find_project_root(project_path)?;
+    let toc_path = root.join("toc.md");
+
+    // Prepare the output directory.
+    fs::create_dir_all(output_path)
+        .map_err(|err| format!("Unable to create output directory {output_path:?}: {err}."))?;
+    let output_path = output_path
+        .canonicalize()
+        .map_err(|err| format!("Unable to resolve output directory {output_path:?}: {err}."))?;
+    if root.starts_with(&output_path) {
+        return Err(format!(
+            "The output directory {output_path:?} must not contain the project {root:?}."
+        ));
+    }
+
+    // Find every file in the project, then classify it as a page to render or
+    // a file to copy. All pages must be known before rendering, since each
+    // page's links depend on which files are pages.
+    let mut file_list = Vec::new();
+    collect_files(&root, Some(&output_path), &mut file_list)?;
+    let mut pages = Vec::new();
+    let mut copies = Vec::new();
+    for file_path in file_list {
+        let rel_path = file_path.strip_prefix(&root).unwrap().to_slash_lossy();
+        match classify_file(&file_path) {
+            Some(codechat_for_web) => pages.push((rel_path.to_string(), codechat_for_web)),
+            None => copies.push((rel_path.to_string(), file_path)),
+        }
+    }
+    let page_set: HashSet<String> = pages.iter().map(|(rel_path, _)| rel_path.clone()).collect();
+
+    // Index the pages, which assigns ids to headings and numbers them.
+    let mut anchor_index = AnchorIndex::new(Some(toc_path.clone()));
+    for (rel_path, codechat_for_web) in &mut pages {
+        anchor_index.upsert_codechat_for_web(&root.join(&**rel_path), codechat_for_web);
+    }
+    // With every page indexed, provide text for empty links.
+    for (rel_path, codechat_for_web) in &mut pages {
+        anchor_index.fill_link_text_codechat_for_web(&root.join(&**rel_path), codechat_for_web);
+    }
+
+    // The TOC sidebar is the HTML of the rendered `toc.md`.
+    let toc_html = match pages.iter().find(|(rel_path, _)| rel_path == "toc.md") {
+        Some((_, codechat_for_web)) => codechat_for_web.source.doc.clone(),
+        None => return Err(format!("Unable to translate {toc_path:?}.")),
+    };
+
+    // Write everything.
+    for (rel_path, codechat_for_web) in &pages {
+        let html = render_page(rel_path, codechat_for_web, &toc_html, &page_set);
+        write_file(&output_path, &format!("{rel_path}.html"), html.as_bytes())?;
+    }
+    for (rel_path, file_path) in &copies {
+        let dest = output_path.join(rel_path);
+        create_parent(&dest)?;
+        fs::copy(file_path, &dest)
+            .map_err(|err| format!("Unable to copy {file_path:?} to {dest:?}: {err}."))?;
+    }
+    write_file(&output_path, EXPORT_CSS_NAME, EXPORT_CSS.as_bytes())?;
+    write_file(
+        &output_path,
+        "index.html",
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta charset="UTF-8">
+        <meta http-equiv="refresh" content="0; url=toc.md.html">
+        <title>The CodeChat Editor</title>
+    </head>
+    <body>
+        <p><a href="toc.md.html">Table of contents</a></p>
+    </body>
+</html>
+"#
+        .as_bytes(),
+    )?;
+
+    Ok(format!(
+        "Exported {} pages and copied {} files to {output_path:?}.",
+        pages.len(),
+        copies.len()
+    ))
+}
+
+// Recursively collect all files in `dir`, in sorted order. Skip hidden files
+// and directories (`.git`, etc.) and the `excluded` directory.
+pub(crate) fn collect_files(
+    dir: &Path,
+    excluded: Option<&Path>,
+    file_list: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    let mut entries = fs::read_dir(dir)
+        .map_err(|err| format!("Unable to read directory {dir:?}: {err}."))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect::<Vec<_>>();
+    entries.sort();
+    for path in entries {
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            || excluded.is_some_and(|excluded| path == excluded)
+        {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, excluded, file_list)?;
+        } else if path.is_file() {
+            file_list.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Translate a file to the CodeChat Editor format. Return `None` if this isn't
+// a CodeChat Editor file, meaning it should be copied unchanged.
+fn classify_file(file_path: &Path) -> Option<CodeChatForWeb> {
+    // Binary files can't be CodeChat Editor files.
+    let file_contents = fs::read_to_string(file_path).ok()?;
+    let ext = lexer_key(file_path);
+    let is_toc = file_path.file_name() == Some(OsStr::new("toc.md"));
+    match source_to_codechat_for_web(&file_contents, &ext, is_toc, true) {
+        TranslationResults::CodeChat(codechat_for_web) => Some(codechat_for_web),
+        TranslationResults::Unknown => None,
+        TranslationResults::Err(err) => {
+            warn!("Unable to translate {file_path:?}; copying it instead: {err}");
+            None
+        }
+    }
+}
+
+// Produce the exported page for a CodeChat Editor file.
+fn render_page(
+    // The path to this file, relative to the project root and using forward
+    // slashes.
+    rel_path: &str,
+    codechat_for_web: &CodeChatForWeb,
+    // The HTML of the TOC.
+    toc_html: &str,
+    // The files (relative to the project root) which are exported as pages.
+    pages: &HashSet<String>,
+) -> String {
+    let (page_dir, name) = match rel_path.rsplit_once('/') {
+        Some((page_dir, name)) => (page_dir, name),
+        None => ("", rel_path),
+    };
+    let name = escape_html(name);
+    let dir = escape_html(page_dir);
+    // Links in the TOC are relative to the project root, while links in this
+    // page are relative to its directory.
+    let toc = rewrite_links(toc_html, "", page_dir, pages);
+    let body = rewrite_links(&code_doc_html(codechat_for_web), page_dir, page_dir, pages);
+    let css_href = relative_url(page_dir, EXPORT_CSS_NAME);
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta charset="UTF-8">
+        <meta name="viewport" content="width=device-width, initial-scale=1">
+        <title>{name} - The CodeChat Editor</title>
+        <link rel="stylesheet" href="{css_href}">
+    </head>
+    <body>
+        <nav id="CodeChat-sidebar">
+            {toc}
+        </nav>
+        <div id="CodeChat-contents">
+            <div id="CodeChat-filename">
+                <p>{name} - {dir}</p>
+            </div>
+            <div id="CodeChat-body">
+                {body}
+            </div>
+        </div>
+    </body>
+</html>
+"#
+    )
+}
+
+// Transform the code and doc blocks of a CodeChat Editor file into HTML.
+fn code_doc_html(codechat_for_web: &CodeChatForWeb) -> String {
+    let code_mirror = &codechat_for_web.source;
+    // Markdown files are a single block of HTML.
+    if codechat_for_web.metadata.mode == "markdown" {
+        return code_mirror.doc.clone();
+    }
+
+    // Doc block locations are given in characters, not bytes.
+    let doc: Vec<char> = code_mirror.doc.chars().collect();
+    let mut html = String::new();
+    let mut index = 0;
+    for (from, to, indent, _delimiter, contents, _style) in &code_mirror.doc_blocks {
+        push_code_html(&mut html, &doc[index..*from]);
+        html.push_str(&format!(
+            r#"<div class="CodeChat-doc"><div class="CodeChat-doc-indent">{}</div><div class="CodeChat-doc-contents">{contents}</div></div>
+"#,
+            escape_html(indent)
+        ));
+        // The doc block replaces the newlines from `from` through `to`. The
+        // last doc block may be zero length, in which case `to` is past the
+        // end of the document.
+        index = (to + 1).min(doc.len());
+    }
+    push_code_html(&mut html, &doc[index..]);
+    html
+}
+
+// Add a block of code to `html`, omitting it if it's empty.
+fn push_code_html(html: &mut String, code: &[char]) {
+    let code: String = code.iter().collect();
+    // A trailing newline produces an extra blank line in a `<pre>`.
+    let code = code.strip_suffix('\n').unwrap_or(&code);
+    if !code.is_empty() {
+        html.push_str(&format!(
+            "<pre class=\"CodeChat-code\"><code>{}</code></pre>\n",
+            escape_html(code)
+        ));
+    }
+}
+
+// Rewrite the relative links in `html` for use in the exported page in
+// `page_dir`, where links in `html` are relative to `base_dir`. Links to pages
+// refer to the exported page instead.
+fn rewrite_links(html: &str, base_dir: &str, page_dir: &str, pages: &HashSet<String>) -> String {
+    LINK_ATTRIBUTE
+        .replace_all(html, |captures: &Captures| {
+            let url = &captures[2];
+            match rewrite_url(url, base_dir, page_dir, pages) {
+                Some(new_url) => format!(r#"{}="{new_url}""#, &captures[1]),
+                None => captures[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+// Rewrite a single URL; return `None` if it doesn't need rewriting.
+fn rewrite_url(
+    url: &str,
+    base_dir: &str,
+    page_dir: &str,
+    pages: &HashSet<String>,
+) -> Option<String> {
+    // Leave absolute URLs, absolute paths, and fragment/query-only URLs alone.
+    if URL_SCHEME.is_match(url)
+        || url.starts_with('/')
+        || url.starts_with(['#', '?'])
+        || url.is_empty()
+    {
+        return None;
+    }
+    // Split off any query and fragment, which are kept as is.
+    let split_index = url.find(['?', '#']).unwrap_or(url.len());
+    let (url_path, suffix) = url.split_at(split_index);
+    let url_path = urlencoding::decode(url_path).ok()?;
+    let mut target = resolve_path(base_dir, &url_path)?;
+    if pages.contains(&target) {
+        target.push_str(".html");
+    }
+    let mut new_url = relative_url(page_dir, &target);
+    if url_path.ends_with('/') {
+        new_url.push_str(if new_url.is_empty() { "./" } else { "/" });
+    }
+    Some(new_url + suffix)
+}
+
+// Resolve `path` relative to `base_dir`, producing a path relative to the
+// project root. Return `None` if the path lies outside the project.
+fn resolve_path(base_dir: &str, path: &str) -> Option<String> {
+    let mut segments: Vec<&str> = base_dir.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+// Given a directory and a target, both relative to the project root, produce
+// a percent-encoded URL to the target from that directory.
+fn relative_url(from_dir: &str, target: &str) -> String {
+    let from: Vec<&str> = from_dir.split('/').filter(|s| !s.is_empty()).collect();
+    let to: Vec<&str> = target.split('/').filter(|s| !s.is_empty()).collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut url_segments = vec!["..".to_string(); from.len() - common];
+    url_segments.extend(
+        to[common..]
+            .iter()
+            .map(|s| urlencoding::encode(s).into_owned()),
+    );
+    url_segments.join("/")
+}
+
+// Write `contents` to `rel_path` in the output directory.
+fn write_file(output_path: &Path, rel_path: &str, contents: &[u8]) -> Result<(), String> {
+    let dest = output_path.join(rel_path);
+    create_parent(&dest)?;
+    fs::write(&dest, contents).map_err(|err| format!("Unable to write {dest:?}: {err}."))
+}
+
+fn create_parent(dest: &Path) -> Result<(), String> {
+    let parent = dest.parent().unwrap();
+    fs::create_dir_all(parent)
+        .map_err(|err| format!("Unable to create directory {parent:?}: {err}."))
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs};
+
+    use super::{export_project, relative_url, resolve_path, rewrite_links};
+    use crate::prep_test_dir;
+
+    #[test]
+    fn test_export_project() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let project_dir = test_dir.join("project");
+        let output_dir = test_dir.join("site");
+
+        // Exporting a directory outside a project fails.
+        assert!(export_project(&test_dir, &output_dir).is_err());
+
+        // Start the export from a subdirectory; the project root is found by
+        // looking for the TOC.
+        let msg = export_project(&project_dir.join("src"), &output_dir).unwrap();
+        assert_eq!(
+            msg,
+            format!(
+                "Exported 3 pages and copied 1 files to {:?}.",
+                output_dir.canonicalize().unwrap()
+            )
+        );
+
+        // Check the list of files produced; hidden files aren't exported.
+        for file in [
+            "index.html",
+            "CodeChatEditorExport.css",
+            "toc.md.html",
+            "notes.md.html",
+            "src/a b.py.html",
+            "src/data.txt",
+        ] {
+            assert!(output_dir.join(file).is_file(), "Missing {file}");
+        }
+        assert!(!output_dir.join(".hidden").exists());
+
+        // Check the contents of a source file's page.
+        let html = fs::read_to_string(output_dir.join("src/a b.py.html")).unwrap();
+        // The stylesheet.
+        assert!(html.contains(r#"href="../CodeChatEditorExport.css""#));
+        // The TOC, with links relative to this page.
+        assert!(html.contains(r#"<a href="a%20b.py.html">"#));
+        assert!(html.contains(r#"<a href="../notes.md.html#intro">"#));
+        // A doc block, including rewritten links.
+        assert!(html.contains(
+            r#"<div class="CodeChat-doc"><div class="CodeChat-doc-indent"></div><div class="CodeChat-doc-contents"><p>See the <a href="../notes.md.html">notes</a> and <a href="data.txt">data</a>."#
+        ));
+        assert!(html.contains(r#"<a href="https://example.com/x.py">"#));
+        // Code, with HTML escaped.
+        assert!(html.contains(
+            r#"<pre class="CodeChat-code"><code>if a &lt; b:</code></pre>
+<div class="CodeChat-doc"><div class="CodeChat-doc-indent">    </div><div class="CodeChat-doc-contents"><p>Indented.</p>"#
+        ));
+        assert!(html.contains(r#"<pre class="CodeChat-code"><code>    a = b</code></pre>"#));
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_rewrite_links() {
+        let pages = HashSet::from(["a.py".to_string(), "sub/b.py".to_string()]);
+        assert_eq!(
+            rewrite_links(
+                r##"<a href="a.py#x"><img src="sub/c.png"><a href="#y"><a href="mailto:me@x.org"><a href="sub/">"##,
+                "",
+                "sub",
+                &pages
+            ),
+            r##"<a href="../a.py.html#x"><img src="c.png"><a href="#y"><a href="mailto:me@x.org"><a href="./">"##
+        );
+        // Links outside the project are left alone.
+        assert_eq!(
+            rewrite_links(r#"<a href="../../x.py">"#, "sub", "sub", &pages),
+            r#"<a href="../../x.py">"#
+        );
+        assert_eq!(
+            resolve_path("sub/dir", "../b.py"),
+            Some("sub/b.py".to_string())
+        );
+        assert_eq!(relative_url("", "sub/a b.py"), "sub/a%20b.py");
+    }
+}
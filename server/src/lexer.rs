@@ -16,20 +16,26 @@
 ///
 /// # `lexer.rs` -- Lex source code into code and doc blocks
 // ## Submodule definitions
+pub mod language_config;
 pub mod supported_languages;
 
 // ## Imports
 //
 // ### Standard library
-#[cfg(feature = "lexer_explain")]
 use std::cmp::min;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
 
 // ### Third-party
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde::Deserialize;
 
 // ### Local
+use language_config::{language_config_files, load_language_config};
 use supported_languages::get_language_lexer_vec;
 
 /// ## Data structures
@@ -41,10 +47,11 @@ use supported_languages::get_language_lexer_vec;
 ///
 /// - It defines block and inline comment delimiters; these (when correctly
 ///   formatted) become doc blocks.
-/// - It defines strings: what is the escape character? Are newlines allowed? If
-///   so, must newlines be escaped?
-/// - It defines heredocs in a flexible form (see `HeredocDelim` for more
-///   details).
+/// - It defines strings: what prefixes may precede the opening delimiter? What
+///   is the escape character? Are newlines allowed? If so, must newlines be
+///   escaped?
+/// - It defines any number of heredocs in a flexible form (see `HeredocDelim`
+///   for more details).
 /// - It associates an Ace mode and filename extensions with the lexer.
 ///
 /// This lexer ignores line continuation characters; in C/C++/Python, it's a `\`
@@ -76,7 +83,8 @@ pub struct BlockCommentDelim {
 }
 
 /// Define the types of newlines supported in a string.
-#[derive(Clone)]
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
 enum NewlineSupport {
     /// This string delimiter allows unescaped newlines. This is a multiline
     /// string.
@@ -92,6 +100,13 @@ enum NewlineSupport {
 /// Define a string from the lexer's perspective.
 #[derive(Clone)]
 struct StringDelimiterSpec {
+    /// Prefixes which may precede the opening delimiter, such as `b` for a
+    /// byte string. Since a prefix may change the escape rules (for example,
+    /// Dart's raw strings `r'...'` have no escape character), each
+    /// combination of prefix and escape rules requires its own
+    /// `StringDelimiterSpec`. Empty if this delimiter has no prefix; include
+    /// an empty string to also match the unprefixed delimiter.
+    prefix_arr: Vec<String>,
     /// Delimiter to indicate the start and end of a string.
     delimiter: String,
     /// Escape character, to allow inserting the string delimiter into the
@@ -106,6 +121,7 @@ struct StringDelimiterSpec {
 /// This defines the delimiters for a
 /// [heredoc](https://en.wikipedia.org/wiki/Here_document) (or heredoc-like
 /// literal).
+#[derive(Clone)]
 struct HeredocDelim {
     /// The prefix before the heredoc's delimiting identifier.
     start_prefix: String,
@@ -117,6 +133,9 @@ struct HeredocDelim {
     stop_prefix: String,
     /// The suffix after the heredoc's closing delimiting identifier.
     stop_suffix: String,
+    /// True if the closing delimiting identifier must be on a line by itself,
+    /// after optional indentation. The stop prefix and suffix are then empty.
+    is_indented: bool,
 }
 
 /// Provide a method to handle special cases that don't fit within the current
@@ -134,6 +153,10 @@ enum SpecialCase {
     /// [block comments](https://www.mathworks.com/help/matlab/matlab_prog/comments.html)
     /// must start and end on a blank line.
     Matlab,
+    /// Python
+    /// [docstrings](https://peps.python.org/pep-0257/) are doc blocks. See
+    /// `lex_docstring`.
+    PythonDocstring,
 }
 
 /// Define a language by providing everything this lexer needs in order to split
@@ -146,7 +169,9 @@ pub struct LanguageLexer {
     pub lexer_name: Arc<String>,
     /// An array of file extensions for this language. They \_do not_begin with
     /// a period, such as `rs`. This is the typical way that the CodeChat Editor
-    /// uses to determine which lexer to use for a given source file.
+    /// uses to determine which lexer to use for a given source file. An entry
+    /// may also be a complete file name, such as `Makefile`, for files which
+    /// typically lack an extension.
     ext_arr: Vec<Arc<String>>,
     /// An array of strings which specify inline comment delimiters. Empty if
     /// this language doesn't provide inline comments.
@@ -157,13 +182,34 @@ pub struct LanguageLexer {
     /// Specify the strings supported by this language. While this could be
     /// empty, such a language would be very odd.
     string_delim_spec_arr: Vec<StringDelimiterSpec>,
-    /// A [heredoc](https://en.wikipedia.org/wiki/Here_document) delimiter;
-    /// `None` if heredocs aren't supported.
-    heredoc_delim: Option<HeredocDelim>,
+    /// [Heredoc](https://en.wikipedia.org/wiki/Here_document) delimiters,
+    /// such as raw strings. Empty if heredocs aren't supported.
+    heredoc_delim_arr: Vec<HeredocDelim>,
     /// Any special case treatment for this language.
     special_case: SpecialCase,
 }
 
+impl LanguageLexer {
+    // Return true if `delimiter` opens a docstring in this language.
+    pub fn is_docstring_delimiter(&self, delimiter: &str) -> bool {
+        matches!(self.special_case, SpecialCase::PythonDocstring)
+            && PYTHON_DOCSTRING_DELIMITERS.contains(&delimiter)
+    }
+
+    // Return true if `delimiter` opens a Javadoc-style block comment in this
+    // language.
+    pub fn is_javadoc_delimiter(&self, delimiter: &str) -> bool {
+        delimiter == JAVADOC_OPENING_DELIMITER
+            && self
+                .block_comment_delim_arr
+                .iter()
+                .any(|block_comment_delim| {
+                    block_comment_delim.opening == JAVADOC_OPENING_DELIMITER
+                        && block_comment_delim.closing == JAVADOC_CLOSING_DELIMITER
+                })
+    }
+}
+
 /// ### Compiled language definition
 // Store the results of compiling a language lexer.
 pub struct LanguageLexerCompiled {
@@ -216,11 +262,17 @@ enum RegexDelimType {
         Regex,
     ),
     Heredoc(
-        /// The regex-escaped `HeredocDelim.stop_prefix`.
+        /// The regex-escaped `HeredocDelim.stop_prefix`, or
+        /// `INDENTED_HEREDOC_STOP_PREFIX` for an indented heredoc.
         String,
-        /// The regex-escaped `HeredocDelim.stop_suffix`.
+        /// The regex-escaped `HeredocDelim.stop_suffix`, or
+        /// `INDENTED_HEREDOC_STOP_SUFFIX` for an indented heredoc.
         String,
     ),
+    /// The group, nested inside the preceding `Heredoc` group, which captures
+    /// the heredoc's delimiting identifier. It only matches along with that
+    /// group, so the lexer never classifies a token using it.
+    HeredocIdent,
     TemplateLiteral,
 }
 
@@ -229,6 +281,8 @@ enum RegexDelimType {
 #[derive(PartialEq)]
 // To allow printing with `println!`.
 #[derive(Debug)]
+// To allow reusing unchanged blocks when lexing incrementally.
+#[derive(Clone)]
 pub struct DocBlock {
     /// The whitespace characters which created the indent for this doc block.
     pub indent: String,
@@ -237,6 +291,10 @@ pub struct DocBlock {
     /// The contents of this block: documentation (with the comment delimiters
     /// removed).
     pub contents: String,
+    /// How the comment producing this block was written, where its contents
+    /// don't capture this: a list of words separated by spaces (see
+    /// `JAVADOC_NO_GUTTER`, for example). Usually empty.
+    pub style: String,
     /// The number of source code lines in this doc block. Only valid when
     /// converting from source code to its web-editable equivalent; in the
     /// opposite conversion (web-editable to source file), this is not valid
@@ -248,6 +306,8 @@ pub struct DocBlock {
 #[derive(PartialEq)]
 // To allow printing with `println!`.
 #[derive(Debug)]
+// To allow reusing unchanged blocks when lexing incrementally.
+#[derive(Clone)]
 pub enum CodeDocBlock {
     CodeBlock(
         // This contains the code defining this code block.
@@ -284,8 +344,45 @@ lazy_static! {
         "`"),
     ).unwrap();
 
-    /// A vector of all supported languages.
-    pub static ref LEXERS: LanguageLexersCompiled = compile_lexers(get_language_lexer_vec());
+    /// All supported languages: the built-in languages, plus any user-defined
+    /// languages added by `load_user_languages`.
+    static ref LEXERS: RwLock<Arc<LanguageLexersCompiled>> =
+        RwLock::new(Arc::new(compile_lexers(get_language_lexer_vec())));
+}
+
+// Return all supported languages.
+pub fn lexers() -> Arc<LanguageLexersCompiled> {
+    LEXERS.read().unwrap().clone()
+}
+
+// Add the languages defined by the user and by the project in `project_root`
+// (see [language_config.rs](lexer/language_config.rs)) to the supported
+// languages. Return the files these languages were loaded from.
+pub fn load_user_languages(project_root: Option<&Path>) -> Result<Vec<PathBuf>, String> {
+    let builtin_lexers = get_language_lexer_vec();
+    let mut names: HashSet<_> = builtin_lexers
+        .iter()
+        .map(|language_lexer| language_lexer.lexer_name.clone())
+        .collect();
+    let config_files = language_config_files(project_root);
+    let mut language_lexers = Vec::new();
+    for config_file in &config_files {
+        for language_lexer in load_language_config(config_file)? {
+            if !names.insert(language_lexer.lexer_name.clone()) {
+                return Err(format!(
+                    "Invalid language definition in {}: language {} is already defined.",
+                    config_file.display(),
+                    language_lexer.lexer_name
+                ));
+            }
+            language_lexers.push(language_lexer);
+        }
+    }
+    // Place user-defined languages first, so that their extensions take
+    // precedence over the built-in languages.
+    language_lexers.extend(builtin_lexers);
+    *LEXERS.write().unwrap() = Arc::new(compile_lexers(language_lexers));
+    Ok(config_files)
 }
 
 // Support C# verbatim string literals, which end with a `"`; a `""` inserts a
@@ -295,6 +392,31 @@ const C_SHARP_VERBATIM_STRING_CLOSING: &str =
     // string, followed by a double quote to end the string.
     r#"([^"]|"")*""#;
 
+// The regexes which surround the closing delimiting identifier of an indented
+// heredoc: it begins a line, after optional indentation, and ends that line.
+const INDENTED_HEREDOC_STOP_PREFIX: &str = "\n[ \t]*";
+const INDENTED_HEREDOC_STOP_SUFFIX: &str = "[ \t]*(?m:$)";
+
+// The delimiters of a Python docstring.
+const PYTHON_DOCSTRING_DELIMITERS: [&str; 2] = ["\"\"\"", "'''"];
+
+// The delimiters of a Javadoc-style comment (also used by Doxygen, JSDoc,
+// etc.).
+const JAVADOC_OPENING_DELIMITER: &str = "/**";
+const JAVADOC_CLOSING_DELIMITER: &str = "*/";
+
+/// The style of a Javadoc-style comment with no gutter, which is lexed and
+/// saved like any other block comment.
+pub const JAVADOC_NO_GUTTER: &str = "no-gutter";
+/// The style of a Javadoc-style comment whose text begins on the line
+/// containing the opening delimiter, as in `/** Summary.`.
+pub const JAVADOC_INLINE: &str = "inline";
+
+// Return true if the `style` of a doc block contains `word`.
+pub fn has_style(style: &str, word: &str) -> bool {
+    style.split(' ').any(|style_word| style_word == word)
+}
+
 /// ### Language "compiler"
 ///
 /// "Compile" a language description into regexes used to lex the language.
@@ -489,14 +611,26 @@ fn build_lexer_regex(
             (false, NewlineSupport::None) => Regex::new(&format!("{}|\n", &escaped_delimiter)),
         }
         .unwrap();
+        // Each prefix produces an opening delimiter; all share the same
+        // closing regex. The regex builder escapes these delimiters.
+        let opening_delim_arr = if string_delim_spec.prefix_arr.is_empty() {
+            vec![string_delim_spec.delimiter.clone()]
+        } else {
+            string_delim_spec
+                .prefix_arr
+                .iter()
+                .map(|prefix| prefix.to_string() + &string_delim_spec.delimiter)
+                .collect()
+        };
         regex_builder(
-            &[regex::escape(&string_delim_spec.delimiter)].to_vec(),
+            &opening_delim_arr,
             RegexDelimType::String(end_of_string_regex),
         );
     }
 
     match language_lexer.special_case {
-        SpecialCase::None => (),
+        // Docstrings are strings until the lexer examines them.
+        SpecialCase::None | SpecialCase::PythonDocstring => (),
         // A C# verbatim string has asymmetric opening and closing delimiters,
         // making it a special case.
         SpecialCase::CSharpVerbatimStringLiteral => regex_builder(
@@ -561,12 +695,15 @@ fn build_lexer_regex(
         }
     };
 
-    // This must be last, since it includes one group (so the index of all
-    // future items will be off by 1). Build a regex for a heredoc start.
-    let regex_str;
-    if let Some(heredoc_delim) = &language_lexer.heredoc_delim {
+    // Build a regex for each heredoc start. Place these last: when several
+    // delimiters match at the same location, the earliest in the regex wins,
+    // so that comments and strings take precedence over heredocs. Each regex
+    // includes a group which captures the delimiting identifier; add a
+    // placeholder for this group to the map, so that the group indices of any
+    // following heredocs remain correct.
+    for heredoc_delim in &language_lexer.heredoc_delim_arr {
         // First, create the string which defines the regex.
-        regex_str = format!(
+        let regex_str = format!(
             "{}({}){}",
             regex::escape(&heredoc_delim.start_prefix),
             heredoc_delim.delim_ident_regex,
@@ -574,10 +711,18 @@ fn build_lexer_regex(
         );
         // Then add it. Do this manually, since we don't want the regex escaped.
         regex_strings_arr.push(regex_str);
-        regex_group_map.push(RegexDelimType::Heredoc(
-            regex::escape(&heredoc_delim.stop_prefix),
-            regex::escape(&heredoc_delim.stop_suffix),
-        ));
+        regex_group_map.push(if heredoc_delim.is_indented {
+            RegexDelimType::Heredoc(
+                INDENTED_HEREDOC_STOP_PREFIX.to_string(),
+                INDENTED_HEREDOC_STOP_SUFFIX.to_string(),
+            )
+        } else {
+            RegexDelimType::Heredoc(
+                regex::escape(&heredoc_delim.stop_prefix),
+                regex::escape(&heredoc_delim.stop_suffix),
+            )
+        });
+        regex_group_map.push(RegexDelimType::HeredocIdent);
     }
 
     // Combine all this into a single regex, which is this or of each
@@ -631,9 +776,6 @@ pub fn compile_lexers(language_lexer_arr: Vec<LanguageLexer>) -> LanguageLexersC
 /// ## Source lexer
 ///
 /// This lexer categorizes source code into code blocks or doc blocks.
-///
-/// These linter warnings would IMHO make the code less readable.
-#[allow(clippy::bool_to_int_with_if)]
 pub fn source_lexer(
     // The source code to lex.
     source_code: &str,
@@ -641,6 +783,201 @@ pub fn source_lexer(
     language_lexer_compiled: &LanguageLexerCompiled,
     // The return value is an array of code and doc blocks.
 ) -> Vec<CodeDocBlock> {
+    source_lexer_resync(
+        &normalize_line_endings(source_code),
+        language_lexer_compiled,
+        Vec::new(),
+        0,
+        &[],
+    )
+    .0
+}
+
+// Normalize all line endings.
+fn normalize_line_endings(source_code: &str) -> String {
+    source_code.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// ## Incremental source lexer
+///
+/// The results of re-lexing an edited source file; see
+/// `source_lexer_incremental`.
+pub struct IncrementalLex {
+    /// The code and doc blocks for the edited source file; this is identical
+    /// to the result of calling `source_lexer` on it.
+    pub classified_source: Vec<CodeDocBlock>,
+    /// The index of the first block which differs from the previous blocks.
+    /// All blocks before this are identical to the previous blocks.
+    pub first_changed_index: usize,
+    /// The number of blocks at the end of `classified_source` which are
+    /// identical to the last blocks of the previous blocks.
+    pub unchanged_tail_len: usize,
+}
+
+/// Given the previous contents of a source file and the result of lexing them,
+/// lex the edited contents of this file. Rather than lexing the entire file,
+/// this begins at the start of the code block which contains the edit (a point
+/// where the lexer is known to be lexing plain code), then stops as soon as the
+/// lexer reaches the start of a code block which follows the edit in the
+/// previous blocks, since all blocks from there on are unchanged.
+pub fn source_lexer_incremental(
+    // The source code which produced `prev_classified_source`.
+    prev_source_code: &str,
+    // The result of lexing `prev_source_code` using `language_lexer_compiled`.
+    prev_classified_source: &[CodeDocBlock],
+    // The edited source code to lex.
+    source_code: &str,
+    // A description of the language, used to lex the `source_code`.
+    language_lexer_compiled: &LanguageLexerCompiled,
+) -> IncrementalLex {
+    let prev_source_code = normalize_line_endings(prev_source_code);
+    let source_code = normalize_line_endings(source_code);
+
+    // Find the edit: the bytes between the common prefix and the common suffix
+    // of the previous and the edited source code.
+    let mut prefix_len = prev_source_code
+        .bytes()
+        .zip(source_code.bytes())
+        .take_while(|(prev, new)| prev == new)
+        .count();
+    while !source_code.is_char_boundary(prefix_len) {
+        prefix_len -= 1;
+    }
+    let max_suffix_len = min(prev_source_code.len(), source_code.len()) - prefix_len;
+    let mut suffix_len = prev_source_code
+        .bytes()
+        .rev()
+        .zip(source_code.bytes().rev())
+        .take(max_suffix_len)
+        .take_while(|(prev, new)| prev == new)
+        .count();
+    while !source_code.is_char_boundary(source_code.len() - suffix_len) {
+        suffix_len -= 1;
+    }
+    let prev_edit_end = prev_source_code.len() - suffix_len;
+    let edit_end = source_code.len() - suffix_len;
+
+    // Find the index into the previous source code where each previous block
+    // starts. A code block's contents are the source code; a doc block spans
+    // `lines` lines of source code.
+    let mut block_start_arr = Vec::with_capacity(prev_classified_source.len());
+    let mut index = 0;
+    for code_doc_block in prev_classified_source {
+        block_start_arr.push(index);
+        match code_doc_block {
+            CodeDocBlock::CodeBlock(contents) => index += contents.len(),
+            CodeDocBlock::DocBlock(doc_block) => {
+                for _ in 0..doc_block.lines {
+                    index = match prev_source_code[index..].find('\n') {
+                        Some(newline_index) => index + newline_index + 1,
+                        None => prev_source_code.len(),
+                    };
+                }
+            }
+        }
+    }
+    // If the previous blocks don't match the previous source code, or if there
+    // are no previous blocks to reuse, lex everything.
+    if index != prev_source_code.len() || prev_classified_source.is_empty() {
+        return IncrementalLex {
+            classified_source: source_lexer(&source_code, language_lexer_compiled),
+            first_changed_index: 0,
+            unchanged_tail_len: 0,
+        };
+    }
+
+    // Find the block containing the last unchanged character before the edit.
+    // Changing the character following a block may change that block (for
+    // example, by extending a comment), so this block must be re-lexed.
+    let edit_block_index = block_start_arr
+        .partition_point(|block_start| *block_start < prefix_len)
+        .saturating_sub(1);
+    // Back up to the nearest code block at or before this block. Since each
+    // code block either begins the file or follows a doc block, the lexer is
+    // lexing plain code at its start.
+    let resync_block_index = (0..=edit_block_index)
+        .rev()
+        .find(|block_index| {
+            matches!(
+                prev_classified_source[*block_index],
+                CodeDocBlock::CodeBlock(_)
+            )
+        })
+        .unwrap_or(0);
+
+    // Lexing may stop at the start of any code block which follows the edit:
+    // the lexer is lexing plain code there both before and after the edit, and
+    // the source code which follows is unchanged, so the rest of the blocks are
+    // unchanged. Record these locations in the edited source code. Python
+    // docstrings depend on the code preceding them, which the edit may have
+    // changed; for these, lex to the end of the file.
+    let mut resync_block_index_arr = Vec::new();
+    let mut resync_index_arr = Vec::new();
+    if !matches!(
+        language_lexer_compiled.language_lexer.special_case,
+        SpecialCase::PythonDocstring
+    ) {
+        for (block_index, code_doc_block) in prev_classified_source.iter().enumerate() {
+            let block_start = block_start_arr[block_index];
+            if block_start >= prev_edit_end
+                && block_index > resync_block_index
+                && matches!(code_doc_block, CodeDocBlock::CodeBlock(_))
+            {
+                resync_block_index_arr.push(block_index);
+                resync_index_arr.push(block_start - prev_edit_end + edit_end);
+            }
+        }
+    }
+
+    // Lex, starting with the unchanged blocks preceding the resync block.
+    let (mut classified_source, resync) = source_lexer_resync(
+        &source_code,
+        language_lexer_compiled,
+        prev_classified_source[..resync_block_index].to_vec(),
+        block_start_arr[resync_block_index],
+        &resync_index_arr,
+    );
+    // The last unchanged block may have been extended by a doc block
+    // immediately following it.
+    let first_changed_index = if resync_block_index > 0
+        && classified_source.get(resync_block_index - 1)
+            != prev_classified_source.get(resync_block_index - 1)
+    {
+        resync_block_index - 1
+    } else {
+        resync_block_index
+    };
+    // Append the unchanged blocks following the resync point.
+    let unchanged_tail_len = if let Some(resync) = resync {
+        let unchanged_tail = &prev_classified_source[resync_block_index_arr[resync]..];
+        classified_source.extend_from_slice(unchanged_tail);
+        unchanged_tail.len()
+    } else {
+        0
+    };
+
+    IncrementalLex {
+        classified_source,
+        first_changed_index,
+        unchanged_tail_len,
+    }
+}
+
+// Lex `source_code` (whose line endings must already be normalized) starting
+// at `start_index`, which must be a location where the lexer is lexing plain
+// code, appending the results to `classified_source`. If the lexer reaches the
+// start of a new code block at one of the locations in the sorted
+// `resync_index_arr`, stop lexing and return the index of that location.
+//
+// These linter warnings would IMHO make the code less readable.
+#[allow(clippy::bool_to_int_with_if)]
+fn source_lexer_resync(
+    source_code: &str,
+    language_lexer_compiled: &LanguageLexerCompiled,
+    mut classified_source: Vec<CodeDocBlock>,
+    start_index: usize,
+    resync_index_arr: &[usize],
+) -> (Vec<CodeDocBlock>, Option<usize>) {
     // Rather than attempt to lex the entire language, this lexer's only goal is
     // to categorize all the source code into code blocks or doc blocks. To do
     // it, it only needs to:
@@ -681,8 +1018,7 @@ pub fn source_lexer(
     // Provide a method to intelligently append to the code/doc block vec. Empty
     // appends are ignored; appends of the same type append to `contents`
     // instead of creating a new entry.
-    let mut classified_source: Vec<CodeDocBlock> = Vec::new();
-    let mut append_code_doc_block = |indent: &str, delimiter: &str, contents: &str| {
+    let mut append_code_doc_block = |indent: &str, delimiter: &str, style: &str, contents: &str| {
         // Don't append empty entries.
         if delimiter.is_empty() && contents.is_empty() {
             assert!(indent.is_empty());
@@ -705,7 +1041,10 @@ pub fn source_lexer(
             let end = classified_source.len() - 1;
             match classified_source[end] {
                 CodeDocBlock::DocBlock(ref mut last_doc_block) => {
-                    if last_doc_block.indent == indent && last_doc_block.delimiter == delimiter {
+                    if last_doc_block.indent == indent
+                        && last_doc_block.delimiter == delimiter
+                        && last_doc_block.style == style
+                    {
                         // Yes, so append the provided contents to it. We must
                         // access the array directly since `last_doc_block`
                         // provides only a reference.
@@ -732,6 +1071,7 @@ pub fn source_lexer(
                 indent: indent.to_string(),
                 delimiter: delimiter.to_string(),
                 contents: contents.to_string(),
+                style: style.to_string(),
                 lines,
             })
         });
@@ -739,17 +1079,26 @@ pub fn source_lexer(
 
     // ### Main loop
     //
-    // Normalize all line endings.
-    let source_code = source_code.replace("\r\n", "\n").replace('\r', "\n");
     // This index marks the start of code that hasn't been lexed.
-    let mut source_code_unlexed_index: usize = 0;
+    let mut source_code_unlexed_index: usize = start_index;
     // Ths index marks the start of code that belongs to the current code block.
     // The current code block is always defined as
     // `source_code[current_code_block_index..source_code_unlexed_index]`.
-    let mut current_code_block_index: usize = 0;
+    let mut current_code_block_index: usize = start_index;
+    // The index into `resync_index_arr` where lexing stopped, if it stopped
+    // early.
+    let mut resync = None;
 
     // Main loop: lex the provided source code.
     while source_code_unlexed_index < source_code.len() {
+        // The current code block is empty only at the start of lexing or after
+        // a doc block; if this is a resync point, stop.
+        if current_code_block_index == source_code_unlexed_index {
+            if let Ok(index) = resync_index_arr.binary_search(&source_code_unlexed_index) {
+                resync = Some(index);
+                break;
+            }
+        }
         #[cfg(feature = "lexer_explain")]
         println!(
             "Searching the following source_code using the pattern {:?}:\n'{}'\n\nThe current code block is '{}'\n",
@@ -779,6 +1128,9 @@ pub fn source_lexer(
             // Move everything preceding this match from `source_code` to the
             // current code block, since per the assumptions this is code.
             source_code_unlexed_index += classify_match.get(matching_group_index).unwrap().start();
+            // Save this location, since `append_code` borrows
+            // `source_code_unlexed_index`.
+            let token_index = source_code_unlexed_index;
 
             #[cfg(feature = "lexer_explain")]
             println!(
@@ -905,7 +1257,7 @@ pub fn source_lexer(
                     {
                         // This is a doc block. Transition from the preceding
                         // code block to this doc block.
-                        append_code_doc_block("", "", code_lines_before_comment);
+                        append_code_doc_block("", "", "", code_lines_before_comment);
 
                         // Add this doc block by pushing the array \[whitespace
                         // before the inline comment, inline comment contents,
@@ -916,7 +1268,12 @@ pub fn source_lexer(
                         // contents, omit the leading space if it's there (this
                         // might be just a newline or an EOF).
                         let contents = &full_comment[if has_space_after_comment { 1 } else { 0 }..];
-                        append_code_doc_block(comment_line_prefix, matching_group_str, contents);
+                        append_code_doc_block(
+                            comment_line_prefix,
+                            matching_group_str,
+                            "",
+                            contents,
+                        );
 
                         #[cfg(feature = "lexer_explain")]
                         println!(
@@ -1054,7 +1411,11 @@ pub fn source_lexer(
                                 opening_delimiter.start(),
                                 opening_delimiter.len()
                             );
-                            source_code_unlexed_index +=
+                            // Since `opening_delimiter` was found by searching
+                            // from `comment_start_index`, this is the absolute
+                            // index of the nested opening delimiter, which
+                            // replaces (rather than adds to) the current index.
+                            source_code_unlexed_index =
                                 comment_start_index + opening_delimiter.start();
                             comment_start_index =
                                 source_code_unlexed_index + opening_delimiter.len();
@@ -1188,7 +1549,7 @@ pub fn source_lexer(
                             {
                                 // Put the `code_lines_before_comment` into the
                                 // code block.
-                                append_code_doc_block("", "", code_lines_before_comment);
+                                append_code_doc_block("", "", "", code_lines_before_comment);
 
                                 // If there's a space at the end of the comment
                                 // body, remove it; also remove the initial
@@ -1331,8 +1692,38 @@ pub fn source_lexer(
                                     contents
                                 };
 
+                                // Javadoc-style comments place a gutter
+                                // (` * `) before each line; remove it. Record
+                                // the style of these comments, so they can be
+                                // saved in the same style.
+                                let (javadoc_contents, style) = if language_lexer_compiled
+                                    .language_lexer
+                                    .is_javadoc_delimiter(delimiter)
+                                {
+                                    match lex_javadoc(
+                                        indent,
+                                        &source_code
+                                            [comment_start_index..source_code_unlexed_index],
+                                    ) {
+                                        Some((javadoc_contents, true)) => {
+                                            (Some(javadoc_contents), JAVADOC_INLINE)
+                                        }
+                                        Some((javadoc_contents, false)) => {
+                                            (Some(javadoc_contents), "")
+                                        }
+                                        None => (None, JAVADOC_NO_GUTTER),
+                                    }
+                                } else {
+                                    (None, "")
+                                };
+
                                 // Add this doc block:
-                                append_code_doc_block(indent, delimiter, dedented_contents);
+                                append_code_doc_block(
+                                    indent,
+                                    delimiter,
+                                    style,
+                                    javadoc_contents.as_deref().unwrap_or(dedented_contents),
+                                );
 
                                 // print the doc block
                                 #[cfg(feature = "lexer_explain")]
@@ -1360,7 +1751,34 @@ pub fn source_lexer(
                 RegexDelimType::String(closing_regex) => {
                     #[cfg(feature = "lexer_explain")]
                     print!("This is a string. ");
-                    append_code(closing_regex)
+                    let docstring = if language_lexer_compiled
+                        .language_lexer
+                        .is_docstring_delimiter(matching_group_str)
+                    {
+                        lex_docstring(source_code, token_index, matching_group_str, closing_regex)
+                    } else {
+                        None
+                    };
+                    if let Some((line_start_index, contents, end_index)) = docstring {
+                        // This string is a doc block. Transition from the
+                        // preceding code block to this doc block.
+                        append_code_doc_block(
+                            "",
+                            "",
+                            "",
+                            &source_code[current_code_block_index..line_start_index],
+                        );
+                        append_code_doc_block(
+                            &source_code[line_start_index..token_index],
+                            matching_group_str,
+                            "",
+                            &contents,
+                        );
+                        source_code_unlexed_index = end_index;
+                        current_code_block_index = end_index;
+                    } else {
+                        append_code(closing_regex)
+                    }
                 }
 
                 RegexDelimType::TemplateLiteral => {
@@ -1374,8 +1792,9 @@ pub fn source_lexer(
                     print!("This is a heredoc. ");
 
                     // Get the string from the source code which (along with the
-                    // stop prefix/suffix) defines the end of the heredoc.
-                    let heredoc_string = &classify_match[language_lexer_compiled.map.len() + 1];
+                    // stop prefix/suffix) defines the end of the heredoc. It's
+                    // captured by the group following this heredoc's group.
+                    let heredoc_string = &classify_match[matching_group_index + 1];
                     // Make a regex from it.
                     let closing_regex = Regex::new(
                         &(stop_prefix.to_owned() + &regex::escape(heredoc_string) + stop_suffix),
@@ -1385,6 +1804,9 @@ pub fn source_lexer(
                     // `current_source_code`.
                     append_code(&closing_regex);
                 }
+
+                // The heredoc's group always matches first.
+                RegexDelimType::HeredocIdent => unreachable!(),
             }
         } else {
             // There's no match, so the rest of the source code belongs in the
@@ -1393,10 +1815,210 @@ pub fn source_lexer(
         }
     }
 
-    // Any leftover code is source code.
-    append_code_doc_block("", "", &source_code[current_code_block_index..]);
+    // Any leftover code is source code, unless lexing stopped at a resync
+    // point.
+    if resync.is_none() {
+        append_code_doc_block("", "", "", &source_code[current_code_block_index..]);
+    }
+
+    (classified_source, resync)
+}
+
+// ### Docstrings
+//
+// Determine if the Python triple-quoted string starting at `string_index` in
+// `source_code` is a docstring which can be represented as a doc block. The
+// criteria:
+//
+// 1.  The string is in docstring position: it's the first statement in the
+//     file, or the first statement after a line ending with a colon (such as
+//     a `def` or `class` statement). Blank lines and comments are ignored.
+// 2.  Only whitespace precedes the opening delimiter on its line; this
+//     becomes the indent. Nothing follows the closing delimiter on its line.
+// 3.  Every line after the first either begins with the indent or is blank.
+// 4.  Translating the resulting doc block back to source code (see
+//     `docstring_to_source`) reproduces this docstring exactly.
+//
+// If so, return the index of the start of the line containing the docstring,
+// the contents of the doc block, and the index of the end of the line
+// containing the closing delimiter.
+fn lex_docstring(
+    source_code: &str,
+    string_index: usize,
+    delimiter: &str,
+    closing_regex: &Regex,
+) -> Option<(usize, String, usize)> {
+    let line_start_index = source_code[..string_index]
+        .rfind('\n')
+        .map_or(0, |index| index + 1);
+    let indent = &source_code[line_start_index..string_index];
+    let is_docstring_position = match source_code[..line_start_index]
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+    {
+        // This is the first statement in the file.
+        None => true,
+        Some(line) => line.ends_with(':'),
+    };
+    if !WHITESPACE_ONLY_REGEX.is_match(indent) || !is_docstring_position {
+        return None;
+    }
+
+    // Find the body of the string and the remainder of its last line.
+    let body_index = string_index + delimiter.len();
+    let closing_index = body_index + closing_regex.find(&source_code[body_index..])?.end();
+    let body = &source_code[body_index..closing_index - delimiter.len()];
+    let end_index = source_code[closing_index..]
+        .find('\n')
+        .map_or(source_code.len(), |index| closing_index + index + 1);
+    let post_closing_delimiter_line = &source_code[closing_index..end_index];
+    if !post_closing_delimiter_line.is_empty() && post_closing_delimiter_line != "\n" {
+        return None;
+    }
+
+    // Remove the indent from every line after the first.
+    let mut lines = body.split_inclusive('\n');
+    let mut contents = lines.next().unwrap_or("").to_string();
+    for line in lines {
+        contents += if line == "\n" {
+            line
+        } else {
+            line.strip_prefix(indent)?
+        };
+    }
+    contents += post_closing_delimiter_line;
+
+    (docstring_to_source(indent, delimiter, &contents) == source_code[line_start_index..end_index])
+        .then_some((line_start_index, contents, end_index))
+}
+
+// Translate a docstring doc block back to source code: the inverse of
+// `lex_docstring`.
+pub fn docstring_to_source(indent: &str, delimiter: &str, contents: &str) -> String {
+    // The newline ending the docstring's last line isn't part of its body.
+    let (body, newline) = match contents.strip_suffix('\n') {
+        Some(body) => (body, "\n"),
+        None => (contents, ""),
+    };
+    // Escape anything in the body which would end the docstring early.
+    let quote = &delimiter[..1];
+    let mut body = body.replace(delimiter, &format!("\\{delimiter}"));
+    if body.ends_with(quote) {
+        body.insert(body.len() - 1, '\\');
+    }
+
+    // Indent every line after the first, except for blank lines.
+    let mut lines = body.split_inclusive('\n');
+    let mut source = format!("{indent}{delimiter}{}", lines.next().unwrap_or(""));
+    for line in lines {
+        if line != "\n" {
+            source += indent;
+        }
+        source += line;
+    }
+    // When the closing delimiter is on its own line, indent it.
+    if body.ends_with('\n') {
+        source += indent;
+    }
+    source + delimiter + newline
+}
+
+// ### Javadoc-style comments
+//
+// Determine if a Javadoc-style comment is written with a gutter:
+//
+// ```Java
+// /**
+//  * Each line begins with the indent, then a gutter of ` * `.
+//  *
+//  * Blank lines contain only ` *`.
+//  */
+// ```
+//
+// Text may also follow the opening delimiter, as in `/** Summary.`.
+// `comment` is the text after the opening delimiter through the end of the
+// line containing the closing delimiter. If so, return the contents of the doc
+// block: the text of each line with its gutter removed, followed by a newline
+// for the line containing the closing delimiter. This keeps the number of lines
+// in the doc block the same as the comment. Also return true if text follows
+// the opening delimiter.
+fn lex_javadoc(indent: &str, comment: &str) -> Option<(String, bool)> {
+    let closing_index = comment.rfind(JAVADOC_CLOSING_DELIMITER)?;
+    let post_closing_delimiter_line = &comment[closing_index + JAVADOC_CLOSING_DELIMITER.len()..];
+    let mut lines: Vec<&str> = comment[..closing_index].split('\n').collect();
+    // The closing delimiter must be on its own line, so there must be at least
+    // two lines.
+    let last_line = lines.pop()?;
+    if lines.is_empty() || !WHITESPACE_ONLY_REGEX.is_match(last_line) {
+        return None;
+    }
+    let first_line = lines.remove(0).trim();
+    let is_inline = !first_line.is_empty();
+    let mut contents = first_line.to_string() + "\n";
+    for line in lines {
+        let gutter_line = line.strip_prefix(indent)?.strip_prefix(" *")?;
+        if !gutter_line.trim().is_empty() {
+            contents += gutter_line.strip_prefix(' ')?;
+        }
+        contents += "\n";
+    }
+    // An empty comment isn't a doc block.
+    if contents.trim().is_empty() {
+        return None;
+    }
+    if post_closing_delimiter_line.contains('\n') {
+        contents += "\n";
+    }
+    Some((contents, is_inline))
+}
 
-    classified_source
+// Translate a Javadoc-style doc block back to source code: the inverse of
+// `lex_javadoc`. Contents which span several lines are written with a gutter,
+// placing the text of the first line after the opening delimiter if
+// `is_inline`; otherwise, this produces a one-line comment.
+pub fn javadoc_to_source(indent: &str, contents: &str, is_inline: bool) -> String {
+    // The newline ending the comment's last line isn't part of its body.
+    let (body, newline) = match contents.strip_suffix('\n') {
+        Some(body) => (body, "\n"),
+        None => (contents, ""),
+    };
+    // Escape anything in the body which would end the comment early.
+    let body = body.replace(JAVADOC_CLOSING_DELIMITER, "*&#47;");
+    if !body.contains('\n') {
+        return if body.is_empty() {
+            format!("{indent}{JAVADOC_OPENING_DELIMITER} {JAVADOC_CLOSING_DELIMITER}{newline}")
+        } else {
+            format!(
+                "{indent}{JAVADOC_OPENING_DELIMITER} {body} {JAVADOC_CLOSING_DELIMITER}{newline}"
+            )
+        };
+    }
+    // The closing delimiter goes on its own line.
+    let body = body.strip_prefix('\n').unwrap_or(&body);
+    let body = body.strip_suffix('\n').unwrap_or(body);
+    let mut lines = body.split('\n');
+    let mut source = format!("{indent}{JAVADOC_OPENING_DELIMITER}");
+    if is_inline {
+        // `split` always returns at least one item.
+        let first_line = lines.next().unwrap();
+        if !first_line.is_empty() {
+            source += " ";
+            source += first_line;
+        }
+    }
+    source += "\n";
+    for line in lines {
+        source += indent;
+        source += " *";
+        if !line.is_empty() {
+            source += " ";
+            source += line;
+        }
+        source += "\n";
+    }
+    format!("{source}{indent} {JAVADOC_CLOSING_DELIMITER}{newline}")
 }
 
 // ## Tests
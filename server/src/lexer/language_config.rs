@@ -0,0 +1,374 @@
+/// Copyright (C) 2023 Bryan A. Jones.
+///
+/// This file is part of the CodeChat Editor. The CodeChat Editor is free
+/// software: you can redistribute it and/or modify it under the terms of the
+/// GNU General Public License as published by the Free Software Foundation,
+/// either version 3 of the License, or (at your option) any later version.
+///
+/// The CodeChat Editor is distributed in the hope that it will be useful, but
+/// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+/// or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+/// more details.
+///
+/// You should have received a copy of the GNU General Public License along with
+/// the CodeChat Editor. If not, see
+/// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+///
+/// # `language_config.rs` - Load user-defined languages
+///
+/// In addition to the [built-in languages](supported_languages.rs), users may
+/// define languages in a `codechat-languages.toml` (or
+/// `codechat-languages.json`) file, placed either in the root of a project
+/// (next to its `toc.md`) or in the `codechat-editor` subdirectory of the
+/// user's config directory. For example:
+///
+/// ```toml
+/// [[language]]
+/// name = "pascal"
+/// extensions = ["pas", "pp"]
+/// inline_comment_delimiters = ["//"]
+/// block_comment_delimiters = [
+///     { opening = "{", closing = "}" },
+///     { opening = "(*", closing = "*)" },
+/// ]
+/// strings = [{ delimiter = "'", newline_support = "none" }]
+/// ```
+///
+/// A string may also list the `prefixes` which precede its opening delimiter,
+/// such as `prefixes = ["r", "R"]` for a raw string. A JSON file contains the
+/// same structure: `{"language": [{"name": ...}]}`. Heredocs and special cases
+/// aren't supported by user-defined languages.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+
+// ### Local
+use super::{BlockCommentDelim, LanguageLexer, NewlineSupport, SpecialCase, StringDelimiterSpec};
+
+// ## Globals
+/// The name (without an extension) of a file defining languages.
+pub const LANGUAGE_CONFIG_NAME: &str = "codechat-languages";
+
+lazy_static! {
+    /// A lexer name must be usable in a lexer directive (see
+    /// `LEXER_DIRECTIVE` in `processing.rs`).
+    static ref LEXER_NAME: Regex = Regex::new(r"^\w+$").unwrap();
+}
+
+// ## Data structures
+//
+// These mirror the [language definition](../lexer.rs#data-structures), in a
+// form that's convenient to write by hand.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LanguageConfig {
+    language: Vec<LanguageDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LanguageDefinition {
+    name: String,
+    extensions: Vec<String>,
+    #[serde(default)]
+    inline_comment_delimiters: Vec<String>,
+    #[serde(default)]
+    block_comment_delimiters: Vec<BlockCommentDefinition>,
+    #[serde(default)]
+    strings: Vec<StringDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockCommentDefinition {
+    opening: String,
+    closing: String,
+    #[serde(default)]
+    nestable: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StringDefinition {
+    #[serde(default)]
+    prefixes: Vec<String>,
+    delimiter: String,
+    #[serde(default)]
+    escape_char: String,
+    newline_support: NewlineSupport,
+}
+
+// ## Code
+//
+// Return the language definition files which exist, in the order they should
+// be loaded: first the user's, then the project's.
+pub fn language_config_files(project_root: Option<&Path>) -> Vec<PathBuf> {
+    let dirs = dirs::config_dir()
+        .map(|config_dir| config_dir.join("codechat-editor"))
+        .into_iter()
+        .chain(project_root.map(Path::to_path_buf));
+    let mut files = Vec::new();
+    for dir in dirs {
+        for ext in ["toml", "json"] {
+            let file_path = dir.join(format!("{LANGUAGE_CONFIG_NAME}.{ext}"));
+            if file_path.is_file() {
+                files.push(file_path);
+            }
+        }
+    }
+    files
+}
+
+// Load and validate the languages defined in `file_path`.
+pub fn load_language_config(file_path: &Path) -> Result<Vec<LanguageLexer>, String> {
+    let contents = fs::read_to_string(file_path)
+        .map_err(|err| format!("Unable to read {}: {err}", file_path.display()))?;
+    let is_json = file_path.extension().is_some_and(|ext| ext == "json");
+    parse_language_config(&contents, is_json).map_err(|err| {
+        format!(
+            "Invalid language definition in {}: {err}",
+            file_path.display()
+        )
+    })
+}
+
+fn parse_language_config(contents: &str, is_json: bool) -> Result<Vec<LanguageLexer>, String> {
+    let language_config: LanguageConfig = if is_json {
+        serde_json::from_str(contents).map_err(|err| err.to_string())?
+    } else {
+        toml::from_str(contents).map_err(|err| err.to_string())?
+    };
+    language_config
+        .language
+        .into_iter()
+        .enumerate()
+        .map(|(index, language_definition)| {
+            // Identify the language by name if possible; otherwise, by its
+            // position in the file.
+            let name = if language_definition.name.is_empty() {
+                format!("language {}", index + 1)
+            } else {
+                format!("language {}", language_definition.name)
+            };
+            to_language_lexer(language_definition).map_err(|err| format!("{name}: {err}."))
+        })
+        .collect()
+}
+
+// Check a language definition, then convert it to a `LanguageLexer`.
+fn to_language_lexer(language_definition: LanguageDefinition) -> Result<LanguageLexer, String> {
+    if !LEXER_NAME.is_match(&language_definition.name) {
+        return Err("the name must contain only letters, digits, and underscores".to_string());
+    }
+    if language_definition.extensions.is_empty() {
+        return Err("at least one extension is required".to_string());
+    }
+    for ext in &language_definition.extensions {
+        if ext.is_empty() || ext.starts_with('.') {
+            return Err(format!(
+                "the extension \"{ext}\" must be non-empty and not begin with a period"
+            ));
+        }
+    }
+    if language_definition.inline_comment_delimiters.is_empty()
+        && language_definition.block_comment_delimiters.is_empty()
+    {
+        return Err("at least one inline or block comment delimiter is required".to_string());
+    }
+    if language_definition
+        .inline_comment_delimiters
+        .iter()
+        .any(String::is_empty)
+    {
+        return Err("an inline comment delimiter is empty".to_string());
+    }
+    for block_comment in &language_definition.block_comment_delimiters {
+        if block_comment.opening.is_empty() || block_comment.closing.is_empty() {
+            return Err("a block comment delimiter is empty".to_string());
+        }
+        if block_comment.nestable && block_comment.opening == block_comment.closing {
+            return Err(format!(
+                "the nestable block comment delimiter \"{}\" must have different opening and closing delimiters",
+                block_comment.opening
+            ));
+        }
+    }
+    for string in &language_definition.strings {
+        if string.delimiter.is_empty() {
+            return Err("a string delimiter is empty".to_string());
+        }
+        if matches!(string.newline_support, NewlineSupport::Escaped)
+            && string.escape_char.is_empty()
+        {
+            return Err(format!(
+                "the string delimiter \"{}\" supports escaped newlines, but has no escape character",
+                string.delimiter
+            ));
+        }
+    }
+
+    Ok(LanguageLexer {
+        lexer_name: Arc::new(language_definition.name),
+        ext_arr: language_definition
+            .extensions
+            .into_iter()
+            .map(Arc::new)
+            .collect(),
+        inline_comment_delim_arr: language_definition.inline_comment_delimiters,
+        block_comment_delim_arr: language_definition
+            .block_comment_delimiters
+            .into_iter()
+            .map(|block_comment| BlockCommentDelim {
+                opening: block_comment.opening,
+                closing: block_comment.closing,
+                is_nestable: block_comment.nestable,
+            })
+            .collect(),
+        string_delim_spec_arr: language_definition
+            .strings
+            .into_iter()
+            .map(|string| StringDelimiterSpec {
+                prefix_arr: string.prefixes,
+                delimiter: string.delimiter,
+                escape_char: string.escape_char,
+                newline_support: string.newline_support,
+            })
+            .collect(),
+        heredoc_delim_arr: Vec::new(),
+        special_case: SpecialCase::None,
+    })
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{load_language_config, parse_language_config};
+    use crate::lexer::{compile_lexers, source_lexer, CodeDocBlock, DocBlock};
+    use crate::prep_test_dir;
+
+    #[test]
+    fn test_load_language_config() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+
+        // Both formats produce a usable lexer.
+        for file_name in ["codechat-languages.toml", "codechat-languages.json"] {
+            let language_lexers = load_language_config(&test_dir.join(file_name)).unwrap();
+            assert_eq!(language_lexers.len(), 1);
+            let llc = compile_lexers(language_lexers);
+            let pascal = llc.map_ext_to_lexer_vec.get(&String::from("pas")).unwrap();
+            assert_eq!(pascal[0].language_lexer.lexer_name.as_str(), "pascal");
+            assert_eq!(
+                source_lexer("x := '{';\n{ Note }\n", &pascal[0]),
+                [
+                    CodeDocBlock::CodeBlock("x := '{';\n".to_string()),
+                    CodeDocBlock::DocBlock(DocBlock {
+                        indent: "".to_string(),
+                        delimiter: "{".to_string(),
+                        contents: "Note\n".to_string(),
+                        style: "".to_string(),
+                        lines: 1,
+                    }),
+                ]
+            );
+        }
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_parse_language_config() {
+        let parse_err = |language: &str| {
+            parse_language_config(&format!("[[language]]\n{language}"), false)
+                .err()
+                .unwrap()
+        };
+        assert_eq!(
+            parse_err(
+                "name = \"my lang\"\nextensions = [\"x\"]\ninline_comment_delimiters = [\"#\"]"
+            ),
+            "language my lang: the name must contain only letters, digits, and underscores."
+        );
+        assert_eq!(
+            parse_err("name = \"x\"\nextensions = []\ninline_comment_delimiters = [\"#\"]"),
+            "language x: at least one extension is required."
+        );
+        assert_eq!(
+            parse_err("name = \"x\"\nextensions = [\".x\"]\ninline_comment_delimiters = [\"#\"]"),
+            "language x: the extension \".x\" must be non-empty and not begin with a period."
+        );
+        assert_eq!(
+            parse_err("name = \"x\"\nextensions = [\"x\"]"),
+            "language x: at least one inline or block comment delimiter is required."
+        );
+        assert_eq!(
+            parse_err(
+                "name = \"x\"\nextensions = [\"x\"]\nblock_comment_delimiters = [{ opening = \"|\", closing = \"|\", nestable = true }]"
+            ),
+            "language x: the nestable block comment delimiter \"|\" must have different opening and closing delimiters."
+        );
+        assert_eq!(
+            parse_err(
+                "name = \"x\"\nextensions = [\"x\"]\ninline_comment_delimiters = [\"#\"]\nstrings = [{ delimiter = \"'\", newline_support = \"escaped\" }]"
+            ),
+            "language x: the string delimiter \"'\" supports escaped newlines, but has no escape character."
+        );
+        // Strings may have prefixes.
+        let language_lexers = parse_language_config(
+            "[[language]]\nname = \"x\"\nextensions = [\"x\"]\ninline_comment_delimiters = [\"#\"]\nstrings = [{ prefixes = [\"r\"], delimiter = \"'\", newline_support = \"none\" }, { delimiter = \"'\", escape_char = \"\\\\\", newline_support = \"none\" }]",
+            false,
+        )
+        .unwrap();
+        let llc = compile_lexers(language_lexers);
+        let x = llc.map_mode_to_lexer.get(&String::from("x")).unwrap();
+        assert_eq!(
+            source_lexer("r'\\' '\\'#'\n# Note\n", x),
+            [
+                CodeDocBlock::CodeBlock("r'\\' '\\'#'\n".to_string()),
+                CodeDocBlock::DocBlock(DocBlock {
+                    indent: "".to_string(),
+                    delimiter: "#".to_string(),
+                    contents: "Note\n".to_string(),
+                    style: "".to_string(),
+                    lines: 1,
+                }),
+            ]
+        );
+        // String delimiters may contain regex metacharacters.
+        let language_lexers = parse_language_config(
+            "[[language]]\nname = \"x\"\nextensions = [\"x\"]\ninline_comment_delimiters = [\"#\"]\nstrings = [{ delimiter = \"$$\", newline_support = \"unescaped\" }]",
+            false,
+        )
+        .unwrap();
+        let llc = compile_lexers(language_lexers);
+        let x = llc.map_mode_to_lexer.get(&String::from("x")).unwrap();
+        assert_eq!(
+            source_lexer("$$\n# Code\n$$\n# Note\n", x),
+            [
+                CodeDocBlock::CodeBlock("$$\n# Code\n$$\n".to_string()),
+                CodeDocBlock::DocBlock(DocBlock {
+                    indent: "".to_string(),
+                    delimiter: "#".to_string(),
+                    contents: "Note\n".to_string(),
+                    style: "".to_string(),
+                    lines: 1,
+                }),
+            ]
+        );
+
+        // Syntax errors and unknown fields are reported by the parser.
+        assert!(
+            parse_err("name = \"x\"\nextension = [\"x\"]").contains("unknown field `extension`")
+        );
+    }
+}
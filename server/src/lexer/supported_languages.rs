@@ -62,7 +62,7 @@ fn make_language_lexer(
     inline_comment_delim_arr: &[&str],
     block_comment_delim_arr: &[BlockCommentDelim],
     string_delim_spec_arr: &[StringDelimiterSpec],
-    heredoc_delim: Option<HeredocDelim>,
+    heredoc_delim_arr: &[HeredocDelim],
     special_case: SpecialCase,
 ) -> LanguageLexer {
     LanguageLexer {
@@ -74,7 +74,7 @@ fn make_language_lexer(
             .collect(),
         block_comment_delim_arr: block_comment_delim_arr.to_vec(),
         string_delim_spec_arr: string_delim_spec_arr.to_vec(),
-        heredoc_delim,
+        heredoc_delim_arr: heredoc_delim_arr.to_vec(),
         special_case,
     }
 }
@@ -83,8 +83,18 @@ fn make_string_delimiter_spec(
     delimiter: &str,
     escape_char: &str,
     newline_support: NewlineSupport,
+) -> StringDelimiterSpec {
+    make_prefixed_string_delimiter_spec(&[], delimiter, escape_char, newline_support)
+}
+
+fn make_prefixed_string_delimiter_spec(
+    prefix_arr: &[&str],
+    delimiter: &str,
+    escape_char: &str,
+    newline_support: NewlineSupport,
 ) -> StringDelimiterSpec {
     StringDelimiterSpec {
+        prefix_arr: prefix_arr.iter().map(|x| x.to_string()).collect(),
         delimiter: delimiter.to_string(),
         escape_char: escape_char.to_string(),
         newline_support,
@@ -97,14 +107,28 @@ fn make_heredoc_delim(
     start_suffix: &str,
     stop_prefix: &str,
     stop_suffix: &str,
-) -> Option<HeredocDelim> {
-    Some(HeredocDelim {
+) -> HeredocDelim {
+    HeredocDelim {
         start_prefix: start_prefix.to_string(),
         delim_ident_regex: delim_ident_regex.to_string(),
         start_suffix: start_suffix.to_string(),
         stop_prefix: stop_prefix.to_string(),
         stop_suffix: stop_suffix.to_string(),
-    })
+        is_indented: false,
+    }
+}
+
+// Define a heredoc whose closing delimiting identifier may be indented, such as
+// Ruby's `<<~EOS`.
+fn make_indented_heredoc_delim(
+    start_prefix: &str,
+    delim_ident_regex: &str,
+    start_suffix: &str,
+) -> HeredocDelim {
+    HeredocDelim {
+        is_indented: true,
+        ..make_heredoc_delim(start_prefix, delim_ident_regex, start_suffix, "", "")
+    }
 }
 
 fn make_block_comment_delim(opening: &str, closing: &str, is_nestable: bool) -> BlockCommentDelim {
@@ -128,10 +152,31 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
                 make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
             ],
-            // This doesn't quite match the spec (search for here documents in
-            // the bash man page), since it doesn't correctly handle unmatched
-            // or mismatched quote; for example, `TODO`.
-            make_heredoc_delim("<<-?('|\")?", "\\w+", "('|\")?", "", ""),
+            // See here documents in the bash man page. The closing word must
+            // begin a line, except after `<<-`, which allows leading tabs.
+            // Require a letter or underscore to begin an unquoted word, so
+            // that shifts such as `$((x<<2))` aren't heredocs.
+            &[
+                make_heredoc_delim("<<", "[A-Za-z_]\\w*", "", "\n", ""),
+                make_heredoc_delim("<< ", "[A-Za-z_]\\w*", "", "\n", ""),
+                make_heredoc_delim("<<'", "\\w+", "'", "\n", ""),
+                make_heredoc_delim("<<\"", "\\w+", "\"", "\n", ""),
+                make_heredoc_delim("<<-", "[A-Za-z_]\\w*", "", "", ""),
+            ],
+            SpecialCase::None,
+        ),
+        // ### Assembly
+        make_language_lexer(
+            "assembly",
+            &["asm", "s", "S"],
+            // Assemblers disagree on comments: NASM and MASM use `;`, while the
+            // GNU assembler uses `#` on x86 and `//` on ARM64. All of these
+            // accept C-style block comments when run through the C
+            // preprocessor.
+            &[";", "#", "//"],
+            &[make_block_comment_delim("/*", "*/", false)],
+            &[make_string_delimiter_spec("\"", "\\", NewlineSupport::None)],
+            &[],
             SpecialCase::None,
         ),
         // ### C/C++
@@ -140,7 +185,12 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
             // Note that the `.ino` extension is for Arduino source files.
             &["c", "cc", "cpp", "h", "hh", "hpp", "ino"],
             &["//"],
-            &[make_block_comment_delim("/*", "*/", false)],
+            // Place documentation comments (`/**`) before other comments, so
+            // that the longer delimiter matches first.
+            &[
+                make_block_comment_delim("/**", "*/", false),
+                make_block_comment_delim("/*", "*/", false),
+            ],
             &[make_string_delimiter_spec(
                 "\"",
                 "\\",
@@ -151,7 +201,16 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
             // raw string syntax in C++11 and newer is IMHO so rare we won't
             // encounter it in older code. See the C++
             // [string literals docs for the reasoning behind the start body regex.](https://en.cppreference.com/w/cpp/language/string_literal)
-            make_heredoc_delim("R\"", "[^()\\\\[[:space:]]]*", "(", ")", "\""),
+            // A raw string may begin with an encoding prefix, such as `u8R"`.
+            &["", "u8", "u", "U", "L"].map(|encoding_prefix| {
+                make_heredoc_delim(
+                    &format!("{encoding_prefix}R\""),
+                    "[^()\\\\[[:space:]]]*",
+                    "(",
+                    ")",
+                    "\"",
+                )
+            }),
             SpecialCase::None,
         ),
         // ### C#
@@ -162,20 +221,52 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
             // [6.3.3 Comments](https://learn.microsoft.com/en-us/dotnet/csharp/language-reference/language-specification/lexical-structure#633-comments).
             // Also provide support for
             // [documentation comments](https://learn.microsoft.com/en-us/dotnet/csharp/language-reference/language-specification/documentation-comments).
-            &["//", "///"],
+            // Place the longer delimiters first, so that they match before
+            // their prefixes do.
+            &["///", "//"],
             &[
-                make_block_comment_delim("/*", "*/", false),
                 make_block_comment_delim("/**", "*/", false),
+                make_block_comment_delim("/*", "*/", false),
             ],
-            &[make_string_delimiter_spec(
+            &[
+                // See
+                // [raw string literals](https://learn.microsoft.com/en-us/dotnet/csharp/language-reference/tokens/raw-string),
+                // which may be interpolated. Raw strings delimited by more than
+                // three quotes aren't supported.
+                make_prefixed_string_delimiter_spec(
+                    &["", "$", "$$"],
+                    "\"\"\"",
+                    "",
+                    NewlineSupport::Unescaped,
+                ),
                 // See
                 // [6.4.5.6 String literals](https://learn.microsoft.com/en-us/dotnet/csharp/language-reference/language-specification/lexical-structure#6456-string-literals).
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::None),
+            ],
+            &[],
+            SpecialCase::CSharpVerbatimStringLiteral,
+        ),
+        // ### [CMake](https://cmake.org/cmake/help/latest/manual/cmake-language.7.html)
+        make_language_lexer(
+            "cmake",
+            &["cmake", "CMakeLists.txt"],
+            &["#"],
+            // See
+            // [bracket comments](https://cmake.org/cmake/help/latest/manual/cmake-language.7.html#bracket-comment).
+            // Only the form without equals signs is a doc block; a comment
+            // such as `#[==[` is lexed as an inline comment followed by a
+            // bracket argument, which leaves it as code.
+            &[make_block_comment_delim("#[[", "]]", false)],
+            &[make_string_delimiter_spec(
                 "\"",
                 "\\",
-                NewlineSupport::None,
+                NewlineSupport::Unescaped,
             )],
-            None,
-            SpecialCase::CSharpVerbatimStringLiteral,
+            // See
+            // [bracket arguments](https://cmake.org/cmake/help/latest/manual/cmake-language.7.html#bracket-argument),
+            // such as `[==[contents]==]`.
+            &[make_heredoc_delim("[", "=*", "[", "]", "]")],
+            SpecialCase::None,
         ),
         // ### CSS
         make_language_lexer(
@@ -187,7 +278,113 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
                 make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
             ],
-            None,
+            &[],
+            SpecialCase::None,
+        ),
+        // ### [Dart](https://dart.dev/language)
+        make_language_lexer(
+            "dart",
+            &["dart"],
+            // See [comments](https://dart.dev/language/comments); block
+            // comments nest.
+            &["///", "//"],
+            &[
+                make_block_comment_delim("/**", "*/", true),
+                make_block_comment_delim("/*", "*/", true),
+            ],
+            // See
+            // [strings](https://dart.dev/language/built-in-types#strings). Raw
+            // strings (`r'...'`) have no escape character.
+            &[
+                make_prefixed_string_delimiter_spec(
+                    &["r"],
+                    "\"\"\"",
+                    "",
+                    NewlineSupport::Unescaped,
+                ),
+                make_prefixed_string_delimiter_spec(&["r"], "'''", "", NewlineSupport::Unescaped),
+                make_prefixed_string_delimiter_spec(&["r"], "\"", "", NewlineSupport::None),
+                make_prefixed_string_delimiter_spec(&["r"], "'", "", NewlineSupport::None),
+                make_string_delimiter_spec("\"\"\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'''", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::None),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::None),
+            ],
+            &[],
+            SpecialCase::None,
+        ),
+        // ### [Dockerfile](https://docs.docker.com/reference/dockerfile/)
+        make_language_lexer(
+            "dockerfile",
+            &["dockerfile", "Dockerfile"],
+            &["#"],
+            &[],
+            &[
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::None),
+                make_string_delimiter_spec("'", "", NewlineSupport::None),
+            ],
+            // See
+            // [here-documents](https://docs.docker.com/reference/dockerfile/#here-documents).
+            // The closing identifier must begin a line, except after `<<-`,
+            // which allows leading tabs.
+            &[
+                make_heredoc_delim("<<", "[A-Za-z_][A-Za-z0-9_]*", "", "\n", ""),
+                make_heredoc_delim("<<\"", "[A-Za-z_][A-Za-z0-9_]*", "\"", "\n", ""),
+                make_heredoc_delim("<<-", "[A-Za-z_][A-Za-z0-9_]*", "", "", ""),
+            ],
+            SpecialCase::None,
+        ),
+        // ### [Elixir](https://hexdocs.pm/elixir/syntax-reference.html)
+        make_language_lexer(
+            "elixir",
+            &["ex", "exs"],
+            &["#"],
+            &[],
+            // Heredocs are delimited by triple quotes, so they're strings for
+            // this lexer. Character literals such as `?"` aren't supported.
+            &[
+                make_string_delimiter_spec("\"\"\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'''", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
+            ],
+            &[],
+            SpecialCase::None,
+        ),
+        // ### [Erlang](https://www.erlang.org/doc/system/reference_manual.html)
+        make_language_lexer(
+            "erlang",
+            &["erl", "hrl"],
+            // By convention, `%%%` introduces module-level comments and `%%`
+            // function-level comments; place the longer delimiters first.
+            &["%%%", "%%", "%"],
+            &[],
+            // Quoted atoms (`'an atom'`) lex like strings. Triple-quoted
+            // strings were added in OTP 27.
+            &[
+                make_string_delimiter_spec("\"\"\"", "", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
+            ],
+            &[],
+            SpecialCase::None,
+        ),
+        // ### Fortran
+        make_language_lexer(
+            "fortran",
+            // Only free-form source is supported; fixed-form source (`.f`,
+            // `.for`) marks comments with a `C` in the first column.
+            &["f90", "f95", "f03", "f08"],
+            &["!"],
+            &[],
+            // Strings use [string delimiter doubling](#string_delimiter_doubling)
+            // and end at a newline unless continued with `&`, which this lexer
+            // ignores.
+            &[
+                make_string_delimiter_spec("\"", "", NewlineSupport::None),
+                make_string_delimiter_spec("'", "", NewlineSupport::None),
+            ],
+            &[],
             SpecialCase::None,
         ),
         // ### Go
@@ -204,7 +401,42 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::None),
                 make_string_delimiter_spec("`", "", NewlineSupport::Unescaped),
             ],
-            None,
+            &[],
+            SpecialCase::None,
+        ),
+        // ### [Haskell](https://www.haskell.org/onlinereport/haskell2010/haskellch2.html)
+        make_language_lexer(
+            "haskell",
+            &["hs"],
+            &["--"],
+            // Block comments nest.
+            &[make_block_comment_delim("{-", "-}", true)],
+            // Character literals (`'"'`) aren't included, since a single quote
+            // also appears in identifiers such as `foldl'`. A string may
+            // continue onto the next line using a
+            // [string gap](https://www.haskell.org/onlinereport/haskell2010/haskellch2.html#x7-200002.6).
+            &[make_string_delimiter_spec(
+                "\"",
+                "\\",
+                NewlineSupport::Escaped,
+            )],
+            &[],
+            SpecialCase::None,
+        ),
+        // ### [HCL](https://github.com/hashicorp/hcl/blob/main/hclsyntax/spec.md) (Terraform)
+        make_language_lexer(
+            "hcl",
+            &["tf", "tfvars", "hcl"],
+            &["#", "//"],
+            &[make_block_comment_delim("/*", "*/", false)],
+            &[make_string_delimiter_spec("\"", "\\", NewlineSupport::None)],
+            // Heredocs such as `<<EOT`, where the closing identifier must begin
+            // a line, and indented heredocs such as `<<-EOT`, where it may be
+            // indented.
+            &[
+                make_heredoc_delim("<<", "[A-Za-z_][A-Za-z0-9_]*", "", "\n", ""),
+                make_heredoc_delim("<<-", "[A-Za-z_][A-Za-z0-9_]*", "", "", ""),
+            ],
             SpecialCase::None,
         ),
         // ### HTML
@@ -217,7 +449,7 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
                 make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
             ],
-            None,
+            &[],
             SpecialCase::None,
         ),
         // ### Java
@@ -229,9 +461,14 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
             // [§3.7. Comments](https://docs.oracle.com/javase/specs/jls/se19/html/jls-3.html#jls-3.7).
             // The end of this section notes that <q>comments do not occur
             // within character literals, string literals, or text blocks,</q>
-            // which describes the approach of this lexer nicely.
+            // which describes the approach of this lexer nicely. Place
+            // documentation comments (`/**`) before other comments, so that the
+            // longer delimiter matches first.
             &["//"],
-            &[make_block_comment_delim("/*", "*/", false)],
+            &[
+                make_block_comment_delim("/**", "*/", false),
+                make_block_comment_delim("/*", "*/", false),
+            ],
             // See
             // [§3.10.5. String Literals](https://docs.oracle.com/javase/specs/jls/se19/html/jls-3.html#jls-3.10.5).
             &[
@@ -247,7 +484,7 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 // [§3.10.6. Text Blocks](https://docs.oracle.com/javase/specs/jls/se19/html/jls-3.html#jls-3.10.6).
                 make_string_delimiter_spec("\"\"\"", "\\", NewlineSupport::Unescaped),
             ],
-            None,
+            &[],
             SpecialCase::None,
         ),
         // ### JavaScript
@@ -271,7 +508,7 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::Escaped),
                 make_string_delimiter_spec("'", "\\", NewlineSupport::Escaped),
             ],
-            None,
+            &[],
             SpecialCase::TemplateLiteral,
         ),
         // ### JSON5
@@ -284,7 +521,88 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::Escaped),
                 make_string_delimiter_spec("'", "\\", NewlineSupport::Escaped),
             ],
-            None,
+            &[],
+            SpecialCase::None,
+        ),
+        // ### [Julia](https://docs.julialang.org/en/v1/manual/strings/)
+        make_language_lexer(
+            "julia",
+            &["jl"],
+            &["#"],
+            // Block comments nest.
+            &[make_block_comment_delim("#=", "=#", true)],
+            // A single quote is both a character literal and the transpose
+            // operator (`a'`); since character literals can't span lines,
+            // a misidentified transpose only affects the rest of its line.
+            &[
+                make_string_delimiter_spec("\"\"\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::None),
+            ],
+            &[],
+            SpecialCase::None,
+        ),
+        // ### [Kotlin](https://kotlinlang.org/spec/syntax-and-grammar.html)
+        make_language_lexer(
+            "kotlin",
+            &["kt", "kts"],
+            &["//"],
+            // Block comments nest.
+            &[
+                make_block_comment_delim("/**", "*/", true),
+                make_block_comment_delim("/*", "*/", true),
+            ],
+            // Multiline (raw) strings don't support escapes.
+            &[
+                make_string_delimiter_spec("\"\"\"", "", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::None),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::None),
+            ],
+            &[],
+            SpecialCase::None,
+        ),
+        // ### LaTeX
+        make_language_lexer(
+            "latex",
+            &["tex", "sty", "cls", "ltx"],
+            &["%"],
+            &[],
+            // LaTeX has no strings; however, an escaped percent sign (`\%`)
+            // doesn't start a comment. Treat it as a string which lasts until
+            // the next `\%` or the end of the line, so the line remains code.
+            &[make_string_delimiter_spec("\\%", "", NewlineSupport::None)],
+            &[],
+            SpecialCase::None,
+        ),
+        // ### [Lua](https://www.lua.org/manual/5.4/manual.html#3.1)
+        make_language_lexer(
+            "lua",
+            &["lua"],
+            &["--"],
+            // Long comments with a level (`--[==[`) are lexed as an inline
+            // comment followed by a long string, which leaves them as code.
+            &[make_block_comment_delim("--[[", "]]", false)],
+            // A backslash followed by a newline continues a string.
+            &[
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::Escaped),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::Escaped),
+            ],
+            // Long strings, such as `[==[contents]==]`.
+            &[make_heredoc_delim("[", "=*", "[", "]", "]")],
+            SpecialCase::None,
+        ),
+        // ### [Makefile](https://www.gnu.org/software/make/manual/make.html)
+        make_language_lexer(
+            "makefile",
+            &["mk", "mak", "Makefile", "makefile", "GNUmakefile"],
+            &["#"],
+            &[],
+            // Make itself has no strings, but recipes are shell commands.
+            &[
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::None),
+                make_string_delimiter_spec("'", "", NewlineSupport::None),
+            ],
+            &[],
             SpecialCase::None,
         ),
         // ### MATLAB
@@ -307,9 +625,125 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 make_string_delimiter_spec("\"", "", NewlineSupport::None),
                 make_string_delimiter_spec("'", "", NewlineSupport::None),
             ],
-            None,
+            &[],
             SpecialCase::Matlab,
         ),
+        // ### [Nix](https://nix.dev/manual/nix/stable/language/syntax)
+        make_language_lexer(
+            "nix",
+            &["nix"],
+            &["#"],
+            &[make_block_comment_delim("/*", "*/", false)],
+            // Indented strings (`''...''`) escape using a prefix of `''`, as
+            // in `'''` or `''$`, rather than a backslash. Treating `'''` as
+            // the end of a string then the start of another keeps the lexer in
+            // sync.
+            &[
+                make_string_delimiter_spec("''", "", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
+            ],
+            &[],
+            SpecialCase::None,
+        ),
+        // ### [OCaml](https://ocaml.org/manual/latest/lex.html)
+        make_language_lexer(
+            "ocaml",
+            &["ml", "mli"],
+            &[],
+            // Comments nest; place documentation comments (`(**`) first.
+            &[
+                make_block_comment_delim("(**", "*)", true),
+                make_block_comment_delim("(*", "*)", true),
+            ],
+            // Character literals aren't included, since a single quote also
+            // appears in identifiers such as `x'` and type variables such as
+            // `'a`.
+            &[make_string_delimiter_spec(
+                "\"",
+                "\\",
+                NewlineSupport::Unescaped,
+            )],
+            // Quoted strings, such as `{id|contents|id}`.
+            &[make_heredoc_delim("{", "[a-z_]*", "|", "|", "}")],
+            SpecialCase::None,
+        ),
+        // ### [Perl](https://perldoc.perl.org/perlsyn)
+        make_language_lexer(
+            "perl",
+            &["pl", "pm", "t"],
+            &["#"],
+            // Treat [POD](https://perldoc.perl.org/perlpod) as block comments.
+            // The closing `=cut` must begin a line; it stays there only if the
+            // doc block ends with a blank line.
+            &[
+                make_block_comment_delim("=pod", "=cut", false),
+                make_block_comment_delim("=head1", "=cut", false),
+            ],
+            // Quote-like operators (`q{}`, `qw()`, etc.) aren't supported.
+            &[
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("`", "\\", NewlineSupport::Unescaped),
+            ],
+            // See
+            // [here-documents](https://perldoc.perl.org/perlop#%3C%3CEOF).
+            // The closing identifier must begin a line, though it may be
+            // indented in indented here-documents (`<<~EOT`). Require an unquoted identifier to
+            // begin with a capital letter or underscore, so that shifts such as
+            // `$x<<2` aren't here-documents.
+            &[
+                make_heredoc_delim("<<", "[A-Z_]\\w*", "", "\n", ""),
+                make_heredoc_delim("<<\"", "\\w+", "\"", "\n", ""),
+                make_heredoc_delim("<<'", "\\w+", "'", "\n", ""),
+                make_indented_heredoc_delim("<<~", "[A-Z_]\\w*", ""),
+                make_indented_heredoc_delim("<<~\"", "\\w+", "\""),
+                make_indented_heredoc_delim("<<~'", "\\w+", "'"),
+            ],
+            SpecialCase::None,
+        ),
+        // ### [PHP](https://www.php.net/manual/en/language.basic-syntax.php)
+        make_language_lexer(
+            "php",
+            &["php"],
+            &["//", "#"],
+            &[
+                make_block_comment_delim("/**", "*/", false),
+                make_block_comment_delim("/*", "*/", false),
+            ],
+            &[
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("`", "\\", NewlineSupport::Unescaped),
+            ],
+            // See
+            // [heredoc](https://www.php.net/manual/en/language.types.string.php#language.types.string.syntax.heredoc).
+            // The closing identifier must begin a line, so indented closing
+            // identifiers (allowed by PHP 7.3 and newer) aren't recognized. The
+            // identifier may be double quoted, or single quoted for a nowdoc.
+            &[
+                make_heredoc_delim("<<<", "[A-Za-z_][A-Za-z0-9_]*", "", "\n", ""),
+                make_heredoc_delim("<<<\"", "[A-Za-z_][A-Za-z0-9_]*", "\"", "\n", ""),
+                make_heredoc_delim("<<<'", "[A-Za-z_][A-Za-z0-9_]*", "'", "\n", ""),
+            ],
+            SpecialCase::None,
+        ),
+        // ### [PowerShell](https://learn.microsoft.com/en-us/powershell/module/microsoft.powershell.core/about/about_quoting_rules)
+        make_language_lexer(
+            "powershell",
+            &["ps1", "psm1", "psd1"],
+            &["#"],
+            &[make_block_comment_delim("<#", "#>", false)],
+            // PowerShell escapes using a backtick; single-quoted strings use
+            // [string delimiter doubling](#string_delimiter_doubling).
+            &[
+                make_string_delimiter_spec("\"", "`", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'", "", NewlineSupport::Unescaped),
+            ],
+            // Here-strings, such as `@"` to `"@`; the closing delimiter must
+            // begin a line.
+            &[make_heredoc_delim("@", "[\"']", "", "\n", "@")],
+            SpecialCase::None,
+        ),
         // ### Python
         make_language_lexer(
             "python",
@@ -320,12 +754,75 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 // Note that raw strings still allow escaping the single/double
                 // quote. See the
                 // [language reference](https://docs.python.org/3/reference/lexical_analysis.html#literals).
+                // Therefore, string prefixes (`r`, `b`, `f`, etc.) don't change
+                // where a string ends, so they need no separate definitions.
                 make_string_delimiter_spec("\"\"\"", "\\", NewlineSupport::Unescaped),
                 make_string_delimiter_spec("'''", "\\", NewlineSupport::Unescaped),
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::Escaped),
                 make_string_delimiter_spec("'", "\\", NewlineSupport::Escaped),
             ],
-            None,
+            &[],
+            SpecialCase::None,
+        ),
+        // ### Python with docstrings
+        //
+        // To treat docstrings as doc blocks, select this lexer by placing
+        // `CodeChat Editor lexer: python_docstrings` in a comment.
+        make_language_lexer(
+            "python_docstrings",
+            &[],
+            &["#"],
+            &[],
+            &[
+                make_string_delimiter_spec("\"\"\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'''", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::Escaped),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::Escaped),
+            ],
+            &[],
+            SpecialCase::PythonDocstring,
+        ),
+        // ### [R](https://cran.r-project.org/doc/manuals/r-release/R-lang.html)
+        make_language_lexer(
+            "r",
+            &["r", "R"],
+            &["#"],
+            &[],
+            &[
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
+                // Backticks quote names, which can't span lines.
+                make_string_delimiter_spec("`", "\\", NewlineSupport::None),
+            ],
+            // Raw strings, such as `r"-(contents)-"`. Only the lowercase,
+            // double-quoted, parenthesized form is supported.
+            &[make_heredoc_delim("r\"", "-*", "(", ")", "\"")],
+            SpecialCase::None,
+        ),
+        // ### [Ruby](https://docs.ruby-lang.org/en/master/syntax/literals_rdoc.html)
+        make_language_lexer(
+            "ruby",
+            &["rb", "rake", "gemspec", "Rakefile", "Gemfile"],
+            &["#"],
+            // As with Perl's POD, the closing `=end` must begin a line.
+            &[make_block_comment_delim("=begin", "=end", false)],
+            // Percent literals (`%q{}`, `%w[]`, etc.) aren't supported.
+            &[
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("`", "\\", NewlineSupport::Unescaped),
+            ],
+            // Squiggly (`<<~EOS`) and dash (`<<-EOS`) heredocs, whose
+            // identifiers may be quoted. The closing identifier is on a line by
+            // itself, but may be indented. Plain heredocs (`<<EOS`) aren't
+            // supported, since they're hard to distinguish from a shift such as
+            // `bits<<SIZE`.
+            &[
+                make_indented_heredoc_delim("<<~", "[A-Z_][A-Z0-9_]*", ""),
+                make_indented_heredoc_delim("<<-", "[A-Z_][A-Z0-9_]*", ""),
+                make_indented_heredoc_delim("<<~'", "\\w+", "'"),
+                make_indented_heredoc_delim("<<~\"", "\\w+", "\""),
+            ],
             SpecialCase::None,
         ),
         // ### [Rust](https://doc.rust-lang.org/reference/tokens.html#literals)
@@ -339,9 +836,28 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 // Byte strings behave like strings for this lexer.
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
             ],
-            // Likewise, raw byte strings behave identically to raw strings from
-            // this lexer's perspective.
-            make_heredoc_delim("r", "#+", "\"", "\"", ""),
+            // Raw strings (`r"..."`, `r#"..."#`), along with raw byte and raw C
+            // strings, which behave identically from this lexer's perspective.
+            &["r", "br", "cr"].map(|prefix| make_heredoc_delim(prefix, "#*", "\"", "\"", "")),
+            SpecialCase::None,
+        ),
+        // ### [Scala](https://scala-lang.org/files/archive/spec/2.13/01-lexical-syntax.html)
+        make_language_lexer(
+            "scala",
+            &["scala", "sc"],
+            &["//"],
+            // Block comments nest.
+            &[
+                make_block_comment_delim("/**", "*/", true),
+                make_block_comment_delim("/*", "*/", true),
+            ],
+            // Multiline strings don't support escapes.
+            &[
+                make_string_delimiter_spec("\"\"\"", "", NewlineSupport::Unescaped),
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::None),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::None),
+            ],
+            &[],
             SpecialCase::None,
         ),
         // ### SQL
@@ -368,7 +884,7 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 // vendor-specific flavors of this basic lexer definition.
                 make_string_delimiter_spec("'", "", NewlineSupport::Unescaped),
             ],
-            None,
+            &[],
             SpecialCase::None,
         ),
         // ### [Swift](https://docs.swift.org/swift-book/documentation/the-swift-programming-language/)
@@ -391,13 +907,13 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
             ],
             // Swift supports
             // [extended string delimiters](https://docs.swift.org/swift-book/documentation/the-swift-programming-language/stringsandcharacters#Extended-String-Delimiters)
-            // in both string literal and multiline string flavors. Since this
-            // parser only supports a single heredoc type, we ignore the string
-            // literal flavor. This is a bug: consider the string
-            // `#"Not a comment "/*"#`. This would parse as a code block
-            // containing just `#`, then the string `"Not a comment "` then a
-            // comment starting with `/*"#`.
-            make_heredoc_delim("", "#+", "\"\"\"", "\"\"\"", ""),
+            // in both multiline string and string literal flavors. Place the
+            // multiline flavor first, so that it matches before the string
+            // literal flavor does.
+            &[
+                make_heredoc_delim("", "#+", "\"\"\"", "\"\"\"", ""),
+                make_heredoc_delim("", "#+", "\"", "\"", ""),
+            ],
             SpecialCase::None,
         ),
         // ### [TOML](https://toml.io/en/)
@@ -416,7 +932,7 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 // Literal strings
                 make_string_delimiter_spec("'", "\\", NewlineSupport::Escaped),
             ],
-            None,
+            &[],
             SpecialCase::None,
         ),
         // ### TypeScript
@@ -429,7 +945,7 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
                 make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
             ],
-            None,
+            &[],
             SpecialCase::TemplateLiteral,
         ),
         // ### VHDL
@@ -446,7 +962,7 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
             // newlines. This language uses
             // [string delimiter doubling](#string_delimiter_doubling).
             &[make_string_delimiter_spec("\"", "", NewlineSupport::None)],
-            None,
+            &[],
             SpecialCase::None,
         ),
         // ### Verilog
@@ -460,7 +976,7 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 "\\",
                 NewlineSupport::Escaped,
             )],
-            None,
+            &[],
             SpecialCase::None,
         ),
         // ### [V](https://vlang.io/)
@@ -478,7 +994,7 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
                 make_string_delimiter_spec("'", "\\", NewlineSupport::Unescaped),
             ],
-            None,
+            &[],
             SpecialCase::None,
         ),
         // ### YAML
@@ -504,10 +1020,29 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 // contents as inline or block comments.
                 make_string_delimiter_spec("'", "", NewlineSupport::Unescaped),
             ],
-            None,
+            &[],
+            SpecialCase::None,
+        ),
+        // ### [Zig](https://ziglang.org/documentation/master/#Comments)
+        make_language_lexer(
+            "zig",
+            &["zig", "zon"],
+            // Zig has no block comments.
+            &["///", "//!", "//"],
+            &[],
+            &[
+                // Each line of a
+                // [multiline string literal](https://ziglang.org/documentation/master/#Multiline-String-Literals)
+                // begins with `\\` and lasts until the end of the line, with no
+                // escapes.
+                make_string_delimiter_spec("\\\\", "", NewlineSupport::None),
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::None),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::None),
+            ],
+            &[],
             SpecialCase::None,
         ),
         // ### Markdown
-        make_language_lexer("markdown", &["md"], &[], &[], &[], None, SpecialCase::None),
+        make_language_lexer("markdown", &["md"], &[], &[], &[], &[], SpecialCase::None),
     ]
 }
@@ -17,34 +17,100 @@
 /// # `test.rs` -- Unit tests for the lexer
 // ## Imports
 use super::supported_languages::get_language_lexer_vec;
-use super::{compile_lexers, source_lexer, CodeDocBlock, DocBlock};
+use super::{
+    compile_lexers, javadoc_to_source, source_lexer, source_lexer_incremental, CodeDocBlock,
+    DocBlock, LanguageLexerCompiled, JAVADOC_INLINE, JAVADOC_NO_GUTTER,
+};
+use crate::processing::code_doc_block_vec_to_source;
 
 // ## Utilities
 //
 // Provide a compact way to create a `CodeDocBlock`.
 fn build_doc_block(indent: &str, delimiter: &str, contents: &str) -> CodeDocBlock {
-    return CodeDocBlock::DocBlock(DocBlock {
+    build_styled_doc_block(indent, delimiter, "", contents)
+}
+
+fn build_styled_doc_block(
+    indent: &str,
+    delimiter: &str,
+    style: &str,
+    contents: &str,
+) -> CodeDocBlock {
+    CodeDocBlock::DocBlock(DocBlock {
         indent: indent.to_string(),
         delimiter: delimiter.to_string(),
         contents: contents.to_string(),
+        style: style.to_string(),
         lines: contents.matches("\n").count()
             + (if contents.chars().last().unwrap_or('\n') == '\n' {
                 0
             } else {
                 1
             }),
-    });
+    })
 }
 
 fn build_code_block(contents: &str) -> CodeDocBlock {
-    return CodeDocBlock::CodeBlock(contents.to_string());
+    CodeDocBlock::CodeBlock(contents.to_string())
+}
+
+// Lex `source`, then verify that translating the result back to source code
+// reproduces it.
+fn assert_round_trip(source: &str, llc: &LanguageLexerCompiled) {
+    assert_eq!(
+        code_doc_block_vec_to_source(source_lexer(source, llc), llc).unwrap(),
+        source
+    );
+}
+
+// Lex `prev_source`, then incrementally lex `source`. Verify that this produces
+// the same blocks as lexing `source`, and that the blocks reported as unchanged
+// are. Return the number of unchanged blocks at the end.
+fn assert_incremental(prev_source: &str, source: &str, llc: &LanguageLexerCompiled) -> usize {
+    let prev_classified_source = source_lexer(prev_source, llc);
+    let incremental_lex =
+        source_lexer_incremental(prev_source, &prev_classified_source, source, llc);
+    let classified_source = incremental_lex.classified_source;
+    assert_eq!(classified_source, source_lexer(source, llc));
+    let first_changed_index = incremental_lex.first_changed_index;
+    let unchanged_tail_len = incremental_lex.unchanged_tail_len;
+    assert_eq!(
+        classified_source[..first_changed_index],
+        prev_classified_source[..first_changed_index]
+    );
+    assert!(first_changed_index + unchanged_tail_len <= classified_source.len());
+    assert_eq!(
+        classified_source[classified_source.len() - unchanged_tail_len..],
+        prev_classified_source[prev_classified_source.len() - unchanged_tail_len..]
+    );
+    unchanged_tail_len
 }
 
 // ### Source lexer tests
+#[test]
+fn test_sh() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let sh = llc.map_mode_to_lexer.get(&String::from("sh")).unwrap();
+
+    // Heredocs may quote their word; a shift isn't a heredoc.
+    let source =
+        "cat <<'EOF'\n# Not a comment\nEOF\ncat << EOF\n# Nor this\nEOF\necho $((1<<2))\n# Done\n";
+    assert_eq!(
+        source_lexer(source, sh),
+        [
+            build_code_block(
+                "cat <<'EOF'\n# Not a comment\nEOF\ncat << EOF\n# Nor this\nEOF\necho $((1<<2))\n"
+            ),
+            build_doc_block("", "#", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, sh);
+}
+
 #[test]
 fn test_py() {
     let llc = compile_lexers(get_language_lexer_vec());
-    let py = llc.map_mode_to_lexer.get(&"python".to_string()).unwrap();
+    let py = llc.map_mode_to_lexer.get(&String::from("python")).unwrap();
 
     // Try basic cases: make sure than newlines are processed correctly.
     assert_eq!(source_lexer("", py), []);
@@ -222,7 +288,7 @@ fn test_js() {
     let llc = compile_lexers(get_language_lexer_vec());
     let js = llc
         .map_mode_to_lexer
-        .get(&"javascript".to_string())
+        .get(&String::from("javascript"))
         .unwrap();
 
     // JavaScript tests.
@@ -459,7 +525,7 @@ fn test_js() {
 #[test]
 fn test_cpp() {
     let llc = compile_lexers(get_language_lexer_vec());
-    let cpp = llc.map_mode_to_lexer.get(&"c_cpp".to_string()).unwrap();
+    let cpp = llc.map_mode_to_lexer.get(&String::from("c_cpp")).unwrap();
 
     // Try out a C++ heredoc.
     assert_eq!(
@@ -469,12 +535,53 @@ fn test_cpp() {
             build_doc_block("", "//", "Test 2")
         ]
     );
+
+    // Raw strings may have an encoding prefix.
+    assert_eq!(
+        source_lexer("u8R\"(\n// Test 1)\" LR\"x(\n// Test 2)x\"\n// Test 3", cpp),
+        [
+            build_code_block("u8R\"(\n// Test 1)\" LR\"x(\n// Test 2)x\"\n"),
+            build_doc_block("", "//", "Test 3")
+        ]
+    );
+}
+
+#[test]
+fn test_block_comment_closing_line() {
+    let llc = compile_lexers(get_language_lexer_vec());
+
+    // A closing delimiter on a line by itself stays there when saved, in each
+    // language with C-style block comments.
+    for mode in [
+        "c_cpp",
+        "css",
+        "golang",
+        "java",
+        "javascript",
+        "rust",
+        "swift",
+        "typescript",
+    ] {
+        let lexer = llc.map_mode_to_lexer.get(&String::from(mode)).unwrap();
+        let source = "x;\n  /* Test 1\n     Test 2\n  */\ny;\n";
+        assert_eq!(
+            source_lexer(source, lexer),
+            [
+                build_code_block("x;\n"),
+                build_doc_block("  ", "/*", "Test 1\nTest 2\n\n"),
+                build_code_block("y;\n"),
+            ]
+        );
+        assert_round_trip(source, lexer);
+        // Otherwise, it stays at the end of the last line.
+        assert_round_trip("x;\n  /* Test 1\n     Test 2 */\ny;\n", lexer);
+    }
 }
 
 #[test]
 fn test_csharp() {
     let llc = compile_lexers(get_language_lexer_vec());
-    let csharp = llc.map_mode_to_lexer.get(&"csharp".to_string()).unwrap();
+    let csharp = llc.map_mode_to_lexer.get(&String::from("csharp")).unwrap();
 
     // Try out a verbatim string literal with embedded double quotes.
     assert_eq!(
@@ -484,12 +591,21 @@ fn test_csharp() {
             build_code_block("@\"\n// Test 2\"\"\n// Test 3\"")
         ]
     );
+
+    // Try out raw string literals, which have no escape character.
+    assert_eq!(
+        source_lexer("$\"\"\"\n// Test 1 \\\"\n\"\"\"\n// Test 2", csharp),
+        [
+            build_code_block("$\"\"\"\n// Test 1 \\\"\n\"\"\"\n"),
+            build_doc_block("", "//", "Test 2")
+        ]
+    );
 }
 
 #[test]
 fn test_matlab() {
     let llc = compile_lexers(get_language_lexer_vec());
-    let matlab = llc.map_mode_to_lexer.get(&"matlab".to_string()).unwrap();
+    let matlab = llc.map_mode_to_lexer.get(&String::from("matlab")).unwrap();
 
     // Test both inline comment styles. Verify that escaped quotes are ignored,
     // and that doubled quotes are handled correctly.
@@ -533,7 +649,7 @@ a = 2
 #[test]
 fn test_rust() {
     let llc = compile_lexers(get_language_lexer_vec());
-    let rust = llc.map_mode_to_lexer.get(&"rust".to_string()).unwrap();
+    let rust = llc.map_mode_to_lexer.get(&String::from("rust")).unwrap();
 
     // Test Rust raw strings.
     assert_eq!(
@@ -543,6 +659,18 @@ fn test_rust() {
             build_doc_block("", "//", "Test 2")
         ]
     );
+    // Raw strings need no hashes, so a trailing backslash doesn't escape the
+    // closing quote. Raw byte and raw C strings behave the same way.
+    assert_eq!(
+        source_lexer(
+            "(r\"C:\\\", br#\"\n// Test 1\"#, cr\"\\\")\n// Test 2",
+            rust
+        ),
+        [
+            build_code_block("(r\"C:\\\", br#\"\n// Test 1\"#, cr\"\\\")\n"),
+            build_doc_block("", "//", "Test 2")
+        ]
+    );
 
     // Test Rust comments, which can be nested but aren't here. TODO: test
     // nested comments.
@@ -573,12 +701,25 @@ More depth 1 */"#
             ),
         ]
     );
+
+    // Nested comments which follow code are found at the correct location.
+    let source = "f();\n/* Depth 1\n  /* Depth 2 comment */\n  /* Depth 2 */ */\n// Test\n";
+    assert_eq!(
+        source_lexer(source, rust),
+        [
+            build_code_block("f();\n/* Depth 1\n"),
+            build_doc_block("  ", "/*", "Depth 2 comment\n"),
+            build_code_block("  /* Depth 2 */ */\n"),
+            build_doc_block("", "//", "Test\n"),
+        ]
+    );
+    assert_round_trip(source, rust);
 }
 
 #[test]
 fn test_sql() {
     let llc = compile_lexers(get_language_lexer_vec());
-    let sql = llc.map_mode_to_lexer.get(&"sql".to_string()).unwrap();
+    let sql = llc.map_mode_to_lexer.get(&String::from("sql")).unwrap();
 
     // Test strings with embedded single quotes.
     assert_eq!(
@@ -593,7 +734,7 @@ fn test_sql() {
 #[test]
 fn test_swift() {
     let llc = compile_lexers(get_language_lexer_vec());
-    let swift = llc.map_mode_to_lexer.get(&"swift".to_string()).unwrap();
+    let swift = llc.map_mode_to_lexer.get(&String::from("swift")).unwrap();
 
     // Test comments.
     assert_eq!(
@@ -610,6 +751,14 @@ fn test_swift() {
             build_code_block("some_code()")
         ]
     );
+    // Swift's block comments nest.
+    assert_eq!(
+        source_lexer("some_code()\n/* Outer /* inner */ */\n/* Test */\n", swift),
+        [
+            build_code_block("some_code()\n/* Outer /* inner */ */\n"),
+            build_doc_block("", "/*", "Test\n"),
+        ]
+    );
 
     // Test strings.
     assert_eq!(
@@ -643,6 +792,15 @@ foo("""
     );
 
     // Test extended string delimiters for a string literal.
+    assert_eq!(
+        source_lexer("foo(#\"Not a comment \"/*\"#)\n// Test 1", swift),
+        [
+            build_code_block("foo(#\"Not a comment \"/*\"#)\n"),
+            build_doc_block("", "//", "Test 1")
+        ]
+    );
+
+    // Test extended string delimiters for a multiline string.
     assert_eq!(
         source_lexer(
             r##"// Test 1
@@ -669,7 +827,7 @@ foo(#"""
 #[test]
 fn test_toml() {
     let llc = compile_lexers(get_language_lexer_vec());
-    let toml = llc.map_mode_to_lexer.get(&"toml".to_string()).unwrap();
+    let toml = llc.map_mode_to_lexer.get(&String::from("toml")).unwrap();
     assert_eq!(toml.language_lexer.lexer_name.as_str(), "toml");
 
     // Multi-line literal strings don't have escapes.
@@ -690,12 +848,551 @@ fn test_toml() {
     );
 }
 
+#[test]
+fn test_assembly() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let asm = llc
+        .map_mode_to_lexer
+        .get(&String::from("assembly"))
+        .unwrap();
+
+    // Each assembler's comment style is recognized; strings hide comments.
+    let source =
+        "; NASM\nmov eax, 1 ; Code\n# GNU\n// ARM64\n/* Block */\n.ascii \"; Not a comment\"\n";
+    assert_eq!(
+        source_lexer(source, asm),
+        [
+            build_doc_block("", ";", "NASM\n"),
+            build_code_block("mov eax, 1 ; Code\n"),
+            build_doc_block("", "#", "GNU\n"),
+            build_doc_block("", "//", "ARM64\n"),
+            build_doc_block("", "/*", "Block\n"),
+            build_code_block(".ascii \"; Not a comment\"\n"),
+        ]
+    );
+    assert_round_trip(source, asm);
+}
+
+#[test]
+fn test_cmake() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let cmake = llc.map_mode_to_lexer.get(&String::from("cmake")).unwrap();
+
+    // Bracket comments are block comments; bracket arguments and strings hide
+    // comments.
+    let source =
+        "#[[ Bracket\n    comment ]]\nset(X [=[\n# Not a comment\n]=] \"# Nor this\")\n# Line\n";
+    assert_eq!(
+        source_lexer(source, cmake),
+        [
+            build_doc_block("", "#[[", "Bracket\ncomment\n"),
+            build_code_block("set(X [=[\n# Not a comment\n]=] \"# Nor this\")\n"),
+            build_doc_block("", "#", "Line\n"),
+        ]
+    );
+    assert_round_trip(source, cmake);
+}
+
+#[test]
+fn test_dart() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let dart = llc.map_mode_to_lexer.get(&String::from("dart")).unwrap();
+
+    // Raw strings have no escape character.
+    assert_eq!(
+        source_lexer("var p = r'C:\\';\n// Note\n", dart),
+        [
+            build_code_block("var p = r'C:\\';\n"),
+            build_doc_block("", "//", "Note\n"),
+        ]
+    );
+
+    let source = "/// Doc\nvar s = '''\n// Not a comment\n''';\n/* Outer /* inner */ outer */\n/* Note */\nvar t = \"/*\";\n";
+    assert_eq!(
+        source_lexer(source, dart),
+        [
+            build_doc_block("", "///", "Doc\n"),
+            build_code_block(
+                "var s = '''\n// Not a comment\n''';\n/* Outer /* inner */ outer */\n"
+            ),
+            build_doc_block("", "/*", "Note\n"),
+            build_code_block("var t = \"/*\";\n"),
+        ]
+    );
+    assert_round_trip(source, dart);
+}
+
+#[test]
+fn test_dockerfile() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let docker = llc
+        .map_mode_to_lexer
+        .get(&String::from("dockerfile"))
+        .unwrap();
+
+    // The heredoc ends only when its identifier begins a line.
+    let source = "# Build\nRUN <<EOF\n# Not a comment EOF\nEOF\n# Done\n";
+    assert_eq!(
+        source_lexer(source, docker),
+        [
+            build_doc_block("", "#", "Build\n"),
+            build_code_block("RUN <<EOF\n# Not a comment EOF\nEOF\n"),
+            build_doc_block("", "#", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, docker);
+}
+
+#[test]
+fn test_elixir() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let elixir = llc.map_mode_to_lexer.get(&String::from("elixir")).unwrap();
+
+    let source = "@doc \"\"\"\n# Not a comment\n\"\"\"\n# Comment\nx = '# no'\n";
+    assert_eq!(
+        source_lexer(source, elixir),
+        [
+            build_code_block("@doc \"\"\"\n# Not a comment\n\"\"\"\n"),
+            build_doc_block("", "#", "Comment\n"),
+            build_code_block("x = '# no'\n"),
+        ]
+    );
+    assert_round_trip(source, elixir);
+}
+
+#[test]
+fn test_erlang() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let erlang = llc.map_mode_to_lexer.get(&String::from("erlang")).unwrap();
+
+    let source = "%%% Module\n%% Function\nf() -> '% atom', \"% string\". % Code\n";
+    assert_eq!(
+        source_lexer(source, erlang),
+        [
+            build_doc_block("", "%%%", "Module\n"),
+            build_doc_block("", "%%", "Function\n"),
+            build_code_block("f() -> '% atom', \"% string\". % Code\n"),
+        ]
+    );
+    assert_round_trip(source, erlang);
+}
+
+#[test]
+fn test_fortran() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let fortran = llc.map_mode_to_lexer.get(&String::from("fortran")).unwrap();
+
+    // Doubled quotes don't end a string.
+    let source = "! Note\nprint *, 'It''s ! not a comment'\n";
+    assert_eq!(
+        source_lexer(source, fortran),
+        [
+            build_doc_block("", "!", "Note\n"),
+            build_code_block("print *, 'It''s ! not a comment'\n"),
+        ]
+    );
+    assert_round_trip(source, fortran);
+}
+
+#[test]
+fn test_haskell() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let haskell = llc.map_mode_to_lexer.get(&String::from("haskell")).unwrap();
+
+    // A prime in an identifier doesn't start a string. Comments nest; only
+    // the innermost comment may be a doc block.
+    let source = "-- Fold\nf = foldl' g \"-- no\"\n{- Outer {- inner -} outer -}\n{- Note -}\n";
+    assert_eq!(
+        source_lexer(source, haskell),
+        [
+            build_doc_block("", "--", "Fold\n"),
+            build_code_block("f = foldl' g \"-- no\"\n{- Outer {- inner -} outer -}\n"),
+            build_doc_block("", "{-", "Note\n"),
+        ]
+    );
+    assert_round_trip(source, haskell);
+}
+
+#[test]
+fn test_hcl() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let hcl = llc.map_mode_to_lexer.get(&String::from("hcl")).unwrap();
+
+    let source = "# Policy\npolicy = <<EOT\n# Not a comment\nEOT\n// Done\n";
+    assert_eq!(
+        source_lexer(source, hcl),
+        [
+            build_doc_block("", "#", "Policy\n"),
+            build_code_block("policy = <<EOT\n# Not a comment\nEOT\n"),
+            build_doc_block("", "//", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, hcl);
+}
+
+#[test]
+fn test_julia() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let julia = llc.map_mode_to_lexer.get(&String::from("julia")).unwrap();
+
+    let source =
+        "#= Note =#\n#= Outer #= inner =# outer =#\ns = \"\"\"\n# Not a comment\n\"\"\"\n# Done\n";
+    assert_eq!(
+        source_lexer(source, julia),
+        [
+            build_doc_block("", "#=", "Note\n"),
+            build_code_block(
+                "#= Outer #= inner =# outer =#\ns = \"\"\"\n# Not a comment\n\"\"\"\n"
+            ),
+            build_doc_block("", "#", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, julia);
+}
+
+#[test]
+fn test_kotlin_scala() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    for lexer_name in ["kotlin", "scala"] {
+        let lexer = llc
+            .map_mode_to_lexer
+            .get(&String::from(lexer_name))
+            .unwrap();
+
+        // Raw strings have no escapes, so a backslash before the closing
+        // delimiter doesn't hide it.
+        let source =
+            "val s = \"\"\"C:\\\"\"\"\n// Note\n/* Outer /* inner */ outer */\n/* Note */\n";
+        assert_eq!(
+            source_lexer(source, lexer),
+            [
+                build_code_block("val s = \"\"\"C:\\\"\"\"\n"),
+                build_doc_block("", "//", "Note\n"),
+                build_code_block("/* Outer /* inner */ outer */\n"),
+                build_doc_block("", "/*", "Note\n"),
+            ]
+        );
+        assert_round_trip(source, lexer);
+    }
+}
+
+#[test]
+fn test_latex() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let latex = llc.map_mode_to_lexer.get(&String::from("latex")).unwrap();
+
+    // An escaped percent sign doesn't start a comment.
+    let source = "% Intro\nA 50\\% % share.\n";
+    assert_eq!(
+        source_lexer(source, latex),
+        [
+            build_doc_block("", "%", "Intro\n"),
+            build_code_block("A 50\\% % share.\n"),
+        ]
+    );
+    assert_round_trip(source, latex);
+}
+
+#[test]
+fn test_lua() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let lua = llc.map_mode_to_lexer.get(&String::from("lua")).unwrap();
+
+    let source = "--[[ Long\n     comment ]]\ns = [==[\n-- Not a comment ]]\n]==]\n-- Done\n";
+    assert_eq!(
+        source_lexer(source, lua),
+        [
+            build_doc_block("", "--[[", "Long\ncomment\n"),
+            build_code_block("s = [==[\n-- Not a comment ]]\n]==]\n"),
+            build_doc_block("", "--", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, lua);
+}
+
+#[test]
+fn test_makefile() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    // Makefiles are identified by name.
+    let makefile = &llc
+        .map_ext_to_lexer_vec
+        .get(&String::from("Makefile"))
+        .unwrap()[0];
+    assert_eq!(makefile.language_lexer.lexer_name.as_str(), "makefile");
+
+    let source = "# Build\nall:\n\techo '# no'\n";
+    assert_eq!(
+        source_lexer(source, makefile),
+        [
+            build_doc_block("", "#", "Build\n"),
+            build_code_block("all:\n\techo '# no'\n"),
+        ]
+    );
+    assert_round_trip(source, makefile);
+}
+
+#[test]
+fn test_nix() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let nix = llc.map_mode_to_lexer.get(&String::from("nix")).unwrap();
+
+    let source = "# Package\nscript = ''\n  # Not a comment\n'';\n/* Done */\n";
+    assert_eq!(
+        source_lexer(source, nix),
+        [
+            build_doc_block("", "#", "Package\n"),
+            build_code_block("script = ''\n  # Not a comment\n'';\n"),
+            build_doc_block("", "/*", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, nix);
+}
+
+#[test]
+fn test_ocaml() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let ocaml = llc.map_mode_to_lexer.get(&String::from("ocaml")).unwrap();
+
+    let source =
+        "(** Doc *)\nlet s = {id|(* no *)|id}\n(* Outer (* inner *) outer *)\n(* Done *)\n";
+    assert_eq!(
+        source_lexer(source, ocaml),
+        [
+            build_doc_block("", "(**", "Doc\n"),
+            build_code_block("let s = {id|(* no *)|id}\n(* Outer (* inner *) outer *)\n"),
+            build_doc_block("", "(*", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, ocaml);
+}
+
+#[test]
+fn test_perl() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let perl = llc.map_mode_to_lexer.get(&String::from("perl")).unwrap();
+
+    let source = "=pod Docs\n=cut\nprint <<\"EOT\";\n# Not a comment\nEOT\n# Done\n";
+    assert_eq!(
+        source_lexer(source, perl),
+        [
+            build_doc_block("", "=pod", "Docs\n\n"),
+            build_code_block("print <<\"EOT\";\n# Not a comment\nEOT\n"),
+            build_doc_block("", "#", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, perl);
+}
+
+#[test]
+fn test_php() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let php = llc.map_mode_to_lexer.get(&String::from("php")).unwrap();
+
+    let source = "<?php\n# Shell style\n$s = <<<HTML\n// Not a comment\nHTML;\n// Done\n";
+    assert_eq!(
+        source_lexer(source, php),
+        [
+            build_code_block("<?php\n"),
+            build_doc_block("", "#", "Shell style\n"),
+            build_code_block("$s = <<<HTML\n// Not a comment\nHTML;\n"),
+            build_doc_block("", "//", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, php);
+}
+
+#[test]
+fn test_powershell() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let ps = llc
+        .map_mode_to_lexer
+        .get(&String::from("powershell"))
+        .unwrap();
+
+    let source = "<# Help #>\n$s = @\"\n# Not a comment \"@\n\"@\n$t = \"`\"# no\"\n# Done\n";
+    assert_eq!(
+        source_lexer(source, ps),
+        [
+            build_doc_block("", "<#", "Help\n"),
+            build_code_block("$s = @\"\n# Not a comment \"@\n\"@\n$t = \"`\"# no\"\n"),
+            build_doc_block("", "#", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, ps);
+}
+
+#[test]
+fn test_r() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let r = llc.map_mode_to_lexer.get(&String::from("r")).unwrap();
+
+    let source = "# Path\np <- r\"-(C:\\# no)\")-\"\n# Done\n";
+    assert_eq!(
+        source_lexer(source, r),
+        [
+            build_doc_block("", "#", "Path\n"),
+            build_code_block("p <- r\"-(C:\\# no)\")-\"\n"),
+            build_doc_block("", "#", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, r);
+}
+
+#[test]
+fn test_ruby() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let ruby = llc.map_mode_to_lexer.get(&String::from("ruby")).unwrap();
+
+    let source = "=begin Docs\n=end\nsql = <<~SQL\n  # Not a comment\n  SQL\n# Done\n";
+    assert_eq!(
+        source_lexer(source, ruby),
+        [
+            build_doc_block("", "=begin", "Docs\n\n"),
+            build_code_block("sql = <<~SQL\n  # Not a comment\n  SQL\n"),
+            build_doc_block("", "#", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, ruby);
+
+    // The closing identifier must be on a line by itself; it doesn't close the
+    // heredoc when it appears inside a line or as part of a longer identifier.
+    let source = "s = <<~EOS\n  Not EOS\n  EOS2\n# Not a comment\n    EOS\n# Done\n";
+    assert_eq!(
+        source_lexer(source, ruby),
+        [
+            build_code_block("s = <<~EOS\n  Not EOS\n  EOS2\n# Not a comment\n    EOS\n"),
+            build_doc_block("", "#", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, ruby);
+
+    // The same holds for dash heredocs.
+    let source = "s = <<-EOS\nEOS.strip\n# Not a comment\nEOS\n# Done\n";
+    assert_eq!(
+        source_lexer(source, ruby),
+        [
+            build_code_block("s = <<-EOS\nEOS.strip\n# Not a comment\nEOS\n"),
+            build_doc_block("", "#", "Done\n"),
+        ]
+    );
+    assert_round_trip(source, ruby);
+}
+
+#[test]
+fn test_zig() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let zig = llc.map_mode_to_lexer.get(&String::from("zig")).unwrap();
+
+    // Each line of a multiline string literal lasts until the end of the line.
+    let source =
+        "//! Module\nconst s =\n    \\\\// Not a comment \"\n    \\\\// Nor this\n;\n/// Doc\n";
+    assert_eq!(
+        source_lexer(source, zig),
+        [
+            build_doc_block("", "//!", "Module\n"),
+            build_code_block("const s =\n    \\\\// Not a comment \"\n    \\\\// Nor this\n;\n"),
+            build_doc_block("", "///", "Doc\n"),
+        ]
+    );
+    assert_round_trip(source, zig);
+}
+
+// ### Incremental lexer tests
+#[test]
+fn test_incremental() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let c = llc.map_mode_to_lexer.get(&String::from("c_cpp")).unwrap();
+
+    let source = "// One\nint a;\n// Two\nint b;\n/* Three */\nint c;\n// Four\n";
+    // Edit a code block; lexing stops at the next code block.
+    assert_eq!(
+        assert_incremental(
+            source,
+            "// One\nint a2;\n// Two\nint b;\n/* Three */\nint c;\n// Four\n",
+            c
+        ),
+        4
+    );
+    // Edit a doc block.
+    assert_eq!(
+        assert_incremental(
+            source,
+            "// One\nint a;\n// Two, edited\nint b;\n/* Three */\nint c;\n// Four\n",
+            c
+        ),
+        4
+    );
+    // Edit the first and last blocks.
+    assert_incremental(
+        source,
+        "// Zero\n// One\nint a;\n// Two\nint b;\n/* Three */\nint c;\n// Four\n",
+        c,
+    );
+    assert_incremental(
+        source,
+        "// One\nint a;\n// Two\nint b;\n/* Three */\nint c;\n// Four\nint d;",
+        c,
+    );
+    assert_incremental(source, "", c);
+    assert_incremental("", source, c);
+    // Delete the code between two doc blocks, merging them.
+    assert_incremental(
+        source,
+        "// One\nint a;\n// Two\n/* Three */\nint c;\n// Four\n",
+        c,
+    );
+    assert_incremental(
+        source,
+        "// One\n// Two\nint b;\n/* Three */\nint c;\n// Four\n",
+        c,
+    );
+    // Opening a comment or string changes the remainder of the file; closing it
+    // restores it.
+    let unclosed = "// One\nint a; /*\n// Two\nint b;\n/* Three */\nint c;\n// Four\n";
+    assert_incremental(source, unclosed, c);
+    assert_incremental(unclosed, source, c);
+    let unclosed = "// One\nint a = \"\\\n// Two\nint b;\n/* Three */\nint c;\n// Four\n";
+    assert_incremental(source, unclosed, c);
+    assert_incremental(unclosed, source, c);
+    // Changing line endings or multi-byte characters works.
+    assert_incremental(source, &source.replace('\n', "\r\n"), c);
+    assert_incremental("// Ünï\nint ä;\n// Two\n", "// Ünï\nint äö;\n// Two\n", c);
+    assert_incremental("// Ünï\nint ä;\n// Two\n", "// Ün\nint ä;\n// Two\n", c);
+
+    // Try deleting each character and inserting syntax at each location.
+    let source = "// One\n  // Two\nint a = \"/*\"; // x\n/* Three\n   four */\nint b;\n//\n";
+    for (index, char) in source.char_indices() {
+        let next_index = index + char.len_utf8();
+        assert_incremental(
+            source,
+            &(source[..index].to_string() + &source[next_index..]),
+            c,
+        );
+        for insert in ["\n", "/*", "*/", "\"", "// "] {
+            assert_incremental(
+                source,
+                &(source[..index].to_string() + insert + &source[index..]),
+                c,
+            );
+        }
+    }
+
+    // Python docstrings depend on the preceding code.
+    let py = llc
+        .map_mode_to_lexer
+        .get(&String::from("python_docstrings"))
+        .unwrap();
+    let source = "# One\ndef f():\n    \"\"\"Doc.\"\"\"\n    return 1\n";
+    assert_incremental(source, &source.replace("def f():", "x = 1"), py);
+    assert_incremental(&source.replace("def f():", "x = 1"), source, py);
+}
+
 // ### Compiler tests
 #[test]
 fn test_compiler() {
     let llc = compile_lexers(get_language_lexer_vec());
 
-    let c_ext_lexer_arr = llc.map_ext_to_lexer_vec.get(&"c".to_string()).unwrap();
+    let c_ext_lexer_arr = llc.map_ext_to_lexer_vec.get(&String::from("c")).unwrap();
     assert_eq!(c_ext_lexer_arr.len(), 1);
     assert_eq!(
         c_ext_lexer_arr[0].language_lexer.lexer_name.as_str(),
@@ -703,7 +1400,7 @@ fn test_compiler() {
     );
     assert_eq!(
         llc.map_mode_to_lexer
-            .get(&"verilog".to_string())
+            .get(&String::from("verilog"))
             .unwrap()
             .language_lexer
             .lexer_name
@@ -711,3 +1408,132 @@ fn test_compiler() {
         "verilog"
     );
 }
+
+#[test]
+fn test_python_docstrings() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let py = llc
+        .map_mode_to_lexer
+        .get(&String::from("python_docstrings"))
+        .unwrap();
+
+    // A module docstring, then a function docstring with its closing delimiter
+    // on a separate line.
+    assert_eq!(
+        source_lexer(
+            "# Intro\n\"\"\"Module.\"\"\"\ndef f():\n    '''Summary.\n\n    Details.\n    '''\n    return 1\n",
+            py
+        ),
+        [
+            build_doc_block("", "#", "Intro\n"),
+            build_doc_block("", "\"\"\"", "Module.\n"),
+            build_code_block("def f():\n"),
+            build_doc_block("    ", "'''", "Summary.\n\nDetails.\n\n"),
+            build_code_block("    return 1\n"),
+        ]
+    );
+
+    // Strings which aren't in docstring position are code.
+    assert_eq!(
+        source_lexer("x = 1\n\"\"\"Not a docstring.\"\"\"\n", py),
+        [build_code_block("x = 1\n\"\"\"Not a docstring.\"\"\"\n")]
+    );
+    // Likewise, docstrings followed by code, or with inconsistent indents, are
+    // code.
+    assert_eq!(
+        source_lexer("\"\"\"Doc.\"\"\"; x = 1\n", py),
+        [build_code_block("\"\"\"Doc.\"\"\"; x = 1\n")]
+    );
+    assert_eq!(
+        source_lexer("class C:\n    \"\"\"Doc\n  more.\"\"\"\n", py),
+        [build_code_block("class C:\n    \"\"\"Doc\n  more.\"\"\"\n")]
+    );
+
+    // The standard Python lexer treats docstrings as strings.
+    let python = llc.map_mode_to_lexer.get(&String::from("python")).unwrap();
+    assert_eq!(
+        source_lexer("\"\"\"Module.\"\"\"\n", python),
+        [build_code_block("\"\"\"Module.\"\"\"\n")]
+    );
+}
+
+#[test]
+fn test_javadoc() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let java = llc.map_mode_to_lexer.get(&String::from("java")).unwrap();
+
+    // The gutter is removed from a Javadoc-style comment.
+    let source = "class C {\n    /**\n     * Summary.\n     *\n     * @param x The value.\n     */\n    void f(int x);\n}\n";
+    assert_eq!(
+        source_lexer(source, java),
+        [
+            build_code_block("class C {\n"),
+            build_doc_block("    ", "/**", "\nSummary.\n\n@param x The value.\n\n"),
+            build_code_block("    void f(int x);\n}\n"),
+        ]
+    );
+    assert_eq!(
+        javadoc_to_source("    ", "\nSummary.\n\n@param x The value.\n\n", false),
+        "    /**\n     * Summary.\n     *\n     * @param x The value.\n     */\n"
+    );
+    assert_round_trip(source, java);
+
+    // Text may follow the opening delimiter; it stays there when translated
+    // back to source.
+    let source = "/** Summary.\n * More.\n */\n";
+    assert_eq!(
+        source_lexer(source, java),
+        [build_styled_doc_block(
+            "",
+            "/**",
+            JAVADOC_INLINE,
+            "Summary.\nMore.\n\n"
+        )]
+    );
+    assert_eq!(javadoc_to_source("", "Summary.\nMore.\n\n", true), source);
+    assert_round_trip(source, java);
+
+    // A one-line comment has no gutter. Edited contents which span several
+    // lines receive one.
+    assert_eq!(
+        source_lexer("/** Summary. */\n", java),
+        [build_styled_doc_block(
+            "",
+            "/**",
+            JAVADOC_NO_GUTTER,
+            "Summary.\n"
+        )]
+    );
+    assert_round_trip("/** Summary. */\n", java);
+    assert_eq!(
+        javadoc_to_source("", "Summary.\n", false),
+        "/** Summary. */\n"
+    );
+    assert_eq!(
+        javadoc_to_source("", "Summary.\n\nMore */ text.\n", false),
+        "/**\n * Summary.\n *\n * More *&#47; text.\n */\n"
+    );
+
+    // Comments without a gutter are saved like any other block comment.
+    for source in [
+        "/** Summary.\n    More. */\n",
+        "  /** Summary.\n      More. */\n",
+    ] {
+        let classified_source = source_lexer(source, java);
+        match &classified_source[0] {
+            CodeDocBlock::DocBlock(doc_block) => assert_eq!(doc_block.style, JAVADOC_NO_GUTTER),
+            CodeDocBlock::CodeBlock(_) => panic!("Expected a doc block."),
+        }
+        assert_round_trip(source, java);
+    }
+
+    // Both C# documentation comment styles are recognized.
+    let csharp = llc.map_mode_to_lexer.get(&String::from("csharp")).unwrap();
+    assert_eq!(
+        source_lexer("/// Summary.\n/** Summary. */\n", csharp),
+        [
+            build_doc_block("", "///", "Summary.\n"),
+            build_styled_doc_block("", "/**", JAVADOC_NO_GUTTER, "Summary.\n"),
+        ]
+    );
+}
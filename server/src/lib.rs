@@ -18,7 +18,9 @@
 ///
 /// TODO: Add the ability to use
 /// [plugins](https://zicklag.github.io/rust-tutorials/rust-plugins.html).
+pub mod export;
 pub mod lexer;
+pub mod link_checker;
 pub mod processing;
 pub mod webserver;
 
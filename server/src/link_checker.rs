@@ -0,0 +1,217 @@
+/// Copyright (C) 2023 Bryan A. Jones.
+///
+/// This file is part of the CodeChat Editor. The CodeChat Editor is free
+/// software: you can redistribute it and/or modify it under the terms of the
+/// GNU General Public License as published by the Free Software Foundation,
+/// either version 3 of the License, or (at your option) any later version.
+///
+/// The CodeChat Editor is distributed in the hope that it will be useful, but
+/// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+/// or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+/// more details.
+///
+/// You should have received a copy of the GNU General Public License along with
+/// the CodeChat Editor. If not, see
+/// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+///
+/// # `link_checker.rs` -- Find broken links in a project
+///
+/// This loads every file in a project into an anchor index (see
+/// `processing.rs`), then reports each hyperlink whose target file or anchor
+/// doesn't exist.
+// ## Imports
+//
+// ### Standard library
+use std::path::Path;
+
+// ### Third-party
+use path_slash::PathExt;
+use serde::Serialize;
+
+// ### Local
+use crate::export::collect_files;
+use crate::processing::{find_project_root, AnchorIndex, FileAnchor};
+
+// ## Data structures
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BrokenLinkKind {
+    /// The file this link refers to doesn't exist.
+    MissingFile,
+    /// The file exists, but doesn't contain the anchor this link refers to.
+    MissingAnchor,
+    /// An entry in the TOC refers to a file which doesn't exist.
+    MissingTocEntry,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct BrokenLink {
+    pub kind: BrokenLinkKind,
+    /// The file containing this link, relative to the project root.
+    pub file: String,
+    /// The anchor (heading or non-heading) nearest this link.
+    pub anchor: Option<String>,
+    /// The file this link refers to, relative to the project root if it's in
+    /// the project.
+    pub target: String,
+    /// The anchor this link refers to.
+    pub target_anchor: Option<String>,
+}
+
+/// The results of checking a project.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct LinkReport {
+    /// The root directory of the project.
+    pub root: String,
+    /// The number of files checked.
+    pub files_checked: usize,
+    pub broken_links: Vec<BrokenLink>,
+}
+
+impl LinkReport {
+    // Format this report for display on the command line.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for broken_link in &self.broken_links {
+            let target = match &broken_link.target_anchor {
+                Some(target_anchor) => format!("{}#{target_anchor}", broken_link.target),
+                None => broken_link.target.clone(),
+            };
+            let location = match &broken_link.anchor {
+                Some(anchor) => format!("{}#{anchor}", broken_link.file),
+                None => broken_link.file.clone(),
+            };
+            let problem = match broken_link.kind {
+                BrokenLinkKind::MissingFile => "missing file",
+                BrokenLinkKind::MissingAnchor => "missing anchor",
+                BrokenLinkKind::MissingTocEntry => "missing TOC entry",
+            };
+            text.push_str(&format!("{location}: {problem} {target}\n"));
+        }
+        text.push_str(&format!(
+            "Checked {} files in {}; found {} broken links.",
+            self.files_checked,
+            self.root,
+            self.broken_links.len()
+        ));
+        text
+    }
+}
+
+// ## Code
+//
+// Check all links in the project containing `project_path`.
+pub fn check_project(project_path: &Path) -> Result<LinkReport, String> {
+    let root = find_project_root(project_path)?;
+    let mut file_list = Vec::new();
+    collect_files(&root, None, &mut file_list)?;
+
+    // Load every file in the project, then any files outside the project
+    // which these refer to.
+    let toc_path = root.join("toc.md");
+    let mut anchor_index = AnchorIndex::new(Some(toc_path.clone()));
+    for file_path in &file_list {
+        anchor_index.load_file(file_path);
+    }
+    anchor_index.load_pending();
+
+    // Display paths relative to the root of the project.
+    let display_path = |path: &Path| {
+        path.strip_prefix(&root)
+            .unwrap_or(path)
+            .to_slash_lossy()
+            .to_string()
+    };
+    let mut broken_links = Vec::new();
+    for file_anchor in anchor_index.files() {
+        let FileAnchor::Html(html_file_anchor) = file_anchor else {
+            continue;
+        };
+        // Only report links in the project.
+        if !html_file_anchor.file_anchor.path.starts_with(&root) {
+            continue;
+        }
+        for hyperlink in &html_file_anchor.hyperlinks {
+            let kind = match (
+                anchor_index.get_file(&hyperlink.file),
+                &hyperlink.html_anchor,
+            ) {
+                (None, _) if hyperlink.source.file == toc_path => BrokenLinkKind::MissingTocEntry,
+                (None, _) => BrokenLinkKind::MissingFile,
+                // Fragments in non-HTML files (for example, a page number in a
+                // PDF) can't be checked.
+                (Some(FileAnchor::Plain(_)), _) | (Some(_), None) => continue,
+                (Some(FileAnchor::Html(target_file_anchor)), Some(html_anchor)) => {
+                    if target_file_anchor.ids.contains_key(html_anchor) {
+                        continue;
+                    }
+                    BrokenLinkKind::MissingAnchor
+                }
+            };
+            broken_links.push(BrokenLink {
+                kind,
+                file: display_path(&hyperlink.source.file),
+                anchor: hyperlink.source.anchor.clone(),
+                target: display_path(&hyperlink.file),
+                target_anchor: hyperlink.html_anchor.clone(),
+            });
+        }
+    }
+    // Report links in a consistent order.
+    broken_links.sort_by(|a, b| {
+        (&a.file, &a.target, &a.target_anchor).cmp(&(&b.file, &b.target, &b.target_anchor))
+    });
+
+    Ok(LinkReport {
+        root: root.to_string_lossy().to_string(),
+        files_checked: file_list.len(),
+        broken_links,
+    })
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{check_project, BrokenLink, BrokenLinkKind};
+    use crate::prep_test_dir;
+
+    #[test]
+    fn test_check_project() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+
+        let report = check_project(&test_dir.join("src")).unwrap();
+        assert_eq!(report.files_checked, 3);
+        assert_eq!(
+            report.broken_links,
+            vec![
+                BrokenLink {
+                    kind: BrokenLinkKind::MissingFile,
+                    file: "src/a.py".to_string(),
+                    anchor: Some("setup".to_string()),
+                    target: "src/missing.py".to_string(),
+                    target_anchor: None,
+                },
+                BrokenLink {
+                    kind: BrokenLinkKind::MissingAnchor,
+                    file: "src/a.py".to_string(),
+                    anchor: Some("setup".to_string()),
+                    target: "toc.md".to_string(),
+                    target_anchor: Some("nowhere".to_string()),
+                },
+                BrokenLink {
+                    kind: BrokenLinkKind::MissingTocEntry,
+                    file: "toc.md".to_string(),
+                    anchor: Some("contents".to_string()),
+                    target: "TheDescent/esh207/HW1.cpp".to_string(),
+                    target_anchor: None,
+                },
+            ]
+        );
+        let text = report.to_text();
+        assert!(text.contains("toc.md#contents: missing TOC entry TheDescent/esh207/HW1.cpp\n"));
+        assert!(text.ends_with("found 3 broken links."));
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+}
@@ -15,10 +15,181 @@
 /// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
 ///
 /// # `main.rs` -- Entrypoint for the CodeChat Editor Server
-use code_chat_editor::webserver;
+// ## Imports
+//
+// ### Standard library
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
+// ### Third-party
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+// ### Local
+use code_chat_editor::{
+    export, lexer, link_checker, processing,
+    webserver::{self, IP_ADDRESS, IP_PORT},
+};
+
+// ## Command-line interface
+/// The CodeChat Editor Server.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// The action to take; if omitted, start the server using the default
+    /// options.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Start the server.
+    Serve {
+        #[command(flatten)]
+        address: Address,
+        /// The directory to show when browsing the filesystem.
+        #[arg(long)]
+        root: Option<PathBuf>,
+        /// The log4rs config file. If it doesn't exist, log to the console.
+        #[arg(long, default_value = "log4rs.yml")]
+        log_config: PathBuf,
+    },
+    /// Stop a running server.
+    Stop {
+        #[command(flatten)]
+        address: Address,
+    },
+    /// Report the status of a running server.
+    Status {
+        #[command(flatten)]
+        address: Address,
+    },
+    /// Export a project (a directory tree containing a `toc.md`) to a static
+    /// HTML site.
+    Export {
+        /// A file or directory in the project to export.
+        project: PathBuf,
+        /// The directory to write the site to.
+        output: PathBuf,
+    },
+    /// Check a project for broken links.
+    Check {
+        /// A file or directory in the project to check.
+        project: PathBuf,
+        /// The format of the report.
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// Print the JSON Schema for the messages exchanged with an IDE.
+    Schema,
+}
+
+#[derive(Clone, ValueEnum)]
+enum ReportFormat {
+    Text,
+    Json,
+}
+
+/// The address of the server.
+#[derive(Args)]
+struct Address {
+    /// The host name or IP address of the server.
+    #[arg(long, default_value = IP_ADDRESS)]
+    host: String,
+    /// The port of the server.
+    #[arg(long, default_value_t = IP_PORT)]
+    port: u16,
+}
+
+// ## Code
 #[cfg(not(tarpaulin_include))]
-fn main() {
-    webserver::configure_logger();
-    webserver::main().unwrap();
+fn main() -> ExitCode {
+    let command = Cli::parse().command.unwrap_or(Command::Serve {
+        address: Address {
+            host: IP_ADDRESS.to_string(),
+            port: IP_PORT,
+        },
+        root: None,
+        log_config: PathBuf::from("log4rs.yml"),
+    });
+    let result = match command {
+        Command::Serve {
+            address,
+            root,
+            log_config,
+        } => {
+            webserver::configure_logger(&log_config);
+            // Verify the root path before starting the server.
+            let root = match root.map(|root| root.canonicalize().map_err(|err| (root, err))) {
+                None => None,
+                Some(Ok(root)) => Some(root),
+                Some(Err((root, err))) => {
+                    eprintln!("Invalid root path {root:?}: {err}.");
+                    return ExitCode::FAILURE;
+                }
+            };
+            // Use the languages of the project being browsed.
+            let project_path = root.clone().unwrap_or_else(|| env::current_dir().unwrap());
+            match load_languages(&project_path) {
+                Ok(config_files) => {
+                    for config_file in config_files {
+                        log::info!("Loaded languages from {}.", config_file.display());
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            webserver::main(&address.host, address.port, root)
+                .map(|_| "Server stopped.".to_string())
+                .map_err(|err| format!("Server error: {err}."))
+        }
+        Command::Stop { address } => webserver::stop_server(&address.host, address.port),
+        Command::Status { address } => webserver::server_status(&address.host, address.port),
+        Command::Export { project, output } => {
+            load_languages(&project).and_then(|_| export::export_project(&project, &output))
+        }
+        Command::Check { project, format } => match load_languages(&project)
+            .and_then(|_| link_checker::check_project(&project))
+        {
+            Ok(link_report) => {
+                println!(
+                    "{}",
+                    match format {
+                        ReportFormat::Text => link_report.to_text(),
+                        ReportFormat::Json => serde_json::to_string_pretty(&link_report).unwrap(),
+                    }
+                );
+                // Report failure if there are broken links, for use in scripts.
+                return if link_report.broken_links.is_empty() {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                };
+            }
+            Err(err) => Err(err),
+        },
+        Command::Schema => Ok(serde_json::to_string_pretty(&webserver::protocol_schema()).unwrap()),
+    };
+    match result {
+        Ok(msg) => {
+            println!("{msg}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Load the languages defined by the user and by the project containing
+// `project_path`, if it's in a project.
+fn load_languages(project_path: &Path) -> Result<Vec<PathBuf>, String> {
+    let project_root = processing::find_project_root(project_path).ok();
+    lexer::load_user_languages(project_root.as_deref())
 }
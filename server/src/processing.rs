@@ -15,32 +15,47 @@
 /// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
 ///
 /// # `processing.rs` -- Transform source code to its web-editable equivalent and back
+// ## Submodule definitions
+mod diff;
+mod doc_comment;
+mod merge;
+mod position;
+
 // ## Imports
 //
 // ### Standard library
 //
-// For commented-out caching code.
-/**
-use std::collections::{HashMap, HashSet};
-use std::fs::Metadata;
-use std::io;
-use std::ops::Deref;
-use std::rc::{Rc, Weak};
-*/
 use std::cmp::max;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
-use std::path::Path;
+use std::fs;
 use std::path::PathBuf;
+use std::path::{Component, Path};
+use std::sync::Mutex;
 
 // ### Third-party
 use lazy_static::lazy_static;
 use pulldown_cmark::{html, Options, Parser};
-use regex::Regex;
+use regex::{Captures, Regex};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::lexer::LEXERS;
+use crate::lexer::lexers;
 // ### Local
-use crate::lexer::{source_lexer, CodeDocBlock, DocBlock, LanguageLexerCompiled};
+use crate::lexer::{
+    docstring_to_source, has_style, javadoc_to_source, source_lexer, source_lexer_incremental,
+    CodeDocBlock, DocBlock, LanguageLexerCompiled, JAVADOC_INLINE, JAVADOC_NO_GUTTER,
+};
+pub use diff::{new_version, CodeChatForWebDiff, CodeChatForWebUpdate, DocBlockDiff, StringDiff};
+use doc_comment::{
+    add_doc_comment_style, doc_comment_to_markdown, is_doc_comment_delimiter,
+    markdown_to_doc_comment, INTRA_DOC_LINK_TITLE,
+};
+pub use merge::merge3;
+pub use position::{
+    CodeMirrorPosition, CursorPosition, LineScrollPosition, ScrollPosition, Selection,
+    SourcePosition,
+};
 
 // ## Data structures
 //
@@ -59,7 +74,7 @@ use crate::lexer::{source_lexer, CodeDocBlock, DocBlock, LanguageLexerCompiled};
 
 /// <a id="LexedSourceFile"></a>Define the JSON data structure used to represent
 /// a source file in a web-editable format.
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct CodeChatForWeb {
     pub metadata: SourceFileMetadata,
     pub source: CodeMirror,
@@ -69,14 +84,36 @@ pub struct CodeChatForWeb {
 /// it both to and from the client. TODO: currently, this is too simple to
 /// justify a struct. This allows for future growth -- perhaps the valid types
 /// of comment delimiters?
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct SourceFileMetadata {
     pub mode: String,
+    /// The hyperlinks which refer to this file or to anchors in it. The Server
+    /// provides these; the Client displays them, but doesn't send them back.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backlinks: Vec<Backlink>,
+}
+
+/// <a id="Backlink"></a>A hyperlink which refers to a file or an anchor in it,
+/// as seen from that file.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct Backlink {
+    /// The id of the anchor this hyperlink refers to, or `None` if it refers to
+    /// the file.
+    pub anchor: Option<String>,
+    /// The file containing this hyperlink, relative to the directory of the
+    /// file it refers to.
+    pub file: String,
+    /// The anchor nearest this hyperlink in the file containing it.
+    pub source_anchor: Option<String>,
+    /// A snippet of text preceding this hyperlink.
+    pub pre_snippet: String,
+    /// A snippet of text starting with this hyperlink's contents.
+    pub post_snippet: String,
 }
 
 /// The format used by CodeMirror to serialize/deserialize editor contents.
 /// TODO: Link to JS code where this data structure is defined.
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct CodeMirror {
     /// The document being edited.
     pub doc: String,
@@ -85,7 +122,7 @@ pub struct CodeMirror {
 }
 
 /// This defines a doc block for CodeMirror.
-pub type CodeMirrorDocBlocks = Vec<(
+pub type CodeMirrorDocBlock = (
     // From -- the starting character this doc block is anchored to.
     usize,
     // To -- the ending character this doc block is anchored to.
@@ -96,7 +133,13 @@ pub type CodeMirrorDocBlocks = Vec<(
     String,
     // contents
     String,
-)>;
+    // style -- details of how the comment was written which the contents don't
+    // capture (see `DocBlock`).
+    String,
+);
+
+/// The doc blocks in a CodeMirror document.
+pub type CodeMirrorDocBlocks = Vec<CodeMirrorDocBlock>;
 
 /// This enum contains the results of translating a source file to the CodeChat
 /// Editor format.
@@ -130,6 +173,26 @@ pub enum TranslationResultsString {
     Toc(String),
 }
 
+/// The results of translating a source file, which allow the next translation
+/// of the same file (for example, after the user types a character in the IDE)
+/// to re-lex and re-render only what changed.
+#[derive(Default)]
+pub struct TranslationCache {
+    // The name of the lexer used for this translation.
+    lexer_name: String,
+    // The source code which was translated.
+    file_contents: String,
+    // The result of lexing `file_contents`.
+    code_doc_block_vec: Vec<CodeDocBlock>,
+    // The HTML rendered from each doc block in `code_doc_block_vec`.
+    doc_block_html_vec: Vec<String>,
+    // True if any doc block contains a link reference or footnote definition.
+    has_link_definitions: bool,
+    // True if the doc blocks were rendered as a single Markdown document; see
+    // `render_together`.
+    is_rendered_together: bool,
+}
+
 // On save, the process is CodeChatForWeb -> Vec\<CodeDocBlocks> -> source code.
 //
 // ## Globals
@@ -137,8 +200,34 @@ lazy_static! {
     /// Match the lexer directive in a source file.
     static ref LEXER_DIRECTIVE: Regex = Regex::new(r"CodeChat Editor lexer: (\w+)").unwrap();
     static ref DOC_BLOCK_SEPARATOR_STRING_SHORT: String = remove_first_last_chars(DOC_BLOCK_SEPARATOR_STRING);
+    /// Match a Markdown link reference definition or footnote definition, such
+    /// as `[1]: http://foo.org` or `[^1]: A footnote.`
+    static ref LINK_DEFINITION: Regex = Regex::new(r"(?m)^ {0,3}\[[^\]]+\]:").unwrap();
+    /// Match an HTML start tag, capturing its name, attributes, and a trailing
+    /// `/` for self-closing tags.
+    static ref HTML_START_TAG: Regex = Regex::new(r"<([a-zA-Z][a-zA-Z0-9-]*)((?:\s[^>]*?)?)(/?)>").unwrap();
+    /// Match any HTML tag.
+    static ref HTML_TAG: Regex = Regex::new(r"</?[a-zA-Z][^>]*>").unwrap();
+    /// Match an attribute in a start tag, capturing its name and value.
+    static ref HTML_ATTRIBUTE: Regex = Regex::new(r#"\s([a-zA-Z][a-zA-Z0-9_:.-]*)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    /// Match a hyperlink with no contents, capturing its attributes.
+    static ref EMPTY_LINK: Regex = Regex::new(r"<[aA](\s[^>]*)></[aA]>").unwrap();
+    static ref HEADING_TAG_NAME: Regex = Regex::new(r"^[hH]([1-6])$").unwrap();
+    /// Match the start and end tags which determine the structure of the TOC,
+    /// capturing the `/` of an end tag, the tag name, and its attributes.
+    static ref TOC_TAG: Regex = Regex::new(r"<(/?)((?i)ol|ul|li|a)(\s[^>]*)?>").unwrap();
+    /// A URL with a scheme (`http:`, `mailto:`, etc.) is absolute.
+    static ref URL_SCHEME: Regex = Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:").unwrap();
+    /// The anchor index for each project.
+    static ref ANCHOR_INDEXES: Mutex<HashMap<PathBuf, AnchorIndex>> = Mutex::new(HashMap::new());
 }
 
+/// The groups of numbered items. An anchor whose id begins with a group's
+/// prefix followed by a colon, such as `<a id="fig:setup"></a>`, is numbered
+/// within that group and labeled with the group's name.
+pub const NUMBERING_GROUPS: [(&str, &str); 3] =
+    [("fig", "Figure"), ("tbl", "Table"), ("eq", "Equation")];
+
 const DOC_BLOCK_SEPARATOR_STRING: &str = "\n<CodeChatEditor-separator/>\n\n";
 
 fn remove_first_last_chars(str: &str) -> String {
@@ -170,6 +259,33 @@ pub fn find_path_to_toc(file_path: &Path) -> Option<PathBuf> {
     }
 }
 
+// Find the root of the project containing the provided file or directory: the
+// directory containing its `toc.md`.
+pub fn find_project_root(project_path: &Path) -> Result<PathBuf, String> {
+    let project_path = project_path
+        .canonicalize()
+        .map_err(|err| format!("Unable to open project {project_path:?}: {err}."))?;
+    let project_dir = if project_path.is_dir() {
+        project_path
+    } else {
+        project_path.parent().unwrap().to_path_buf()
+    };
+    // `find_path_to_toc` returns a path relative to the directory containing
+    // the provided file; provide a file in `project_dir` to start the search
+    // there.
+    let Some(path_to_toc) = find_path_to_toc(&project_dir.join("toc.md")) else {
+        return Err(format!(
+            "No toc.md found in {project_dir:?} or its parents; this isn't a project."
+        ));
+    };
+    Ok(project_dir
+        .join(path_to_toc)
+        .parent()
+        .unwrap()
+        .canonicalize()
+        .unwrap())
+}
+
 // ## Transform `CodeChatForWeb` to source code
 //
 // This function takes in a source file in web-editable format
@@ -179,7 +295,8 @@ pub fn codechat_for_web_to_source(
     codechat_for_web: CodeChatForWeb,
 ) -> Result<String, String> {
     // Given the mode, find the lexer.
-    let lexer: &std::sync::Arc<crate::lexer::LanguageLexerCompiled> = match LEXERS
+    let lexers = lexers();
+    let lexer: &std::sync::Arc<crate::lexer::LanguageLexerCompiled> = match lexers
         .map_mode_to_lexer
         .get(&codechat_for_web.metadata.mode)
     {
@@ -213,11 +330,17 @@ fn code_mirror_to_code_doc_blocks(code_mirror: &CodeMirror) -> Vec<CodeDocBlock>
             let s: String = code_contents.iter().collect();
             code_doc_block_arr.push(CodeDocBlock::CodeBlock(s.to_string()))
         }
-        // Append the doc block.
+        // Append the doc block, translating the Markdown produced from
+        // documentation comment tags back to these tags.
         code_doc_block_arr.push(CodeDocBlock::DocBlock(DocBlock {
             indent: codemirror_doc_block.2.to_string(),
             delimiter: codemirror_doc_block.3.to_string(),
-            contents: codemirror_doc_block.4.to_string(),
+            contents: if is_doc_comment_delimiter(&codemirror_doc_block.3) {
+                markdown_to_doc_comment(&codemirror_doc_block.4, &codemirror_doc_block.5)
+            } else {
+                codemirror_doc_block.4.to_string()
+            },
+            style: codemirror_doc_block.5.to_string(),
             lines: 0,
         }));
         code_index = codemirror_doc_block.1 + 1;
@@ -235,7 +358,7 @@ fn code_mirror_to_code_doc_blocks(code_mirror: &CodeMirror) -> Vec<CodeDocBlock>
 }
 
 // Turn this vec of CodeDocBlocks into a string of source code.
-fn code_doc_block_vec_to_source(
+pub(crate) fn code_doc_block_vec_to_source(
     code_doc_block_vec: Vec<CodeDocBlock>,
     lexer: &LanguageLexerCompiled,
 ) -> Result<String, String> {
@@ -266,7 +389,28 @@ fn code_doc_block_vec_to_source(
                     .contains(&doc_block.delimiter);
 
                 // Build a comment based on the type of the delimiter.
-                if is_inline_delim {
+                if lexer
+                    .language_lexer
+                    .is_docstring_delimiter(&doc_block.delimiter)
+                {
+                    // Docstrings have their own format.
+                    file_contents += &docstring_to_source(
+                        &doc_block.indent,
+                        &doc_block.delimiter,
+                        &doc_block.contents,
+                    );
+                } else if lexer
+                    .language_lexer
+                    .is_javadoc_delimiter(&doc_block.delimiter)
+                    && !has_style(&doc_block.style, JAVADOC_NO_GUTTER)
+                {
+                    // So do Javadoc-style comments with a gutter.
+                    file_contents += &javadoc_to_source(
+                        &doc_block.indent,
+                        &doc_block.contents,
+                        has_style(&doc_block.style, JAVADOC_INLINE),
+                    );
+                } else if is_inline_delim {
                     // To produce an inline comment, split the contents into a
                     // series of lines, adding the indent and inline comment
                     // delimiter to each line.
@@ -352,9 +496,18 @@ fn code_doc_block_vec_to_source(
                             );
                         // Since this isn't a first line:
                         } else {
+                            // - If the last line is just a newline, the
+                            //   closing delimiter was on a line by itself;
+                            //   keep it there.
+                            if is_last && *content_line == "\n" {
+                                append_doc_block(
+                                    &doc_block.indent,
+                                    block_comment_closing_delimiter,
+                                    "\n",
+                                );
                             // - If this line is just a newline, include just
                             //   the newline.
-                            if *content_line == "\n" {
+                            } else if *content_line == "\n" {
                                 append_doc_block("", "", "\n");
                             // - Otherwise, include spaces in place of the
                             //   delimiter.
@@ -388,19 +541,40 @@ fn code_doc_block_vec_to_source(
 pub fn source_to_codechat_for_web(
     // The file's contents.
     file_contents: &str,
-    // The file's extension.
+    // The file's extension or, for files such as `Makefile`, its name; see
+    // `lexer_key`.
+    file_ext: &str,
+    // True if this file is a TOC.
+    is_toc: bool,
+    // True if this file is part of a project.
+    is_project: bool,
+) -> TranslationResults {
+    source_to_codechat_for_web_cached(file_contents, file_ext, is_toc, is_project, None)
+}
+
+// Like `source_to_codechat_for_web`, but use the previous translation in
+// `translation_cache` (if provided) to translate only what changed, then
+// update it with this translation.
+fn source_to_codechat_for_web_cached(
+    // The file's contents.
+    file_contents: &str,
+    // The file's extension or, for files such as `Makefile`, its name; see
+    // `lexer_key`.
     file_ext: &str,
     // True if this file is a TOC.
     _is_toc: bool,
     // True if this file is part of a project.
     _is_project: bool,
+    // The previous translation of this file, if available.
+    translation_cache: Option<&mut TranslationCache>,
 ) -> TranslationResults {
     // Determine the lexer to use for this file.
+    let lexers = lexers();
     let lexer_name;
     // First, search for a lexer directive in the file contents.
     let lexer = if let Some(captures) = LEXER_DIRECTIVE.captures(file_contents) {
         lexer_name = captures[1].to_string();
-        match LEXERS.map_mode_to_lexer.get(&lexer_name) {
+        match lexers.map_mode_to_lexer.get(&lexer_name) {
             Some(v) => v,
             None => {
                 return TranslationResults::Err(format!(
@@ -411,7 +585,7 @@ pub fn source_to_codechat_for_web(
         }
     } else {
         // Otherwise, look up the lexer by the file's extension.
-        if let Some(llc) = LEXERS.map_ext_to_lexer_vec.get(&file_ext.to_string()) {
+        if let Some(llc) = lexers.map_ext_to_lexer_vec.get(&String::from(file_ext)) {
             llc.first().unwrap()
         } else {
             // The file type is unknown; treat it as plain text.
@@ -424,6 +598,7 @@ pub fn source_to_codechat_for_web(
     let codechat_for_web = CodeChatForWeb {
         metadata: SourceFileMetadata {
             mode: lexer.language_lexer.lexer_name.to_string(),
+            backlinks: Vec::new(),
         },
         source: if lexer.language_lexer.lexer_name.as_str() == "markdown" {
             // Document-only files are easy: just encode the contents.
@@ -443,65 +618,201 @@ pub fn source_to_codechat_for_web(
                 doc_blocks: Vec::new(),
             };
 
-            // Lex the code.
-            code_doc_block_arr = source_lexer(file_contents, lexer);
-
-            // Combine all the doc blocks into a single string, separated by a
-            // delimiter. Transform this to markdown, then split the transformed
-            // content back into the doc blocks they came from. This is
-            // necessary to allow references between doc blocks to work; for
-            // example, `[Link][1]` in one doc block, then `[1]: http:/foo.org`
-            // in another doc block requires both to be in the same Markdown
-            // document to translate correctly.
-            let mut doc_block_contents_vec: Vec<&str> = Vec::new();
-            for code_or_doc_block in &code_doc_block_arr {
+            // Lex the code. If the previous translation used the same lexer,
+            // re-lex only the changed part of the file.
+            let lexer_name = lexer.language_lexer.lexer_name.as_str();
+            let prev_translation = translation_cache
+                .as_deref()
+                .filter(|prev_translation| prev_translation.lexer_name == lexer_name);
+            let (first_changed_index, unchanged_tail_len);
+            (code_doc_block_arr, first_changed_index, unchanged_tail_len) =
+                if let Some(prev_translation) = prev_translation {
+                    let incremental_lex = source_lexer_incremental(
+                        &prev_translation.file_contents,
+                        &prev_translation.code_doc_block_vec,
+                        file_contents,
+                        lexer,
+                    );
+                    (
+                        incremental_lex.classified_source,
+                        incremental_lex.first_changed_index,
+                        incremental_lex.unchanged_tail_len,
+                    )
+                } else {
+                    (source_lexer(file_contents, lexer), 0, 0)
+                };
+
+            // Documentation comments contain tags which must first be
+            // translated to Markdown.
+            let doc_block_markdown = |doc_block: &DocBlock| {
+                if is_doc_comment_delimiter(&doc_block.delimiter) {
+                    doc_comment_to_markdown(&doc_block.contents)
+                } else {
+                    doc_block.contents.clone()
+                }
+            };
+            let count_doc_blocks = |code_doc_block_slice: &[CodeDocBlock]| {
+                code_doc_block_slice
+                    .iter()
+                    .filter(|code_doc_block| matches!(code_doc_block, CodeDocBlock::DocBlock(_)))
+                    .count()
+            };
+
+            // Translate the changed doc blocks to Markdown.
+            let changed_end_index = code_doc_block_arr.len() - unchanged_tail_len;
+            let mut changed_doc_block_markdown_vec: Vec<String> = Vec::new();
+            for code_or_doc_block in &code_doc_block_arr[first_changed_index..changed_end_index] {
                 if let CodeDocBlock::DocBlock(doc_block) = code_or_doc_block {
-                    doc_block_contents_vec.push(&doc_block.contents);
+                    changed_doc_block_markdown_vec.push(doc_block_markdown(doc_block));
                 }
             }
-            let combined_doc_blocks = &doc_block_contents_vec.join(DOC_BLOCK_SEPARATOR_STRING);
-            let html = markdown_to_html(combined_doc_blocks);
+            let changed_has_link_definitions = changed_doc_block_markdown_vec
+                .iter()
+                .any(|markdown| LINK_DEFINITION.is_match(markdown));
+
+            // If any doc block (including those in the previous translation)
+            // contains a definition, render all of them, since a definition
+            // may be used by any doc block; see `render_together`. Otherwise,
+            // render only the changed doc blocks, then reuse the HTML from the
+            // previous translation for the unchanged doc blocks.
+            let partial_render = prev_translation
+                .filter(|prev_translation| {
+                    !prev_translation.has_link_definitions
+                        && prev_translation.is_rendered_together
+                        && !changed_has_link_definitions
+                })
+                .and_then(|prev_translation| {
+                    let prefix_len = count_doc_blocks(&code_doc_block_arr[..first_changed_index]);
+                    let tail_len = count_doc_blocks(&code_doc_block_arr[changed_end_index..]);
+                    let prev_html_vec = &prev_translation.doc_block_html_vec;
+                    let tail_html_vec = &prev_html_vec[prev_html_vec.len() - tail_len..];
+                    let mut doc_block_contents_vec = prev_html_vec[..prefix_len].to_vec();
+                    if !changed_doc_block_markdown_vec.is_empty() {
+                        // A changed doc block may leave block-level state open
+                        // which alters the rendering of the doc blocks after
+                        // it; for example, an unclosed fenced code block or
+                        // `<pre>` swallows everything which follows. To catch
+                        // this, render the first unchanged doc block after the
+                        // changed doc blocks along with them; unless its HTML
+                        // matches the previous translation, fall back to a
+                        // full render.
+                        let next_doc_block_markdown = code_doc_block_arr[changed_end_index..]
+                            .iter()
+                            .find_map(|code_or_doc_block| match code_or_doc_block {
+                                CodeDocBlock::DocBlock(doc_block) => {
+                                    Some(doc_block_markdown(doc_block))
+                                }
+                                CodeDocBlock::CodeBlock(_) => None,
+                            });
+                        let mut html_vec = render_together(
+                            &[
+                                &changed_doc_block_markdown_vec[..],
+                                next_doc_block_markdown.as_slice(),
+                            ]
+                            .concat(),
+                        )?;
+                        if next_doc_block_markdown.is_some()
+                            && html_vec.pop().as_ref() != tail_html_vec.first()
+                        {
+                            return None;
+                        }
+                        doc_block_contents_vec.extend(html_vec);
+                    }
+                    doc_block_contents_vec.extend_from_slice(tail_html_vec);
+                    Some(doc_block_contents_vec)
+                });
+            let (doc_block_contents_vec, has_link_definitions, is_rendered_together) =
+                match partial_render {
+                    Some(doc_block_contents_vec) => (doc_block_contents_vec, false, true),
+                    None => {
+                        let doc_block_markdown_vec: Vec<String> = code_doc_block_arr
+                            .iter()
+                            .filter_map(|code_or_doc_block| match code_or_doc_block {
+                                CodeDocBlock::DocBlock(doc_block) => {
+                                    Some(doc_block_markdown(doc_block))
+                                }
+                                CodeDocBlock::CodeBlock(_) => None,
+                            })
+                            .collect();
+                        let has_link_definitions = doc_block_markdown_vec
+                            .iter()
+                            .any(|markdown| LINK_DEFINITION.is_match(markdown));
+                        match render_together(&doc_block_markdown_vec) {
+                            Some(doc_block_contents_vec) => {
+                                (doc_block_contents_vec, has_link_definitions, true)
+                            }
+                            // Some doc block swallowed the separators after
+                            // it. Since each doc block is displayed on its own,
+                            // render each one separately instead.
+                            None => (
+                                doc_block_markdown_vec
+                                    .iter()
+                                    .map(|markdown| markdown_to_html(markdown))
+                                    .collect(),
+                                has_link_definitions,
+                                false,
+                            ),
+                        }
+                    }
+                };
             // Now that we have HTML, process it. TODO.
-            //
-            // After processing by Markdown, the double newline at the of the
-            // doc block separator string becomes a single newline; split using
-            // this slightly shorter string.
-            doc_block_contents_vec = html.split(&*DOC_BLOCK_SEPARATOR_STRING_SHORT).collect();
 
             // Translate each `CodeDocBlock` to its `CodeMirror` equivalent.
+            // Track the length of the document in characters (not bytes,
+            // which is what `len()` returns).
+            let mut doc_len = 0;
             let mut index = 0;
-            for code_or_doc_block in code_doc_block_arr {
+            for code_or_doc_block in &code_doc_block_arr {
                 match code_or_doc_block {
-                    CodeDocBlock::CodeBlock(code_string) => code_mirror.doc.push_str(&code_string),
+                    CodeDocBlock::CodeBlock(code_string) => {
+                        code_mirror.doc.push_str(code_string);
+                        doc_len += code_string.chars().count();
+                    }
                     CodeDocBlock::DocBlock(doc_block) => {
                         // Create the doc block.
-                        //
-                        // Get the length of the string in characters (not
-                        // bytes, which is what `len()` returns).
-                        let len = code_mirror.doc.chars().count();
                         code_mirror.doc_blocks.push((
                             // From
-                            len,
+                            doc_len,
                             // To. Make this one line short, which allows
                             // CodeMirror to correctly handle inserts at the
                             // first character of the following code block. Note
                             // that the last doc block could be zero length, so
                             // handle this case.
-                            len + max(doc_block.lines, 1) - 1,
+                            doc_len + max(doc_block.lines, 1) - 1,
                             doc_block.indent.to_string(),
                             doc_block.delimiter.to_string(),
                             // Used the markdown-translated replacement for this
                             // doc block, rather than the original string.
                             doc_block_contents_vec[index].to_string(),
+                            // Documentation comments also record the tags they
+                            // use, so these can be restored.
+                            if is_doc_comment_delimiter(&doc_block.delimiter) {
+                                add_doc_comment_style(&doc_block.style, &doc_block.contents)
+                            } else {
+                                doc_block.style.clone()
+                            },
                         ));
                         index += 1;
                         // Append newlines to the document; the doc block will
                         // replace these in the editor. This keeps the line
                         // numbering of non-doc blocks correct.
                         code_mirror.doc.push_str(&"\n".repeat(doc_block.lines));
+                        doc_len += doc_block.lines;
                     }
                 }
             }
+
+            // Save this translation for next time.
+            if let Some(translation_cache) = translation_cache {
+                *translation_cache = TranslationCache {
+                    lexer_name: lexer_name.to_string(),
+                    file_contents: file_contents.to_string(),
+                    code_doc_block_vec: code_doc_block_arr,
+                    doc_block_html_vec: doc_block_contents_vec,
+                    has_link_definitions,
+                    is_rendered_together,
+                };
+            }
             code_mirror
         },
     };
@@ -509,6 +820,23 @@ pub fn source_to_codechat_for_web(
     TranslationResults::CodeChat(codechat_for_web)
 }
 
+// Return the key used to look up the lexer for `file_path`. Some files, such
+// as `Makefile` or `CMakeLists.txt`, are identified by their complete name;
+// all others are identified by their extension.
+pub fn lexer_key(file_path: &Path) -> String {
+    if let Some(file_name) = file_path.file_name() {
+        let file_name = file_name.to_string_lossy().to_string();
+        if lexers().map_ext_to_lexer_vec.contains_key(&file_name) {
+            return file_name;
+        }
+    }
+    file_path
+        .extension()
+        .unwrap_or_else(|| OsStr::new(""))
+        .to_string_lossy()
+        .to_string()
+}
+
 // Like `source_to_codechat_for_web`, translate a source file to the CodeChat
 // Editor client format. This wraps a call to that function with additional
 // processing (determine if this is part of a project, encode the output as
@@ -520,12 +848,12 @@ pub fn source_to_codechat_for_web_string(
     file_path: &Path,
     // True if this file is a TOC.
     is_toc: bool,
+    // The previous translation of this file, if available; see
+    // `TranslationCache`.
+    translation_cache: Option<&mut TranslationCache>,
 ) -> (TranslationResultsString, Option<PathBuf>) {
-    // Determine the file's extension, in order to look up a lexer.
-    let ext = &file_path
-        .extension()
-        .unwrap_or_else(|| OsStr::new(""))
-        .to_string_lossy();
+    // Determine the key used to look up a lexer.
+    let ext = &lexer_key(file_path);
 
     // To determine if this source code is part of a project, look for a project
     // file by searching the current directory, then all its parents, for a file
@@ -534,8 +862,16 @@ pub fn source_to_codechat_for_web_string(
     let is_project = path_to_toc.is_some();
 
     (
-        match source_to_codechat_for_web(file_contents, ext, is_toc, is_project) {
-            TranslationResults::CodeChat(codechat_for_web) => {
+        match source_to_codechat_for_web_cached(
+            file_contents,
+            ext,
+            is_toc,
+            is_project,
+            translation_cache,
+        ) {
+            TranslationResults::CodeChat(mut codechat_for_web) => {
+                // Catalog this file's anchors and hyperlinks.
+                index_codechat_for_web(file_path, &path_to_toc, &mut codechat_for_web);
                 if is_toc {
                     // For the table of contents sidebar, which is pure
                     // markdown, just return the resulting HTML, rather than the
@@ -554,6 +890,33 @@ pub fn source_to_codechat_for_web_string(
 
 /// Convert markdown to HTML. (This assumes the Markdown defined in the
 /// CommonMark spec.)
+// Combine all the doc blocks into a single string, separated by a delimiter.
+// Transform this to markdown, then split the transformed content back into the
+// doc blocks they came from. This is necessary to allow references between doc
+// blocks to work; for example, `[Link][1]` in one doc block, then
+// `[1]: http:/foo.org` in another doc block requires both to be in the same
+// Markdown document to translate correctly. Return `None` if this doesn't
+// split back into one string per doc block, which happens when a doc block
+// leaves a fenced code block or an HTML block such as `<pre>` or `<!--` open,
+// causing the separators which follow to be treated as its contents.
+fn render_together(doc_block_markdown_vec: &[String]) -> Option<Vec<String>> {
+    let combined_doc_blocks = doc_block_markdown_vec.join(DOC_BLOCK_SEPARATOR_STRING);
+    let html = markdown_to_html(&combined_doc_blocks);
+    // After processing by Markdown, the double newline at the of the doc block
+    // separator string becomes a single newline; split using this slightly
+    // shorter string.
+    let html_vec: Vec<String> = html
+        .split(&*DOC_BLOCK_SEPARATOR_STRING_SHORT)
+        .map(|html| html.to_string())
+        .collect();
+    // With no doc blocks, there's still one (empty) string.
+    if html_vec.len() == doc_block_markdown_vec.len().max(1) {
+        Some(html_vec)
+    } else {
+        None
+    }
+}
+
 fn markdown_to_html(markdown: &str) -> String {
     let mut options = Options::all();
     // Turndown (which converts HTML back to Markdown) doesn't support smart
@@ -565,236 +928,1218 @@ fn markdown_to_html(markdown: &str) -> String {
     html_output
 }
 
+// ## Anchor and hyperlink index
+//
 // Goal: make it easy to update the data structure. We update on every
 // load/save, then do some accesses during those processes.
 //
-// Top-level data structures: a file HashSet<PathBuf, FileAnchor> and an id
-// HashMap<id, {Anchor, HashSet<referring_id>}>. Some FileAnchors in the file
-// HashSet are also in a pending load list.
+// Top-level data structures: a file HashMap<PathBuf, FileAnchor> and an id
+// HashMap<id, AnchorVal>, where an `AnchorVal` records the file containing the
+// anchor and the set of links referring to it. Hyperlinks to files which
+// aren't in the file HashMap are kept in a pending hyperlinks list.
 //
 // - To update a file:
-//   - Remove the old file from the file HasHMap. Add an empty FileAnchor to the
-//     file HashMap.
-//   - For each id, see if that id already exists.
-//     - If the id exists: if it refers to an id in the old FileAnchor, replace
-//       it with the new one. If not, need to perform resolution on this id (we
-//       have a non-unique id; how to fix?).
-//     - If the id doesn't exist: create a new one.
-//   - For each hyperlink, see if that id already exists.
-//     - If so, upsert the referring id. Check the metadata on the id to make
-//       sure that data is current. If not, add this to the pending hyperlinks
-//       list. If the file is missing, delete it from the cache.
-//     - If not, create a new entry in the id HashSet and add the referring id
-//       to the HashSet. Add the file to a pending hyperlinks list.
-//   - When the file is processed:
-//     - Look for all entries in the pending file list that refer to the current
-//       file and resolve these. Start another task to load in all pending
-//       files.
-//     - Look at the old file; remove each id that's still in the id HashMap. If
-//       the id was in the HashMap and it also was a Hyperlink, remove that from
-//       the HashSet.
-// - To remove a file from the HashMap:
-//   - Remove it from the file HashMap.
-//   - For each hyperlink, remove it from the HashSet of referring links (if
-//     that id still exists).
-//   - For each id, remove it from the id HashMap.
-// - To add a file from the HashSet:
-//   - Perform an update with an empty FileAnchor.
-//
-// Pending hyperlinks list: for each hyperlink,
-//
-// - check if the id is now current in the cache. If so, add the referring id to
-//   the HashSet then move to the next hyperlink.
-// - check if the file is now current in the cache. If not, load the file and
-//   update the cache, then go to step 1.
-// - The id was not found, even in the expected file. Add the hyperlink to a
-//   broken links set?
-//
-// Global operations:
-//
-// - Scan all files, then perform add/upsert/removes based on differences with
-//   the cache.
-//
-// Functions:
-//
-// - Upsert an Anchor.
-// - Upsert a Hyperlink.
-// - Upsert a file.
-// - Remove a file.
-/**
+//   - Remove the old file from the file HashMap, each of its ids from the id
+//     HashMap, and each of its hyperlinks from the referring links of their
+//     targets.
+//   - For each heading and anchor, assign an id. Headings without an id receive
+//     one based on their contents. If the id is already in use (by another
+//     file, or earlier in this file), append a numeric suffix to make it
+//     unique. Record the mapping from the id used in the file to this globally
+//     unique id, so that hyperlinks to the file can be translated.
+//   - For each hyperlink, translate its `#fragment` to the target's globally
+//     unique id if the target file is known.
+//   - Add the file to the file HashMap and its ids to the id HashMap.
+//   - Resolve each of this file's hyperlinks, along with all hyperlinks in
+//     other files which refer to this file: if the target file is known, add
+//     the referring link to the target anchor (or file); otherwise, add the
+//     hyperlink to the pending hyperlinks list. A hyperlink to an id not in
+//     its (known) target file is broken.
+// - To remove a file:
+//   - Remove it as in the first step of an update, then resolve all hyperlinks
+//     which refer to it; these become pending.
+// - To load pending files:
+//   - Load each file referred to by the pending hyperlinks list and update it
+//     as above. Since this may add new pending hyperlinks, repeat until no new
+//     files are found. Hyperlinks to files which don't exist remain pending.
+
 /// There are two types of files that can serve as an anchor: these are file
 /// anchor targets.
-enum FileAnchor {
+#[derive(Debug)]
+pub enum FileAnchor {
     Plain(PlainFileAnchor),
     Html(HtmlFileAnchor),
 }
 
+impl FileAnchor {
+    pub fn plain_file_anchor(&self) -> &PlainFileAnchor {
+        match self {
+            FileAnchor::Plain(plain_file_anchor) => plain_file_anchor,
+            FileAnchor::Html(html_file_anchor) => &html_file_anchor.file_anchor,
+        }
+    }
+
+    fn plain_file_anchor_mut(&mut self) -> &mut PlainFileAnchor {
+        match self {
+            FileAnchor::Plain(plain_file_anchor) => plain_file_anchor,
+            FileAnchor::Html(html_file_anchor) => &mut html_file_anchor.file_anchor,
+        }
+    }
+}
+
 /// This is the cached metadata for a file that serves as an anchor: perhaps an
 /// image, a PDF, or a video.
-struct PlainFileAnchor {
-    /// A relative path to this file, rooted at the project's TOC.
-    path: Rc<PathBuf>,
-    /// The globally-unique anchor used to link to this file. It's generated
-    /// based on hash of the file's contents, so that each file will have a
-    /// unique identifier.
-    anchor: String,
-    /// Metadata captured when this data was cached. If it disagrees with the
-    /// file's current state, then this cached data should be re=generated from
-    /// the file.
-    file_metadata: Metadata,
+#[derive(Debug)]
+pub struct PlainFileAnchor {
+    /// The path to this file.
+    pub path: PathBuf,
+    /// All hyperlinks which target this file (rather than an anchor in it).
+    pub referring_links: HashSet<LinkSource>,
 }
 
 /// Cached metadata for an HTML file.
-struct HtmlFileAnchor {
+#[derive(Debug)]
+pub struct HtmlFileAnchor {
     /// The file containing this HTML.
-    file_anchor: PlainFileAnchor,
-    /// The TOC numbering of this file.
-    numbering: Vec<Option<u32>>,
+    pub file_anchor: PlainFileAnchor,
+    /// The TOC numbering of this file: for each enclosing list in the TOC, the
+    /// item number if the list is ordered, or `None` if not. This is empty for
+    /// files not in the TOC.
+    pub numbering: Vec<Option<u32>>,
     /// The headings in this file.
-    headings: Vec<HeadingAnchor>,
+    pub headings: Vec<HeadingAnchor>,
     /// Anchors which appear before the first heading.
-    pre_anchors: Vec<NonHeadingAnchor>,
+    pub pre_anchors: Vec<NonHeadingAnchor>,
+    /// The hyperlinks in this file.
+    pub hyperlinks: Vec<Hyperlink>,
+    /// A map from each id used in this file's source to its globally-unique
+    /// id.
+    pub ids: HashMap<String, String>,
 }
 
 /// Cached metadata shared by both headings (which are also anchors) and
 /// non-heading anchors.
-struct AnchorCommon {
-    /// The HTML file containing this anchor.
-    html_file_anchor: Weak<FileAnchor>,
+#[derive(Debug)]
+pub struct AnchorCommon {
     /// The globally-unique anchor used to link to this object.
-    anchor: String,
+    pub anchor: String,
     /// The inner HTML of this anchor.
-    inner_html: String,
-    /// The hyperlink this anchor contains.
-    hyperlink: Option<Rc<Hyperlink>>,
-}
-
-/// An anchor is defined only in these two places: the anchor source.
-enum HtmlAnchor {
-    Heading(HeadingAnchor),
-    NonHeading(NonHeadingAnchor),
+    pub inner_html: String,
 }
 
 /// Cached metadata for a heading (which is always also an anchor).
-struct HeadingAnchor {
-    anchor_common: AnchorCommon,
-    /// The numbering of this heading on the HTML file containing it.
-    numbering: Vec<Option<u32>>,
+#[derive(Debug)]
+pub struct HeadingAnchor {
+    pub anchor_common: AnchorCommon,
+    /// The level of this heading: 1 for `<h1>`, etc.
+    pub level: u8,
+    /// The numbering of this heading on the HTML file containing it. The
+    /// heading's full number is the file's numbering followed by this.
+    pub numbering: Vec<Option<u32>>,
     /// Non-heading anchors which appear after this heading but before the next
     /// heading.
-    non_heading_anchors: Vec<NonHeadingAnchor>,
+    pub non_heading_anchors: Vec<NonHeadingAnchor>,
 }
 
 /// Cached metadata for a non-heading anchor.
-struct NonHeadingAnchor {
-    anchor_common: AnchorCommon,
-    /// The heading this anchor appears after (unless it appears before the
-    /// first heading in this file).
-    parent_heading: Option<Weak<HeadingAnchor>>,
-    /// A snippet of HTML preceding this anchor.
-    pre_snippet: String,
-    /// A snippet of HTML following this anchor.
-    post_snippet: String,
+#[derive(Debug)]
+pub struct NonHeadingAnchor {
+    pub anchor_common: AnchorCommon,
+    /// A snippet of text preceding this anchor.
+    pub pre_snippet: String,
+    /// A snippet of text following this anchor.
+    pub post_snippet: String,
     /// If this is a numbered item, the name of the numbering group it belongs
-    /// to.
-    numbering_group: Option<String>,
-    /// If this is a numbered item, its number.
-    number: u32,
-}
-
-/// An anchor can refer to any of these structs: these are all possible anchor
-/// targets.
-enum Anchor {
-    Html(HtmlAnchor),
-    File(FileAnchor),
+    /// to: the prefix of its id (see `NUMBERING_GROUPS`).
+    pub numbering_group: Option<String>,
+    /// If this is a numbered item, its number within this file.
+    pub number: u32,
 }
 
 /// The metadata for a hyperlink.
-struct Hyperlink {
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hyperlink {
+    /// The location of this hyperlink.
+    pub source: LinkSource,
     /// The file this hyperlink refers to.
-    file: PathBuf,
-    /// The anchor this hyperlink refers to.
-    html_anchor: String,
+    pub file: PathBuf,
+    /// The anchor this hyperlink refers to, as written in the hyperlink (not
+    /// the globally-unique id).
+    pub html_anchor: Option<String>,
+    /// A snippet of text preceding this hyperlink.
+    pub pre_snippet: String,
+    /// A snippet of text starting with this hyperlink's contents.
+    pub post_snippet: String,
+}
+
+/// The location of a hyperlink: the file containing it, and the nearest anchor
+/// (heading or non-heading) preceding it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinkSource {
+    pub file: PathBuf,
+    pub anchor: Option<String>,
 }
 
 /// The value stored in the id HashMap.
-struct AnchorVal {
-    /// The target anchor this id refers to.
-    anchor: Anchor,
+#[derive(Debug)]
+pub struct AnchorVal {
+    /// The file containing the anchor this id refers to.
+    pub file: PathBuf,
     /// All hyperlinks which target this anchor.
-    referring_links: Rc<HashSet<String>>,
+    pub referring_links: HashSet<LinkSource>,
 }
 
-// Given HTML, catalog all link targets and link-like items, ensuring that they
-// have a globally unique id.
-fn html_analyze(
-    file_path: &Path,
-    html: &str,
-    mut file_map: HashMap<Rc<PathBuf>, Rc<FileAnchor>>,
-    mut anchor_map: HashMap<Rc<String>, HashSet<AnchorVal>>,
-) -> io::Result<String> {
-    // Create the missing anchors:
-    //
-    // A missing file.
-    let missing_html_file_anchor = Rc::new(FileAnchor::Html(HtmlFileAnchor {
-        file_anchor: PlainFileAnchor {
-            path: Rc::new(PathBuf::new()),
-            anchor: "".to_string(),
-            // TODO: is there some way to create generic/empty metadata?
-            file_metadata: Path::new(".").metadata().unwrap(),
-        },
-        numbering: Vec::new(),
-        headings: Vec::new(),
-        pre_anchors: Vec::new(),
-    }));
-    // Define an anchor in this file.
-    let missing_anchor = NonHeadingAnchor {
-        anchor_common: AnchorCommon {
-            html_file_anchor: Rc::downgrade(&missing_html_file_anchor),
-            anchor: "".to_string(),
-            hyperlink: None,
-            inner_html: "".to_string(),
-        },
-        parent_heading: None,
-        pre_snippet: "".to_string(),
-        post_snippet: "".to_string(),
-        numbering_group: None,
-        number: 0,
+/// The index of all anchors and hyperlinks in a project.
+#[derive(Debug, Default)]
+pub struct AnchorIndex {
+    /// The project's TOC, if this is a project.
+    toc_path: Option<PathBuf>,
+    /// The numbering of each file in the TOC.
+    toc_numbering: HashMap<PathBuf, Vec<Option<u32>>>,
+    /// The file HashMap.
+    file_map: HashMap<PathBuf, FileAnchor>,
+    /// The id HashMap.
+    anchor_map: HashMap<String, AnchorVal>,
+    /// Hyperlinks to files not in the file HashMap.
+    pending_hyperlinks: Vec<Hyperlink>,
+}
+
+impl AnchorIndex {
+    pub fn new(toc_path: Option<PathBuf>) -> AnchorIndex {
+        AnchorIndex {
+            toc_path,
+            ..Default::default()
+        }
+    }
+
+    pub fn get_file(&self, file_path: &Path) -> Option<&FileAnchor> {
+        self.file_map.get(file_path)
+    }
+
+    pub fn get_anchor(&self, id: &str) -> Option<&AnchorVal> {
+        self.anchor_map.get(id)
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &FileAnchor> {
+        self.file_map.values()
+    }
+
+    pub fn pending_hyperlinks(&self) -> &[Hyperlink] {
+        &self.pending_hyperlinks
+    }
+
+    // Update a CodeChat Editor file, assigning ids to the HTML it contains.
+    pub fn upsert_codechat_for_web(
+        &mut self,
+        file_path: &Path,
+        codechat_for_web: &mut CodeChatForWeb,
+    ) {
+        update_codechat_for_web_html(codechat_for_web, |html| {
+            self.upsert_html_file(file_path, html)
+        });
+    }
+
+    // Provide text for the empty links and targets for the intra-doc links in
+    // a CodeChat Editor file. Call this after loading pending files, so that
+    // the targets of these links are known.
+    pub fn fill_link_text_codechat_for_web(
+        &self,
+        file_path: &Path,
+        codechat_for_web: &mut CodeChatForWeb,
+    ) {
+        update_codechat_for_web_html(codechat_for_web, |html| {
+            self.fill_link_text(file_path, &self.resolve_intra_doc_links(file_path, html))
+        });
+    }
+
+    // Point each intra-doc link (see `doc_comment.rs`) in this HTML to an
+    // anchor in the project whose id matches the item it names: first the
+    // full path (`crate::foo`), then the item's name (`foo`), then the id a
+    // heading with this name would receive. Anchors in this file take
+    // precedence. Leave links to unknown items unchanged.
+    pub fn resolve_intra_doc_links(&self, file_path: &Path, html: &str) -> String {
+        HTML_START_TAG
+            .replace_all(html, |captures: &Captures| {
+                let attributes = &captures[2];
+                if !captures[1].eq_ignore_ascii_case("a")
+                    || get_attribute(attributes, "title").as_deref() != Some(INTRA_DOC_LINK_TITLE)
+                {
+                    return captures[0].to_string();
+                }
+                let Some(path) = get_attribute(attributes, "href")
+                    .and_then(|href| href.strip_prefix('#').map(str::to_string))
+                else {
+                    return captures[0].to_string();
+                };
+                let name = path.rsplit("::").next().unwrap();
+                let local_ids = match self.file_map.get(file_path) {
+                    Some(FileAnchor::Html(html_file_anchor)) => Some(&html_file_anchor.ids),
+                    _ => None,
+                };
+                let ids = [path.as_str(), name, &slugify(name)];
+                let target = ids
+                    .iter()
+                    .find_map(|id| {
+                        local_ids?
+                            .get(*id)
+                            .map(|global_id| (file_path.to_path_buf(), global_id.clone()))
+                    })
+                    .or_else(|| {
+                        ids.iter().find_map(|id| {
+                            self.anchor_map
+                                .get(*id)
+                                .map(|anchor_val| (anchor_val.file.clone(), id.to_string()))
+                        })
+                    });
+                match target {
+                    Some((target_file, global_id)) => {
+                        let href = if target_file == file_path {
+                            format!("#{global_id}")
+                        } else {
+                            format!("{}#{global_id}", relative_path(file_path, &target_file))
+                        };
+                        format!(
+                            "<{}{}{}>",
+                            &captures[1],
+                            set_attribute(attributes, "href", &href),
+                            &captures[3]
+                        )
+                    }
+                    None => captures[0].to_string(),
+                }
+            })
+            .to_string()
+    }
+
+    // Provide text for each empty link (`<a href="..."></a>`) in this HTML
+    // based on its target: the number and contents of a heading, the label of
+    // a numbered item, etc. As with numbering, the Client displays this text
+    // using CSS, so that the link remains empty when saved.
+    pub fn fill_link_text(&self, file_path: &Path, html: &str) -> String {
+        EMPTY_LINK
+            .replace_all(html, |captures: &Captures| {
+                let link_text = get_attribute(&captures[1], "href")
+                    .and_then(|href| link_target(&href, file_path))
+                    .and_then(|(target, html_anchor)| self.link_text(&target, &html_anchor));
+                match link_text {
+                    Some(link_text) => format!(
+                        "<a{}></a>",
+                        set_attribute(&captures[1], "data-codechat-link-text", &link_text)
+                    ),
+                    None => captures[0].to_string(),
+                }
+            })
+            .to_string()
+    }
+
+    // Produce the text for a link to the provided target, or `None` if the
+    // target isn't known.
+    fn link_text(&self, file_path: &Path, html_anchor: &Option<String>) -> Option<String> {
+        let FileAnchor::Html(html_file_anchor) = self.file_map.get(file_path)? else {
+            return None;
+        };
+        let heading_text = |heading: &HeadingAnchor| {
+            let text = strip_tags(&heading.anchor_common.inner_html);
+            match html_file_anchor.number_string(&heading.numbering) {
+                Some(number) => format!("§{number} {text}"),
+                None => text,
+            }
+        };
+        // A link to a file refers to its first heading.
+        let Some(html_anchor) = html_anchor else {
+            return html_file_anchor.headings.first().map(heading_text);
+        };
+        let global_id = html_file_anchor.ids.get(html_anchor)?;
+        if let Some(heading) = html_file_anchor
+            .headings
+            .iter()
+            .find(|heading| &heading.anchor_common.anchor == global_id)
+        {
+            return Some(heading_text(heading));
+        }
+        let non_heading_anchor = html_file_anchor
+            .pre_anchors
+            .iter()
+            .chain(
+                html_file_anchor
+                    .headings
+                    .iter()
+                    .flat_map(|heading| &heading.non_heading_anchors),
+            )
+            .find(|non_heading_anchor| &non_heading_anchor.anchor_common.anchor == global_id)?;
+        if let Some(label) = non_heading_anchor.numbering_label(html_file_anchor) {
+            return Some(label);
+        }
+        let text = strip_tags(&non_heading_anchor.anchor_common.inner_html);
+        Some(if text.is_empty() {
+            global_id.clone()
+        } else {
+            text
+        })
+    }
+
+    // Gather all hyperlinks which refer to this file or to anchors in it:
+    // first those referring to the file, then those referring to each anchor
+    // in the order they appear in the file.
+    pub fn backlinks(&self, file_path: &Path) -> Vec<Backlink> {
+        let Some(file_anchor) = self.file_map.get(file_path) else {
+            return Vec::new();
+        };
+        let mut targets = vec![(None, &file_anchor.plain_file_anchor().referring_links)];
+        let mut ids = None;
+        if let FileAnchor::Html(html_file_anchor) = file_anchor {
+            ids = Some(&html_file_anchor.ids);
+            let anchors = html_file_anchor
+                .pre_anchors
+                .iter()
+                .map(|non_heading_anchor| &non_heading_anchor.anchor_common)
+                .chain(html_file_anchor.headings.iter().flat_map(|heading| {
+                    std::iter::once(&heading.anchor_common).chain(
+                        heading
+                            .non_heading_anchors
+                            .iter()
+                            .map(|non_heading_anchor| &non_heading_anchor.anchor_common),
+                    )
+                }));
+            for anchor_common in anchors {
+                if let Some(anchor_val) = self.anchor_map.get(&anchor_common.anchor) {
+                    targets.push((Some(&anchor_common.anchor), &anchor_val.referring_links));
+                }
+            }
+        }
+
+        let mut backlinks = Vec::new();
+        for (anchor, referring_links) in targets {
+            let mut anchor_backlinks = Vec::new();
+            for link_source in referring_links {
+                let Some(FileAnchor::Html(source_file_anchor)) =
+                    self.file_map.get(&link_source.file)
+                else {
+                    continue;
+                };
+                // A link source may contain several hyperlinks to this target.
+                for hyperlink in &source_file_anchor.hyperlinks {
+                    let refers_to_target = match (&hyperlink.html_anchor, anchor) {
+                        (None, None) => true,
+                        (Some(html_anchor), Some(anchor)) => {
+                            ids.and_then(|ids| ids.get(html_anchor)) == Some(anchor)
+                        }
+                        _ => false,
+                    };
+                    if &hyperlink.source != link_source
+                        || hyperlink.file != file_path
+                        || !refers_to_target
+                    {
+                        continue;
+                    }
+                    anchor_backlinks.push(Backlink {
+                        anchor: anchor.cloned(),
+                        file: relative_path(file_path, &link_source.file),
+                        source_anchor: link_source.anchor.clone(),
+                        pre_snippet: hyperlink.pre_snippet.clone(),
+                        post_snippet: hyperlink.post_snippet.clone(),
+                    });
+                }
+            }
+            // Present these in a consistent order.
+            anchor_backlinks.sort_by(|a, b| {
+                (&a.file, &a.source_anchor, &a.pre_snippet).cmp(&(
+                    &b.file,
+                    &b.source_anchor,
+                    &b.pre_snippet,
+                ))
+            });
+            backlinks.append(&mut anchor_backlinks);
+        }
+        backlinks
+    }
+
+    // Update an HTML file, returning its HTML with ids assigned to all
+    // headings and anchors.
+    pub fn upsert_html_file(&mut self, file_path: &Path, html: &str) -> String {
+        let is_toc = self.toc_path.as_deref() == Some(file_path);
+        // Headings are numbered based on the TOC, so load it first.
+        if let Some(toc_path) = self.toc_path.clone() {
+            if !is_toc && !self.file_map.contains_key(&toc_path) && toc_path.is_file() {
+                self.load_file(&toc_path);
+            }
+        }
+        self.remove_file_entries(file_path);
+        let (mut html, html_file_anchor) = self.html_analyze(file_path, html);
+        if is_toc {
+            html = self.number_toc(file_path, &html);
+        }
+        for global_id in html_file_anchor.ids.values() {
+            self.anchor_map.insert(
+                global_id.clone(),
+                AnchorVal {
+                    file: file_path.to_path_buf(),
+                    referring_links: HashSet::new(),
+                },
+            );
+        }
+        let hyperlinks = html_file_anchor.hyperlinks.clone();
+        self.file_map
+            .insert(file_path.to_path_buf(), FileAnchor::Html(html_file_anchor));
+        for hyperlink in hyperlinks {
+            self.resolve_hyperlink(hyperlink);
+        }
+        self.resolve_hyperlinks_to(file_path);
+        html
+    }
+
+    // Update a file which contains no anchors or hyperlinks.
+    pub fn upsert_plain_file(&mut self, file_path: &Path) {
+        self.remove_file_entries(file_path);
+        self.file_map.insert(
+            file_path.to_path_buf(),
+            FileAnchor::Plain(PlainFileAnchor {
+                path: file_path.to_path_buf(),
+                referring_links: HashSet::new(),
+            }),
+        );
+        self.resolve_hyperlinks_to(file_path);
+    }
+
+    pub fn remove_file(&mut self, file_path: &Path) {
+        self.remove_file_entries(file_path);
+        self.resolve_hyperlinks_to(file_path);
+    }
+
+    // Update a file which changed on disk, removing it if it no longer
+    // exists.
+    pub fn reload_file(&mut self, file_path: &Path) {
+        if file_path.is_file() {
+            self.load_file(file_path);
+        } else {
+            self.remove_file(file_path);
+        }
+    }
+
+    // True if this index refers to the provided file: it's loaded, or a
+    // pending hyperlink targets it.
+    pub fn contains(&self, file_path: &Path) -> bool {
+        self.file_map.contains_key(file_path)
+            || self
+                .pending_hyperlinks
+                .iter()
+                .any(|hyperlink| hyperlink.file == file_path)
+    }
+
+    // True if the Client's display of `file_path` shows `target`: it's the TOC
+    // in the sidebar, or an image or hyperlink in this file refers to it.
+    pub fn refers_to(&self, file_path: &Path, target: &Path) -> bool {
+        self.toc_path.as_deref() == Some(target)
+            || match self.file_map.get(file_path) {
+                Some(FileAnchor::Html(html_file_anchor)) => html_file_anchor
+                    .hyperlinks
+                    .iter()
+                    .any(|hyperlink| hyperlink.file == target),
+                _ => false,
+            }
+    }
+
+    // Load all files referred to by pending hyperlinks.
+    pub fn load_pending(&mut self) {
+        let mut attempted = HashSet::new();
+        loop {
+            let file_paths: Vec<PathBuf> = self
+                .pending_hyperlinks
+                .iter()
+                .filter(|hyperlink| attempted.insert(hyperlink.file.clone()))
+                .map(|hyperlink| hyperlink.file.clone())
+                .collect();
+            if file_paths.is_empty() {
+                break;
+            }
+            for file_path in file_paths {
+                // Missing files stay pending; these are broken links.
+                if file_path.is_file() {
+                    self.load_file(&file_path);
+                }
+            }
+        }
+    }
+
+    // Read a file from disk and update it.
+    pub fn load_file(&mut self, file_path: &Path) {
+        let ext = lexer_key(file_path);
+        // Binary files can't contain anchors.
+        match fs::read_to_string(file_path)
+            .map(|file_contents| source_to_codechat_for_web(&file_contents, &ext, false, true))
+        {
+            Ok(TranslationResults::CodeChat(mut codechat_for_web)) => {
+                self.upsert_codechat_for_web(file_path, &mut codechat_for_web)
+            }
+            _ => self.upsert_plain_file(file_path),
+        }
+    }
+
+    // Remove a file from the file and id HashMaps, along with its hyperlinks.
+    fn remove_file_entries(&mut self, file_path: &Path) {
+        let Some(file_anchor) = self.file_map.remove(file_path) else {
+            return;
+        };
+        self.pending_hyperlinks
+            .retain(|hyperlink| hyperlink.source.file != file_path);
+        if let FileAnchor::Html(html_file_anchor) = file_anchor {
+            for global_id in html_file_anchor.ids.values() {
+                self.anchor_map.remove(global_id);
+            }
+            for hyperlink in &html_file_anchor.hyperlinks {
+                if let Some(referring_links) = self.referring_links_mut(hyperlink) {
+                    referring_links.remove(&hyperlink.source);
+                }
+            }
+        }
+    }
+
+    // Resolve all hyperlinks in other files which refer to the provided file.
+    fn resolve_hyperlinks_to(&mut self, file_path: &Path) {
+        self.pending_hyperlinks
+            .retain(|hyperlink| hyperlink.file != file_path);
+        let hyperlinks: Vec<Hyperlink> = self
+            .file_map
+            .values()
+            .filter_map(|file_anchor| match file_anchor {
+                FileAnchor::Html(html_file_anchor) => Some(&html_file_anchor.hyperlinks),
+                FileAnchor::Plain(_) => None,
+            })
+            .flatten()
+            .filter(|hyperlink| hyperlink.file == file_path && hyperlink.source.file != file_path)
+            .cloned()
+            .collect();
+        for hyperlink in hyperlinks {
+            self.resolve_hyperlink(hyperlink);
+        }
+    }
+
+    // Add a hyperlink to the referring links of its target, or to the pending
+    // hyperlinks if the target isn't loaded.
+    fn resolve_hyperlink(&mut self, hyperlink: Hyperlink) {
+        if !self.file_map.contains_key(&hyperlink.file) {
+            self.pending_hyperlinks.push(hyperlink);
+        } else if let Some(referring_links) = self.referring_links_mut(&hyperlink) {
+            referring_links.insert(hyperlink.source);
+        }
+    }
+
+    // Find the referring links for the target of a hyperlink. Return `None` if
+    // the target doesn't exist.
+    fn referring_links_mut(&mut self, hyperlink: &Hyperlink) -> Option<&mut HashSet<LinkSource>> {
+        let file_anchor = self.file_map.get_mut(&hyperlink.file)?;
+        match &hyperlink.html_anchor {
+            None => Some(&mut file_anchor.plain_file_anchor_mut().referring_links),
+            Some(html_anchor) => {
+                let FileAnchor::Html(html_file_anchor) = file_anchor else {
+                    return None;
+                };
+                let global_id = html_file_anchor.ids.get(html_anchor)?;
+                self.anchor_map
+                    .get_mut(global_id)
+                    .map(|anchor_val| &mut anchor_val.referring_links)
+            }
+        }
+    }
+
+    // Given HTML, catalog all link targets and link-like items, ensuring that
+    // they have a globally unique id. Return the updated HTML.
+    fn html_analyze(&self, file_path: &Path, html: &str) -> (String, HtmlFileAnchor) {
+        let mut html_file_anchor = HtmlFileAnchor {
+            file_anchor: PlainFileAnchor {
+                path: file_path.to_path_buf(),
+                referring_links: HashSet::new(),
+            },
+            numbering: self
+                .toc_numbering
+                .get(file_path)
+                .cloned()
+                .unwrap_or_default(),
+            headings: Vec::new(),
+            pre_anchors: Vec::new(),
+            hyperlinks: Vec::new(),
+            ids: HashMap::new(),
+        };
+
+        // First, assign a globally unique id to each heading and anchor.
+        let tags: Vec<_> = HTML_START_TAG.captures_iter(html).collect();
+        let heading_level = |captures: &Captures| {
+            HEADING_TAG_NAME
+                .captures(&captures[1])
+                .map(|level| level[1].parse::<u8>().unwrap())
+        };
+        // Number headings relative to the highest-level heading in this file,
+        // but only for files with a number in the TOC.
+        let top_level = tags.iter().filter_map(heading_level).min().unwrap_or(1);
+        let mut heading_counters = [0; 6];
+        let mut group_counters = HashMap::new();
+        let mut used_ids = HashSet::new();
+        let mut tag_ids = Vec::new();
+        let mut tag_numbers = Vec::new();
+        for captures in &tags {
+            let whole_tag = captures.get(0).unwrap();
+            let tag_name = captures[1].to_ascii_lowercase();
+            let level = heading_level(captures);
+            let id = get_attribute(&captures[2], "id");
+            if level.is_none() && id.is_none() {
+                tag_ids.push(None);
+                tag_numbers.push(None);
+                continue;
+            }
+            let inner_html = if &captures[3] == "/" {
+                ""
+            } else {
+                element_inner_html(&html[whole_tag.end()..], &tag_name)
+            };
+            let local_id = id.unwrap_or_else(|| slugify(&strip_tags(inner_html)));
+            let mut global_id = local_id.clone();
+            let mut suffix = 0;
+            while used_ids.contains(&global_id) || self.anchor_map.contains_key(&global_id) {
+                suffix += 1;
+                global_id = format!("{local_id}-{suffix}");
+            }
+            used_ids.insert(global_id.clone());
+            // Map both the id in the source and the globally-unique id (which
+            // is what the resulting HTML contains) to the globally-unique id.
+            html_file_anchor
+                .ids
+                .entry(local_id)
+                .or_insert_with(|| global_id.clone());
+            html_file_anchor
+                .ids
+                .insert(global_id.clone(), global_id.clone());
+
+            let anchor_common = AnchorCommon {
+                anchor: global_id.clone(),
+                inner_html: inner_html.to_string(),
+            };
+            if let Some(level) = level {
+                let level_index = usize::from(level) - 1;
+                heading_counters[level_index] += 1;
+                heading_counters[level_index + 1..].fill(0);
+                let numbering: Vec<_> = heading_counters[usize::from(top_level) - 1..=level_index]
+                    .iter()
+                    .map(|counter| Some(*counter))
+                    .collect();
+                tag_numbers.push(html_file_anchor.number_string(&numbering));
+                html_file_anchor.headings.push(HeadingAnchor {
+                    anchor_common,
+                    level,
+                    numbering,
+                    non_heading_anchors: Vec::new(),
+                });
+            } else {
+                // Number items whose id begins with the prefix of a numbering
+                // group.
+                let numbering_group = global_id.split_once(':').and_then(|(prefix, _)| {
+                    NUMBERING_GROUPS
+                        .iter()
+                        .any(|(group, _)| *group == prefix)
+                        .then(|| prefix.to_string())
+                });
+                let number = match &numbering_group {
+                    Some(numbering_group) => {
+                        let counter = group_counters.entry(numbering_group.clone()).or_insert(0);
+                        *counter += 1;
+                        *counter
+                    }
+                    None => 0,
+                };
+                let non_heading_anchor = NonHeadingAnchor {
+                    anchor_common,
+                    pre_snippet: snippet(&html[..whole_tag.start()], true),
+                    post_snippet: snippet(&html[whole_tag.end()..], false),
+                    numbering_group,
+                    number,
+                };
+                tag_numbers.push(non_heading_anchor.numbering_label(&html_file_anchor));
+                match html_file_anchor.headings.last_mut() {
+                    Some(heading) => heading.non_heading_anchors.push(non_heading_anchor),
+                    None => html_file_anchor.pre_anchors.push(non_heading_anchor),
+                }
+            }
+            tag_ids.push(Some(global_id));
+        }
+
+        // Next, rewrite the HTML with these ids and catalog all hyperlinks.
+        let mut new_html = String::new();
+        let mut html_index = 0;
+        let mut current_anchor = None;
+        for ((captures, tag_id), tag_number) in tags.iter().zip(tag_ids).zip(tag_numbers) {
+            let whole_tag = captures.get(0).unwrap();
+            new_html.push_str(&html[html_index..whole_tag.start()]);
+            html_index = whole_tag.end();
+            let mut attributes = captures[2].to_string();
+            if let Some(tag_id) = tag_id {
+                attributes = set_attribute(&attributes, "id", &tag_id);
+                current_anchor = Some(tag_id);
+            }
+            // The Client displays this number using CSS, so that it's not
+            // part of the heading's text.
+            if let Some(tag_number) = tag_number {
+                attributes = set_attribute(&attributes, "data-codechat-number", &tag_number);
+            }
+            // Hyperlinks and images both refer to other files.
+            let link_attribute = match captures[1].to_ascii_lowercase().as_str() {
+                "a" => Some("href"),
+                "img" => Some("src"),
+                _ => None,
+            };
+            if let Some(href) =
+                link_attribute.and_then(|link_attribute| get_attribute(&attributes, link_attribute))
+            {
+                if let Some((hyperlink, new_href)) = self.analyze_hyperlink(
+                    &href,
+                    file_path,
+                    &html_file_anchor,
+                    &current_anchor,
+                    (&html[..whole_tag.start()], &html[whole_tag.end()..]),
+                ) {
+                    if new_href != href {
+                        attributes = set_attribute(&attributes, link_attribute.unwrap(), &new_href);
+                    }
+                    html_file_anchor.hyperlinks.push(hyperlink);
+                }
+            }
+            new_html.push_str(&format!("<{}{attributes}{}>", &captures[1], &captures[3]));
+        }
+        new_html.push_str(&html[html_index..]);
+
+        (new_html, html_file_anchor)
+    }
+
+    // Number the files in the TOC based on their position in its ordered
+    // lists, then update the numbering of each file. Return the TOC's HTML with
+    // each item in an ordered list numbered.
+    fn number_toc(&mut self, toc_path: &Path, html: &str) -> String {
+        let mut toc_numbering = HashMap::new();
+        // The open lists: whether each is ordered, and its current item
+        // number.
+        let mut lists: Vec<(bool, u32)> = Vec::new();
+        // For each open list item, whether its link has been found. Only the
+        // first link in an item refers to the numbered file.
+        let mut item_linked: Vec<bool> = Vec::new();
+        let current_numbering = |lists: &[(bool, u32)]| -> Vec<Option<u32>> {
+            lists
+                .iter()
+                .map(|(is_ordered, number)| is_ordered.then_some(*number))
+                .collect()
+        };
+        let mut new_html = String::new();
+        let mut html_index = 0;
+        for captures in TOC_TAG.captures_iter(html) {
+            let whole_tag = captures.get(0).unwrap();
+            new_html.push_str(&html[html_index..whole_tag.start()]);
+            html_index = whole_tag.end();
+            let mut tag = whole_tag.as_str().to_string();
+            let attributes = captures.get(3).map_or("", |m| m.as_str());
+            match (&captures[1], captures[2].to_ascii_lowercase().as_str()) {
+                ("", "ol") => {
+                    let start = get_attribute(attributes, "start")
+                        .and_then(|start| start.parse::<u32>().ok())
+                        .unwrap_or(1);
+                    lists.push((true, start.saturating_sub(1)));
+                }
+                ("", "ul") => lists.push((false, 0)),
+                (_, "ol" | "ul") => {
+                    lists.pop();
+                }
+                ("", "li") => {
+                    item_linked.push(false);
+                    if let Some((is_ordered, number)) = lists.last_mut() {
+                        *number += 1;
+                        if *is_ordered {
+                            if let Some(number) = format_numbering(&current_numbering(&lists)) {
+                                tag = format!(
+                                    "<{}{}>",
+                                    &captures[2],
+                                    set_attribute(attributes, "data-codechat-number", &number)
+                                );
+                            }
+                        }
+                    }
+                }
+                (_, "li") => {
+                    item_linked.pop();
+                }
+                ("", "a") if item_linked.last() == Some(&false) => {
+                    if let Some((target, _)) = get_attribute(attributes, "href")
+                        .and_then(|href| link_target(&href, toc_path))
+                    {
+                        toc_numbering.insert(target, current_numbering(&lists));
+                        *item_linked.last_mut().unwrap() = true;
+                    }
+                }
+                _ => (),
+            }
+            new_html.push_str(&tag);
+        }
+        new_html.push_str(&html[html_index..]);
+
+        // Renumber all files.
+        for (file_path, file_anchor) in self.file_map.iter_mut() {
+            if let FileAnchor::Html(html_file_anchor) = file_anchor {
+                html_file_anchor.numbering =
+                    toc_numbering.get(file_path).cloned().unwrap_or_default();
+            }
+        }
+        self.toc_numbering = toc_numbering;
+        new_html
+    }
+
+    // Determine the target of a hyperlink. Return `None` for hyperlinks to
+    // locations outside the filesystem. Otherwise, return the hyperlink and
+    // its href, with any fragment translated to a globally-unique id.
+    fn analyze_hyperlink(
+        &self,
+        href: &str,
+        file_path: &Path,
+        // The file containing this hyperlink.
+        html_file_anchor: &HtmlFileAnchor,
+        // The nearest anchor preceding this hyperlink.
+        current_anchor: &Option<String>,
+        // The HTML before and after this hyperlink's start tag.
+        (pre_html, post_html): (&str, &str),
+    ) -> Option<(Hyperlink, String)> {
+        let (target, html_anchor) = link_target(href, file_path)?;
+
+        // Translate the fragment to its globally-unique id.
+        let ids = if target == file_path {
+            Some(&html_file_anchor.ids)
+        } else if let Some(FileAnchor::Html(target_file_anchor)) = self.file_map.get(&target) {
+            Some(&target_file_anchor.ids)
+        } else {
+            None
+        };
+        let new_href = match (ids, &html_anchor) {
+            (Some(ids), Some(html_anchor)) => match ids.get(html_anchor) {
+                Some(global_id) if global_id != html_anchor => {
+                    format!("{}#{global_id}", href.split('#').next().unwrap())
+                }
+                _ => href.to_string(),
+            },
+            _ => href.to_string(),
+        };
+
+        Some((
+            Hyperlink {
+                source: LinkSource {
+                    file: file_path.to_path_buf(),
+                    anchor: current_anchor.clone(),
+                },
+                file: target,
+                html_anchor,
+                pre_snippet: snippet(pre_html, true),
+                post_snippet: snippet(post_html, false),
+            },
+            new_href,
+        ))
+    }
+}
+
+impl HtmlFileAnchor {
+    // Format the number of an item in this file, given its numbering relative
+    // to the file. Return `None` if this file isn't numbered.
+    fn number_string(&self, numbering: &[Option<u32>]) -> Option<String> {
+        if self.numbering.iter().any(Option::is_some) {
+            format_numbering(&[self.numbering.as_slice(), numbering].concat())
+        } else {
+            None
+        }
+    }
+}
+
+impl NonHeadingAnchor {
+    // Produce the label for a numbered item, such as `Figure 3.1`, or `None`
+    // if this isn't a numbered item. Numbered items in a file which isn't
+    // numbered by the TOC are labeled by their number in that file.
+    fn numbering_label(&self, html_file_anchor: &HtmlFileAnchor) -> Option<String> {
+        let numbering_group = self.numbering_group.as_ref()?;
+        let (_, name) = NUMBERING_GROUPS
+            .iter()
+            .find(|(group, _)| group == numbering_group)?;
+        let number = html_file_anchor
+            .number_string(&[Some(self.number)])
+            .unwrap_or(self.number.to_string());
+        Some(format!("{name} {number}"))
+    }
+}
+
+// Apply `f` to the HTML in a CodeChat Editor file.
+fn update_codechat_for_web_html<F: FnOnce(&str) -> String>(
+    codechat_for_web: &mut CodeChatForWeb,
+    f: F,
+) {
+    let source = &mut codechat_for_web.source;
+    if codechat_for_web.metadata.mode == "markdown" {
+        source.doc = f(&source.doc);
+    } else {
+        // Process all doc blocks together, since ids must be unique and
+        // hyperlinks may refer to anchors in other doc blocks.
+        let combined_doc_blocks = source
+            .doc_blocks
+            .iter()
+            .map(|doc_block| doc_block.4.as_str())
+            .collect::<Vec<_>>()
+            .join(&DOC_BLOCK_SEPARATOR_STRING_SHORT);
+        let html = f(&combined_doc_blocks);
+        for (doc_block, contents) in source
+            .doc_blocks
+            .iter_mut()
+            .zip(html.split(&*DOC_BLOCK_SEPARATOR_STRING_SHORT))
+        {
+            doc_block.4 = contents.to_string();
+        }
+    }
+}
+
+// Determine the file and anchor a link in `file_path` refers to. Return `None`
+// for links to locations outside the filesystem.
+fn link_target(href: &str, file_path: &Path) -> Option<(PathBuf, Option<String>)> {
+    if href.is_empty() || URL_SCHEME.is_match(href) || href.starts_with('/') {
+        return None;
+    }
+    let (href_path, fragment) = match href.split_once('#') {
+        Some((href_path, fragment)) => (href_path, Some(fragment)),
+        None => (href, None),
+    };
+    let url_path = href_path.split('?').next().unwrap();
+    let target = if url_path.is_empty() {
+        // A query-only link refers to nothing.
+        fragment?;
+        file_path.to_path_buf()
+    } else {
+        let url_path = urlencoding::decode(url_path).ok()?;
+        normalize_path(&file_path.parent().unwrap_or(Path::new("")).join(&*url_path))
+    };
+    let html_anchor = match fragment {
+        Some(fragment) if !fragment.is_empty() => {
+            Some(urlencoding::decode(fragment).ok()?.into_owned())
+        }
+        _ => None,
     };
-    // Add this to the top-level hashes.
-    let anchor_val = AnchorVal {
-        anchor: Anchor::Html(HtmlAnchor::NonHeading(missing_anchor)),
-        referring_links: Rc::new(HashSet::new()),
+    Some((target, html_anchor))
+}
+
+// Format a numbering as a string, such as `4.2.1`. Unnumbered (unordered list)
+// levels are omitted. Return `None` if nothing is numbered.
+pub fn format_numbering(numbering: &[Option<u32>]) -> Option<String> {
+    let numbers: Vec<String> = numbering.iter().flatten().map(u32::to_string).collect();
+    if numbers.is_empty() {
+        None
+    } else {
+        Some(numbers.join("."))
+    }
+}
+
+// Find the value of an attribute in a tag's attributes.
+fn get_attribute(attributes: &str, name: &str) -> Option<String> {
+    HTML_ATTRIBUTE
+        .captures_iter(attributes)
+        .find(|captures| captures[1].eq_ignore_ascii_case(name))
+        .map(|captures| {
+            captures
+                .get(2)
+                .or_else(|| captures.get(3))
+                .unwrap()
+                .as_str()
+                .to_string()
+        })
+}
+
+// Set the value of an attribute in a tag's attributes, adding it if it's not
+// present.
+fn set_attribute(attributes: &str, name: &str, value: &str) -> String {
+    let value = value.replace('"', "&quot;");
+    match HTML_ATTRIBUTE
+        .captures_iter(attributes)
+        .find(|captures| captures[1].eq_ignore_ascii_case(name))
+    {
+        Some(captures) => {
+            let range = captures.get(0).unwrap().range();
+            format!(
+                r#"{} {name}="{value}"{}"#,
+                &attributes[..range.start],
+                &attributes[range.end..]
+            )
+        }
+        None => format!(r#"{attributes} {name}="{value}""#),
+    }
+}
+
+// Return the HTML inside an element, given the HTML following its start tag.
+// This doesn't handle nested elements with the same tag name.
+fn element_inner_html<'a>(html: &'a str, tag_name: &str) -> &'a str {
+    let end_tag = format!("</{tag_name}>");
+    match html.to_ascii_lowercase().find(&end_tag) {
+        Some(index) => &html[..index],
+        None => "",
+    }
+}
+
+// Remove all tags from HTML, collapsing whitespace.
+fn strip_tags(html: &str) -> String {
+    HTML_TAG
+        .replace_all(html, " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Produce an id from the text of a heading.
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+// Return a snippet of the text at the end (if `at_end`) or the beginning of
+// the provided HTML.
+fn snippet(html: &str, at_end: bool) -> String {
+    const SNIPPET_LENGTH: usize = 50;
+    let text = strip_tags(html);
+    let chars: Vec<char> = text.chars().collect();
+    if at_end {
+        chars[chars.len().saturating_sub(SNIPPET_LENGTH)..]
+            .iter()
+            .collect()
+    } else {
+        chars[..chars.len().min(SNIPPET_LENGTH)].iter().collect()
+    }
+}
+
+// Produce the path to `path`, relative to the directory containing
+// `from_file`, using forward slashes.
+fn relative_path(from_file: &Path, path: &Path) -> String {
+    let from: Vec<_> = from_file
+        .parent()
+        .unwrap_or(from_file)
+        .components()
+        .collect();
+    let to: Vec<_> = path.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut segments = vec!["..".to_string(); from.len() - common];
+    segments.extend(
+        to[common..]
+            .iter()
+            .map(|component| component.as_os_str().to_string_lossy().to_string()),
+    );
+    segments.join("/")
+}
+
+// Produce a canonical path if possible (the file exists); otherwise, remove
+// `.` and `..` components.
+fn normalize_path(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+// Determine the index for a file and its path in that index. Each project has
+// an index, keyed by the directory containing its TOC; files outside a project
+// share an index with other files in their directory.
+fn anchor_index_key(file_path: &Path, path_to_toc: &Option<PathBuf>) -> (PathBuf, PathBuf) {
+    let file_path = normalize_path(file_path);
+    let file_dir = file_path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let root = match path_to_toc {
+        Some(path_to_toc) => normalize_path(&file_dir.join(path_to_toc))
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_path_buf(),
+        None => file_dir,
     };
-    //file_map.insert(mfa.file_anchor.path, missing_html_file_anchor);
-    //let anchor_val_set: HashSet<AnchorVal> = HashSet::new();
-    //anchor_val_set.insert(anchor_val);
-    //anchor_map.insert(&mfa.file_anchor.anchor, anchor_val_set);
+    (root, file_path)
+}
 
-    Ok("".to_string())
+// Add a file to the anchor index of its project, updating the ids in its HTML.
+// Then load any files it refers to.
+fn index_codechat_for_web(
+    file_path: &Path,
+    path_to_toc: &Option<PathBuf>,
+    codechat_for_web: &mut CodeChatForWeb,
+) {
+    let (root, file_path) = anchor_index_key(file_path, path_to_toc);
+    let mut anchor_indexes = ANCHOR_INDEXES.lock().unwrap();
+    let toc_path = path_to_toc.as_ref().map(|_| root.join("toc.md"));
+    let anchor_index = anchor_indexes
+        .entry(root)
+        .or_insert_with(|| AnchorIndex::new(toc_path));
+    anchor_index.upsert_codechat_for_web(&file_path, codechat_for_web);
+    anchor_index.load_pending();
+    anchor_index.fill_link_text_codechat_for_web(&file_path, codechat_for_web);
+    codechat_for_web.metadata.backlinks = anchor_index.backlinks(&file_path);
+}
+
+// Update the anchor index of the project containing `file_path` after this
+// file changed on disk. Files the index doesn't refer to are ignored, since
+// nothing depends on them. Return true if this change affects the Client's
+// display of `current_file_path`, which should then be refreshed.
+pub fn reindex_changed_file(file_path: &Path, current_file_path: &Path) -> bool {
+    let (root, file_path) = anchor_index_key(file_path, &find_path_to_toc(file_path));
+    let (current_root, current_file_path) =
+        anchor_index_key(current_file_path, &find_path_to_toc(current_file_path));
+    let mut anchor_indexes = ANCHOR_INDEXES.lock().unwrap();
+    let Some(anchor_index) = anchor_indexes.get_mut(&root) else {
+        return false;
+    };
+    if !anchor_index.contains(&file_path) {
+        return false;
+    }
+    anchor_index.reload_file(&file_path);
+    anchor_index.load_pending();
+    root == current_root && anchor_index.refers_to(&current_file_path, &file_path)
 }
-*/
 
 // ## Tests
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::fs;
+    use std::path::{Path, PathBuf};
     use std::str::FromStr;
 
-    use super::{find_path_to_toc, TranslationResults};
-    use super::{CodeChatForWeb, CodeMirror, CodeMirrorDocBlocks, SourceFileMetadata};
+    use super::{
+        find_path_to_toc, lexer_key, reindex_changed_file, source_to_codechat_for_web_cached,
+        source_to_codechat_for_web_string, AnchorIndex, Backlink, FileAnchor, LinkSource,
+        TranslationCache, TranslationResults,
+    };
+    use super::{
+        CodeChatForWeb, CodeMirror, CodeMirrorDocBlock, CodeMirrorDocBlocks, SourceFileMetadata,
+    };
     use crate::lexer::{
-        compile_lexers, supported_languages::get_language_lexer_vec, CodeDocBlock, DocBlock,
+        compile_lexers, source_lexer, supported_languages::get_language_lexer_vec, CodeDocBlock,
+        DocBlock,
     };
     use crate::processing::{
         code_doc_block_vec_to_source, code_mirror_to_code_doc_blocks, codechat_for_web_to_source,
         source_to_codechat_for_web,
     };
 
-    use crate::prep_test_dir;
+    use crate::{cast, prep_test_dir};
 
     // ### Utilities
-    fn build_codechat_for_web<'a>(
+    fn build_codechat_for_web(
         mode: &str,
         doc: &str,
         doc_blocks: CodeMirrorDocBlocks,
@@ -803,6 +2148,7 @@ mod tests {
         CodeChatForWeb {
             metadata: SourceFileMetadata {
                 mode: mode.to_string(),
+                backlinks: Vec::new(),
             },
             source: CodeMirror {
                 doc: doc.to_string(),
@@ -819,30 +2165,32 @@ mod tests {
         indent: &str,
         delimiter: &str,
         contents: &str,
-    ) -> (usize, usize, String, String, String) {
+    ) -> CodeMirrorDocBlock {
         (
             start,
             end,
             indent.to_string(),
             delimiter.to_string(),
             contents.to_string(),
+            "".to_string(),
         )
     }
 
     fn build_doc_block(indent: &str, delimiter: &str, contents: &str) -> CodeDocBlock {
-        return CodeDocBlock::DocBlock(DocBlock {
+        CodeDocBlock::DocBlock(DocBlock {
             indent: indent.to_string(),
             delimiter: delimiter.to_string(),
             contents: contents.to_string(),
+            style: "".to_string(),
             lines: 0,
-        });
+        })
     }
 
     fn build_code_block(contents: &str) -> CodeDocBlock {
-        return CodeDocBlock::CodeBlock(contents.to_string());
+        CodeDocBlock::CodeBlock(contents.to_string())
     }
 
-    fn run_test<'a>(mode: &str, doc: &str, doc_blocks: CodeMirrorDocBlocks) -> Vec<CodeDocBlock> {
+    fn run_test(mode: &str, doc: &str, doc_blocks: CodeMirrorDocBlocks) -> Vec<CodeDocBlock> {
         let codechat_for_web = build_codechat_for_web(mode, doc, doc_blocks);
         code_mirror_to_code_doc_blocks(&codechat_for_web.source)
     }
@@ -999,7 +2347,7 @@ mod tests {
     #[test]
     fn test_code_doc_blocks_to_source_py() {
         let llc = compile_lexers(get_language_lexer_vec());
-        let py_lexer = llc.map_mode_to_lexer.get(&"python".to_string()).unwrap();
+        let py_lexer = llc.map_mode_to_lexer.get(&String::from("python")).unwrap();
 
         // An empty document.
         assert_eq!(code_doc_block_vec_to_source(vec![], py_lexer).unwrap(), "");
@@ -1084,12 +2432,47 @@ mod tests {
         );
     }
 
+    // Docstrings must round-trip exactly.
+    #[test]
+    fn test_code_doc_blocks_to_source_python_docstrings() {
+        let llc = compile_lexers(get_language_lexer_vec());
+        let py_lexer = llc
+            .map_mode_to_lexer
+            .get(&String::from("python_docstrings"))
+            .unwrap();
+
+        for source in [
+            "\"\"\"Module.\"\"\"\nimport os\n",
+            "def f():\n    \"\"\"Summary.\n\n    Details.\n    \"\"\"\n    pass\n",
+            "class C:\n    '''\n    Doc.\n    '''",
+            "\"\"\"One.\n\"\"\"",
+            // Docstrings which can't be represented exactly remain code.
+            "class C:\n    \"\"\"Doc.\n  \n    \"\"\"\n",
+            "\"\"\"Quote \\\"\"\" inside.\"\"\"\n",
+        ] {
+            assert_eq!(
+                code_doc_block_vec_to_source(source_lexer(source, py_lexer), py_lexer).unwrap(),
+                source
+            );
+        }
+
+        // Edits which would end the docstring early are escaped.
+        assert_eq!(
+            code_doc_block_vec_to_source(
+                vec![build_doc_block("", "\"\"\"", "Say \"\"\"hi\"\n")],
+                py_lexer
+            )
+            .unwrap(),
+            "\"\"\"Say \\\"\"\"hi\\\"\"\"\"\n"
+        );
+    }
+
     // A language with just one block comment delimiter and no inline comment
     // delimiters.
     #[test]
     fn test_code_doc_blocks_to_source_css() {
         let llc = compile_lexers(get_language_lexer_vec());
-        let css_lexer = llc.map_mode_to_lexer.get(&"css".to_string()).unwrap();
+        let css_lexer = llc.map_mode_to_lexer.get(&String::from("css")).unwrap();
 
         // An empty document.
         assert_eq!(code_doc_block_vec_to_source(vec![], css_lexer).unwrap(), "");
@@ -1143,6 +2526,16 @@ mod tests {
 "#
         );
 
+        // A closing delimiter on a line by itself stays there.
+        assert_eq!(
+            code_doc_block_vec_to_source(
+                vec![build_doc_block("  ", "/*", "Test 1\nTest 2\n\n")],
+                css_lexer
+            )
+            .unwrap(),
+            "  /* Test 1\n     Test 2\n  */\n"
+        );
+
         // Basic code.
         assert_eq!(
             code_doc_block_vec_to_source(vec![build_code_block("Test")], css_lexer).unwrap(),
@@ -1161,7 +2554,7 @@ mod tests {
     #[test]
     fn test_code_doc_blocks_to_source_csharp() {
         let llc = compile_lexers(get_language_lexer_vec());
-        let csharp_lexer = llc.map_mode_to_lexer.get(&"csharp".to_string()).unwrap();
+        let csharp_lexer = llc.map_mode_to_lexer.get(&String::from("csharp")).unwrap();
 
         // An empty document.
         assert_eq!(
@@ -1201,13 +2594,116 @@ mod tests {
         );
     }
 
+    // Documentation comments are rendered with structured tags, then saved
+    // with their original tags and gutter.
+    #[test]
+    fn test_doc_comments() {
+        let source =
+            "/**\n * Add.\n *\n * @param a The first.\n * @return The sum.\n */\nint add(int a);\n";
+        let TranslationResults::CodeChat(codechat_for_web) =
+            source_to_codechat_for_web(source, "java", false, false)
+        else {
+            panic!("Not a CodeChat Editor file.");
+        };
+        // The doc block's style records the tags it uses.
+        let mut doc_block = build_codemirror_doc_block(
+            0,
+            5,
+            "",
+            "/**",
+            "<p>Add.</p>\n<p><strong>Parameters:</strong></p>\n<ul>\n<li><code>a</code>: The first.</li>\n</ul>\n<p><strong>Returns:</strong> The sum.</p>\n",
+        );
+        doc_block.5 = "@param @return".to_string();
+        assert_eq!(codechat_for_web.source.doc_blocks, vec![doc_block]);
+
+        // The Client converts this HTML back to Markdown.
+        assert_eq!(
+            codechat_for_web_to_source(build_codechat_for_web(
+                "java",
+                "\n\nint add(int a);\n",
+                vec![build_codemirror_doc_block(
+                    0,
+                    1,
+                    "",
+                    "/**",
+                    "Add.\n\n**Parameters:**\n\n- `a`: The first.\n\n**Returns:** The sum.\n"
+                )]
+            )),
+            Ok(source.to_string())
+        );
+
+        // Other spellings of tags and other layouts of the comment are saved
+        // as written, using the Markdown the Client sends back.
+        for (source, markdown) in [
+            (
+                "/** \\brief Add.\n * \\returns The sum.\n */\n",
+                "**Summary:** Add.\n\n**Returns:** The sum.\n\n",
+            ),
+            (
+                "/**\n * Add.\n *\n * @exception E If bad.\n * @returns The sum.\n */\n",
+                "Add.\n\n**Throws:**\n\n- `E`: If bad.\n\n**Returns:** The sum.\n\n",
+            ),
+            (
+                "/** @sa add_all\n    @throw E If bad. */\n",
+                "**See also:** add_all\n\n**Throws:**\n\n- `E`: If bad.\n",
+            ),
+        ] {
+            let TranslationResults::CodeChat(mut codechat_for_web) =
+                source_to_codechat_for_web(source, "java", false, false)
+            else {
+                panic!("Not a CodeChat Editor file.");
+            };
+            codechat_for_web.source.doc_blocks[0].4 = markdown.to_string();
+            assert_eq!(
+                codechat_for_web_to_source(codechat_for_web),
+                Ok(source.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_intra_doc_links() {
+        let mut anchor_index = AnchorIndex::new(None);
+        let lib_path = PathBuf::from_str("/project/src/lib.rs").unwrap();
+        let util_path = PathBuf::from_str("/project/src/util/mod.rs").unwrap();
+        anchor_index.upsert_html_file(&lib_path, "<h1>Parser</h1><a id=\"Token\"></a>");
+        anchor_index.upsert_html_file(&util_path, "<h1>Token</h1>");
+
+        let link = |path: &str| {
+            format!("<a href=\"#{path}\" title=\"intra-doc link\"><code>{path}</code></a>")
+        };
+        // Links resolve by path, name, or heading id, preferring anchors in
+        // the same file.
+        assert_eq!(
+            anchor_index.resolve_intra_doc_links(&util_path, &link("crate::Parser")),
+            "<a href=\"../lib.rs#parser\" title=\"intra-doc link\"><code>crate::Parser</code></a>"
+        );
+        assert_eq!(
+            anchor_index.resolve_intra_doc_links(&lib_path, &link("Token")),
+            "<a href=\"#Token\" title=\"intra-doc link\"><code>Token</code></a>"
+        );
+        assert_eq!(
+            anchor_index.resolve_intra_doc_links(&util_path, &link("Token")),
+            "<a href=\"#token\" title=\"intra-doc link\"><code>Token</code></a>"
+        );
+        // Unknown items and other links are unchanged.
+        assert_eq!(
+            anchor_index.resolve_intra_doc_links(&lib_path, &link("Unknown")),
+            link("Unknown")
+        );
+        assert_eq!(
+            anchor_index.resolve_intra_doc_links(&lib_path, "<a href=\"#Token\">Token</a>"),
+            "<a href=\"#Token\">Token</a>"
+        );
+    }
+
     // ### Tests for `source_to_codechat_for_web`
     #[test]
     fn test_source_to_codechat_for_web_1() {
         // A file with an unknown extension and no lexer, which is classified as
         // a text file.
         assert_eq!(
-            source_to_codechat_for_web(&"".to_string(), ".xxx", false, false),
+            source_to_codechat_for_web("", ".xxx", false, false),
             TranslationResults::Unknown
         );
 
@@ -1221,7 +2717,7 @@ mod tests {
 
         // A CodeChat Editor document via filename.
         assert_eq!(
-            source_to_codechat_for_web(&"".to_string(), "md", false, false),
+            source_to_codechat_for_web("", "md", false, false),
             TranslationResults::CodeChat(build_codechat_for_web("markdown", "", vec![]))
         );
 
@@ -1237,13 +2733,13 @@ mod tests {
 
         // An empty source file.
         assert_eq!(
-            source_to_codechat_for_web(&"".to_string(), "js", false, false),
+            source_to_codechat_for_web("", "js", false, false),
             TranslationResults::CodeChat(build_codechat_for_web("javascript", "", vec![]))
         );
 
         // A zero doc block source file.
         assert_eq!(
-            source_to_codechat_for_web(&"let a = 1;".to_string(), "js", false, false),
+            source_to_codechat_for_web("let a = 1;", "js", false, false),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "javascript",
                 "let a = 1;",
@@ -1253,7 +2749,7 @@ mod tests {
 
         // One doc block source files.
         assert_eq!(
-            source_to_codechat_for_web(&"// Test".to_string(), "js", false, false),
+            source_to_codechat_for_web("// Test", "js", false, false),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "javascript",
                 "\n",
@@ -1261,7 +2757,7 @@ mod tests {
             ))
         );
         assert_eq!(
-            source_to_codechat_for_web(&"let a = 1;\n// Test".to_string(), "js", false, false,),
+            source_to_codechat_for_web("let a = 1;\n// Test", "js", false, false,),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "javascript",
                 "let a = 1;\n\n",
@@ -1275,7 +2771,7 @@ mod tests {
             ))
         );
         assert_eq!(
-            source_to_codechat_for_web(&"// Test\nlet a = 1;".to_string(), "js", false, false,),
+            source_to_codechat_for_web("// Test\nlet a = 1;", "js", false, false,),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "javascript",
                 "\nlet a = 1;",
@@ -1286,7 +2782,7 @@ mod tests {
         // A two doc block source file.
         assert_eq!(
             source_to_codechat_for_web(
-                &"// [Link][1]\nlet a = 1;\n/* [1]: http://b.org */".to_string(),
+                "// [Link][1]\nlet a = 1;\n/* [1]: http://b.org */",
                 "js",
                 false,
                 false,
@@ -1313,7 +2809,7 @@ mod tests {
         // - A doc block in the middle of the file
         // - A doc block with no trailing newline at the end of the file.
         assert_eq!(
-            source_to_codechat_for_web(&"//\n\n//\n\n//".to_string(), "cpp", false, false),
+            source_to_codechat_for_web("//\n\n//\n\n//", "cpp", false, false),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "c_cpp",
                 "\n\n\n\n",
@@ -1327,7 +2823,7 @@ mod tests {
 
         // Test Unicode characters in code.
         assert_eq!(
-            source_to_codechat_for_web(&"; // σ\n//".to_string(), "cpp", false, false),
+            source_to_codechat_for_web("; // σ\n//", "cpp", false, false),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "c_cpp",
                 "; // σ\n",
@@ -1337,7 +2833,7 @@ mod tests {
 
         // Test Unicode characters in strings.
         assert_eq!(
-            source_to_codechat_for_web(&"\"σ\";\n//".to_string(), "cpp", false, false),
+            source_to_codechat_for_web("\"σ\";\n//", "cpp", false, false),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "c_cpp",
                 "\"σ\";\n",
@@ -1346,6 +2842,125 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_source_to_codechat_for_web_cached() {
+        // Translate a sequence of edits to the same file, verifying that each
+        // cached translation matches an uncached translation.
+        let mut translation_cache = TranslationCache::default();
+        let mut translate = |source: &str, ext: &str| {
+            assert_eq!(
+                source_to_codechat_for_web_cached(
+                    source,
+                    ext,
+                    false,
+                    false,
+                    Some(&mut translation_cache)
+                ),
+                source_to_codechat_for_web(source, ext, false, false)
+            );
+        };
+        translate(
+            "// *One*\nint a;\n// Two\nint b;\n/** Three */\nint c;\n",
+            "cpp",
+        );
+        translate(
+            "// *One*\nint a;\n// Two, edited\nint b;\n/** Three */\nint c;\n",
+            "cpp",
+        );
+        translate(
+            "// *One*\nint a;\n// Two, edited\n/** Three */\nint c;\n",
+            "cpp",
+        );
+        translate(
+            "// *One*\nint a; /*\n// Two, edited\n/** Three */\nint c;\n",
+            "cpp",
+        );
+        translate(
+            "// *One*\nint a;\n// Two, edited\n/** Three */\nint c;\n// Four",
+            "cpp",
+        );
+        // Changing a definition used by another doc block updates both.
+        translate("// [Link][1]\nint a;\n// [1]: http://foo.org\n", "cpp");
+        translate("// [Link][1]\nint a;\n// [1]: http://bar.org\n", "cpp");
+        translate("// [Link][1]\nint a;\n// [2]: http://bar.org\n", "cpp");
+        translate("// [Link][1]\nint b;\n// [2]: http://bar.org\n", "cpp");
+        // A different lexer invalidates the cache.
+        translate("# [Link][1]\nb = 1\n", "py");
+        translate("# [Link][2]\nb = 1\n", "py");
+    }
+
+    #[test]
+    fn test_source_to_codechat_for_web_cached_open_blocks() {
+        // Edit the first doc block, leaving some block-level state open, then
+        // close it again. Verify that each cached translation matches an
+        // uncached translation.
+        let translate_edits = |sources: &[&str]| {
+            let mut translation_cache = TranslationCache::default();
+            for source in sources {
+                assert_eq!(
+                    source_to_codechat_for_web_cached(
+                        source,
+                        "cpp",
+                        false,
+                        false,
+                        Some(&mut translation_cache)
+                    ),
+                    source_to_codechat_for_web(source, "cpp", false, false)
+                );
+            }
+        };
+        // An unclosed fenced code block.
+        translate_edits(&[
+            "// ```\n// a\n// ```\nint a;\n// b\n",
+            "// ```\n// a\nint a;\n// b\n",
+            "// ```\n// a\n// ```\nint a;\n// b\n",
+        ]);
+        // An unclosed HTML block, whose end the following doc block supplies.
+        translate_edits(&[
+            "// a\nint a;\n// b\n// </pre>\n",
+            "// <pre>\nint a;\n// b\n// </pre>\n",
+            "// a\nint a;\n// b\n// </pre>\n",
+        ]);
+        translate_edits(&[
+            "// a\nint a;\n// b -->\n",
+            "// <!--\nint a;\n// b -->\n",
+            "// a\nint a;\n// b -->\n",
+        ]);
+        // An unclosed list.
+        translate_edits(&[
+            "// a\nint a;\n//   b\n",
+            "// - a\nint a;\n//   b\n",
+            "// a\nint a;\n//   b\n",
+        ]);
+        // An unclosed block quote.
+        translate_edits(&[
+            "// a\nint a;\n// b\n",
+            "// > a\nint a;\n// b\n",
+            "// a\nint a;\n// b\n",
+        ]);
+
+        // Since each doc block is displayed separately, block-level state
+        // doesn't span doc blocks.
+        let TranslationResults::CodeChat(codechat_for_web) =
+            source_to_codechat_for_web("// ```\n// a\nint a;\n// b\n", "cpp", false, false)
+        else {
+            panic!("Expected CodeChat result");
+        };
+        let doc_blocks = codechat_for_web.source.doc_blocks;
+        assert_eq!(doc_blocks[0].4, "<pre><code>a\n</code></pre>\n");
+        assert_eq!(doc_blocks[1].4, "<p>b</p>\n");
+    }
+
+    #[test]
+    fn test_lexer_key() {
+        assert_eq!(lexer_key(Path::new("src/main.rs")), "rs");
+        assert_eq!(lexer_key(Path::new("README")), "");
+        // Some files are identified by name.
+        assert_eq!(lexer_key(Path::new("project/Makefile")), "Makefile");
+        assert_eq!(lexer_key(Path::new("CMakeLists.txt")), "CMakeLists.txt");
+        assert_eq!(lexer_key(Path::new("notes.txt")), "txt");
+    }
+
     #[test]
     fn test_find_path_to_toc_1() {
         let (temp_dir, test_dir) = prep_test_dir!();
@@ -1366,4 +2981,376 @@ mod tests {
         // Report any errors produced when removing the temporary directory.
         temp_dir.close().unwrap();
     }
+
+    fn referring_links(anchor_index: &AnchorIndex, id: &str) -> Vec<LinkSource> {
+        let mut v: Vec<_> = anchor_index
+            .get_anchor(id)
+            .unwrap()
+            .referring_links
+            .iter()
+            .cloned()
+            .collect();
+        v.sort_by_key(|link_source| link_source.file.clone());
+        v
+    }
+
+    #[test]
+    fn test_anchor_index_1() {
+        let mut anchor_index = AnchorIndex::default();
+        let a = PathBuf::from("/project/a.md");
+        let b = PathBuf::from("/project/b.md");
+
+        // Headings receive ids; duplicates are made unique. Hyperlinks to
+        // files not in the index are pending.
+        assert_eq!(
+            anchor_index.upsert_html_file(
+                &a,
+                r#"<h1>Setup</h1>
+<p>See <a href="b.md#intro">b</a>.</p>
+<h2>Setup</h2>
+<p><a id="x"></a>Text.</p>"#
+            ),
+            r#"<h1 id="setup">Setup</h1>
+<p>See <a href="b.md#intro">b</a>.</p>
+<h2 id="setup-1">Setup</h2>
+<p><a id="x"></a>Text.</p>"#
+        );
+        let FileAnchor::Html(html_file_anchor) = anchor_index.get_file(&a).unwrap() else {
+            panic!();
+        };
+        assert_eq!(html_file_anchor.headings.len(), 2);
+        let non_heading_anchor = &html_file_anchor.headings[1].non_heading_anchors[0];
+        assert_eq!(non_heading_anchor.anchor_common.anchor, "x");
+        assert_eq!(non_heading_anchor.pre_snippet, "Setup See b . Setup");
+        assert_eq!(non_heading_anchor.post_snippet, "Text.");
+        assert_eq!(anchor_index.pending_hyperlinks().len(), 1);
+        assert_eq!(anchor_index.pending_hyperlinks()[0].file, b);
+
+        // An id used by another file is made globally unique, and links to
+        // it are updated.
+        assert_eq!(
+            anchor_index.upsert_html_file(
+                &b,
+                r##"<h1>Intro</h1>
+<p id="x"><a href="a.md#setup-1">Back</a>, <a href="#x">self</a>.</p>"##
+            ),
+            r##"<h1 id="intro">Intro</h1>
+<p id="x-1"><a href="a.md#setup-1">Back</a>, <a href="#x-1">self</a>.</p>"##
+        );
+        assert!(anchor_index.pending_hyperlinks().is_empty());
+        assert_eq!(
+            referring_links(&anchor_index, "intro"),
+            vec![LinkSource {
+                file: a.clone(),
+                anchor: Some("setup".to_string())
+            }]
+        );
+        assert_eq!(
+            referring_links(&anchor_index, "setup-1"),
+            vec![LinkSource {
+                file: b.clone(),
+                anchor: Some("x-1".to_string())
+            }]
+        );
+        assert_eq!(
+            referring_links(&anchor_index, "x-1"),
+            vec![LinkSource {
+                file: b.clone(),
+                anchor: Some("x-1".to_string())
+            }]
+        );
+        assert!(referring_links(&anchor_index, "x").is_empty());
+
+        // Updating a file replaces its anchors and hyperlinks.
+        anchor_index.upsert_html_file(&a, "<h1>Setup</h1>");
+        assert!(referring_links(&anchor_index, "intro").is_empty());
+        assert!(anchor_index.get_anchor("setup-1").is_none());
+
+        // Removing a file makes links to it pending.
+        anchor_index.remove_file(&a);
+        assert!(anchor_index.get_anchor("setup").is_none());
+        assert_eq!(anchor_index.pending_hyperlinks().len(), 1);
+        assert_eq!(anchor_index.pending_hyperlinks()[0].file, a);
+    }
+
+    #[test]
+    fn test_anchor_index_2() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let test_dir = test_dir.canonicalize().unwrap();
+        let mut anchor_index = AnchorIndex::default();
+
+        // Load a file, then all the files it refers to.
+        let c = test_dir.join("c.md");
+        let mut codechat_for_web = cast!(
+            source_to_codechat_for_web(&fs::read_to_string(&c).unwrap(), "md", false, true),
+            TranslationResults::CodeChat
+        );
+        anchor_index.upsert_codechat_for_web(&c, &mut codechat_for_web);
+        assert_eq!(anchor_index.pending_hyperlinks().len(), 3);
+        anchor_index.load_pending();
+
+        // The source file was loaded and its anchor found.
+        let d = test_dir.join("sub/d.py");
+        assert_eq!(anchor_index.get_anchor("fn").unwrap().file, d);
+        assert_eq!(
+            anchor_index
+                .get_anchor("fn")
+                .unwrap()
+                .referring_links
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![&LinkSource {
+                file: c.clone(),
+                anchor: Some("title".to_string())
+            }]
+        );
+        // The image is a plain file, with a link to it.
+        let FileAnchor::Plain(plain_file_anchor) =
+            anchor_index.get_file(&test_dir.join("e.png")).unwrap()
+        else {
+            panic!();
+        };
+        assert_eq!(plain_file_anchor.referring_links.len(), 1);
+        // The missing file remains pending.
+        assert_eq!(anchor_index.pending_hyperlinks().len(), 1);
+        assert_eq!(
+            anchor_index.pending_hyperlinks()[0].file,
+            test_dir.join("missing.md")
+        );
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_anchor_index_3() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let test_dir = test_dir.canonicalize().unwrap();
+        let toc_path = test_dir.join("toc.md");
+        let mut anchor_index = AnchorIndex::new(Some(toc_path.clone()));
+        let upsert = |anchor_index: &mut AnchorIndex, name: &str, ext: &str| {
+            let file_path = test_dir.join(name);
+            let mut codechat_for_web = cast!(
+                source_to_codechat_for_web(
+                    &fs::read_to_string(&file_path).unwrap(),
+                    ext,
+                    false,
+                    true
+                ),
+                TranslationResults::CodeChat
+            );
+            anchor_index.upsert_codechat_for_web(&file_path, &mut codechat_for_web);
+            codechat_for_web.source
+        };
+
+        // Headings are numbered beneath the file's position in the TOC, which
+        // is loaded automatically.
+        assert_eq!(
+            upsert(&mut anchor_index, "a.md", "md").doc,
+            r#"<h1 id="alpha" data-codechat-number="1.1.1">Alpha</h1>
+<h2 id="one" data-codechat-number="1.1.1.1">One</h2>
+<h2 id="two" data-codechat-number="1.1.1.2">Two</h2>
+<h3 id="two-point-one" data-codechat-number="1.1.1.2.1">Two point one</h3>
+"#
+        );
+        let FileAnchor::Html(html_file_anchor) =
+            anchor_index.get_file(&test_dir.join("a.md")).unwrap()
+        else {
+            panic!();
+        };
+        assert_eq!(html_file_anchor.numbering, vec![Some(1), Some(1)]);
+        assert_eq!(
+            html_file_anchor.headings[3].numbering,
+            vec![Some(1), Some(2), Some(1)]
+        );
+        // Numbering starts with the highest-level heading in the file.
+        assert_eq!(
+            upsert(&mut anchor_index, "b.py", "py").doc_blocks[0].4,
+            "<h2 id=\"beta\" data-codechat-number=\"1.2.1\">Beta</h2>\n"
+        );
+        assert_eq!(
+            upsert(&mut anchor_index, "c.md", "md").doc,
+            "<h1 id=\"gamma\" data-codechat-number=\"2.1\">Gamma</h1>\n"
+        );
+        // Files in unordered lists aren't numbered.
+        assert_eq!(
+            upsert(&mut anchor_index, "d.md", "md").doc,
+            "<h1 id=\"delta\">Delta</h1>\n"
+        );
+
+        // The TOC numbers items in ordered lists.
+        assert_eq!(
+            upsert(&mut anchor_index, "toc.md", "md").doc,
+            r#"<h1 id="contents">Contents</h1>
+<ol>
+<li data-codechat-number="1">Part
+<ol>
+<li data-codechat-number="1.1"><a href="a.md">A</a></li>
+<li data-codechat-number="1.2"><a href="b.py">B</a></li>
+</ol>
+</li>
+<li data-codechat-number="2"><a href="c.md">C</a></li>
+</ol>
+<ul>
+<li><a href="d.md">D</a></li>
+</ul>
+"#
+        );
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_anchor_index_4() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let test_dir = test_dir.canonicalize().unwrap();
+        let mut anchor_index = AnchorIndex::new(Some(test_dir.join("toc.md")));
+        let file_path = test_dir.join("b.md");
+        let mut codechat_for_web = cast!(
+            source_to_codechat_for_web(&fs::read_to_string(&file_path).unwrap(), "md", false, true),
+            TranslationResults::CodeChat
+        );
+        anchor_index.upsert_codechat_for_web(&file_path, &mut codechat_for_web);
+        anchor_index.load_pending();
+
+        // Items whose id begins with a numbering group's prefix are numbered.
+        let FileAnchor::Html(html_file_anchor) =
+            anchor_index.get_file(&test_dir.join("a.md")).unwrap()
+        else {
+            panic!();
+        };
+        let non_heading_anchors = &html_file_anchor.headings[0].non_heading_anchors;
+        assert_eq!(
+            non_heading_anchors[0].numbering_group,
+            Some("fig".to_string())
+        );
+        assert_eq!(non_heading_anchors[0].number, 1);
+        let non_heading_anchors = &html_file_anchor.headings[1].non_heading_anchors;
+        assert_eq!(
+            non_heading_anchors[0].numbering_group,
+            Some("eq".to_string())
+        );
+        assert_eq!(non_heading_anchors[1].numbering_group, None);
+
+        // Empty links receive the text of their target.
+        anchor_index.fill_link_text_codechat_for_web(&file_path, &mut codechat_for_web);
+        assert_eq!(
+            codechat_for_web.source.doc,
+            r##"<h1 id="links" data-codechat-number="2.1">Links</h1>
+<p><a href="a.md#install" data-codechat-link-text="§1.1.1 Install"></a> <a href="a.md#fig:arch" data-codechat-link-text="Figure 1.1"></a> <a href="a.md#eq:one" data-codechat-link-text="Equation 1.1"></a> <a href="a.md" data-codechat-link-text="§1.1 Setup"></a> <a href="a.md#plain" data-codechat-link-text="plain"></a>
+<a href="#links" data-codechat-link-text="§2.1 Links"></a> <a href="a.md#missing"></a></p>
+"##
+        );
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_anchor_index_5() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let test_dir = test_dir.canonicalize().unwrap();
+        let (a_path, b_path) = (test_dir.join("a.md"), test_dir.join("b.md"));
+        let (fig_path, toc_path) = (test_dir.join("fig.png"), test_dir.join("toc.md"));
+        let mut anchor_index = AnchorIndex::new(Some(toc_path.clone()));
+        anchor_index.load_file(&a_path);
+        anchor_index.load_pending();
+
+        // The index contains the TOC and the files `a.md` refers to, but not
+        // unrelated files.
+        for file_path in [&a_path, &b_path, &fig_path, &toc_path] {
+            assert!(anchor_index.contains(file_path));
+        }
+        assert!(!anchor_index.contains(&test_dir.join("c.md")));
+        // The display of `a.md` shows the TOC, its image, and its links.
+        for target in [&b_path, &fig_path, &toc_path] {
+            assert!(anchor_index.refers_to(&a_path, target));
+        }
+        assert!(!anchor_index.refers_to(&b_path, &fig_path));
+        assert!(!anchor_index.refers_to(&a_path, &test_dir.join("c.md")));
+
+        // Reloading a changed file updates its anchors.
+        assert!(anchor_index.backlinks(&b_path).is_empty());
+        fs::write(&b_path, "# Intro\n").unwrap();
+        anchor_index.reload_file(&b_path);
+        assert_eq!(anchor_index.backlinks(&b_path).len(), 1);
+        // Reloading a deleted file removes it, leaving links to it pending.
+        fs::remove_file(&b_path).unwrap();
+        anchor_index.reload_file(&b_path);
+        assert!(anchor_index.get_file(&b_path).is_none());
+        assert!(anchor_index.contains(&b_path));
+
+        // Changes to files the current file displays should refresh it.
+        source_to_codechat_for_web_string(
+            &fs::read_to_string(&a_path).unwrap(),
+            &a_path,
+            false,
+            None,
+        );
+        assert!(reindex_changed_file(&fig_path, &a_path));
+        assert!(reindex_changed_file(&toc_path, &a_path));
+        assert!(!reindex_changed_file(&test_dir.join("c.md"), &a_path));
+        assert!(!reindex_changed_file(&fig_path, &b_path));
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_backlinks() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let test_dir = test_dir.canonicalize().unwrap();
+        let mut anchor_index = AnchorIndex::new(None);
+        anchor_index.load_file(&test_dir.join("a.md"));
+        anchor_index.load_file(&test_dir.join("sub/b.md"));
+
+        let backlink =
+            |anchor: Option<&str>, source_anchor: &str, pre: &str, post: &str| Backlink {
+                anchor: anchor.map(str::to_string),
+                file: "sub/b.md".to_string(),
+                source_anchor: Some(source_anchor.to_string()),
+                pre_snippet: pre.to_string(),
+                post_snippet: post.to_string(),
+            };
+        // Links to the file come first, then links to each anchor in order.
+        // Each link's file is relative to the directory of the file it refers
+        // to.
+        assert_eq!(
+            anchor_index.backlinks(&test_dir.join("a.md")),
+            vec![
+                backlink(
+                    None,
+                    "more",
+                    "up for details. Also see the figure . More Or read",
+                    "all of it , then the setup again ."
+                ),
+                backlink(
+                    Some("setup"),
+                    "more",
+                    "lso see the figure . More Or read all of it , then",
+                    "the setup again ."
+                ),
+                backlink(
+                    Some("setup"),
+                    "uses",
+                    "Uses Before starting, see",
+                    "the setup for details. Also see the figure . More "
+                ),
+                backlink(
+                    Some("fig:arch"),
+                    "uses",
+                    "fore starting, see the setup for details. Also see",
+                    "the figure . More Or read all of it , then the set"
+                ),
+            ]
+        );
+        // Nothing refers to `b.md`.
+        assert!(anchor_index
+            .backlinks(&test_dir.join("sub/b.md"))
+            .is_empty());
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
 }
@@ -0,0 +1,433 @@
+/// Copyright (C) 2023 Bryan A. Jones.
+///
+/// This file is part of the CodeChat Editor. The CodeChat Editor is free
+/// software: you can redistribute it and/or modify it under the terms of the
+/// GNU General Public License as published by the Free Software Foundation,
+/// either version 3 of the License, or (at your option) any later version.
+///
+/// The CodeChat Editor is distributed in the hope that it will be useful, but
+/// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+/// or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+/// more details.
+///
+/// You should have received a copy of the GNU General Public License along with
+/// the CodeChat Editor. If not, see
+/// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+///
+/// # `diff.rs` -- Compute and apply changes to a `CodeMirror` document
+///
+/// Rather than sending the entire contents of a file with each update, the
+/// Client, IDE, and Server send the changes to the previous version of these
+/// contents. Each version of a file's contents is identified by a random
+/// version number; a diff applies only to the contents whose version matches
+/// the `base_version` of the `Update` message which contains it. When the
+/// versions diverge, the sender falls back to a snapshot of the entire
+/// contents.
+///
+/// A diff consists of edits to the `doc` string, followed by inserts, deletes,
+/// and replacements of doc blocks. Before applying the doc block changes, the
+/// location of each doc block is moved to account for the edits to `doc` (see
+/// `map_position`), so that doc blocks which an edit only moved don't need to
+/// be sent. The TypeScript equivalent of this code is in
+/// [Diff.mts](../../../client/src/Diff.mts); the two must agree.
+// ## Imports
+//
+// ### Standard library
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+// ### Third-party
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::{CodeChatForWeb, CodeMirror, CodeMirrorDocBlock, SourceFileMetadata};
+
+// ## Data structures
+/// The contents of a file sent in an `Update` message: either the entire
+/// contents, or the changes to a previous version of them.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub enum CodeChatForWebUpdate {
+    /// The entire contents of the file.
+    Snapshot(CodeChatForWeb),
+    /// The changes to a previous version of the file's contents.
+    Diff(CodeChatForWebDiff),
+}
+
+/// The changes to a previous version of a file's contents.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct CodeChatForWebDiff {
+    /// The metadata for the changed contents.
+    pub metadata: SourceFileMetadata,
+    /// Edits to the `doc`. These are sorted and don't overlap; the positions
+    /// of each refer to the `doc` before any edits are applied.
+    pub doc: Vec<StringDiff>,
+    /// Changes to the doc blocks, applied in order after the edits to `doc`.
+    pub doc_blocks: Vec<DocBlockDiff>,
+}
+
+/// Replace the characters (not bytes) from `from` up to `to` with `insert`.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct StringDiff {
+    pub from: usize,
+    pub to: usize,
+    pub insert: String,
+}
+
+/// A change to the doc blocks. Each index refers to the doc blocks after the
+/// preceding changes were applied.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub enum DocBlockDiff {
+    /// Insert a doc block at this index.
+    Insert(usize, CodeMirrorDocBlock),
+    /// Delete the doc block at this index.
+    Delete(usize),
+    /// Replace the doc block at this index.
+    Replace(usize, CodeMirrorDocBlock),
+}
+
+// ## Versions
+/// Return a new version number, which identifies a new version of a file's
+/// contents. Like the Client's `Math.random()`, this is a random number in
+/// \[0, 1). The standard library's randomly-keyed hasher provides the
+/// randomness.
+pub fn new_version() -> f64 {
+    // Keep the 53 bits which an `f64` represents exactly.
+    (RandomState::new().build_hasher().finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// ## Updates
+impl CodeChatForWebUpdate {
+    /// Return an update which changes `base` (if provided) to `contents`. With
+    /// no `base`, this is a snapshot.
+    pub fn new(base: Option<&CodeChatForWeb>, contents: CodeChatForWeb) -> Self {
+        match base {
+            Some(base) => {
+                let (doc, doc_blocks) = diff_code_mirror(&base.source, &contents.source);
+                CodeChatForWebUpdate::Diff(CodeChatForWebDiff {
+                    metadata: contents.metadata,
+                    doc,
+                    doc_blocks,
+                })
+            }
+            None => CodeChatForWebUpdate::Snapshot(contents),
+        }
+    }
+
+    /// Return the contents this update provides. A diff must be applied to
+    /// `base`, the contents the diff is based on.
+    pub fn into_contents(self, base: Option<&CodeChatForWeb>) -> Result<CodeChatForWeb, String> {
+        match self {
+            CodeChatForWebUpdate::Snapshot(contents) => Ok(contents),
+            CodeChatForWebUpdate::Diff(diff) => match base {
+                Some(base) => Ok(CodeChatForWeb {
+                    metadata: diff.metadata,
+                    source: apply_code_mirror_diff(
+                        base.source.clone(),
+                        &diff.doc,
+                        &diff.doc_blocks,
+                    )?,
+                }),
+                None => Err("Out of sync: no contents to apply this diff to.".to_string()),
+            },
+        }
+    }
+}
+
+// ## Diffs
+/// Return the changes which transform `before` into `after`.
+pub fn diff_code_mirror(
+    before: &CodeMirror,
+    after: &CodeMirror,
+) -> (Vec<StringDiff>, Vec<DocBlockDiff>) {
+    let doc_diff_vec = diff_str(&before.doc, &after.doc);
+
+    // Compare each doc block, after moving it to account for the edits to
+    // `doc`, with the new doc blocks.
+    let mapped_position_vec: Vec<(usize, usize)> = before
+        .doc_blocks
+        .iter()
+        .map(|doc_block| {
+            (
+                map_position(doc_block.0, &doc_diff_vec),
+                map_position(doc_block.1, &doc_diff_vec),
+            )
+        })
+        .collect();
+    let is_unchanged = |index: usize, doc_block: &CodeMirrorDocBlock| {
+        let before_doc_block = &before.doc_blocks[index];
+        mapped_position_vec[index] == (doc_block.0, doc_block.1)
+            && before_doc_block.2 == doc_block.2
+            && before_doc_block.3 == doc_block.3
+            && before_doc_block.4 == doc_block.4
+            && before_doc_block.5 == doc_block.5
+    };
+    // Find the unchanged doc blocks at the beginning and end; everything in
+    // between is replaced, deleted, or inserted.
+    let before_len = before.doc_blocks.len();
+    let after_len = after.doc_blocks.len();
+    let prefix_len = after
+        .doc_blocks
+        .iter()
+        .take(before_len)
+        .enumerate()
+        .take_while(|(index, doc_block)| is_unchanged(*index, doc_block))
+        .count();
+    let suffix_len = after
+        .doc_blocks
+        .iter()
+        .rev()
+        .take(before_len.min(after_len) - prefix_len)
+        .enumerate()
+        .take_while(|(index, doc_block)| is_unchanged(before_len - 1 - index, doc_block))
+        .count();
+    let before_changed_len = before_len - prefix_len - suffix_len;
+    let after_changed_len = after_len - prefix_len - suffix_len;
+
+    let mut doc_block_diff_vec = Vec::new();
+    for index in prefix_len..prefix_len + before_changed_len.min(after_changed_len) {
+        doc_block_diff_vec.push(DocBlockDiff::Replace(
+            index,
+            after.doc_blocks[index].clone(),
+        ));
+    }
+    let index = prefix_len + before_changed_len.min(after_changed_len);
+    for _ in after_changed_len..before_changed_len {
+        doc_block_diff_vec.push(DocBlockDiff::Delete(index));
+    }
+    for index in index..prefix_len + after_changed_len {
+        doc_block_diff_vec.push(DocBlockDiff::Insert(index, after.doc_blocks[index].clone()));
+    }
+
+    (doc_diff_vec, doc_block_diff_vec)
+}
+
+/// Apply the changes produced by `diff_code_mirror` to `code_mirror`.
+pub fn apply_code_mirror_diff(
+    mut code_mirror: CodeMirror,
+    doc_diff_vec: &[StringDiff],
+    doc_block_diff_vec: &[DocBlockDiff],
+) -> Result<CodeMirror, String> {
+    code_mirror.doc = apply_string_diff(&code_mirror.doc, doc_diff_vec)?;
+    for doc_block in &mut code_mirror.doc_blocks {
+        doc_block.0 = map_position(doc_block.0, doc_diff_vec);
+        doc_block.1 = map_position(doc_block.1, doc_diff_vec);
+    }
+    let doc_blocks = &mut code_mirror.doc_blocks;
+    for doc_block_diff in doc_block_diff_vec {
+        match doc_block_diff {
+            DocBlockDiff::Insert(index, doc_block) if *index <= doc_blocks.len() => {
+                doc_blocks.insert(*index, doc_block.clone())
+            }
+            DocBlockDiff::Delete(index) if *index < doc_blocks.len() => {
+                doc_blocks.remove(*index);
+            }
+            DocBlockDiff::Replace(index, doc_block) if *index < doc_blocks.len() => {
+                doc_blocks[*index] = doc_block.clone()
+            }
+            _ => return Err(format!("Invalid doc block change {doc_block_diff:?}.")),
+        }
+    }
+    Ok(code_mirror)
+}
+
+// Return the edits which transform `before` into `after`: the characters
+// between their common prefix and their common suffix.
+fn diff_str(before: &str, after: &str) -> Vec<StringDiff> {
+    if before == after {
+        return Vec::new();
+    }
+    let (mut prefix_chars, mut prefix_bytes) = (0, 0);
+    for (before_char, after_char) in before.chars().zip(after.chars()) {
+        if before_char != after_char {
+            break;
+        }
+        prefix_chars += 1;
+        prefix_bytes += before_char.len_utf8();
+    }
+    let (before, after) = (&before[prefix_bytes..], &after[prefix_bytes..]);
+    let mut suffix_bytes = 0;
+    for (before_char, after_char) in before.chars().rev().zip(after.chars().rev()) {
+        if before_char != after_char {
+            break;
+        }
+        suffix_bytes += before_char.len_utf8();
+    }
+    vec![StringDiff {
+        from: prefix_chars,
+        to: prefix_chars + before[..before.len() - suffix_bytes].chars().count(),
+        insert: after[..after.len() - suffix_bytes].to_string(),
+    }]
+}
+
+// Apply the edits produced by `diff_str` to `string`.
+fn apply_string_diff(string: &str, string_diff_vec: &[StringDiff]) -> Result<String, String> {
+    let mut result = String::with_capacity(string.len());
+    // The byte and character index of the start of the unedited text.
+    let (mut byte_index, mut char_index) = (0, 0);
+    // Starting from the character at `byte_index`/`char_index`, return the
+    // byte index of the character `to_char_index`.
+    let find_byte_index = |byte_index: usize, char_index: usize, to_char_index: usize| {
+        string[byte_index..]
+            .char_indices()
+            .map(|(index, _)| byte_index + index)
+            .chain([string.len()])
+            .nth(to_char_index.checked_sub(char_index)?)
+    };
+    for string_diff in string_diff_vec {
+        let from_byte_index = find_byte_index(byte_index, char_index, string_diff.from);
+        let to_byte_index = from_byte_index.and_then(|from_byte_index| {
+            find_byte_index(from_byte_index, string_diff.from, string_diff.to)
+        });
+        let (Some(from_byte_index), Some(to_byte_index)) = (from_byte_index, to_byte_index) else {
+            return Err(format!("Invalid edit {string_diff:?}."));
+        };
+        result.push_str(&string[byte_index..from_byte_index]);
+        result.push_str(&string_diff.insert);
+        (byte_index, char_index) = (to_byte_index, string_diff.to);
+    }
+    result.push_str(&string[byte_index..]);
+    Ok(result)
+}
+
+// Move `position` to account for the edits in `string_diff_vec`. A position
+// in text which an edit replaced moves to the start of the edit.
+fn map_position(position: usize, string_diff_vec: &[StringDiff]) -> usize {
+    let mut mapped_position = position;
+    for string_diff in string_diff_vec {
+        if position < string_diff.from {
+            break;
+        }
+        let insert_len = string_diff.insert.chars().count();
+        if position >= string_diff.to {
+            mapped_position = mapped_position + insert_len - (string_diff.to - string_diff.from);
+        } else {
+            return mapped_position - (position - string_diff.from);
+        }
+    }
+    mapped_position
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_code_mirror_diff, diff_code_mirror, new_version, CodeChatForWebUpdate, DocBlockDiff,
+        StringDiff,
+    };
+    use crate::processing::{CodeChatForWeb, CodeMirror, SourceFileMetadata};
+
+    fn build_code_mirror(doc: &str, doc_blocks: &[(usize, usize, &str)]) -> CodeMirror {
+        CodeMirror {
+            doc: doc.to_string(),
+            doc_blocks: doc_blocks
+                .iter()
+                .map(|(from, to, contents)| {
+                    (
+                        *from,
+                        *to,
+                        "".to_string(),
+                        "//".to_string(),
+                        contents.to_string(),
+                        "".to_string(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    // Diff `before` and `after`, verify that applying the diff to `before`
+    // produces `after`, then return the diff.
+    fn round_trip(before: &CodeMirror, after: &CodeMirror) -> (Vec<StringDiff>, Vec<DocBlockDiff>) {
+        let (doc_diff_vec, doc_block_diff_vec) = diff_code_mirror(before, after);
+        assert_eq!(
+            &apply_code_mirror_diff(before.clone(), &doc_diff_vec, &doc_block_diff_vec).unwrap(),
+            after
+        );
+        (doc_diff_vec, doc_block_diff_vec)
+    }
+
+    #[test]
+    fn test_diff_code_mirror() {
+        let before = build_code_mirror("a\n\nb\n\nc\n", &[(2, 2, "One"), (6, 6, "Two")]);
+
+        // No changes produce an empty diff.
+        assert_eq!(round_trip(&before, &before), (vec![], vec![]));
+
+        // Editing code moves the following doc blocks without changing them.
+        assert_eq!(
+            round_trip(
+                &before,
+                &build_code_mirror("a\n\nbσb\n\nc\n", &[(2, 2, "One"), (8, 8, "Two")])
+            ),
+            (
+                vec![StringDiff {
+                    from: 4,
+                    to: 4,
+                    insert: "σb".to_string()
+                }],
+                vec![]
+            )
+        );
+
+        // Editing a doc block replaces only it.
+        let after = build_code_mirror("a\n\nb\n\nc\n", &[(2, 2, "One"), (6, 6, "2")]);
+        assert_eq!(
+            round_trip(&before, &after).1,
+            vec![DocBlockDiff::Replace(1, after.doc_blocks[1].clone())]
+        );
+
+        // Insert and delete doc blocks.
+        let after = build_code_mirror(
+            "a\n\nb\n\nc\n\n",
+            &[(2, 2, "One"), (6, 6, "Two"), (8, 8, "Three")],
+        );
+        assert_eq!(
+            round_trip(&before, &after).1,
+            vec![DocBlockDiff::Insert(2, after.doc_blocks[2].clone())]
+        );
+        assert_eq!(round_trip(&after, &before).1, vec![DocBlockDiff::Delete(2)]);
+        round_trip(&before, &build_code_mirror("", &[]));
+        round_trip(&build_code_mirror("", &[]), &before);
+
+        // Invalid changes produce an error.
+        assert!(apply_code_mirror_diff(
+            before.clone(),
+            &[StringDiff {
+                from: 3,
+                to: 20,
+                insert: "".to_string()
+            }],
+            &[]
+        )
+        .is_err());
+        assert!(apply_code_mirror_diff(before.clone(), &[], &[DocBlockDiff::Delete(2)]).is_err());
+    }
+
+    #[test]
+    fn test_codechat_for_web_update() {
+        let build_codechat_for_web = |code_mirror: CodeMirror| CodeChatForWeb {
+            metadata: SourceFileMetadata {
+                mode: "c_cpp".to_string(),
+                backlinks: Vec::new(),
+            },
+            source: code_mirror,
+        };
+        let before = build_codechat_for_web(build_code_mirror("a\n\n", &[(2, 2, "One")]));
+        let after = build_codechat_for_web(build_code_mirror("ab\n\n", &[(3, 3, "One")]));
+        let version = new_version();
+        assert!((0.0..1.0).contains(&version));
+        assert_ne!(version, new_version());
+
+        // Without a base, an update is a snapshot.
+        let update = CodeChatForWebUpdate::new(None, after.clone());
+        assert_eq!(update, CodeChatForWebUpdate::Snapshot(after.clone()));
+        assert_eq!(update.into_contents(None), Ok(after.clone()));
+
+        // Otherwise, it's a diff, which requires the same base.
+        let update = CodeChatForWebUpdate::new(Some(&before), after.clone());
+        assert!(matches!(update, CodeChatForWebUpdate::Diff(_)));
+        assert_eq!(update.clone().into_contents(Some(&before)), Ok(after));
+        assert!(update.into_contents(None).is_err());
+    }
+}
@@ -0,0 +1,454 @@
+/// Copyright (C) 2023 Bryan A. Jones.
+///
+/// This file is part of the CodeChat Editor. The CodeChat Editor is free
+/// software: you can redistribute it and/or modify it under the terms of the
+/// GNU General Public License as published by the Free Software Foundation,
+/// either version 3 of the License, or (at your option) any later version.
+///
+/// The CodeChat Editor is distributed in the hope that it will be useful, but
+/// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+/// or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+/// more details.
+///
+/// You should have received a copy of the GNU General Public License along with
+/// the CodeChat Editor. If not, see
+/// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+///
+/// # `doc_comment.rs` - Render documentation comment tags
+///
+/// Documentation comments (Javadoc, Doxygen, rustdoc) contain tags such as
+/// `@param x The value.` or `\return The sum.`, which are noise when rendered
+/// as Markdown. Before rendering, translate these tags into Markdown sections:
+///
+/// ```Markdown
+/// **Parameters:**
+///
+/// - `x`: The value.
+///
+/// **Returns:** The sum.
+/// ```
+///
+/// After editing, translate these sections back to tags. Doxygen accepts
+/// either an `@` or a `\` before a tag, and several spellings of some tags
+/// (`@return`/`@returns`). The Markdown doesn't capture these, so the style of
+/// each doc block records the tags it uses as written; when saved, each tag is
+/// written the same way.
+///
+/// Likewise, translate rustdoc intra-doc links (`` [`Foo`] ``) into hyperlinks
+/// marked with the `INTRA_DOC_LINK_TITLE`. The [anchor index](../processing.rs)
+/// later points each of these to the matching anchor in the project; after
+/// editing, they're translated back to intra-doc links.
+// ## Imports
+//
+// ### Third-party
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// ## Globals
+/// The opening delimiters of documentation comments.
+const DOC_COMMENT_DELIMITERS: [&str; 3] = ["/**", "///", "//!"];
+
+/// The title of a hyperlink produced from an intra-doc link.
+pub const INTRA_DOC_LINK_TITLE: &str = "intra-doc link";
+
+// The tags which are rendered as sections. The first tag in each entry is
+// used when translating back to tags, unless the doc block's style records
+// another spelling; the remaining tags are alternative spellings. The second
+// item is the section's label; the third item is true for tags followed by a
+// name (such as `@param x`), which are rendered as a list.
+const DOC_COMMENT_TAGS: [(&[&str], &str, bool); 8] = [
+    (&["param"], "Parameters", true),
+    (&["tparam"], "Type parameters", true),
+    (&["throws", "throw", "exception"], "Throws", true),
+    (&["return", "returns", "result"], "Returns", false),
+    (&["brief", "short"], "Summary", false),
+    (&["see", "sa"], "See also", false),
+    (&["since"], "Since", false),
+    (&["deprecated"], "Deprecated", false),
+];
+
+lazy_static! {
+    /// A line beginning with a tag, such as `@param x The value.`
+    static ref TAG_LINE: Regex = Regex::new(r"^[ \t]*([@\\]\w+)(?:[ \t]+(.*))?$").unwrap();
+    /// A line beginning with a section label, such as `**Returns:** The sum.`
+    static ref LABEL_LINE: Regex = Regex::new(r"^\*\*([\w ]+):\*\*(?:[ \t]+(.*))?$").unwrap();
+    /// An item in a list of named tags, such as ``- `x`: The value.``
+    static ref NAMED_ITEM: Regex = Regex::new(r"^[-*+][ \t]+`([^`]+)`(?::[ \t]*(.*))?$").unwrap();
+    /// An intra-doc link which isn't part of a Markdown link, such as
+    /// `` [`Foo`] `` or `` [`crate::foo`] ``.
+    static ref INTRA_DOC_LINK: Regex =
+        Regex::new(r"\[`([A-Za-z_][\w:]*)`\]([^(\[:]|$)").unwrap();
+    /// A hyperlink produced from an intra-doc link, after conversion back to
+    /// Markdown.
+    static ref INTRA_DOC_HYPERLINK: Regex = Regex::new(&format!(
+        r#"\[(`[^`\]]+`)\]\([^\s)]*[ \t]+"{INTRA_DOC_LINK_TITLE}"\)"#
+    ))
+    .unwrap();
+}
+
+// ## Code
+//
+// Return true if `delimiter` opens a documentation comment.
+pub fn is_doc_comment_delimiter(delimiter: &str) -> bool {
+    DOC_COMMENT_DELIMITERS.contains(&delimiter)
+}
+
+// Return true if this line opens or closes a fenced code block.
+fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+// A tag found in a documentation comment.
+struct Tag {
+    // The index of this tag's entry in `DOC_COMMENT_TAGS`.
+    kind: usize,
+    // The tag as written, such as `\returns`.
+    written: String,
+    // For named tags, the name.
+    name: Option<String>,
+    // The description which follows the tag (and name), one entry per line.
+    lines: Vec<String>,
+}
+
+// Translate the tags and intra-doc links in the contents of a documentation
+// comment into Markdown.
+pub fn doc_comment_to_markdown(contents: &str) -> String {
+    let mut markdown_lines: Vec<String> = Vec::new();
+    // The tags in the current group of tags, which ends at a blank line.
+    let mut tags: Vec<Tag> = Vec::new();
+    let mut in_fence = false;
+    for line in contents.split('\n') {
+        if in_fence {
+            in_fence = !is_fence(line);
+            markdown_lines.push(line.to_string());
+            continue;
+        }
+        if let Some(tag) = parse_tag_line(line) {
+            tags.push(tag);
+        } else if !line.trim().is_empty() && !is_fence(line) && !tags.is_empty() {
+            // This continues the description of the previous tag.
+            tags.last_mut().unwrap().lines.push(line.trim().to_string());
+        } else {
+            render_tags(&mut tags, &mut markdown_lines);
+            in_fence = is_fence(line);
+            markdown_lines.push(
+                INTRA_DOC_LINK
+                    .replace_all(line, |captures: &regex::Captures| {
+                        format!(
+                            "[`{0}`](#{0} \"{INTRA_DOC_LINK_TITLE}\"){1}",
+                            &captures[1], &captures[2]
+                        )
+                    })
+                    .to_string(),
+            );
+        }
+    }
+    // Render any tags on the last line.
+    render_tags(&mut tags, &mut markdown_lines);
+    markdown_lines.join("\n")
+}
+
+// Return the index of the entry in `DOC_COMMENT_TAGS` for a tag as written
+// (such as `@param`), or `None` if this isn't a known tag.
+fn tag_kind(written: &str) -> Option<usize> {
+    let tag = written.strip_prefix(['@', '\\'])?;
+    DOC_COMMENT_TAGS
+        .iter()
+        .position(|(tags, _, _)| tags.contains(&tag))
+}
+
+// If this line begins with a known tag, return it.
+fn parse_tag_line(line: &str) -> Option<Tag> {
+    let captures = TAG_LINE.captures(line)?;
+    let kind = tag_kind(&captures[1])?;
+    let mut description = captures.get(2).map_or("", |m| m.as_str()).trim();
+    let mut name = None;
+    if DOC_COMMENT_TAGS[kind].2 {
+        // A named tag requires a name.
+        let (first_word, rest) = description
+            .split_once(char::is_whitespace)
+            .unwrap_or((description, ""));
+        if first_word.is_empty() || first_word.contains('`') {
+            return None;
+        }
+        name = Some(first_word.to_string());
+        description = rest.trim_start();
+    }
+    Some(Tag {
+        kind,
+        written: captures[1].to_string(),
+        name,
+        lines: vec![description.to_string()],
+    })
+}
+
+// Render a group of tags as Markdown sections, separated from the preceding
+// text by a blank line.
+fn render_tags(tags: &mut Vec<Tag>, markdown_lines: &mut Vec<String>) {
+    let mut previous_kind = None;
+    for tag in tags.drain(..) {
+        let (_, label, is_named) = DOC_COMMENT_TAGS[tag.kind];
+        let continues_list = is_named && previous_kind == Some(tag.kind);
+        if !continues_list && markdown_lines.last().is_some_and(|line| !line.is_empty()) {
+            markdown_lines.push(String::new());
+        }
+        let (first_line, other_lines) = tag.lines.split_first().unwrap();
+        if let Some(name) = tag.name {
+            // Start a new list for the first tag of this kind.
+            if !continues_list {
+                markdown_lines.push(format!("**{label}:**"));
+                markdown_lines.push(String::new());
+            }
+            markdown_lines.push(if first_line.is_empty() {
+                format!("- `{name}`")
+            } else {
+                format!("- `{name}`: {first_line}")
+            });
+            // Indent the rest of the description to include it in the list
+            // item.
+            markdown_lines.extend(other_lines.iter().map(|line| format!("  {line}")));
+        } else {
+            markdown_lines.push(if first_line.is_empty() {
+                format!("**{label}:**")
+            } else {
+                format!("**{label}:** {first_line}")
+            });
+            markdown_lines.extend(other_lines.iter().cloned());
+        }
+        previous_kind = Some(tag.kind);
+    }
+}
+
+// Return the style of a doc block (`style`) with the tags used by the
+// documentation comment `contents` added, as written. Only the first tag of
+// each kind is recorded.
+pub fn add_doc_comment_style(style: &str, contents: &str) -> String {
+    let mut style_words: Vec<String> = style
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_string())
+        .collect();
+    let mut in_fence = false;
+    for line in contents.split('\n') {
+        if in_fence {
+            in_fence = !is_fence(line);
+        } else if let Some(tag) = parse_tag_line(line) {
+            if !style_words
+                .iter()
+                .any(|word| tag_kind(word) == Some(tag.kind))
+            {
+                style_words.push(tag.written);
+            }
+        } else {
+            in_fence = is_fence(line);
+        }
+    }
+    style_words.join(" ")
+}
+
+// Return the tag to write for tags of this `kind`: the tag recorded in `style`,
+// if there is one. Otherwise, use the first spelling of this kind, preceded by
+// the same `@` or `\` as the recorded tags.
+fn styled_tag(kind: usize, style: &str) -> String {
+    let mut prefix = '@';
+    for word in style.split(' ') {
+        if let Some(word_kind) = tag_kind(word) {
+            if word_kind == kind {
+                return word.to_string();
+            }
+            // `tag_kind` only accepts a word which begins with a prefix.
+            prefix = word.chars().next().unwrap();
+        }
+    }
+    format!("{prefix}{}", DOC_COMMENT_TAGS[kind].0[0])
+}
+
+// Translate Markdown produced by `doc_comment_to_markdown` (after editing)
+// back to the contents of a documentation comment, writing tags in the `style`
+// recorded by `add_doc_comment_style`.
+pub fn markdown_to_doc_comment(markdown: &str, style: &str) -> String {
+    let lines: Vec<&str> = markdown.split('\n').collect();
+    let mut doc_comment_lines: Vec<String> = Vec::new();
+    // The length of `doc_comment_lines` after the most recent tag. Tags are
+    // placed together, without the blank lines which separate the sections
+    // they were rendered as.
+    let mut tags_end = None;
+    let mut in_fence = false;
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        index += 1;
+        let label = LABEL_LINE.captures(line).and_then(|captures| {
+            let kind = DOC_COMMENT_TAGS
+                .iter()
+                .position(|(_, label, _)| *label == &captures[1])?;
+            Some((kind, captures.get(2).map_or("", |m| m.as_str())))
+        });
+        let tag_lines = match label {
+            Some((kind, first_line)) if !in_fence => {
+                let tag = styled_tag(kind, style);
+                if DOC_COMMENT_TAGS[kind].2 {
+                    parse_named_items(&tag, &lines, &mut index)
+                } else {
+                    // The description continues until a blank line.
+                    let mut tag_lines = vec![format!("{tag} {first_line}").trim_end().to_string()];
+                    while index < lines.len() && !lines[index].trim().is_empty() {
+                        tag_lines.push(lines[index].trim().to_string());
+                        index += 1;
+                    }
+                    tag_lines
+                }
+            }
+            _ => Vec::new(),
+        };
+        if tag_lines.is_empty() {
+            if is_fence(line) {
+                in_fence = !in_fence;
+            }
+            doc_comment_lines.push(if in_fence {
+                line.to_string()
+            } else {
+                INTRA_DOC_HYPERLINK.replace_all(line, "[$1]").to_string()
+            });
+        } else {
+            // Remove the blank lines after the previous tag.
+            if let Some(tags_end) = tags_end {
+                if doc_comment_lines[tags_end..]
+                    .iter()
+                    .all(|line| line.trim().is_empty())
+                {
+                    doc_comment_lines.truncate(tags_end);
+                }
+            }
+            doc_comment_lines.extend(tag_lines);
+            tags_end = Some(doc_comment_lines.len());
+        }
+    }
+    doc_comment_lines.join("\n")
+}
+
+// Translate the list which follows the label of a named tag, starting at
+// `lines[*index]`, to tags. Update `index` to the line after the list. If
+// there's no list, return an empty vector and leave `index` unchanged.
+fn parse_named_items(tag: &str, lines: &[&str], index: &mut usize) -> Vec<String> {
+    let mut tag_lines = Vec::new();
+    let mut list_index = *index;
+    loop {
+        // Skip blank lines between the label and the list, and between list
+        // items.
+        let mut item_index = list_index;
+        while item_index < lines.len() && lines[item_index].trim().is_empty() {
+            item_index += 1;
+        }
+        let Some(captures) = lines
+            .get(item_index)
+            .and_then(|line| NAMED_ITEM.captures(line))
+        else {
+            break;
+        };
+        let description = captures.get(2).map_or("", |m| m.as_str());
+        tag_lines.push(
+            format!("{tag} {} {description}", &captures[1])
+                .trim_end()
+                .to_string(),
+        );
+        // Include the indented lines which continue this item.
+        item_index += 1;
+        while item_index < lines.len()
+            && lines[item_index].starts_with([' ', '\t'])
+            && !lines[item_index].trim().is_empty()
+        {
+            tag_lines.push(lines[item_index].trim().to_string());
+            item_index += 1;
+        }
+        list_index = item_index;
+    }
+    if !tag_lines.is_empty() {
+        *index = list_index;
+    }
+    tag_lines
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{add_doc_comment_style, doc_comment_to_markdown, markdown_to_doc_comment};
+
+    #[test]
+    fn test_doc_comment_tags() {
+        // Tags are rendered as sections, then translated back.
+        let doc_comment = "Add two numbers.\n\n@param a The first\nnumber.\n@param b The second number.\n@return The sum.\n@throws Overflow If the sum is too large.\n";
+        let markdown = "Add two numbers.\n\n**Parameters:**\n\n- `a`: The first\n  number.\n- `b`: The second number.\n\n**Returns:** The sum.\n\n**Throws:**\n\n- `Overflow`: If the sum is too large.\n";
+        assert_eq!(doc_comment_to_markdown(doc_comment), markdown);
+        assert_eq!(markdown_to_doc_comment(markdown, ""), doc_comment);
+
+        // Prettier and Turndown may change the list markers and add space
+        // between list items.
+        assert_eq!(
+            markdown_to_doc_comment(
+                "Add.\n\n**Parameters:**\n\n*   `a`: The first.\n\n*   `b`\n\n**Returns:** The\nsum."
+            , ""),
+            "Add.\n\n@param a The first.\n@param b\n@return The\nsum."
+        );
+
+        // Unknown tags, tags in code blocks, and named tags without a name are
+        // left alone.
+        let doc_comment = "@author Me\n\n```\n@param x\n```\n@param\n";
+        assert_eq!(doc_comment_to_markdown(doc_comment), doc_comment);
+        assert_eq!(markdown_to_doc_comment(doc_comment, ""), doc_comment);
+        // So is a label without a list.
+        assert_eq!(
+            markdown_to_doc_comment("**Parameters:**\n\nNone.", ""),
+            "**Parameters:**\n\nNone."
+        );
+    }
+
+    #[test]
+    fn test_doc_comment_style() {
+        // Each spelling of a tag, with either prefix, is saved as written.
+        for doc_comment in [
+            "\\brief Adds.\n\\returns The sum.",
+            "@short Adds.\n@result The sum.",
+            "@param a The first.\n@throw Overflow If the sum is too large.",
+            "\\param a The first.\n\\exception Overflow If the sum is too large.",
+            "Adds.\n\n@sa add_all\n@returns The sum.\n",
+        ] {
+            let style = add_doc_comment_style("", doc_comment);
+            assert_eq!(
+                markdown_to_doc_comment(&doc_comment_to_markdown(doc_comment), &style),
+                doc_comment
+            );
+        }
+
+        // The style records the first tag of each kind, after any other style.
+        assert_eq!(
+            add_doc_comment_style(
+                "inline",
+                "\\param a A.\n\\param b B.\n@returns C.\n```\n@see x\n```"
+            ),
+            "inline \\param @returns"
+        );
+        // Tags added after editing use the same prefix as the recorded tags.
+        assert_eq!(
+            markdown_to_doc_comment("**Summary:** Adds.\n\n**Returns:** The sum.", "\\param"),
+            "\\brief Adds.\n\\return The sum."
+        );
+    }
+
+    #[test]
+    fn test_intra_doc_links() {
+        assert_eq!(
+            doc_comment_to_markdown("See [`Foo`] and [`crate::bar`].\n"),
+            "See [`Foo`](#Foo \"intra-doc link\") and [`crate::bar`](#crate::bar \"intra-doc link\").\n"
+        );
+        // Markdown links and reference definitions aren't intra-doc links.
+        let markdown = "[`Foo`](foo.rs) [`Foo`][1]\n\n[`Foo`]: foo.rs\n";
+        assert_eq!(doc_comment_to_markdown(markdown), markdown);
+        // After the Anchor index resolves the link, it's still translated back.
+        assert_eq!(
+            markdown_to_doc_comment("See [`Foo`](../foo.rs#Foo \"intra-doc link\").\n", ""),
+            "See [`Foo`].\n"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+/// Copyright (C) 2023 Bryan A. Jones.
+///
+/// This file is part of the CodeChat Editor. The CodeChat Editor is free
+/// software: you can redistribute it and/or modify it under the terms of the
+/// GNU General Public License as published by the Free Software Foundation,
+/// either version 3 of the License, or (at your option) any later version.
+///
+/// The CodeChat Editor is distributed in the hope that it will be useful, but
+/// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+/// or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+/// more details.
+///
+/// You should have received a copy of the GNU General Public License along with
+/// the CodeChat Editor. If not, see
+/// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+///
+/// # `merge.rs` -- Merge concurrent edits to a source file
+///
+/// When the Client and the IDE (or the file on disk) both edit the same
+/// version of a file, the Server merges these edits using a line-based
+/// three-way merge of the source code. Since doc blocks are comments in the
+/// source code, this merges edits to both code and doc blocks. Edits which
+/// change the same lines differently conflict; the Server then reports this
+/// conflict rather than silently discarding one of the edits.
+// ## Code
+/// Merge the changes which `ours` and `theirs` each made to `base`. Return the
+/// merged text or, if both changed the same lines differently, an error
+/// message describing these conflicts.
+pub fn merge3(base: &str, ours: &str, theirs: &str) -> Result<String, String> {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let ours_matches = match_lines(&base_lines, &ours_lines);
+    let theirs_matches = match_lines(&base_lines, &theirs_lines);
+
+    let mut merged = String::with_capacity(ours.len().max(theirs.len()));
+    let mut conflicts = Vec::new();
+    // The index of the next line to merge in each text.
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    while i < base_lines.len() || j < ours_lines.len() || k < theirs_lines.len() {
+        // Copy lines which neither changed.
+        if i < base_lines.len() && ours_matches[i] == Some(j) && theirs_matches[i] == Some(k) {
+            merged.push_str(base_lines[i]);
+            (i, j, k) = (i + 1, j + 1, k + 1);
+            continue;
+        }
+        // Otherwise, the lines up to the next line which neither changed
+        // differ in one or both texts.
+        let (i_end, j_end, k_end) = (i..base_lines.len())
+            .find_map(|index| Some((index, ours_matches[index]?, theirs_matches[index]?)))
+            .unwrap_or((base_lines.len(), ours_lines.len(), theirs_lines.len()));
+        let base_chunk = &base_lines[i..i_end];
+        let ours_chunk = &ours_lines[j..j_end];
+        let theirs_chunk = &theirs_lines[k..k_end];
+        if ours_chunk == base_chunk || ours_chunk == theirs_chunk {
+            merged.extend(theirs_chunk.iter().copied());
+        } else if theirs_chunk == base_chunk {
+            merged.extend(ours_chunk.iter().copied());
+        } else {
+            conflicts.push(format!("{}-{}", i + 1, i_end.max(i + 1)));
+        }
+        (i, j, k) = (i_end, j_end, k_end);
+    }
+
+    if conflicts.is_empty() {
+        Ok(merged)
+    } else {
+        Err(format!(
+            "Conflict: both edits change lines {} of the file.",
+            conflicts.join(", ")
+        ))
+    }
+}
+
+// For each line of `base`, return the index of the matching line in `other`,
+// or `None` if `other` changed this line.
+fn match_lines(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+    // Most edits are small; match the unchanged lines at the beginning and end
+    // directly, then diff only the lines between them.
+    let prefix_len = base
+        .iter()
+        .zip(other)
+        .take_while(|(base_line, other_line)| base_line == other_line)
+        .count();
+    let suffix_len = base[prefix_len..]
+        .iter()
+        .rev()
+        .zip(other[prefix_len..].iter().rev())
+        .take_while(|(base_line, other_line)| base_line == other_line)
+        .count();
+    for (index, line_match) in matches.iter_mut().enumerate().take(prefix_len) {
+        *line_match = Some(index);
+    }
+    for index in 0..suffix_len {
+        matches[base.len() - 1 - index] = Some(other.len() - 1 - index);
+    }
+    for (base_index, other_index) in myers_diff(
+        &base[prefix_len..base.len() - suffix_len],
+        &other[prefix_len..other.len() - suffix_len],
+    ) {
+        matches[prefix_len + base_index] = Some(prefix_len + other_index);
+    }
+    matches
+}
+
+// Return the indices of the matching lines in a shortest edit script which
+// transforms `a` into `b`, using
+// [Myers' algorithm](http://www.xmailserver.org/diff2.pdf).
+fn myers_diff(a: &[&str], b: &[&str]) -> Vec<(usize, usize)> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let offset = n + m;
+    // The furthest-reaching x on each diagonal k = x - y, indexed by k +
+    // offset.
+    let mut v = vec![0isize; 2 * offset as usize + 2];
+    let index = |k: isize| (k + offset) as usize;
+    // The value of `v` before each step of the search, used to find the path
+    // taken.
+    let mut trace = Vec::new();
+    'search: for d in 0..=offset {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                (x, y) = (x + 1, y + 1);
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk back through the path, recording each diagonal (matching) step.
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[index(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            (x, y) = (x - 1, y - 1);
+            matches.push((x as usize, y as usize));
+        }
+        (x, y) = (prev_x, prev_y);
+    }
+    matches.reverse();
+    matches
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{merge3, myers_diff};
+
+    #[test]
+    fn test_myers_diff() {
+        assert_eq!(
+            myers_diff(&["a", "b", "c"], &["a", "c"]),
+            vec![(0, 0), (2, 1)]
+        );
+        assert_eq!(
+            myers_diff(
+                &["a", "b", "c", "a", "b", "b", "a"],
+                &["c", "b", "a", "b", "a", "c"]
+            )
+            .len(),
+            4
+        );
+        assert_eq!(myers_diff(&[], &["a"]), vec![]);
+    }
+
+    #[test]
+    fn test_merge3() {
+        let base = "a\nb\nc\nd\ne\n";
+        // Edits to different lines merge.
+        assert_eq!(
+            merge3(base, "A\nb\nc\nd\ne\n", "a\nb\nc\nd\nE\n"),
+            Ok("A\nb\nc\nd\nE\n".to_string())
+        );
+        // Insertions and deletions merge.
+        assert_eq!(
+            merge3(base, "a\nb\nb2\nc\nd\ne\n", "a\nb\nc\ne\nf"),
+            Ok("a\nb\nb2\nc\ne\nf".to_string())
+        );
+        // Identical edits merge.
+        assert_eq!(
+            merge3(base, "a\nB\nc\nd\ne\n", "a\nB\nc\nd\ne\n"),
+            Ok("a\nB\nc\nd\ne\n".to_string())
+        );
+        // An edit merges with no edit.
+        assert_eq!(merge3(base, base, ""), Ok("".to_string()));
+        // Different edits to the same lines conflict.
+        assert_eq!(
+            merge3(base, "a\nB\nc\nd\nE\n", "a\nb2\nc\nd\ne\n"),
+            Err("Conflict: both edits change lines 2-2 of the file.".to_string())
+        );
+        assert_eq!(
+            merge3(base, "a\nb\nc\nd\ne\nx\n", "a\nb\nc\nd\ne\ny\n"),
+            Err("Conflict: both edits change lines 6-6 of the file.".to_string())
+        );
+    }
+}
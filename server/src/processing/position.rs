@@ -0,0 +1,525 @@
+/// Copyright (C) 2023 Bryan A. Jones.
+///
+/// This file is part of the CodeChat Editor. The CodeChat Editor is free
+/// software: you can redistribute it and/or modify it under the terms of the
+/// GNU General Public License as published by the Free Software Foundation,
+/// either version 3 of the License, or (at your option) any later version.
+///
+/// The CodeChat Editor is distributed in the hope that it will be useful, but
+/// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+/// or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+/// more details.
+///
+/// You should have received a copy of the GNU General Public License along with
+/// the CodeChat Editor. If not, see
+/// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+///
+/// # `position.rs` -- Map cursor and scroll positions between source code and `CodeMirror`
+///
+/// The IDE locates a cursor by its line and column in the source file. The
+/// Client locates a cursor either by its character offset in the `doc` of a
+/// `CodeMirror` document or, inside a doc block, by its line and column in the
+/// contents of that doc block. This maps between the two.
+///
+/// Likewise, the IDE scrolls by source lines, while the Client scrolls by
+/// lines of code and by doc blocks, which are rendered much taller or shorter
+/// than the comments they come from. Scroll positions therefore refer to the
+/// line at the top of the view; the Server spreads the height of each doc
+/// block evenly over the lines of its comment.
+///
+/// Translating source code to a `CodeMirror` document preserves lines: code
+/// is copied unchanged, while each line of a doc block becomes an empty line
+/// which the doc block covers. Therefore, line _n_ of the source file is line
+/// _n_ of the `doc`. Line _k_ of a doc block's contents is the _k_th line of
+/// the doc block in the source file, after removing the indent, delimiter,
+/// and the space following the delimiter.
+// ## Imports
+//
+// ### Third-party
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::{CodeMirror, CodeMirrorDocBlock};
+
+// ## Data structures
+/// A location in a source file.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct SourcePosition {
+    /// The line, where 0 is the first line of the file.
+    pub line: u32,
+    /// The column in this line, measured in characters, where 0 is before the
+    /// first character of the line.
+    pub column: u32,
+}
+
+/// A location in a `CodeMirror` document.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub enum CodeMirrorPosition {
+    /// A character offset into the `doc`.
+    Code(usize),
+    /// A location inside a doc block.
+    DocBlock {
+        /// The index of this doc block in `doc_blocks`.
+        index: usize,
+        /// The line in this doc block's contents.
+        line: u32,
+        /// The column in this line, measured in characters.
+        column: u32,
+    },
+}
+
+/// A selection. When the `anchor` and `head` are identical, this is a cursor.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[schemars(rename = "{Position}Selection")]
+pub struct Selection<Position> {
+    /// The end of the selection which doesn't move when extending the
+    /// selection.
+    pub anchor: Position,
+    /// The end of the selection which contains the cursor.
+    pub head: Position,
+}
+
+/// The cursor or selection in a file, in the coordinates of the IDE or the
+/// Client.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub enum CursorPosition {
+    /// A selection in the source code, used by the IDE.
+    Source(Selection<SourcePosition>),
+    /// A selection in a `CodeMirror` document, used by the Client.
+    CodeMirror(Selection<CodeMirrorPosition>),
+}
+
+/// The scroll position of a file, in the coordinates of the IDE or the
+/// Client.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub enum ScrollPosition {
+    /// The source line at the top of the IDE's view.
+    Source(LineScrollPosition),
+    /// The first line of the line of code or the doc block at the top of the
+    /// Client's view.
+    CodeMirror(LineScrollPosition),
+}
+
+/// A line at the top of a view.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct LineScrollPosition {
+    /// The line, where 0 is the first line of the file.
+    pub line: u32,
+    /// The fraction of this line (in the Client, of this line of code or doc
+    /// block) scrolled above the top of the view, from 0 to 1.
+    pub offset: f32,
+}
+
+impl<Position> Selection<Position> {
+    fn map<Mapped>(self, f: impl Fn(Position) -> Mapped) -> Selection<Mapped> {
+        Selection {
+            anchor: f(self.anchor),
+            head: f(self.head),
+        }
+    }
+}
+
+impl CursorPosition {
+    /// Translate this position to the coordinates of `code_mirror`, the
+    /// translation of `source`.
+    pub fn to_code_mirror(self, source: &str, code_mirror: &CodeMirror) -> Self {
+        match self {
+            CursorPosition::Source(selection) => {
+                let line_map = LineMap::new(source, code_mirror);
+                CursorPosition::CodeMirror(
+                    selection.map(|position| line_map.to_code_mirror(position)),
+                )
+            }
+            CursorPosition::CodeMirror(_) => self,
+        }
+    }
+
+    /// Translate this position to the coordinates of `source`, the
+    /// translation of `code_mirror`.
+    pub fn to_source(self, source: &str, code_mirror: &CodeMirror) -> Self {
+        match self {
+            CursorPosition::CodeMirror(selection) => {
+                let line_map = LineMap::new(source, code_mirror);
+                CursorPosition::Source(selection.map(|position| line_map.to_source(position)))
+            }
+            CursorPosition::Source(_) => self,
+        }
+    }
+}
+
+impl ScrollPosition {
+    /// Translate this position to the coordinates of `code_mirror`, the
+    /// translation of `source`.
+    pub fn to_code_mirror(self, source: &str, code_mirror: &CodeMirror) -> Self {
+        match self {
+            ScrollPosition::Source(LineScrollPosition { line, offset }) => {
+                let line_map = LineMap::new(source, code_mirror);
+                ScrollPosition::CodeMirror(match line_map.doc_block_containing(line as usize) {
+                    // Spread the doc block's height evenly over its lines.
+                    Some((_, first_line, lines)) => LineScrollPosition {
+                        line: first_line as u32,
+                        offset: ((line as usize - first_line) as f32 + offset) / lines as f32,
+                    },
+                    None => LineScrollPosition { line, offset },
+                })
+            }
+            ScrollPosition::CodeMirror(_) => self,
+        }
+    }
+
+    /// Translate this position to the coordinates of `source`, the
+    /// translation of `code_mirror`.
+    pub fn to_source(self, source: &str, code_mirror: &CodeMirror) -> Self {
+        match self {
+            ScrollPosition::CodeMirror(LineScrollPosition { line, offset }) => {
+                let line_map = LineMap::new(source, code_mirror);
+                ScrollPosition::Source(match line_map.doc_block_containing(line as usize) {
+                    Some((_, first_line, lines)) => {
+                        let lines_offset = offset.clamp(0.0, 1.0) * lines as f32;
+                        let line_index = (lines_offset as usize).min(lines - 1);
+                        LineScrollPosition {
+                            line: (first_line + line_index) as u32,
+                            offset: lines_offset - line_index as f32,
+                        }
+                    }
+                    None => LineScrollPosition { line, offset },
+                })
+            }
+            ScrollPosition::Source(_) => self,
+        }
+    }
+}
+
+// ## Mapping
+//
+// The lines of a source file and the lines of its `CodeMirror` document.
+struct LineMap<'a> {
+    source_lines: Vec<&'a str>,
+    // The character offset in the `doc` of the start of each line, followed by
+    // the length of the `doc`.
+    doc_line_starts: Vec<usize>,
+    doc_blocks: &'a [CodeMirrorDocBlock],
+    // The first line and the number of lines of each doc block.
+    doc_block_lines: Vec<(usize, usize)>,
+}
+
+impl<'a> LineMap<'a> {
+    fn new(source: &'a str, code_mirror: &'a CodeMirror) -> Self {
+        let mut doc_line_starts = vec![0];
+        let mut doc_len = 0;
+        for c in code_mirror.doc.chars() {
+            doc_len += 1;
+            if c == '\n' {
+                doc_line_starts.push(doc_len);
+            }
+        }
+        doc_line_starts.push(doc_len);
+        let line_of = |offset: usize| {
+            doc_line_starts[..doc_line_starts.len() - 1].partition_point(|start| *start <= offset)
+                - 1
+        };
+        let doc_block_lines = code_mirror
+            .doc_blocks
+            .iter()
+            .map(|doc_block| {
+                // A doc block covers one newline per line, from its `from` up
+                // to and including its `to`. A doc block at the end of the
+                // document may have no lines.
+                let lines = if doc_block.0 < doc_len {
+                    doc_block.1 - doc_block.0 + 1
+                } else {
+                    0
+                };
+                (line_of(doc_block.0), lines)
+            })
+            .collect();
+        LineMap {
+            source_lines: source.split('\n').collect(),
+            doc_line_starts,
+            doc_blocks: &code_mirror.doc_blocks,
+            doc_block_lines,
+        }
+    }
+
+    // Return the index, first line, and number of lines of the doc block
+    // containing `line`, if there is one.
+    fn doc_block_containing(&self, line: usize) -> Option<(usize, usize, usize)> {
+        self.doc_block_lines
+            .iter()
+            .enumerate()
+            .find(|(_, (first_line, lines))| (*first_line..first_line + lines).contains(&line))
+            .map(|(index, (first_line, lines))| (index, *first_line, *lines))
+    }
+
+    fn to_code_mirror(&self, position: SourcePosition) -> CodeMirrorPosition {
+        let line = position.line as usize;
+        // Look for a doc block containing this line.
+        if let Some((index, first_line, _)) = self.doc_block_containing(line) {
+            let prefix_len = self.doc_block_prefix_len(index, line);
+            return CodeMirrorPosition::DocBlock {
+                index,
+                line: (line - first_line) as u32,
+                column: position.column.saturating_sub(prefix_len as u32),
+            };
+        }
+        // Otherwise, this is code.
+        let doc_len = *self.doc_line_starts.last().unwrap();
+        if line + 1 >= self.doc_line_starts.len() {
+            return CodeMirrorPosition::Code(doc_len);
+        }
+        let line_start = self.doc_line_starts[line];
+        // Exclude the newline ending this line, if there is one.
+        let line_end = if line + 2 < self.doc_line_starts.len() {
+            self.doc_line_starts[line + 1] - 1
+        } else {
+            doc_len
+        };
+        CodeMirrorPosition::Code((line_start + position.column as usize).min(line_end))
+    }
+
+    fn to_source(&self, position: CodeMirrorPosition) -> SourcePosition {
+        match position {
+            CodeMirrorPosition::Code(offset) => {
+                let doc_len = *self.doc_line_starts.last().unwrap();
+                let offset = offset.min(doc_len);
+                let line = self.doc_line_starts[..self.doc_line_starts.len() - 1]
+                    .partition_point(|start| *start <= offset)
+                    - 1;
+                SourcePosition {
+                    line: line as u32,
+                    column: (offset - self.doc_line_starts[line]) as u32,
+                }
+            }
+            CodeMirrorPosition::DocBlock {
+                index,
+                line,
+                column,
+            } => {
+                let Some((first_line, lines)) = self.doc_block_lines.get(index) else {
+                    // This doc block doesn't exist; use the end of the file.
+                    return SourcePosition {
+                        line: self.source_lines.len().saturating_sub(1) as u32,
+                        column: self
+                            .source_lines
+                            .last()
+                            .map_or(0, |line| line.chars().count())
+                            as u32,
+                    };
+                };
+                let source_line = first_line + (line as usize).min(lines.saturating_sub(1));
+                let line_len = self
+                    .source_lines
+                    .get(source_line)
+                    .map_or(0, |line| line.chars().count());
+                SourcePosition {
+                    line: source_line as u32,
+                    column: (self.doc_block_prefix_len(index, source_line) + column as usize)
+                        .min(line_len) as u32,
+                }
+            }
+        }
+    }
+
+    // Return the number of characters on this source `line` of the doc block
+    // at `index` which precede its contents: the indent, the delimiter, then a
+    // space.
+    fn doc_block_prefix_len(&self, index: usize, line: usize) -> usize {
+        let Some(source_line) = self.source_lines.get(line) else {
+            return 0;
+        };
+        let (_, _, indent, delimiter, _, _) = &self.doc_blocks[index];
+        let rest = source_line
+            .strip_prefix(indent.as_str())
+            .unwrap_or(source_line);
+        let rest = match rest.strip_prefix(delimiter.as_str()) {
+            Some(rest) => rest,
+            // Lines after the first line of a block comment replace the
+            // delimiter with spaces.
+            None => {
+                let spaces = rest
+                    .chars()
+                    .take(delimiter.chars().count())
+                    .take_while(|c| *c == ' ')
+                    .count();
+                &rest[spaces..]
+            }
+        };
+        let rest = rest.strip_prefix(' ').unwrap_or(rest);
+        source_line.chars().count() - rest.chars().count()
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{
+        CodeMirrorPosition, CursorPosition, LineScrollPosition, ScrollPosition, Selection,
+        SourcePosition,
+    };
+    use crate::cast;
+    use crate::processing::{source_to_codechat_for_web, TranslationResults};
+
+    fn source_position(line: u32, column: u32) -> SourcePosition {
+        SourcePosition { line, column }
+    }
+
+    fn cursor(position: SourcePosition) -> CursorPosition {
+        CursorPosition::Source(Selection {
+            anchor: position,
+            head: position,
+        })
+    }
+
+    fn code_mirror_cursor(position: CodeMirrorPosition) -> CursorPosition {
+        CursorPosition::CodeMirror(Selection {
+            anchor: position,
+            head: position,
+        })
+    }
+
+    #[test]
+    fn test_cursor_position() {
+        let source = "a = 1\n// Doc\n// more doc\nb = 2\n/* Block\n   comment */\nc";
+        let code_mirror = cast!(
+            source_to_codechat_for_web(source, "cpp", false, false),
+            TranslationResults::CodeChat
+        )
+        .source;
+        assert_eq!(code_mirror.doc, "a = 1\n\n\nb = 2\n\n\nc");
+
+        for (source_position, code_mirror_position) in [
+            // Code.
+            (source_position(0, 3), CodeMirrorPosition::Code(3)),
+            (source_position(3, 5), CodeMirrorPosition::Code(13)),
+            (source_position(6, 0), CodeMirrorPosition::Code(16)),
+            // Inline comments.
+            (
+                source_position(1, 5),
+                CodeMirrorPosition::DocBlock {
+                    index: 0,
+                    line: 0,
+                    column: 2,
+                },
+            ),
+            (
+                source_position(2, 3),
+                CodeMirrorPosition::DocBlock {
+                    index: 0,
+                    line: 1,
+                    column: 0,
+                },
+            ),
+            // Block comments.
+            (
+                source_position(4, 5),
+                CodeMirrorPosition::DocBlock {
+                    index: 1,
+                    line: 0,
+                    column: 2,
+                },
+            ),
+            (
+                source_position(5, 3),
+                CodeMirrorPosition::DocBlock {
+                    index: 1,
+                    line: 1,
+                    column: 0,
+                },
+            ),
+        ] {
+            assert_eq!(
+                cursor(source_position).to_code_mirror(source, &code_mirror),
+                code_mirror_cursor(code_mirror_position)
+            );
+            assert_eq!(
+                code_mirror_cursor(code_mirror_position).to_source(source, &code_mirror),
+                cursor(source_position)
+            );
+        }
+
+        // Positions in the delimiter move to the start of the contents.
+        assert_eq!(
+            cursor(source_position(1, 0)).to_code_mirror(source, &code_mirror),
+            code_mirror_cursor(CodeMirrorPosition::DocBlock {
+                index: 0,
+                line: 0,
+                column: 0
+            })
+        );
+        // Positions past the end of a line or of the file move to the end.
+        assert_eq!(
+            cursor(source_position(0, 10)).to_code_mirror(source, &code_mirror),
+            code_mirror_cursor(CodeMirrorPosition::Code(5))
+        );
+        assert_eq!(
+            cursor(source_position(10, 0)).to_code_mirror(source, &code_mirror),
+            code_mirror_cursor(CodeMirrorPosition::Code(17))
+        );
+        assert_eq!(
+            code_mirror_cursor(CodeMirrorPosition::DocBlock {
+                index: 0,
+                line: 5,
+                column: 20
+            })
+            .to_source(source, &code_mirror),
+            cursor(source_position(2, 11))
+        );
+
+        // Selections map both ends.
+        assert_eq!(
+            CursorPosition::Source(Selection {
+                anchor: source_position(0, 0),
+                head: source_position(1, 3),
+            })
+            .to_code_mirror(source, &code_mirror),
+            CursorPosition::CodeMirror(Selection {
+                anchor: CodeMirrorPosition::Code(0),
+                head: CodeMirrorPosition::DocBlock {
+                    index: 0,
+                    line: 0,
+                    column: 0
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_scroll_position() {
+        let source = "a = 1\n// Doc\n// more doc\n// and more\nb = 2\n";
+        let code_mirror = cast!(
+            source_to_codechat_for_web(source, "cpp", false, false),
+            TranslationResults::CodeChat
+        )
+        .source;
+        let scroll = |line, offset| LineScrollPosition { line, offset };
+
+        for (source_scroll, code_mirror_scroll) in [
+            // Code maps unchanged.
+            (scroll(0, 0.5), scroll(0, 0.5)),
+            (scroll(4, 0.25), scroll(4, 0.25)),
+            // Each line of the doc block maps to a third of its height.
+            (scroll(1, 0.0), scroll(1, 0.0)),
+            (scroll(2, 0.0), scroll(1, 1.0 / 3.0)),
+            (scroll(3, 0.5), scroll(1, 2.5 / 3.0)),
+        ] {
+            assert_eq!(
+                ScrollPosition::Source(source_scroll).to_code_mirror(source, &code_mirror),
+                ScrollPosition::CodeMirror(code_mirror_scroll)
+            );
+            let ScrollPosition::Source(round_trip) =
+                ScrollPosition::CodeMirror(code_mirror_scroll).to_source(source, &code_mirror)
+            else {
+                panic!("Expected a source scroll position.");
+            };
+            assert_eq!(round_trip.line, source_scroll.line);
+            assert!((round_trip.offset - source_scroll.offset).abs() < 1e-5);
+        }
+
+        // The bottom of a doc block is the bottom of its last line.
+        assert_eq!(
+            ScrollPosition::CodeMirror(scroll(1, 1.0)).to_source(source, &code_mirror),
+            ScrollPosition::Source(scroll(3, 1.0))
+        );
+    }
+}
@@ -86,8 +86,8 @@ macro_rules! function_name {
 #[macro_export]
 macro_rules! prep_test_dir {
     () => {{
-        use crate::function_name;
-        use crate::test_utils::_prep_test_dir;
+        use $crate::function_name;
+        use $crate::test_utils::_prep_test_dir;
         _prep_test_dir(function_name!())
     }};
 }
@@ -179,7 +179,7 @@ pub fn check_logger_errors(num_errors: usize) {
                 "Error(s) in logs: saw {}, expected {num_errors}.",
                 error_logs.len()
             );
-            assert!(false);
+            panic!();
         }
     });
 }
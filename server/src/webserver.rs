@@ -16,14 +16,17 @@
 ///
 /// # `webserver.rs` -- Serve CodeChat Editor Client webpages
 mod filewatcher;
-mod vscode;
+mod ide;
+mod schema;
 
 /// ## Imports
 ///
 /// ### Standard library
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{hash_map::RandomState, BTreeMap, HashMap, HashSet, VecDeque},
     env, fs,
+    hash::{BuildHasher, Hasher},
+    io::Write,
     path::{Path, PathBuf},
     str::FromStr,
     sync::{Arc, Mutex},
@@ -33,41 +36,54 @@ use std::{
 // ### Third-party
 use actix_files;
 use actix_web::{
-    dev::{ServiceFactory, ServiceRequest},
+    dev::{ServerHandle, ServiceFactory, ServiceRequest},
     error::Error,
-    http::header::ContentType,
-    web, App, HttpRequest, HttpResponse, HttpServer,
+    get,
+    http::header::{self, ContentType},
+    post, web, App, HttpRequest, HttpResponse, HttpServer,
 };
 use actix_ws::AggregatedMessage;
 use bytes::Bytes;
 use dunce::simplified;
 use futures_util::StreamExt;
 use lazy_static::lazy_static;
-use log::{error, info, warn};
-use log4rs;
+use log::{error, info, warn, LevelFilter};
+use log4rs::{
+    self,
+    append::console::ConsoleAppender,
+    config::{Appender, Config, Root},
+    encode::pattern::PatternEncoder,
+};
 use mime::Mime;
 use mime_guess;
-use path_slash::PathBufExt;
+use path_slash::{PathBufExt, PathExt};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json;
 use tokio::{
+    fs::File,
+    io::AsyncReadExt,
     select,
+    sync::broadcast,
     sync::mpsc::{Receiver, Sender},
     sync::oneshot,
     task::JoinHandle,
     time::sleep,
 };
 use url::Url;
-use vscode::{serve_vscode_fs, vscode_client_websocket, vscode_ide_websocket};
 
 // ### Local
+use crate::link_checker::check_project;
 use crate::processing::{
-    source_to_codechat_for_web_string, CodeChatForWeb, TranslationResultsString,
+    merge3, source_to_codechat_for_web_string, CodeChatForWeb, CodeChatForWebUpdate, CodeMirror,
+    CursorPosition, ScrollPosition, TranslationResultsString,
 };
 use filewatcher::{
     filewatcher_browser_endpoint, filewatcher_client_endpoint, filewatcher_root_fs_redirect,
     filewatcher_websocket,
 };
+use ide::{ide_client_framework, ide_client_websocket, ide_websocket, serve_ide_fs};
+pub use schema::protocol_schema;
 
 /// ## Data structures
 ///
@@ -113,7 +129,7 @@ enum SimpleHttpResponse {
 
 /// Define the data structure used to pass data between the CodeChat Editor
 /// Client, the IDE, and the CodeChat Editor Server.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 struct EditorMessage {
     /// A value unique to this message; it's used to report results
     /// (success/failure) back to the sender.
@@ -124,7 +140,7 @@ struct EditorMessage {
 
 /// Define the data structure used to pass data between the CodeChat Editor
 /// Client, the CodeChat Editor IDE extension, and the CodeChat Editor Server.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 enum EditorMessageContents {
     // #### These messages may be sent by either the IDE or the Client.
     /// This sends an update; any missing fields are unchanged. Valid
@@ -135,17 +151,20 @@ enum EditorMessageContents {
 
     // #### These messages may only be sent by the IDE.
     /// This is the first message sent when the IDE starts up. It may only be
-    /// sent at startup. Valid destinations: Server.
-    Opened(IdeType),
+    /// sent at startup. The Server responds with a `Result` containing its
+    /// `ProtocolInfo`, or with an error if it doesn't support the IDE's
+    /// protocol version. Valid destinations: Server.
+    Opened(IdeType, ProtocolInfo),
     /// Request the Client to save any unsaved data then close. Valid
     /// destinations: Client.
     RequestClose,
 
     // #### These messages may only be sent by the Server or the IDE
     /// Ask the IDE if the provided file is loaded. If so, the IDE should
-    /// respond by sending a `LoadFile` with the requested file. If not, the
-    /// returned `Result` should indicate the error "not loaded". Valid
-    /// destinations: IDE.
+    /// respond with a `Result` containing the file's (possibly unsaved)
+    /// contents. If not, the returned `Result` should indicate the error "not
+    /// loaded"; the Server then reads the file from disk. Valid destinations:
+    /// IDE.
     LoadFile(PathBuf),
 
     // #### These messages may only be sent by the Server.
@@ -153,6 +172,13 @@ enum EditorMessageContents {
     /// HTML for the CodeChat Editor Client to display in its built-in browser.
     /// Valid destinations: IDE.
     ClientHtml(String),
+    /// Ask the Client to reload the provided URLs, since the files they refer
+    /// to changed on disk: the TOC shown in its sidebar, or images shown in
+    /// the current file. Valid destinations: Client.
+    Refresh(Vec<String>),
+    /// Report the other Clients viewing the current file. Valid destinations:
+    /// Client.
+    Presence(Vec<ClientPresence>),
     /// Sent when the IDE or Client websocket was closed, indicating that the
     /// unclosed websocket should be closed as well. Therefore, this message
     /// will never be received by the IDE or Client. Valid destinations: Server.
@@ -161,10 +187,21 @@ enum EditorMessageContents {
     // #### This message may be sent by anyone.
     /// Sent as a response to any of the above messages, reporting
     /// success/error. None indicates success, while Some contains an error.
-    Result(Option<String>, Option<LoadFileResultContents>),
+    /// The second field contains the results of an `Opened` or a `LoadFile`
+    /// message.
+    Result(Option<String>, Option<ResultContents>),
+}
+
+/// The results reported by a `Result` message.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+enum ResultContents {
+    /// The Server's response to an `Opened` message.
+    Opened(ProtocolInfo),
+    /// The IDE's response to a `LoadFile` message.
+    LoadFile(LoadFileResultContents),
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 struct LoadFileResultContents {
     /// The path to the file that was queried.
     file_path: PathBuf,
@@ -172,30 +209,325 @@ struct LoadFileResultContents {
     contents: String,
 }
 
+/// A Client viewing a file; see `FileSession`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct ClientPresence {
+    /// The ID of this Client's connection.
+    connection_id: String,
+    /// The name this Client shows to others viewing the same file.
+    name: String,
+}
+
 /// Specify the type of IDE that this client represents.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 enum IdeType {
     /// True if the CodeChat Editor will be hosted inside VSCode; false means it
     /// should be hosted in an external browser.
     VSCode(bool),
-    /// Another option -- temporary -- to allow for future expansion.
-    DeleteMe,
+    /// Any other IDE or editor, such as Neovim, Emacs, Sublime Text, or a
+    /// JetBrains IDE.
+    Other {
+        /// The name of this IDE, used when reporting on the connection.
+        name: String,
+        /// True if the CodeChat Editor will be hosted in this IDE's built-in
+        /// browser, using the HTML provided in a `ClientHtml` message; false
+        /// means it should be hosted in an external browser.
+        is_self_hosted: bool,
+    },
+}
+
+impl IdeType {
+    /// Return the name of this IDE.
+    fn name(&self) -> &str {
+        match self {
+            IdeType::VSCode(_) => "VSCode",
+            IdeType::Other { name, .. } => name,
+        }
+    }
+
+    /// Return true if the CodeChat Editor will be hosted inside this IDE.
+    fn is_self_hosted(&self) -> bool {
+        match self {
+            IdeType::VSCode(is_self_hosted) => *is_self_hosted,
+            IdeType::Other { is_self_hosted, .. } => *is_self_hosted,
+        }
+    }
+}
+
+/// The protocol spoken by the IDE or the Server, exchanged in the `Opened`
+/// message and the Server's `Result` in response.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct ProtocolInfo {
+    /// The version of the protocol; see `PROTOCOL_VERSION`.
+    protocol_version: u32,
+    /// The optional parts of the protocol which the sender supports.
+    capabilities: Capabilities,
+}
+
+impl ProtocolInfo {
+    /// The protocol spoken by this Server, which supports every capability.
+    fn server() -> Self {
+        ProtocolInfo {
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: Capabilities {
+                diffs: true,
+                cursor_position: true,
+                scroll_position: true,
+                load_file: true,
+            },
+        }
+    }
+}
+
+/// Optional parts of the protocol. The Server doesn't send an IDE messages or
+/// fields which it doesn't support.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Capabilities {
+    /// True if the sender applies `Update`s whose contents are a
+    /// `CodeChatForWebUpdate::Diff`; otherwise, it receives only snapshots.
+    diffs: bool,
+    /// True if the sender provides and receives an `Update`'s
+    /// `cursor_position`.
+    cursor_position: bool,
+    /// True if the sender provides and receives an `Update`'s
+    /// `scroll_position`.
+    scroll_position: bool,
+    /// True if the sender responds to `LoadFile`; otherwise, the Server reads
+    /// files from disk.
+    load_file: bool,
 }
 
 /// Contents of the `Update` message.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 struct UpdateMessageContents {
-    /// The contents of this file. TODO: this should be just a string if sent by
-    /// the IDE.
-    contents: Option<CodeChatForWeb>,
-    /// The current cursor position in the file, where 0 = before the first
-    /// character in the file and contents.length() = after the last character
-    /// in the file. TODO: Selections are not yet supported. TODO: how to get a
-    /// cursor location from within a doc block in the Client?
-    cursor_position: Option<u32>,
-    /// The normalized vertical scroll position in the file, where 0 = top and 1
-    /// = bottom.
-    scroll_position: Option<f32>,
+    /// The path to the file this update applies to. If omitted, the update
+    /// applies to the current file.
+    path: Option<PathBuf>,
+    /// The contents of this file: either a snapshot of the entire contents,
+    /// or the changes to the previous version of these contents. When sent to
+    /// or by the IDE, the `doc` field contains the plain text of the source
+    /// file and `doc_blocks` is empty. The recipient of a diff which doesn't
+    /// apply to its current version responds with an error; the sender then
+    /// sends a snapshot.
+    contents: Option<CodeChatForWebUpdate>,
+    /// The version of this file's contents which the sender edited to produce
+    /// this update, or `None` to replace the contents regardless of their
+    /// version. A diff applies only to this version. The Server merges a
+    /// snapshot based on an earlier version with the edits made since then,
+    /// or responds with an error beginning with `Conflict:` if they conflict.
+    base_version: Option<f64>,
+    /// The version of this file's contents after applying this update; see
+    /// `new_version`.
+    version: f64,
+    /// The current cursor or selection in the file. The IDE sends and
+    /// receives positions in the source code; the Client, positions in its
+    /// `CodeMirror` document. The Server translates between them.
+    cursor_position: Option<CursorPosition>,
+    /// The line at the top of the view. Like the cursor position, the Server
+    /// translates this between the IDE's and the Client's coordinates.
+    scroll_position: Option<ScrollPosition>,
+}
+
+/// The contents of the current file which the Server shares with the Client
+/// or the IDE, at the current version of the file. These allow sending and
+/// receiving updates as diffs.
+#[derive(Debug, Default)]
+struct SharedContents {
+    /// The contents which the Server last sent, if the recipient still
+    /// displays them.
+    sent: Option<CodeChatForWeb>,
+    /// The contents which the Server last received, if they are the latest
+    /// contents.
+    received: Option<CodeChatForWeb>,
+}
+
+impl SharedContents {
+    /// Return the contents of an `Update` which changes the recipient's
+    /// contents at the current version to `contents`. Markdown documents,
+    /// which the Client doesn't edit using CodeMirror, are always sent as a
+    /// snapshot.
+    fn send(&mut self, contents: CodeChatForWeb) -> CodeChatForWebUpdate {
+        let base = self.sent.as_ref().filter(|sent| {
+            sent.metadata.mode == contents.metadata.mode && sent.metadata.mode != "markdown"
+        });
+        let update = CodeChatForWebUpdate::new(base, contents.clone());
+        self.sent = Some(contents);
+        self.received = None;
+        update
+    }
+
+    /// Return the contents provided by a received `Update` based on
+    /// `base_version`, where `version` is the version of the current contents.
+    fn receive(
+        &mut self,
+        version: f64,
+        base_version: Option<f64>,
+        update: CodeChatForWebUpdate,
+    ) -> Result<CodeChatForWeb, String> {
+        if matches!(update, CodeChatForWebUpdate::Diff(_)) && base_version != Some(version) {
+            return Err(format!(
+                "Out of sync: the current version isn't {base_version:?}."
+            ));
+        }
+        let contents = update.into_contents(self.received.as_ref())?;
+        self.sent = None;
+        self.received = Some(contents.clone());
+        Ok(contents)
+    }
+
+    /// After translating contents received from the Client to source code,
+    /// then translating that source code back to `contents`, record these as
+    /// the contents which the Client displays if their code and doc block
+    /// locations match the received contents. The Client's rendering of the
+    /// doc blocks may differ from these contents, but this doesn't matter:
+    /// diffs replace an entire doc block.
+    fn set_displayed(&mut self, contents: CodeChatForWeb) {
+        fn layout(code_mirror: &CodeMirror) -> Vec<(usize, usize, &String, &String)> {
+            code_mirror
+                .doc_blocks
+                .iter()
+                .map(|doc_block| (doc_block.0, doc_block.1, &doc_block.2, &doc_block.3))
+                .collect()
+        }
+        if let Some(received) = &self.received {
+            if received.source.doc == contents.source.doc
+                && layout(&received.source) == layout(&contents.source)
+            {
+                self.sent = Some(contents);
+            }
+        }
+    }
+
+    /// Return a snapshot of the contents which the Server last sent.
+    fn snapshot(&self) -> Option<CodeChatForWebUpdate> {
+        self.sent.clone().map(CodeChatForWebUpdate::Snapshot)
+    }
+}
+
+/// Recent versions of the current file's source code. When an update is based
+/// on an earlier version than the current version, these provide the common
+/// ancestor needed to merge the update with the edits made since then.
+#[derive(Debug, Default)]
+struct VersionHistory(VecDeque<(f64, String)>);
+
+impl VersionHistory {
+    /// The number of versions to keep.
+    const CAPACITY: usize = 16;
+
+    /// Record the source code of a new version.
+    fn push(&mut self, version: f64, source: String) {
+        if self.0.len() == Self::CAPACITY {
+            self.0.pop_front();
+        }
+        self.0.push_back((version, source));
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn get(&self, version: f64) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(source_version, _)| *source_version == version)
+            .map(|(_, source)| source.as_str())
+    }
+
+    /// Given the `source` code produced by an update based on `base_version`,
+    /// return this source merged with the edits made since then, where
+    /// `version` is the current version.
+    fn merge(
+        &self,
+        version: f64,
+        base_version: Option<f64>,
+        source: String,
+    ) -> Result<String, String> {
+        match base_version {
+            Some(base_version) if base_version != version => {
+                match (self.get(base_version), self.get(version)) {
+                    (Some(base), Some(current)) => merge3(base, current, &source),
+                    _ => Err(
+                        "Conflict: this file was changed, but the changes can't be merged."
+                            .to_string(),
+                    ),
+                }
+            }
+            // This update is based on the current version, or replaces it.
+            _ => Ok(source),
+        }
+    }
+}
+
+/// Return true if an update based on `base_version` is based on an earlier
+/// version than the current `version`, so that its contents must be merged
+/// with the edits made since then.
+fn is_stale(version: f64, base_version: Option<f64>) -> bool {
+    base_version.is_some_and(|base_version| base_version != version)
+}
+
+/// In FileWatcher mode, all Clients viewing the same file share a session. A
+/// single task owns the file: it watches the file, records its versions, and
+/// saves the Clients' edits to it, broadcasting each change to all the Clients
+/// in the session.
+#[derive(Debug)]
+struct FileSession {
+    /// Send a message to all Clients in this session.
+    tx: broadcast::Sender<SessionMessage>,
+    /// Send a request to the task which owns the file.
+    request_tx: Sender<FileRequest>,
+    /// The Clients in this session, by connection ID.
+    clients: BTreeMap<u32, ClientPresence>,
+}
+
+/// A change to the contents of the file shared by a `FileSession`.
+#[derive(Debug, Clone)]
+struct FileChange {
+    /// The version of these contents.
+    version: f64,
+    /// The number of versions of the file recorded, including this one. Unlike
+    /// `version`, this orders the versions.
+    revision: u64,
+    /// These contents translated for the Client, or `None` if the file is no
+    /// longer a CodeChat Editor file.
+    codechat_for_web: Option<CodeChatForWeb>,
+}
+
+/// A message broadcast to all Clients in a `FileSession`.
+#[derive(Debug, Clone)]
+enum SessionMessage {
+    /// The file's contents changed.
+    Changed(FileChange),
+    /// The file was moved to this path.
+    Moved(PathBuf),
+    /// The file was deleted or can no longer be read.
+    Closed,
+    /// These other files in the file's project changed.
+    Refresh(Vec<PathBuf>),
+    /// Watching the file produced this error.
+    Error(String),
+    /// The Clients in this session changed; this lists all of them.
+    Presence(Vec<ClientPresence>),
+}
+
+/// A request from a Client in a `FileSession` to the task which owns the file.
+#[derive(Debug)]
+enum FileRequest {
+    /// Record the file's contents on disk as its current version if they
+    /// changed, then reply with the `(version, revision)` of the current
+    /// version; see `FileChange`.
+    Load(oneshot::Sender<(f64, u64)>),
+    /// Save a Client's edits, merging them with any changes made since
+    /// `base_version`. Reply with the resulting change and true if it was
+    /// merged, or an error message.
+    Save {
+        /// The version these edits are based on; see `UpdateMessageContents`.
+        base_version: Option<f64>,
+        /// The version of these edits.
+        version: f64,
+        /// The file's contents after these edits.
+        contents: String,
+        response_tx: oneshot::Sender<Result<(FileChange, bool), String>>,
+    },
 }
 
 /// ### Data structures used by the webserver
@@ -203,6 +535,15 @@ struct UpdateMessageContents {
 /// Define the [state](https://actix.rs/docs/application/#state) available to
 /// all endpoints.
 pub struct AppState {
+    // The URL of this server, such as `http://127.0.0.1:8080`.
+    server_url: String,
+    // The directory shown when browsing the filesystem; if `None`, show the
+    // root of the filesystem.
+    root_path: Option<PathBuf>,
+    // A handle used to stop this server.
+    server_handle: Mutex<Option<ServerHandle>>,
+    // The secret which a request to stop this server must provide.
+    stop_token: String,
     // The number of the next connection ID to assign.
     connection_id: Mutex<u32>,
     // For each connection ID, store a queue tx for the HTTP server to send
@@ -211,11 +552,14 @@ pub struct AppState {
     // For each (connection ID, requested URL) store channel to send the
     // matching response to the HTTP task.
     filewatcher_client_queues: Arc<Mutex<HashMap<String, WebsocketQueues>>>,
-    // For each connection ID, store the queues for the VSCode IDE.
-    vscode_ide_queues: Arc<Mutex<HashMap<String, WebsocketQueues>>>,
-    vscode_client_queues: Arc<Mutex<HashMap<String, WebsocketQueues>>>,
+    // For each file open in FileWatcher mode, the session shared by all
+    // Clients viewing it.
+    filewatcher_sessions: Arc<Mutex<HashMap<PathBuf, FileSession>>>,
+    // For each connection ID, store the queues for the IDE and its Client.
+    ide_queues: Arc<Mutex<HashMap<String, WebsocketQueues>>>,
+    ide_client_queues: Arc<Mutex<HashMap<String, WebsocketQueues>>>,
     // Connection IDs that are currently in use.
-    vscode_connection_id: Arc<Mutex<HashSet<String>>>,
+    ide_connection_id: Arc<Mutex<HashSet<String>>>,
 }
 
 // ## Macros
@@ -248,10 +592,20 @@ macro_rules! queue_send {
 }
 
 /// ## Globals
-/// The IP address on which the server listens for incoming connections.
-const IP_ADDRESS: &str = "127.0.0.1";
-/// The port on which the server listens for incoming connections.
-const IP_PORT: u16 = 8080;
+/// The default IP address on which the server listens for incoming
+/// connections.
+pub const IP_ADDRESS: &str = "127.0.0.1";
+/// The default port on which the server listens for incoming connections.
+pub const IP_PORT: u16 = 8080;
+
+/// The header which carries the token required to stop the server.
+const STOP_TOKEN_HEADER: &str = "X-CodeChat-Stop-Token";
+
+/// The version of the protocol defined by `EditorMessage`. Increment this
+/// after any change to these messages which an IDE written for the previous
+/// version can't handle, then regenerate the published schema using
+/// `codechat-editor-server schema`.
+pub const PROTOCOL_VERSION: u32 = 1;
 
 // The timeout for a reply from a websocket. Use a short timeout to speed up
 // unit tests.
@@ -330,32 +684,58 @@ fn get_client_framework(
     // The URL prefix for a websocket connection to the Server.
     ide_path: &str,
     // The ID of the websocket connection.
-    connection_id: u32, // This returns a response (the Client, or an error).
+    connection_id: &str,
+    // This returns a response (the Client, or an error).
 ) -> HttpResponse {
     // Add in content when testing.
     let is_test_mode = get_test_mode(req);
 
+    match client_framework_html(ide_path, connection_id, is_test_mode, None) {
+        Ok(html) => HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(html),
+        Err(err) => html_not_found(&err),
+    }
+}
+
+// Return the HTML for an instance of the Client.
+fn client_framework_html(
+    // The URL prefix for a websocket connection to the Server.
+    ide_path: &str,
+    // The ID of the websocket connection.
+    connection_id: &str,
+    // True to include test content.
+    is_test_mode: bool,
+    // The URL of the Server, for pages not served by the Server (such as a
+    // page hosted in an IDE's built-in browser). All relative URLs, including
+    // the websocket connection, are resolved against it.
+    base_url: Option<&str>,
+) -> Result<String, String> {
     // Provide the pathname to the websocket connection. Quote the string using
     // JSON to handle any necessary escapes.
     let ws_url = match serde_json::to_string(&format!("{ide_path}/{connection_id}")) {
         Ok(v) => v,
         Err(err) => {
-            return html_not_found(&format!(
+            return Err(format!(
                 "Unable to encode websocket URL for {ide_path}, id {connection_id}: {err}"
             ))
         }
     };
+    let base = match base_url {
+        Some(base_url) => format!(r#"<base href="{}">"#, escape_html(base_url)),
+        None => "".to_string(),
+    };
 
     // Build and return the webpage.
-    HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
+    Ok(format!(
+        r#"<!DOCTYPE html>
 <html lang="en">
     <head>
         <meta charset="UTF-8">
         <meta name="viewport" content="width=device-width, initial-scale=1">
         <title>The CodeChat Editor</title>
+        {base}
+        <link rel="stylesheet" href="/static/css/CodeChatEditor.css">
         <script type="module">
             {}
             page_init({ws_url}, {is_test_mode})
@@ -379,8 +759,9 @@ fn get_client_framework(
         </iframe>
     </body>
 </html>
-"#, *CODECHAT_EDITOR_FRAMEWORK_JS
-        ))
+"#,
+        *CODECHAT_EDITOR_FRAMEWORK_JS
+    ))
 }
 
 // ### Serve file
@@ -409,9 +790,8 @@ pub async fn filesystem_endpoint(
         web::Query<HashMap<String, String>>,
         actix_web::error::QueryPayloadError,
     > = web::Query::<HashMap<String, String>>::from_query(req.query_string());
-    let is_toc = query_params.map_or(false, |query| {
-        query.get("mode").map_or(false, |mode| mode == "toc")
-    });
+    let is_toc =
+        query_params.is_ok_and(|query| query.get("mode").is_some_and(|mode| mode == "toc"));
     let is_test_mode = get_test_mode(req);
 
     // Create a one-shot channel used by the processing task to provide a
@@ -483,7 +863,7 @@ async fn serve_file(
 
     // See if this is a CodeChat Editor file.
     let (translation_results_string, path_to_toc) = if is_current_file || is_toc {
-        source_to_codechat_for_web_string(file_contents, file_path, is_toc)
+        source_to_codechat_for_web_string(file_contents, file_path, is_toc, None)
     } else {
         // If this isn't the current file, then don't parse it.
         (TranslationResultsString::Unknown, None)
@@ -609,6 +989,40 @@ async fn serve_file(
     )
 }
 
+// Read the requested file from the filesystem, then serve it. If the file's
+// contents can't be read as UTF-8, serve it as a binary file; assume this is an
+// image/video/etc.
+async fn serve_file_from_disk(
+    http_request: &ProcessingTaskHttpRequest,
+    // The path to the file currently being edited.
+    current_filepath: &Path,
+) -> (SimpleHttpResponse, Option<CodeChatForWeb>) {
+    let file_path = &http_request.request_path;
+    let mut file_contents = String::new();
+    match File::open(file_path).await {
+        Err(err) => (
+            SimpleHttpResponse::Err(format!("<p>Error opening file {file_path:?}: {err}.")),
+            None,
+        ),
+        Ok(mut fc) => match fc.read_to_string(&mut file_contents).await {
+            Err(_) => (SimpleHttpResponse::Bin(file_path.clone()), None),
+            Ok(_) => {
+                let is_current = file_path
+                    .canonicalize()
+                    .is_ok_and(|p| p == current_filepath);
+                serve_file(
+                    file_path,
+                    &file_contents,
+                    http_request.is_toc,
+                    is_current,
+                    http_request.is_test_mode,
+                )
+                .await
+            }
+        },
+    }
+}
+
 /// ## Websockets
 ///
 /// Each CodeChat Editor IDE instance pairs with a CodeChat Editor Client
@@ -646,9 +1060,9 @@ async fn client_websocket(
             }
         };
 
-        // Assign each message unique id.
-        let mut id: u32 = 0;
-        // Keep track of pending messages.
+        // Keep track of pending messages. The sender (a processing task) is
+        // responsible for assigning each message a unique id, so that it can
+        // correlate the matching `Result`.
         let mut pending_messages: HashMap<u32, JoinHandle<()>> = HashMap::new();
 
         // Shutdown may occur in a controlled process or an immediate websocket
@@ -792,7 +1206,7 @@ async fn client_websocket(
                 }
 
                 // Forward a message from the processing task to the websocket.
-                Some(mut m) = to_websocket_rx.recv() => {
+                Some(m) = to_websocket_rx.recv() => {
                     // Pre-process this message.
                     match m.message {
                         // If it's a `Result`, no additional processing is
@@ -804,27 +1218,29 @@ async fn client_websocket(
                             is_closing = true;
                             break;
                         },
-                        // All other messages are added to the pending queue and
-                        // assigned a unique id.
+                        // All other messages are added to the pending queue.
                         _ => {
-                            // Assign the id for the message.
-                            m.id = id;
-                            id += 1;
+                            let id = m.id;
                             let timeout_tx = from_websocket_tx.clone();
                             let waiting_task = actix_rt::spawn(async move {
                                 sleep(REPLY_TIMEOUT).await;
-                                let msg = format!("Timeout: message id {} unacknowledged.", m.id);
+                                let msg = format!("Timeout: message id {id} unacknowledged.");
                                 error!("{msg}");
                                 // Since the websocket failed to send a `Result`, produce a timeout `Result` for it.
                                 'timeout: {
                                         queue_send!(timeout_tx.send(EditorMessage {
-                                        id: m.id,
+                                        id,
                                         message: EditorMessageContents::Result(Some(msg), None)
                                     }), 'timeout);
                                 }
                             });
-                            pending_messages.insert(m.id, waiting_task);
-                            info!("ID is {id}.");
+                            // If a message with this id is still pending,
+                            // replace it; its `Result` can no longer be
+                            // distinguished from the new message.
+                            if let Some(old_task) = pending_messages.insert(id, waiting_task) {
+                                warn!("Duplicate message id {id}.");
+                                old_task.abort();
+                            }
                         }
                     }
 
@@ -877,30 +1293,203 @@ async fn client_websocket(
 }
 
 // ## Webserver core
-#[actix_web::main]
-pub async fn main() -> std::io::Result<()> {
-    run_server().await
+/// Run the server until it's stopped.
+pub fn main(host: &str, port: u16, root_path: Option<PathBuf>) -> std::io::Result<()> {
+    actix_rt::System::new().block_on(run_server(host, port, root_path))
 }
 
-pub async fn run_server() -> std::io::Result<()> {
+pub async fn run_server(
+    // The host name or IP address to bind to.
+    host: &str,
+    // The port to bind to.
+    port: u16,
+    // The directory shown when browsing the filesystem; if `None`, show the
+    // root of the filesystem.
+    root_path: Option<PathBuf>,
+) -> std::io::Result<()> {
     // Pre-load the bundled files before starting the webserver.
     let _ = &*BUNDLED_FILES_MAP;
     let _ = &*CODECHAT_EDITOR_FRAMEWORK_JS;
-    let app_data = make_app_data();
-    let server = match HttpServer::new(move || configure_app(App::new(), &app_data))
-        .bind((IP_ADDRESS, IP_PORT))
+    let app_data = make_app_data(host, port, root_path);
+    let app_data_server = app_data.clone();
+    let server = match HttpServer::new(move || configure_app(App::new(), &app_data_server))
+        .bind((host, port))
     {
-        Ok(server) => server,
+        Ok(server) => server.run(),
         Err(err) => {
-            error!("Unable to bind to {IP_ADDRESS}:{IP_PORT} - {err}");
+            error!("Unable to bind to {host}:{port} - {err}");
             return Err(err);
         }
     };
-    server.run().await
+    // Provide the handle to the stop endpoint, and the token it requires to
+    // the `stop` command.
+    *app_data.server_handle.lock().unwrap() = Some(server.handle());
+    let stop_token_path = stop_token_path(port);
+    if let Err(err) = write_stop_token(&stop_token_path, &app_data.stop_token) {
+        warn!("Unable to write the stop token to {stop_token_path:?}: {err}.");
+    }
+    info!("Listening on {}.", app_data.server_url);
+    let result = server.await;
+    let _ = fs::remove_file(&stop_token_path);
+    result
+}
+
+/// Return the file which holds the token needed to stop the server on `port`.
+fn stop_token_path(port: u16) -> PathBuf {
+    // Keep the servers run by unit tests out of the user's config directory.
+    let config_dir = if cfg!(test) { None } else { dirs::config_dir() };
+    config_dir
+        .unwrap_or_else(env::temp_dir)
+        .join("codechat-editor")
+        .join(format!("server-{port}.token"))
+}
+
+/// Write the stop token to `path`, readable only by this user.
+fn write_stop_token(path: &Path, stop_token: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(stop_token.as_bytes())
+}
+
+/// Return a random token which a request to stop the server must provide.
+fn new_stop_token() -> String {
+    format!(
+        "{:016x}{:016x}",
+        RandomState::new().build_hasher().finish(),
+        RandomState::new().build_hasher().finish()
+    )
+}
+
+/// Configure logging using the provided log4rs config file. If this file
+/// doesn't exist or can't be loaded, log to the console instead.
+pub fn configure_logger(log_config: &Path) {
+    let err = if log_config.is_file() {
+        match log4rs::init_file(log_config, Default::default()) {
+            Ok(()) => return,
+            Err(err) => format!("Unable to load logging config {log_config:?}: {err}"),
+        }
+    } else {
+        format!("Logging config {log_config:?} not found")
+    };
+
+    // Use a default config matching the one in `log4rs.yml`.
+    let stdout = ConsoleAppender::builder()
+        .encoder(Box::new(PatternEncoder::new("{d} {l} {t} {L} - {m}{n}")))
+        .build();
+    match Config::builder()
+        .appender(Appender::builder().build("stdout", Box::new(stdout)))
+        .build(Root::builder().appender("stdout").build(LevelFilter::Info))
+    {
+        Ok(config) => match log4rs::init_config(config) {
+            Ok(_) => warn!("{err}; using the default logging config."),
+            Err(err) => eprintln!("Unable to configure logging: {err}"),
+        },
+        Err(err) => eprintln!("Unable to configure logging: {err}"),
+    }
+}
+
+/// Report the status of a server running at the provided address.
+pub fn server_status(host: &str, port: u16) -> Result<String, String> {
+    match minreq::get(format!("http://{host}:{port}/server/status")).send() {
+        Ok(response) if response.status_code == 200 => match response.as_str() {
+            Ok(body) => Ok(body.to_string()),
+            Err(err) => Err(format!("Unable to read the server's status: {err}.")),
+        },
+        Ok(response) => Err(format!(
+            "Unexpected response from http://{host}:{port}: {} {}.",
+            response.status_code, response.reason_phrase
+        )),
+        Err(err) => Err(format!("No server running at http://{host}:{port}: {err}.")),
+    }
 }
 
-pub fn configure_logger() {
-    log4rs::init_file("log4rs.yml", Default::default()).unwrap();
+/// Stop a server running at the provided address.
+pub fn stop_server(host: &str, port: u16) -> Result<String, String> {
+    let stop_token_path = stop_token_path(port);
+    let stop_token = fs::read_to_string(&stop_token_path).map_err(|err| {
+        format!("Unable to read the server's stop token from {stop_token_path:?}: {err}.")
+    })?;
+    match minreq::post(format!("http://{host}:{port}/server/stop"))
+        .with_header(STOP_TOKEN_HEADER, stop_token.trim())
+        .send()
+    {
+        Ok(response) if response.status_code == 200 => {
+            Ok(format!("Stopped the server at http://{host}:{port}."))
+        }
+        Ok(response) => Err(format!(
+            "Unable to stop the server at http://{host}:{port}: {} {}.",
+            response.status_code, response.reason_phrase
+        )),
+        Err(err) => Err(format!("No server running at http://{host}:{port}: {err}.")),
+    }
+}
+
+// Report that this server is running.
+#[get("/server/status")]
+async fn server_status_endpoint(app_state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body(format!(
+            "CodeChat Editor Server {} running at {}.",
+            env!("CARGO_PKG_VERSION"),
+            app_state.server_url
+        ))
+}
+
+// Stop this server. Only the `stop` command may do this: refuse requests from
+// web pages served by other sites, and requests without the stop token.
+#[post("/server/stop")]
+async fn server_stop_endpoint(req: HttpRequest, app_state: web::Data<AppState>) -> HttpResponse {
+    if req
+        .headers()
+        .get(header::ORIGIN)
+        .is_some_and(|origin| origin.as_bytes() != app_state.server_url.as_bytes())
+    {
+        return html_forbidden("Error: cross-origin requests can't stop this server.");
+    }
+    if req
+        .headers()
+        .get(STOP_TOKEN_HEADER)
+        .is_none_or(|stop_token| stop_token.as_bytes() != app_state.stop_token.as_bytes())
+    {
+        return html_forbidden("Error: invalid stop token.");
+    }
+    let Some(server_handle) = app_state.server_handle.lock().unwrap().clone() else {
+        return html_not_found("Error: this server can't be stopped.");
+    };
+    info!("Stopping per request.");
+    // A graceful stop waits for this request to finish; therefore, stop after
+    // this response is sent.
+    actix_rt::spawn(async move {
+        sleep(Duration::from_millis(100)).await;
+        server_handle.stop(true).await;
+    });
+    HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body("Stopping.")
+}
+
+// Check the links in the project containing the provided path, returning a
+// JSON report.
+#[get("/server/check/{path:.*}")]
+async fn server_check_endpoint(path: web::Path<String>) -> HttpResponse {
+    // As with the filesystem browser, the path omits the leading slash on
+    // Linux/OS X.
+    #[cfg(not(target_os = "windows"))]
+    let project_path = "/".to_string() + &path;
+    #[cfg(target_os = "windows")]
+    let project_path = path.to_string();
+    // Checking reads every file in the project; don't block the server.
+    match web::block(move || check_project(Path::new(&project_path))).await {
+        Ok(Ok(link_report)) => HttpResponse::Ok().json(link_report),
+        Ok(Err(err)) => html_not_found(&escape_html(&err)),
+        Err(err) => html_not_found(&format!("Error: {err}")),
+    }
 }
 
 // Quoting the [docs](https://actix.rs/docs/application#shared-mutable-state),
@@ -908,14 +1497,19 @@ pub fn configure_logger() {
 // closure passed to `HttpServer::new` and moved/cloned in." Putting this code
 // inside `configure_app` places it inside the closure which calls
 // `configure_app`, preventing globally shared state.
-fn make_app_data() -> web::Data<AppState> {
+fn make_app_data(host: &str, port: u16, root_path: Option<PathBuf>) -> web::Data<AppState> {
     web::Data::new(AppState {
+        server_url: format!("http://{host}:{port}"),
+        root_path,
+        server_handle: Mutex::new(None),
+        stop_token: new_stop_token(),
         connection_id: Mutex::new(0),
         processing_task_queue_tx: Arc::new(Mutex::new(HashMap::new())),
         filewatcher_client_queues: Arc::new(Mutex::new(HashMap::new())),
-        vscode_ide_queues: Arc::new(Mutex::new(HashMap::new())),
-        vscode_client_queues: Arc::new(Mutex::new(HashMap::new())),
-        vscode_connection_id: Arc::new(Mutex::new(HashSet::new())),
+        filewatcher_sessions: Arc::new(Mutex::new(HashMap::new())),
+        ide_queues: Arc::new(Mutex::new(HashMap::new())),
+        ide_client_queues: Arc::new(Mutex::new(HashMap::new())),
+        ide_connection_id: Arc::new(Mutex::new(HashSet::new())),
     })
 }
 
@@ -939,9 +1533,13 @@ where
         .service(filewatcher_browser_endpoint)
         .service(filewatcher_client_endpoint)
         .service(filewatcher_websocket)
-        .service(serve_vscode_fs)
-        .service(vscode_ide_websocket)
-        .service(vscode_client_websocket)
+        .service(serve_ide_fs)
+        .service(ide_client_framework)
+        .service(ide_websocket)
+        .service(ide_client_websocket)
+        .service(server_status_endpoint)
+        .service(server_stop_endpoint)
+        .service(server_check_endpoint)
         // Reroute to the filesystem for typical user-requested URLs.
         .route("/", web::get().to(filewatcher_root_fs_redirect))
         .route("/fw/fsb", web::get().to(filewatcher_root_fs_redirect))
@@ -961,38 +1559,62 @@ async fn send_response(client_tx: &Sender<EditorMessage>, id: u32, result: Optio
     }
 }
 
-fn url_to_path(url_string: String) -> Result<PathBuf, String> {
-    // Convert this URL back to a file path.
-    match urlencoding::decode(&url_string) {
-        Err(err) => Err(format!("Error: unable to decode URL {url_string}: {err}.")),
-        Ok(url_string) => match Url::parse(&url_string) {
-            Err(err) => Err(format!("Error: unable to parse URL {url_string}: {err}")),
-            Ok(url) => match url.path_segments() {
-                None => Err(format!("Error: URL {url} cannot be a base.")),
-                Some(path_segments) => {
-                    // Make sure the path segments start with
-                    // `/fw/fsc/{connection_id}`.
-                    let ps: Vec<_> = path_segments.collect();
-                    if ps.len() <= 3 || ps[0] != "fw" || ps[1] != "fsc" {
-                        Err(format!("Error: URL {url} has incorrect prefix."))
-                    } else {
-                        // Strip these first three segments; the
-                        // remainder is a file path.
-                        let path_str = ps[3..].join("/");
-                        match PathBuf::from_str(&path_str) {
-                            Err(err) => Err(format!(
-                                "Error: unable to parse file path {path_str}: {err}."
-                            )),
-                            Ok(path_buf) => match path_buf.canonicalize() {
-                                Err(err) => {
-                                    Err(format!("Unable to canonicalize {path_buf:?}: {err}."))
-                                }
-                                Ok(p) => Ok(p),
-                            },
-                        }
-                    }
+// Given a file path, return a URL for it, consisting of the provided prefix
+// (such as `/fw/fsc`), the connection ID, then the URL-encoded path.
+fn path_to_url(prefix: &str, connection_id: &str, file_path: &Path) -> String {
+    format!("{prefix}/{connection_id}/{}", encode_path(file_path))
+}
+
+// URL-encode a file path, preserving the path separators.
+fn encode_path(file_path: &Path) -> String {
+    // First, convert the path to use forward slashes.
+    simplified(file_path)
+        .to_slash_lossy()
+        // The convert each part of the path to a URL-encoded string. (This
+        // avoids encoding the slashes.)
+        .split('/')
+        .map(urlencoding::encode)
+        // Then put it all back together.
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Convert a URL produced by `path_to_url` back to a file path. The
+// `expected_prefix` contains the path segments which must begin the URL (for
+// example, `["fw", "fsc"]`); the segment following it is the connection ID.
+fn url_to_path(url_string: String, expected_prefix: &[&str]) -> Result<PathBuf, String> {
+    // Convert this URL back to a file path. Parse the URL before decoding it,
+    // so that encoded characters in the path (such as `#` or `?`) aren't
+    // mistaken for parts of the URL.
+    match Url::parse(&url_string) {
+        Err(err) => Err(format!("Error: unable to parse URL {url_string}: {err}")),
+        Ok(url) => match url.path_segments() {
+            None => Err(format!("Error: URL {url} cannot be a base.")),
+            Some(path_segments) => {
+                // Make sure the path segments start with
+                // `/{expected_prefix}/{connection_id}`.
+                let ps: Vec<_> = path_segments.collect();
+                let prefix_len = expected_prefix.len();
+                if ps.len() <= prefix_len + 1 || ps[..prefix_len] != *expected_prefix {
+                    return Err(format!("Error: URL {url} has incorrect prefix."));
                 }
-            },
+                // Strip the prefix and connection ID; the remainder is a
+                // file path.
+                let encoded_path = ps[prefix_len + 1..].join("/");
+                let path_str = match urlencoding::decode(&encoded_path) {
+                    Ok(v) => v,
+                    Err(err) => return Err(format!("Error: unable to decode URL {url}: {err}.")),
+                };
+                match PathBuf::from_str(&path_str) {
+                    Err(err) => Err(format!(
+                        "Error: unable to parse file path {path_str}: {err}."
+                    )),
+                    Ok(path_buf) => match path_buf.canonicalize() {
+                        Err(err) => Err(format!("Unable to canonicalize {path_buf:?}: {err}.")),
+                        Ok(p) => Ok(p),
+                    },
+                }
+            }
         },
     }
 }
@@ -1010,6 +1632,13 @@ fn html_not_found(msg: &str) -> HttpResponse {
         .body(html_wrapper(msg))
 }
 
+// Refuse a request which isn't authorized.
+fn html_forbidden(msg: &str) -> HttpResponse {
+    HttpResponse::Forbidden()
+        .content_type(ContentType::html())
+        .body(html_wrapper(msg))
+}
+
 // Wrap the provided HTML body in DOCTYPE/html/head tags.
 fn html_wrapper(body: &str) -> String {
     format!(
@@ -1029,9 +1658,115 @@ fn html_wrapper(body: &str) -> String {
 
 // Given text, escape it so it formats correctly as HTML. This is a translation
 // of Python's `html.escape` function.
-fn escape_html(unsafe_text: &str) -> String {
+pub(crate) fn escape_html(unsafe_text: &str) -> String {
     unsafe_text
         .replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
 }
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::header, test as actix_test, App};
+
+    use super::{
+        configure_app, encode_path, make_app_data, path_to_url, url_to_path, IP_ADDRESS, IP_PORT,
+        STOP_TOKEN_HEADER,
+    };
+    use crate::prep_test_dir;
+
+    #[actix_web::test]
+    async fn test_server_endpoints() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let app_data = make_app_data(IP_ADDRESS, IP_PORT, Some(test_dir.clone()));
+        let app = actix_test::init_service(configure_app(App::new(), &app_data)).await;
+
+        // The status reports the server's URL.
+        let req = actix_test::TestRequest::get()
+            .uri("/server/status")
+            .to_request();
+        let body = actix_test::call_and_read_body(&app, req).await;
+        assert_eq!(
+            body,
+            format!(
+                "CodeChat Editor Server {} running at http://{IP_ADDRESS}:{IP_PORT}.",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        // Requests to stop the server without the stop token, or from
+        // another site, are refused.
+        let req = actix_test::TestRequest::post()
+            .uri("/server/stop")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 403);
+        let req = actix_test::TestRequest::post()
+            .uri("/server/stop")
+            .insert_header((STOP_TOKEN_HEADER, "wrong"))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 403);
+        let req = actix_test::TestRequest::post()
+            .uri("/server/stop")
+            .insert_header((STOP_TOKEN_HEADER, app_data.stop_token.as_str()))
+            .insert_header((header::ORIGIN, "http://example.com"))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 403);
+
+        // This server wasn't started by `run_server`, so even with the token
+        // it can't be stopped.
+        let req = actix_test::TestRequest::post()
+            .uri("/server/stop")
+            .insert_header((STOP_TOKEN_HEADER, app_data.stop_token.as_str()))
+            .insert_header((header::ORIGIN, format!("http://{IP_ADDRESS}:{IP_PORT}")))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+
+        // The root redirects to the provided root path.
+        let req = actix_test::TestRequest::get().uri("/").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            &format!("/fw/fsb/{}", encode_path(&test_dir).trim_start_matches('/'))
+        );
+
+        // The link checker reports on the project.
+        let req = actix_test::TestRequest::get()
+            .uri(&format!(
+                "/server/check/{}",
+                encode_path(&test_dir.join("test.py")).trim_start_matches('/')
+            ))
+            .to_request();
+        let link_report: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(link_report["files_checked"], 2);
+        assert_eq!(
+            link_report["broken_links"][0]["target"],
+            "missing.py".to_string()
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_path_to_url() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let mut file_path = test_dir.clone();
+        file_path.push("a file#1.py");
+        let file_path = file_path.canonicalize().unwrap();
+        let url = path_to_url("/vsc/fs", "id", &file_path);
+        assert!(url.contains("a%20file%231.py"));
+        assert_eq!(
+            url_to_path(format!("http://localhost{url}"), &["vsc", "fs"]).unwrap(),
+            file_path
+        );
+        // The prefix must match.
+        assert!(url_to_path(format!("http://localhost{url}"), &["fw", "fsc"]).is_err());
+        assert!(url_to_path("http://localhost/vsc/fs".to_string(), &["vsc", "fs"]).is_err());
+
+        temp_dir.close().unwrap();
+    }
+}
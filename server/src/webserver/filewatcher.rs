@@ -19,7 +19,11 @@
 /// ## Imports
 ///
 /// ### Standard library
-use std::{path::Path, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 // ### Third-party
 use actix_web::{
@@ -28,22 +32,23 @@ use actix_web::{
     http::header::{self, ContentType},
     web, HttpRequest, HttpResponse, Responder,
 };
-use dunce::simplified;
 use lazy_static::lazy_static;
 use log::{error, info, warn};
 use notify_debouncer_full::{
     new_debouncer,
-    notify::{EventKind, RecursiveMode, Watcher},
-    DebounceEventResult,
+    notify::{
+        event::{ModifyKind, RenameMode},
+        EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    },
+    DebounceEventResult, Debouncer, FileIdMap,
 };
-use path_slash::PathExt;
 use regex::Regex;
 use tokio::{
     fs::DirEntry,
     fs::{self, File},
     io::AsyncReadExt,
     select,
-    sync::mpsc,
+    sync::{broadcast, mpsc, oneshot},
 };
 use urlencoding;
 #[cfg(target_os = "windows")]
@@ -51,14 +56,18 @@ use win_partitions::win_api::get_logical_drive;
 
 // ### Local
 use super::{
-    client_websocket, get_client_framework, get_connection_id, html_not_found, html_wrapper,
-    path_display, send_response, serve_file, AppState, EditorMessage, EditorMessageContents,
-    SimpleHttpResponse, UpdateMessageContents, WebsocketQueues,
+    client_websocket, encode_path, get_client_framework, get_connection_id, html_not_found,
+    html_wrapper, is_stale, path_display, path_to_url, send_response, serve_file_from_disk,
+    AppState, ClientPresence, EditorMessage, EditorMessageContents, FileChange, FileRequest,
+    FileSession, SessionMessage, SharedContents, UpdateMessageContents, VersionHistory,
+    WebsocketQueues,
 };
 use crate::{
     oneshot_send,
     processing::{
-        codechat_for_web_to_source, source_to_codechat_for_web_string, TranslationResultsString,
+        codechat_for_web_to_source, find_project_root, new_version, reindex_changed_file,
+        source_to_codechat_for_web_string, CodeChatForWebUpdate, TranslationCache,
+        TranslationResultsString,
     },
     queue_send,
     webserver::{filesystem_endpoint, url_to_path},
@@ -76,10 +85,17 @@ lazy_static! {
 /// a file from the local filesystem for editing. Long term, this should be
 /// replaced by something better.
 ///
-/// Redirect from the root of the filesystem to the actual root path on this OS.
-pub async fn filewatcher_root_fs_redirect() -> impl Responder {
+/// Redirect from the root of the filesystem to the actual root path on this OS,
+/// or to the root path provided when starting the server.
+pub async fn filewatcher_root_fs_redirect(app_state: web::Data<AppState>) -> impl Responder {
+    let location = match &app_state.root_path {
+        // The browser endpoint adds the leading slash of an absolute Posix
+        // path back; remove it here.
+        Some(root_path) => format!("/fw/fsb/{}", encode_path(root_path).trim_start_matches('/')),
+        None => "/fw/fsb/".to_string(),
+    };
     HttpResponse::TemporaryRedirect()
-        .insert_header((header::LOCATION, "/fw/fsb/"))
+        .insert_header((header::LOCATION, location))
         .finish()
 }
 
@@ -98,6 +114,8 @@ async fn filewatcher_browser_endpoint(
     app_state: web::Data<AppState>,
     orig_path: web::Path<String>,
 ) -> impl Responder {
+    // This is only modified on Windows.
+    #[cfg_attr(not(target_os = "windows"), allow(unused_mut))]
     let mut fixed_path = orig_path.to_string();
     #[cfg(target_os = "windows")]
     // On Windows, a path of `drive_letter:` needs a `/` appended.
@@ -128,12 +146,17 @@ async fn filewatcher_browser_endpoint(
     if canon_path.is_dir() {
         return dir_listing(orig_path.as_str(), &canon_path).await;
     } else if canon_path.is_file() {
-        // Get an ID for this connection.
+        // Get an ID for this connection, and the name it shows to other
+        // Clients viewing this file.
         let connection_id = get_connection_id(&app_state);
+        let name = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+            .ok()
+            .and_then(|query| query.get("user").cloned())
+            .unwrap_or_else(|| format!("Client {connection_id}"));
         actix_rt::spawn(async move {
-            processing_task(&canon_path, app_state, connection_id).await;
+            processing_task(&canon_path, app_state, connection_id, name).await;
         });
-        return get_client_framework(&req, "fw/ws", connection_id);
+        return get_client_framework(&req, "fw/ws", &connection_id.to_string());
     }
 
     // It's not a directory or a file...we give up. For simplicity, don't handle
@@ -227,8 +250,7 @@ async fn dir_listing(web_path: &str, dir_path: &Path) -> HttpResponse {
         Ok::<String, std::ffi::OsString>(a.file_name().into_string()?.to_lowercase())
     };
     #[cfg(not(target_os = "windows"))]
-    let file_name_key =
-        |a: &DirEntry| Ok::<String, std::ffi::OsString>(a.file_name().into_string()?);
+    let file_name_key = |a: &DirEntry| a.file_name().into_string();
     files.sort_unstable_by_key(file_name_key);
     dirs.sort_unstable_by_key(file_name_key);
 
@@ -306,57 +328,412 @@ async fn filewatcher_client_endpoint(
     filesystem_endpoint(path, &req, &app_state).await
 }
 
-async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, connection_id: u32) {
+// Return the path to watch for changes to the provided file and how to watch
+// it: the root of its project, so that changes to the TOC and to other project
+// files are seen, or the directory containing the file if it's not part of a
+// project. Watching a directory, rather than the file itself, sees renames of
+// the file and keeps working when an editor saves by replacing the file.
+fn watch_target(file_path: &Path) -> (PathBuf, RecursiveMode) {
+    match find_project_root(file_path) {
+        Ok(project_root) => (project_root, RecursiveMode::Recursive),
+        Err(_) => (
+            file_path.parent().unwrap_or(file_path).to_path_buf(),
+            RecursiveMode::NonRecursive,
+        ),
+    }
+}
+
+// Watch for changes to the provided file, replacing the current watch of
+// `watched_path` (if any) when this file needs a different one.
+fn watch_file(
+    debounced_watcher: &mut Debouncer<RecommendedWatcher, FileIdMap>,
+    watched_path: &mut Option<PathBuf>,
+    file_path: &Path,
+) -> Result<(), String> {
+    let (new_watched_path, recursive_mode) = watch_target(file_path);
+    if let Some(old_watched_path) = watched_path {
+        if *old_watched_path == new_watched_path {
+            return Ok(());
+        }
+        debounced_watcher
+            .watcher()
+            .unwatch(old_watched_path)
+            .map_err(|err| {
+                format!(
+                    "Unable to unwatch '{}': {err}.",
+                    old_watched_path.to_string_lossy()
+                )
+            })?;
+        debounced_watcher.cache().remove_root(&old_watched_path);
+        *watched_path = None;
+    }
+    debounced_watcher
+        .watcher()
+        .watch(&new_watched_path, recursive_mode)
+        .map_err(|err| {
+            format!(
+                "Unable to watch '{}': {err}.",
+                new_watched_path.to_string_lossy()
+            )
+        })?;
+    // The debouncer uses file ids to connect the two halves of a rename on
+    // platforms which don't report renames as a pair.
+    debounced_watcher
+        .cache()
+        .add_root(&new_watched_path, recursive_mode);
+    *watched_path = Some(new_watched_path);
+    Ok(())
+}
+
+// The versions of a file owned by a `file_task`.
+struct FileVersions {
+    // The version of the file's current contents.
+    version: f64,
+    // The number of versions recorded; see `FileChange`.
+    revision: u64,
+    // Recent versions of the file, used to merge edits based on an earlier
+    // version with the changes made since then.
+    history: VersionHistory,
+    // The previous translation of the file, which allows translating each
+    // change to it incrementally.
+    translation_cache: TranslationCache,
+}
+
+impl FileVersions {
+    // Record `contents` as `version` of the file at `file_path`, returning the
+    // change to tell the Clients viewing it about.
+    fn record(&mut self, file_path: &Path, version: f64, contents: String) -> FileChange {
+        let codechat_for_web = match source_to_codechat_for_web_string(
+            &contents,
+            file_path,
+            false,
+            Some(&mut self.translation_cache),
+        )
+        .0
+        {
+            TranslationResultsString::CodeChat(codechat_for_web) => Some(codechat_for_web),
+            _ => None,
+        };
+        self.version = version;
+        self.revision += 1;
+        self.history.push(version, contents);
+        FileChange {
+            version,
+            revision: self.revision,
+            codechat_for_web,
+        }
+    }
+}
+
+// Add a Client to the session for the provided file, creating this session
+// (and the task which owns the file) if necessary. Return queues to send
+// requests to the file's task and to receive from the session.
+fn join_session(
+    app_state: &web::Data<AppState>,
+    file_path: &Path,
+    connection_id: u32,
+    name: &str,
+) -> (
+    mpsc::Sender<FileRequest>,
+    broadcast::Receiver<SessionMessage>,
+) {
+    let mut sessions = app_state.filewatcher_sessions.lock().unwrap();
+    let session = sessions.entry(file_path.to_path_buf()).or_insert_with(|| {
+        let tx = broadcast::channel(16).0;
+        let (request_tx, request_rx) = mpsc::channel(10);
+        actix_rt::spawn(file_task(
+            file_path.to_path_buf(),
+            app_state.clone(),
+            tx.clone(),
+            request_rx,
+        ));
+        FileSession {
+            tx,
+            request_tx,
+            clients: BTreeMap::new(),
+        }
+    });
+    session.clients.insert(
+        connection_id,
+        ClientPresence {
+            connection_id: connection_id.to_string(),
+            name: name.to_string(),
+        },
+    );
+    // Subscribe before announcing this Client, so that it receives the
+    // announcement.
+    let session_rx = session.tx.subscribe();
+    // Sending fails only if there are no receivers, which can't happen here.
+    let _ = session.tx.send(SessionMessage::Presence(
+        session.clients.values().cloned().collect(),
+    ));
+    (session.request_tx.clone(), session_rx)
+}
+
+// Remove a Client from its session, removing this session once no Clients
+// remain. Look for the Client in every session, since the file's task moves its
+// session when the file is renamed.
+fn leave_session(app_state: &AppState, connection_id: u32) {
+    let mut sessions = app_state.filewatcher_sessions.lock().unwrap();
+    sessions.retain(|_, session| {
+        if session.clients.remove(&connection_id).is_none() {
+            return true;
+        }
+        if session.clients.is_empty() {
+            return false;
+        }
+        // Other Clients remain to receive this.
+        let _ = session.tx.send(SessionMessage::Presence(
+            session.clients.values().cloned().collect(),
+        ));
+        true
+    });
+}
+
+// The task which owns a file open in FileWatcher mode. It watches the file,
+// records its versions, and saves edits to it, broadcasting each change to the
+// Clients in its session. Since this is the only task which writes to the
+// file, concurrent saves from different Clients are merged rather than
+// overwriting each other. It runs until all Clients leave the session.
+async fn file_task(
+    mut file_path: PathBuf,
+    app_state: web::Data<AppState>,
+    session_tx: broadcast::Sender<SessionMessage>,
+    mut request_rx: mpsc::Receiver<FileRequest>,
+) {
+    'task: {
+        // Use a channel to send from the watcher (which runs in another thread)
+        // into this async (task) context.
+        let (watcher_tx, mut watcher_rx) = mpsc::channel(10);
+        // Watch this file. Use the debouncer, to avoid multiple notifications
+        // for the same file. This approach returns a result of either a working
+        // debouncer or any errors that occurred. The debouncer's scope needs
+        // live as long as this task does; dropping it early means losing file
+        // change notifications.
+        let Ok(mut debounced_watcher) = new_debouncer(
+            Duration::from_secs(2),
+            None,
+            // Note that this runs in a separate thread created by the watcher,
+            // not in an async context. Therefore, use a blocking send.
+            move |result: DebounceEventResult| {
+                if let Err(err) = watcher_tx.blocking_send(result) {
+                    // Note: we can't break here, since this runs in a separate
+                    // thread. We have no way to shut down the task (which would
+                    // be the best action to take.)
+                    error!("Unable to send: {err}");
+                }
+            },
+        ) else {
+            error!("Unable to create debouncer.");
+            break 'task;
+        };
+        let mut watched_path = None;
+        if let Err(err) = watch_file(&mut debounced_watcher, &mut watched_path, &file_path) {
+            error!("{err}");
+            break 'task;
+        };
+
+        let mut versions = FileVersions {
+            version: new_version(),
+            revision: 0,
+            history: VersionHistory::default(),
+            translation_cache: TranslationCache::default(),
+        };
+
+        loop {
+            select! {
+                // Process results produced by the file watcher.
+                Some(result) = watcher_rx.recv() => {
+                    match result {
+                        Err(err_vec) => {
+                            for err in err_vec {
+                                // Report errors locally and to the CodeChat
+                                // Editor Clients.
+                                let msg = format!("Watcher error: {err}");
+                                error!("{msg}");
+                                let _ = session_tx.send(SessionMessage::Error(msg));
+                            }
+                        }
+
+                        Ok(debounced_event_vec) => {
+                            for debounced_event in debounced_event_vec {
+                                let paths = &debounced_event.event.paths;
+                                match debounced_event.event.kind {
+                                    // The file (or a directory containing it)
+                                    // was moved. Follow it to its new
+                                    // location, then tell the Clients to load
+                                    // it from there. Some editors (such as
+                                    // vim) save by renaming the file to a
+                                    // backup, then writing a new file in its
+                                    // place; since the file still exists, the
+                                    // next arm treats this as a modification.
+                                    EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if paths.len() == 2 && file_path.starts_with(&paths[0]) && !paths[0].exists() => {
+                                        let old_filepath = file_path.clone();
+                                        file_path = match old_filepath.strip_prefix(&paths[0]).unwrap() {
+                                            relative_path if relative_path.as_os_str().is_empty() => paths[1].clone(),
+                                            relative_path => paths[1].join(relative_path),
+                                        };
+                                        if let Err(err) = watch_file(&mut debounced_watcher, &mut watched_path, &file_path) {
+                                            error!("{err}");
+                                        }
+                                        reindex_changed_file(&old_filepath, &file_path);
+                                        // Move this session to the file's new
+                                        // path.
+                                        {
+                                            let mut sessions = app_state.filewatcher_sessions.lock().unwrap();
+                                            if let Some(session) = sessions.remove(&old_filepath) {
+                                                sessions.insert(file_path.clone(), session);
+                                            }
+                                        }
+                                        let _ = session_tx.send(SessionMessage::Moved(file_path.clone()));
+                                    }
+                                    // The file was modified, created, or
+                                    // removed. Editors which save by writing a
+                                    // temporary file then renaming it to the
+                                    // file produce a create or a rename to it,
+                                    // sometimes preceded by a remove;
+                                    // therefore, if the file exists, treat any
+                                    // of these as a modification. On Windows,
+                                    // the modify kind is `Any`; therefore;
+                                    // ignore it rather than trying to look at
+                                    // only content modifications.
+                                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) if paths.contains(&file_path) => {
+                                        // Read the modified file.
+                                        let mut file_contents = String::new();
+                                        let read_ret = match File::open(&file_path).await {
+                                            Ok(fc) => fc,
+                                            Err(_err) => {
+                                                // We can't open the file. If it
+                                                // was deleted or moved out of
+                                                // the watched directory, close
+                                                // the file. Otherwise, this
+                                                // event predates a change which
+                                                // a later event reports.
+                                                if matches!(debounced_event.event.kind, EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))) {
+                                                    let _ = session_tx.send(SessionMessage::Closed);
+                                                }
+                                                continue;
+                                            }
+                                        }
+                                        .read_to_string(&mut file_contents)
+                                        .await;
+
+                                        // Close the file if it can't be read
+                                        // as Unicode text.
+                                        if read_ret.is_err() {
+                                            let _ = session_tx.send(SessionMessage::Closed);
+                                            continue;
+                                        }
+
+                                        // Ignore changes this task made by
+                                        // saving a Client's edits.
+                                        if versions.history.get(versions.version) == Some(file_contents.as_str()) {
+                                            continue;
+                                        }
+
+                                        // Send the new contents to the
+                                        // Clients.
+                                        let change = versions.record(&file_path, new_version(), file_contents);
+                                        let _ = session_tx.send(SessionMessage::Changed(change));
+                                    }
+                                    // Another file in the project changed.
+                                    // Update the anchor index, then ask the
+                                    // Clients to reload this file if it's
+                                    // shown with the file.
+                                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
+                                        let changed_paths: Vec<PathBuf> = paths.iter()
+                                            .filter(|changed_path| **changed_path != file_path && reindex_changed_file(changed_path, &file_path))
+                                            .cloned()
+                                            .collect();
+                                        if !changed_paths.is_empty() {
+                                            let _ = session_tx.send(SessionMessage::Refresh(changed_paths));
+                                        }
+                                    }
+                                    _ => {
+                                        info!("Watcher event: {debounced_event:?}.");
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Process requests from the Clients. Once all the Clients
+                // leave, there are no more requests.
+                request = request_rx.recv() => {
+                    match request {
+                        None => break,
+
+                        Some(FileRequest::Load(response_tx)) => {
+                            // A Client is loading the file from disk. If its
+                            // contents changed, tell the other Clients.
+                            if let Ok(file_contents) = fs::read_to_string(&file_path).await {
+                                if versions.history.get(versions.version) != Some(file_contents.as_str()) {
+                                    let change = versions.record(&file_path, new_version(), file_contents);
+                                    let _ = session_tx.send(SessionMessage::Changed(change));
+                                }
+                            }
+                            // The Client's task may have exited; there's
+                            // nothing to do in this case.
+                            let _ = response_tx.send((versions.version, versions.revision));
+                        }
+
+                        Some(FileRequest::Save { base_version, version, contents, response_tx }) => {
+                            let result = 'process: {
+                                // If the file changed since the Client's edits
+                                // began, merge these changes. If they can't be
+                                // merged, report a conflict rather than
+                                // overwriting them.
+                                let is_merged = is_stale(versions.version, base_version);
+                                let contents = match versions.history.merge(versions.version, base_version, contents) {
+                                    Ok(contents) => contents,
+                                    Err(err) => break 'process Err(err),
+                                };
+
+                                // Save this string to the file. The file
+                                // watcher ignores this change, since its
+                                // contents are the current version.
+                                if let Err(err) = fs::write(&file_path, &contents).await {
+                                    break 'process Err(format!(
+                                        "Unable to save file '{}': {err}.",
+                                        file_path.to_string_lossy()
+                                    ));
+                                }
+                                // Send these edits to all the Clients viewing
+                                // this file. This fails only if there are no
+                                // receivers.
+                                let change = versions.record(&file_path, if is_merged { new_version() } else { version }, contents);
+                                let _ = session_tx.send(SessionMessage::Changed(change.clone()));
+                                Ok((change, is_merged))
+                            };
+                            let _ = response_tx.send(result);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    info!("Watcher closed.");
+}
+
+async fn processing_task(
+    file_path: &Path,
+    app_state: web::Data<AppState>,
+    connection_id: u32,
+    // The name this Client shows to other Clients viewing the same file.
+    name: String,
+) {
     // #### Filewatcher IDE
     //
-    // This is a CodeChat Editor file. Start up the Filewatcher IDE tasks:
-    //
-    // 1.  A task to watch for changes to the file, notifying the CodeChat
-    //     Editor Client when the file should be reloaded.
-    // 2.  A task to receive and respond to messages from the CodeChat Editor
-    //     Client.
-    //
-    // First, allocate variables needed by these two tasks.
+    // This is a CodeChat Editor file. Start up a task to relay messages between
+    // the CodeChat Editor Client and the session for this file, whose task
+    // watches the file and saves edits to it.
     //
     // The path to the currently open CodeChat Editor file.
     let mut current_filepath = file_path.to_path_buf().canonicalize().unwrap();
-    // #### The filewatcher task.
     actix_rt::spawn(async move {
         'task: {
-            // Use a channel to send from the watcher (which runs in another
-            // thread) into this async (task) context.
-            let (watcher_tx, mut watcher_rx) = mpsc::channel(10);
-            // Watch this file. Use the debouncer, to avoid multiple
-            // notifications for the same file. This approach returns a result
-            // of either a working debouncer or any errors that occurred. The
-            // debouncer's scope needs live as long as this connection does;
-            // dropping it early means losing file change notifications.
-            let Ok(mut debounced_watcher) = new_debouncer(
-                Duration::from_secs(2),
-                None,
-                // Note that this runs in a separate thread created by the
-                // watcher, not in an async context. Therefore, use a blocking
-                // send.
-                move |result: DebounceEventResult| {
-                    if let Err(err) = watcher_tx.blocking_send(result) {
-                        // Note: we can't break here, since this runs in a
-                        // separate thread. We have no way to shut down the task
-                        // (which would be the best action to take.)
-                        error!("Unable to send: {err}");
-                    }
-                },
-            ) else {
-                error!("Unable to create debouncer.");
-                break 'task;
-            };
-            if let Err(err) = debounced_watcher
-                .watcher()
-                .watch(&current_filepath, RecursiveMode::NonRecursive)
-            {
-                error!("Unable to watch file: {err}");
-                break 'task;
-            };
-
             // Create the queues for the websocket connection to communicate
             // with this task.
             let (from_websocket_tx, mut from_websocket_rx) = mpsc::channel(10);
@@ -369,20 +746,28 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                 },
             );
 
+            // Assign each message sent to the Client a unique id, so that the
+            // websocket can correlate it with the `Result` it produces.
+            let mut id: u32 = 0;
+            // The version of the file's contents which the Client displays.
+            let mut version = new_version();
+            // The revision of these contents (see `FileChange`), or `None` if
+            // the Client hasn't loaded the file.
+            let mut revision: Option<u64> = None;
+            // The contents of the current file shared with the Client, which
+            // allow sending updates as diffs.
+            let mut client_contents = SharedContents::default();
+            // The ids of `Update` messages sent as diffs. If the Client can't
+            // apply a diff, send a snapshot instead.
+            let mut pending_diffs: HashSet<u32> = HashSet::new();
+
             // Provide it a file to open.
-            let encoded_path =
-                // First, convert the path to use forward slashes.
-                &simplified(&current_filepath).to_slash().unwrap()
-                // The convert each part of the path to a URL-encoded string.
-                // (This avoids encoding the slashes.)
-                .split("/").map(|s| urlencoding::encode(s))
-                // Then put it all back together.
-                .collect::<Vec<_>>().join("/");
-            let url_pathbuf = format!("/fw/fsc/{connection_id}/{encoded_path}");
+            let url_pathbuf = path_to_url("/fw/fsc", &connection_id.to_string(), &current_filepath);
             queue_send!(to_websocket_tx.send(EditorMessage {
-                id: 0,
+                id,
                 message: EditorMessageContents::CurrentFile(url_pathbuf)
             }), 'task);
+            id += 1;
 
             // Create a queue for HTTP requests fo communicate with this task.
             let (from_http_tx, mut from_http_rx) = mpsc::channel(10);
@@ -392,132 +777,133 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                 .unwrap()
                 .insert(connection_id.to_string(), from_http_tx);
 
+            // Join the session shared by all Clients viewing this file.
+            let (mut file_tx, mut session_rx) =
+                join_session(&app_state, &current_filepath, connection_id, &name);
+            // The other Clients in this session, as last reported to this
+            // Client.
+            let mut others: Vec<ClientPresence> = Vec::new();
+
             loop {
                 select! {
-                    // Process results produced by the file watcher.
-                    Some(result) = watcher_rx.recv() => {
-                        match result {
-                            Err(err_vec) => {
-                                for err in err_vec {
-                                    // Report errors locally and to the CodeChat
-                                    // Editor.
-                                    let msg = format!("Watcher error: {err}");
-                                    error!("{msg}");
-                                    // Send using ID 0 to indicate this isn't a
-                                    // response to a message received from the
-                                    // client.
-                                    send_response(&to_websocket_tx, 0, Some(msg)).await;
+                    // Process messages from the session for this file.
+                    session_message = session_rx.recv() => {
+                        match session_message {
+                            // Send the file's new contents to this Client,
+                            // unless it hasn't loaded the file or already has
+                            // these (or later) contents.
+                            Ok(SessionMessage::Changed(change)) => {
+                                if revision.is_none_or(|revision| change.revision <= revision) {
+                                    continue;
                                 }
+                                revision = Some(change.revision);
+                                let Some(codechat_for_web) = change.codechat_for_web else {
+                                    // Close the file -- it's not CodeChat
+                                    // anymore.
+                                    queue_send!(to_websocket_tx.send(EditorMessage {
+                                        id: 0,
+                                        message: EditorMessageContents::Closed
+                                    }));
+                                    continue;
+                                };
+                                let update = UpdateMessageContents {
+                                    path: None,
+                                    contents: Some(client_contents.send(codechat_for_web)),
+                                    base_version: Some(version),
+                                    version: change.version,
+                                    cursor_position: None,
+                                    scroll_position: None,
+                                };
+                                version = change.version;
+                                if matches!(update.contents, Some(CodeChatForWebUpdate::Diff(_))) {
+                                    pending_diffs.insert(id);
+                                }
+                                queue_send!(to_websocket_tx.send(EditorMessage {
+                                    id,
+                                    message: EditorMessageContents::Update(update),
+                                }));
+                                id += 1;
                             }
-
-                            Ok(debounced_event_vec) => {
-                                for debounced_event in debounced_event_vec {
-                                    match debounced_event.event.kind {
-                                        EventKind::Modify(_modify_kind) => {
-                                            // On Windows, the `_modify_kind` is `Any`;
-                                            // therefore; ignore it rather than trying
-                                            // to look at only content modifications.
-                                            if debounced_event.event.paths.len() == 1 && debounced_event.event.paths[0] == current_filepath {
-                                                // Since the parents are identical, send an
-                                                // update. First, read the modified file.
-                                                let mut file_contents = String::new();
-                                                let read_ret = match File::open(&current_filepath).await {
-                                                    Ok(fc) => fc,
-                                                    Err(_err) => {
-                                                        // We can't open the file -- it's been
-                                                        // moved or deleted. Close the file.
-                                                        queue_send!(to_websocket_tx.send(EditorMessage {
-                                                            id: 0,
-                                                            message: EditorMessageContents::Closed
-                                                        }));
-                                                        continue;
-                                                    }
-                                                }
-                                                .read_to_string(&mut file_contents)
-                                                .await;
-
-                                                // Close the file if it can't be read as
-                                                // Unicode text.
-                                                if read_ret.is_err() {
-                                                    queue_send!(to_websocket_tx.send(EditorMessage {
-                                                        id: 0,
-                                                        message: EditorMessageContents::Closed
-                                                    }));
-                                                }
-
-                                                // Translate the file.
-                                                let (translation_results_string, _path_to_toc) =
-                                                source_to_codechat_for_web_string(&file_contents, &current_filepath, false);
-                                                if let TranslationResultsString::CodeChat(cc) = translation_results_string {
-                                                    // Send the new contents
-                                                    queue_send!(to_websocket_tx.send(EditorMessage {
-                                                            id: 0,
-                                                            message: EditorMessageContents::Update(UpdateMessageContents {
-                                                                contents: Some(cc),
-                                                                cursor_position: None,
-                                                                scroll_position: None,
-                                                            }),
-                                                        }));
-
-                                                } else {
-                                                    // Close the file -- it's not CodeChat
-                                                    // anymore.
-                                                    queue_send!(to_websocket_tx.send(EditorMessage {
-                                                        id: 0,
-                                                        message: EditorMessageContents::Closed
-                                                    }));
-                                                }
-
-                                            } else {
-                                                warn!("TODO: Modification to different file.")
-                                            }
-                                        }
-                                        _ => {
-                                            // TODO: handle delete.
-                                            info!("Watcher event: {debounced_event:?}.");
-                                        }
-                                    }
+                            // Tell the Client to load the file from its new
+                            // location.
+                            Ok(SessionMessage::Moved(new_filepath)) => {
+                                current_filepath = new_filepath;
+                                queue_send!(to_websocket_tx.send(EditorMessage {
+                                    id,
+                                    message: EditorMessageContents::CurrentFile(path_to_url("/fw/fsc", &connection_id.to_string(), &current_filepath))
+                                }));
+                                id += 1;
+                            }
+                            Ok(SessionMessage::Closed) => {
+                                queue_send!(to_websocket_tx.send(EditorMessage {
+                                    id: 0,
+                                    message: EditorMessageContents::Closed
+                                }));
+                            }
+                            // Ask the Client to reload the other files which
+                            // changed.
+                            Ok(SessionMessage::Refresh(changed_paths)) => {
+                                let urls = changed_paths.iter()
+                                    .map(|changed_path| path_to_url("/fw/fsc", &connection_id.to_string(), changed_path))
+                                    .collect();
+                                queue_send!(to_websocket_tx.send(EditorMessage {
+                                    id,
+                                    message: EditorMessageContents::Refresh(urls)
+                                }));
+                                id += 1;
+                            }
+                            // Send using ID 0 to indicate this isn't a
+                            // response to a message received from the client.
+                            Ok(SessionMessage::Error(msg)) => send_response(&to_websocket_tx, 0, Some(msg)).await,
+                            // Tell this Client who else is viewing this file.
+                            Ok(SessionMessage::Presence(clients)) => {
+                                let new_others: Vec<ClientPresence> = clients.into_iter().filter(|client| client.connection_id != connection_id.to_string()).collect();
+                                if new_others != others {
+                                    others = new_others;
+                                    queue_send!(to_websocket_tx.send(EditorMessage {
+                                        id,
+                                        message: EditorMessageContents::Presence(others.clone()),
+                                    }));
+                                    id += 1;
                                 }
                             }
+                            // If this task fell behind, the next change sent
+                            // to the Client is a diff from the contents it
+                            // last received, which still applies.
+                            Err(err) => warn!("Unable to receive from session: {err}."),
                         }
                     }
 
                     Some(http_request) = from_http_rx.recv() => {
-                        // Convert the provided URL back into a file name.
-                        let file_path = Path::new(&http_request.request_path);
-
-                        // Read the file
-                        let simple_http_response = match File::open(file_path).await {
-                            Err(err) => SimpleHttpResponse::Err(format!(
-                                "<p>Error opening file {file_path:?}: {err}."
-                            )),
-                            Ok(mut fc) => {
-                                let mut file_contents = String::new();
-                                match fc.read_to_string(&mut file_contents).await {
-                                    // If this is a binary file (meaning we can't read the contents as UTF-8),
-                                    // just serve it raw; assume this is an image/video/etc.
-                                    Err(_) => SimpleHttpResponse::Bin(http_request.request_path),
-                                    Ok(_) => {
-                                        let is_current = file_path.canonicalize().unwrap() == current_filepath;
-                                        let (simple_http_response, option_codechat_for_web) = serve_file(file_path, &file_contents, http_request.is_toc, is_current, http_request.is_test_mode).await;
-                                        // If this file is editable and is the main
-                                        // file, send an `Update`. The
-                                        // `simple_http_response` contains the Client.
-                                        if let Some(codechat_for_web) = option_codechat_for_web {
-                                            queue_send!(to_websocket_tx.send(EditorMessage {
-                                                id: 0,
-                                                message: EditorMessageContents::Update(UpdateMessageContents {
-                                                    contents: Some(codechat_for_web),
-                                                    cursor_position: None,
-                                                    scroll_position: None
-                                                })
-                                            }));
-                                        }
-                                        simple_http_response
-                                    },
-                                }
-                            },
-                        };
+                        let (simple_http_response, option_codechat_for_web) = serve_file_from_disk(&http_request, &current_filepath).await;
+                        // If this file is editable and is the main file, send
+                        // an `Update`. The `simple_http_response` contains the
+                        // Client, which needs a snapshot of the file's
+                        // contents.
+                        if let Some(codechat_for_web) = option_codechat_for_web {
+                            // Find the version of these contents.
+                            let (load_tx, load_rx) = oneshot::channel();
+                            queue_send!(file_tx.send(FileRequest::Load(load_tx)));
+                            let Ok((loaded_version, loaded_revision)) = load_rx.await else {
+                                error!("Unable to load '{}'.", current_filepath.to_string_lossy());
+                                break;
+                            };
+                            client_contents = SharedContents::default();
+                            version = loaded_version;
+                            revision = Some(loaded_revision);
+                            queue_send!(to_websocket_tx.send(EditorMessage {
+                                id,
+                                message: EditorMessageContents::Update(UpdateMessageContents {
+                                    path: None,
+                                    contents: Some(client_contents.send(codechat_for_web)),
+                                    base_version: None,
+                                    version,
+                                    cursor_position: None,
+                                    scroll_position: None
+                                })
+                            }));
+                            id += 1;
+                        }
 
                         oneshot_send!(http_request.response_queue.send(simple_http_response));
                     }
@@ -530,9 +916,13 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                     // save. TODO: this should store and
                                     // remember the path, instead of needing it
                                     // repeated each time.
+                                    let base_version = update_message_contents.base_version;
                                     let codechat_for_web = match update_message_contents.contents {
                                         None => break 'process None,
-                                        Some(cwf) => cwf,
+                                        Some(cwf) => match client_contents.receive(version, base_version, cwf) {
+                                            Ok(cwf) => cwf,
+                                            Err(err) => break 'process Some(err),
+                                        },
                                     };
 
                                     // Translate from the CodeChatForWeb format
@@ -548,27 +938,50 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                         }
                                     };
 
-                                    if let Err(err) = debounced_watcher.watcher().unwatch(&current_filepath) {
-                                        let msg = format!(
-                                            "Unable to unwatch file '{}': {err}.",
-                                            current_filepath.to_string_lossy()
-                                        );
-                                        break 'process Some(msg);
-                                    }
-                                    // Save this string to a file.
-                                    if let Err(err) = fs::write(current_filepath.as_path(), file_contents).await {
-                                        let msg = format!(
-                                            "Unable to save file '{}': {err}.",
-                                            current_filepath.to_string_lossy()
-                                        );
-                                        break 'process Some(msg);
+                                    // Ask the file's task to save these edits,
+                                    // which merges them with any changes made
+                                    // since the Client's edits began, then
+                                    // sends them to the other Clients viewing
+                                    // this file.
+                                    let (response_tx, response_rx) = oneshot::channel();
+                                    if file_tx.send(FileRequest::Save {
+                                        base_version,
+                                        version: update_message_contents.version,
+                                        contents: file_contents,
+                                        response_tx,
+                                    }).await.is_err() {
+                                        break 'process Some("Unable to save: the file's task exited.".to_string());
                                     }
-                                    if let Err(err) = debounced_watcher.watcher().watch(&current_filepath, RecursiveMode::NonRecursive) {
-                                        let msg = format!(
-                                            "Unable to watch file '{}': {err}.",
-                                            current_filepath.to_string_lossy()
-                                        );
-                                        break 'process Some(msg);
+                                    let (change, is_merged) = match response_rx.await {
+                                        Ok(Ok(saved)) => saved,
+                                        Ok(Err(err)) => break 'process Some(err),
+                                        Err(_) => break 'process Some("Unable to save: the file's task exited.".to_string()),
+                                    };
+                                    version = change.version;
+                                    revision = Some(change.revision);
+                                    // Translating the saved file provides the
+                                    // contents the Client displays, so that
+                                    // later updates to the Client may be diffs.
+                                    if let Some(cc) = change.codechat_for_web {
+                                        if is_merged {
+                                            // The Client doesn't have the
+                                            // merged contents; send them.
+                                            client_contents = SharedContents::default();
+                                            queue_send!(to_websocket_tx.send(EditorMessage {
+                                                id,
+                                                message: EditorMessageContents::Update(UpdateMessageContents {
+                                                    path: None,
+                                                    contents: Some(client_contents.send(cc)),
+                                                    base_version: Some(update_message_contents.version),
+                                                    version,
+                                                    cursor_position: None,
+                                                    scroll_position: None,
+                                                })
+                                            }), 'task);
+                                            id += 1;
+                                        } else {
+                                            client_contents.set_displayed(cc);
+                                        }
                                     }
                                     None
                                 };
@@ -576,29 +989,17 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                             }
 
                             EditorMessageContents::CurrentFile(url_string) => {
-                                let result = match url_to_path(url_string) {
+                                let result = match url_to_path(url_string, &["fw", "fsc"]) {
                                     Err(err) => Some(err),
-                                    Ok(file_path) => 'err_exit: {
-                                        // We finally have the desired path! First,
-                                        // unwatch the old path.
-                                        if let Err(err) = debounced_watcher.watcher().unwatch(&current_filepath) {
-                                            break 'err_exit Some(format!(
-                                                "Unable to unwatch file '{}': {err}.",
-                                                current_filepath.to_string_lossy()
-                                            ));
-                                        }
-                                        // Update to the new path.
+                                    Ok(file_path) => {
+                                        // We finally have the desired path!
+                                        // Move to the session for the new
+                                        // path, then update to it.
+                                        leave_session(&app_state, connection_id);
+                                        (file_tx, session_rx) = join_session(&app_state, &file_path, connection_id, &name);
                                         current_filepath = file_path;
-                                        // Watch the new file.
-                                        if let Err(err) = debounced_watcher.watcher().watch(&current_filepath, RecursiveMode::NonRecursive) {
-                                            break 'err_exit Some(format!(
-                                                "Unable to watch file '{}': {err}.",
-                                                current_filepath.to_string_lossy()
-                                            ));
-                                        }
-
-                                        // Indicate there was no error in the
-                                        // `Result` message.
+                                        client_contents = SharedContents::default();
+                                        revision = None;
                                         None
                                     }
                                 };
@@ -608,6 +1009,29 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                             // Process a result, the respond to a message we
                             // sent.
                             EditorMessageContents::Result(err, _) => {
+                                // If the Client couldn't apply a diff, send a
+                                // snapshot instead. However, if the Client has
+                                // unsaved edits which conflict with it, the
+                                // Client saves them instead; the file's task
+                                // merges them with these contents, then sends
+                                // the merged contents back.
+                                if pending_diffs.remove(&m.id) && err.as_ref().is_some_and(|err| !err.starts_with("Conflict")) {
+                                    if let Some(snapshot) = client_contents.snapshot() {
+                                        queue_send!(to_websocket_tx.send(EditorMessage {
+                                            id,
+                                            message: EditorMessageContents::Update(UpdateMessageContents {
+                                                path: None,
+                                                contents: Some(snapshot),
+                                                base_version: None,
+                                                version,
+                                                cursor_position: None,
+                                                scroll_position: None,
+                                            })
+                                        }));
+                                        id += 1;
+                                        continue;
+                                    }
+                                }
                                 // Report errors to the log.
                                 if let Some(err_msg) = err {
                                     error!("Error in message {}: {err_msg}.", m.id);
@@ -619,7 +1043,7 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                 break;
                             }
 
-                            EditorMessageContents::Opened(_) | EditorMessageContents::ClientHtml(_) | EditorMessageContents::RequestClose => {
+                            EditorMessageContents::Opened(..) | EditorMessageContents::ClientHtml(_) | EditorMessageContents::RequestClose | EditorMessageContents::Refresh(_) => {
                                 let msg = format!("Client sent unsupported message type {m:?}");
                                 error!("{msg}");
                                 send_response(&to_websocket_tx, m.id, Some(msg)).await;
@@ -642,7 +1066,8 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
             }
         }
 
-        info!("Watcher closed.");
+        leave_session(&app_state, connection_id);
+        info!("Client closed.");
     });
 }
 
@@ -666,7 +1091,12 @@ pub async fn filewatcher_websocket(
 // ## Tests
 #[cfg(test)]
 mod tests {
-    use std::{fs, path::PathBuf, str::FromStr, time::Duration};
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        str::FromStr,
+        time::Duration,
+    };
 
     use actix_http::Request;
     use actix_web::{
@@ -676,31 +1106,36 @@ mod tests {
     };
     use assertables::{assert_starts_with, assert_starts_with_as_result};
     use path_slash::PathExt;
-    use tokio::{select, sync::mpsc::Receiver, time::sleep};
+    use tokio::{
+        select,
+        sync::mpsc::{Receiver, Sender},
+        time::sleep,
+    };
     use url::Url;
 
     use super::{
-        super::{configure_app, make_app_data, WebsocketQueues},
-        send_response, AppState, EditorMessage, EditorMessageContents, UpdateMessageContents,
+        super::{configure_app, make_app_data, WebsocketQueues, IP_ADDRESS, IP_PORT},
+        send_response, AppState, ClientPresence, EditorMessage, EditorMessageContents,
+        UpdateMessageContents,
     };
     use crate::{
         processing::{
-            source_to_codechat_for_web, CodeChatForWeb, CodeMirror, SourceFileMetadata,
-            TranslationResults,
+            source_to_codechat_for_web, CodeChatForWeb, CodeChatForWebUpdate, CodeMirror,
+            SourceFileMetadata, TranslationResults,
         },
         test_utils::{check_logger_errors, configure_testing_logger},
-        webserver::IdeType,
+        webserver::{IdeType, ProtocolInfo},
         {cast, cast2, prep_test_dir},
     };
 
     async fn get_websocket_queues(
         // A path to the temporary directory where the source file is located.
-        test_dir: &PathBuf,
+        test_dir: &Path,
     ) -> (
         WebsocketQueues,
         impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
     ) {
-        let app_data = make_app_data();
+        let app_data = make_app_data(IP_ADDRESS, IP_PORT, None);
         let app = test::init_service(configure_app(App::new(), &app_data)).await;
 
         // Load in a test source file to create a websocket.
@@ -718,10 +1153,10 @@ mod tests {
         let mut joint_editors = app_state.filewatcher_client_queues.lock().unwrap();
         let connection_id = *app_state.connection_id.lock().unwrap();
         assert_eq!(joint_editors.len(), 1);
-        return (
+        (
             joint_editors.remove(&connection_id.to_string()).unwrap(),
             app,
-        );
+        )
     }
 
     async fn get_message(client_rx: &mut Receiver<EditorMessage>) -> EditorMessageContents {
@@ -786,9 +1221,12 @@ mod tests {
         send_response(&ide_tx_queue, 0, None).await;
 
         // Check the contents.
-        let translation_results = source_to_codechat_for_web(&"".to_string(), "py", false, false);
+        let translation_results = source_to_codechat_for_web("", "py", false, false);
         let codechat_for_web = cast!(translation_results, TranslationResults::CodeChat);
-        assert_eq!(umc.contents, Some(codechat_for_web));
+        assert_eq!(
+            umc.contents,
+            Some(CodeChatForWebUpdate::Snapshot(codechat_for_web))
+        );
 
         // Report any errors produced when removing the temporary directory.
         check_logger_errors(0);
@@ -820,7 +1258,10 @@ mod tests {
             .send(EditorMessage {
                 id: 0,
                 message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
                     contents: None,
+                    base_version: None,
+                    version: 0.0,
                     cursor_position: None,
                     scroll_position: None,
                 }),
@@ -836,9 +1277,10 @@ mod tests {
 
         // 2.  Send invalid messages.
         for msg in [
-            EditorMessageContents::Opened(IdeType::VSCode(true)),
+            EditorMessageContents::Opened(IdeType::VSCode(true), ProtocolInfo::server()),
             EditorMessageContents::ClientHtml("".to_string()),
             EditorMessageContents::RequestClose,
+            EditorMessageContents::Refresh(vec![]),
         ] {
             ide_tx_queue
                 .send(EditorMessage {
@@ -861,15 +1303,19 @@ mod tests {
             .send(EditorMessage {
                 id: 0,
                 message: EditorMessageContents::Update(UpdateMessageContents {
-                    contents: Some(CodeChatForWeb {
+                    path: None,
+                    contents: Some(CodeChatForWebUpdate::Snapshot(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "".to_string(),
+                            backlinks: Vec::new(),
                         },
                         source: CodeMirror {
                             doc: "".to_string(),
                             doc_blocks: vec![],
                         },
-                    }),
+                    })),
+                    base_version: None,
+                    version: 1.0,
                     cursor_position: None,
                     scroll_position: None,
                 }),
@@ -888,15 +1334,19 @@ mod tests {
             .send(EditorMessage {
                 id: 0,
                 message: EditorMessageContents::Update(UpdateMessageContents {
-                    contents: Some(CodeChatForWeb {
+                    path: None,
+                    contents: Some(CodeChatForWebUpdate::Snapshot(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "nope".to_string(),
+                            backlinks: Vec::new(),
                         },
                         source: CodeMirror {
                             doc: "testing".to_string(),
                             doc_blocks: vec![],
                         },
-                    }),
+                    })),
+                    base_version: Some(1.0),
+                    version: 2.0,
                     cursor_position: None,
                     scroll_position: None,
                 }),
@@ -920,15 +1370,19 @@ mod tests {
             .send(EditorMessage {
                 id: 0,
                 message: EditorMessageContents::Update(UpdateMessageContents {
-                    contents: Some(CodeChatForWeb {
+                    path: None,
+                    contents: Some(CodeChatForWebUpdate::Snapshot(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "python".to_string(),
+                            backlinks: Vec::new(),
                         },
                         source: CodeMirror {
-                            doc: "testing()".to_string(),
+                            doc: "testing()\n".to_string(),
                             doc_blocks: vec![],
                         },
-                    }),
+                    })),
+                    base_version: Some(1.0),
+                    version: 3.0,
                     cursor_position: None,
                     scroll_position: None,
                 }),
@@ -942,46 +1396,121 @@ mod tests {
 
         // Check that the requested file is written.
         let mut s = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(s, "testing()");
+        assert_eq!(s, "testing()\n");
         // Wait for the filewatcher to debounce this file write.
         sleep(Duration::from_secs(1)).await;
 
-        // 6.  Change this file and verify that this produces an update.
+        // 6.  Change this file and verify that this produces an update, sent
+        //     as a diff to the contents the Client saved.
         s.push_str("123");
         fs::write(&file_path, s).unwrap();
+        let message = client_rx.recv().await.unwrap();
+        let umc = cast!(message.message, EditorMessageContents::Update);
+        let contents = umc.contents.unwrap();
+        assert!(matches!(contents, CodeChatForWebUpdate::Diff(_)));
+        let saved = CodeChatForWeb {
+            metadata: SourceFileMetadata {
+                mode: "python".to_string(),
+                backlinks: Vec::new(),
+            },
+            source: CodeMirror {
+                doc: "testing()\n".to_string(),
+                doc_blocks: vec![],
+            },
+        };
+        let expected = CodeChatForWeb {
+            metadata: SourceFileMetadata {
+                mode: "python".to_string(),
+                backlinks: Vec::new(),
+            },
+            source: CodeMirror {
+                doc: "testing()\n123".to_string(),
+                doc_blocks: vec![],
+            },
+        };
+        assert_eq!(contents.into_contents(Some(&saved)), Ok(expected.clone()));
+        // Report that the diff can't be applied; this produces a snapshot.
+        send_response(&ide_tx_queue, message.id, Some("Out of sync".to_string())).await;
+        let snapshot_umc = get_message_as!(client_rx, EditorMessageContents::Update);
         assert_eq!(
-            get_message_as!(client_rx, EditorMessageContents::Update),
+            snapshot_umc,
             UpdateMessageContents {
-                contents: Some(CodeChatForWeb {
+                path: None,
+                contents: Some(CodeChatForWebUpdate::Snapshot(expected)),
+                base_version: None,
+                version: umc.version,
+                cursor_position: None,
+                scroll_position: None,
+            }
+        );
+        // Acknowledge this message.
+        send_response(&ide_tx_queue, 0, None).await;
+
+        // 7.  Send an update based on the contents the Client saved, before
+        //     the file changed. This merges both edits.
+        let stale_update = |doc: &str, version| {
+            EditorMessageContents::Update(UpdateMessageContents {
+                path: None,
+                contents: Some(CodeChatForWebUpdate::Snapshot(CodeChatForWeb {
                     metadata: SourceFileMetadata {
                         mode: "python".to_string(),
+                        backlinks: Vec::new(),
                     },
                     source: CodeMirror {
-                        doc: "testing()123".to_string(),
+                        doc: doc.to_string(),
                         doc_blocks: vec![],
                     },
-                }),
+                })),
+                base_version: Some(3.0),
+                version,
                 cursor_position: None,
                 scroll_position: None,
-            }
-        );
-        // Acknowledge this message.
+            })
+        };
+        ide_tx_queue
+            .send(EditorMessage {
+                id: 0,
+                message: stale_update("x = 1\ntesting()\n", 4.0),
+            })
+            .await
+            .unwrap();
+        // The Client receives the merged contents, then the result.
+        let merged_umc = get_message_as!(client_rx, EditorMessageContents::Update);
+        assert_eq!(merged_umc.base_version, Some(4.0));
+        let merged = cast!(merged_umc.contents.unwrap(), CodeChatForWebUpdate::Snapshot);
+        assert_eq!(merged.source.doc, "x = 1\ntesting()\n123");
         send_response(&ide_tx_queue, 0, None).await;
-
-        // 7.  Rename it and check for an close (the file watcher can't detect
-        //     the destination file, so it's treated as the file is deleted).
-        let mut dest = file_path.clone().parent().unwrap().to_path_buf();
-        dest.push("test2.py");
-        fs::rename(file_path, dest.as_path()).unwrap();
         assert_eq!(
-            client_rx.recv().await.unwrap(),
-            EditorMessage {
+            get_message_as2!(client_rx, EditorMessageContents::Result),
+            (None, None)
+        );
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "x = 1\ntesting()\n123"
+        );
+
+        // 8.  Send a conflicting update based on the same contents.
+        ide_tx_queue
+            .send(EditorMessage {
                 id: 0,
-                message: EditorMessageContents::Closed
-            }
+                message: stale_update("y = 2\ntesting()\n", 5.0),
+            })
+            .await
+            .unwrap();
+        assert_starts_with!(
+            cast!(
+                get_message_as2!(client_rx, EditorMessageContents::Result).0,
+                Option::Some
+            ),
+            "Conflict:"
+        );
+        // The file is unchanged.
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "x = 1\ntesting()\n123"
         );
 
-        // 8. Load another file from the Client.
+        // 9.  Load another file from the Client.
         let mut new_file_path = test_dir.clone();
         new_file_path.push("test1.py");
         let new_uri = format!(
@@ -1007,12 +1536,393 @@ mod tests {
         get_message_as!(client_rx, EditorMessageContents::Update);
         send_response(&ide_tx_queue, 0, None).await;
 
-        // 9. Writes to this file should produce an update.
+        // 10. Writes to this file should produce an update.
         fs::write(&new_file_path, "testing 1").unwrap();
         get_message_as!(client_rx, EditorMessageContents::Update);
 
-        // Each of the three invalid message types produces one error.
-        check_logger_errors(3);
+        // Each of the four invalid message types produces one error.
+        check_logger_errors(4);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Open `test.py` in the provided directory in a Client, then load it.
+    // Return queues to send to and receive from the Client, along with the app
+    // serving it.
+    async fn open_test_py(
+        test_dir: &Path,
+    ) -> (
+        Sender<EditorMessage>,
+        Receiver<EditorMessage>,
+        impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
+    ) {
+        let (je, app) = get_websocket_queues(test_dir).await;
+        let ide_tx_queue = je.from_websocket_tx;
+        let mut client_rx = je.to_websocket_rx;
+        get_message_as!(client_rx, EditorMessageContents::CurrentFile);
+        send_response(&ide_tx_queue, 0, None).await;
+        let uri = format!("/fw/fsc/1/{}/test.py", test_dir.to_string_lossy());
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        get_message_as!(client_rx, EditorMessageContents::Update);
+        send_response(&ide_tx_queue, 0, None).await;
+        (ide_tx_queue, client_rx, app)
+    }
+
+    #[actix_web::test]
+    async fn test_websocket_rename_1() {
+        configure_testing_logger();
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let (ide_tx_queue, mut client_rx, _app) = open_test_py(&test_dir).await;
+
+        // Rename the file; the Client should load it from its new location.
+        fs::rename(test_dir.join("test.py"), test_dir.join("test2.py")).unwrap();
+        let url_string = get_message_as!(client_rx, EditorMessageContents::CurrentFile);
+        assert!(url_string.ends_with("/test2.py"));
+        send_response(&ide_tx_queue, 0, None).await;
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_websocket_delete_1() {
+        configure_testing_logger();
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let (_ide_tx_queue, mut client_rx, _app) = open_test_py(&test_dir).await;
+
+        // Deleting the file closes it.
+        fs::remove_file(test_dir.join("test.py")).unwrap();
+        assert_eq!(
+            get_message(&mut client_rx).await,
+            EditorMessageContents::Closed
+        );
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_websocket_atomic_save_1() {
+        configure_testing_logger();
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let (ide_tx_queue, mut client_rx, _app) = open_test_py(&test_dir).await;
+
+        // Save the file the way many editors do: write a temporary file, then
+        // rename it to the file. This produces an update, rather than
+        // following the rename.
+        let temp_path = test_dir.join("test.py.tmp");
+        fs::write(&temp_path, "x = 1\n").unwrap();
+        fs::rename(&temp_path, test_dir.join("test.py")).unwrap();
+        let umc = get_message_as!(client_rx, EditorMessageContents::Update);
+        assert!(umc.base_version.is_some());
+        send_response(&ide_tx_queue, 0, None).await;
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_websocket_backup_save_1() {
+        configure_testing_logger();
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let (ide_tx_queue, mut client_rx, _app) = open_test_py(&test_dir).await;
+
+        // Save the file the way vim does: rename it to a backup, then write
+        // a new file in its place. This produces an update, rather than
+        // following the rename to the backup.
+        let file_path = test_dir.join("test.py");
+        fs::rename(&file_path, test_dir.join("test.py~")).unwrap();
+        fs::write(&file_path, "x = 1\n").unwrap();
+        let umc = get_message_as!(client_rx, EditorMessageContents::Update);
+        assert!(umc.base_version.is_some());
+        send_response(&ide_tx_queue, 0, None).await;
+        // The Client keeps editing the same file.
+        sleep(Duration::from_secs(3)).await;
+        assert!(client_rx.try_recv().is_err());
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_websocket_refresh_1() {
+        configure_testing_logger();
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let (je, app) = get_websocket_queues(&test_dir).await;
+        let ide_tx_queue = je.from_websocket_tx;
+        let mut client_rx = je.to_websocket_rx;
+
+        // Load the file, which indexes it and the files it refers to.
+        get_message_as!(client_rx, EditorMessageContents::CurrentFile);
+        send_response(&ide_tx_queue, 0, None).await;
+        let uri = format!("/fw/fsc/1/{}/test.py", test_dir.to_string_lossy());
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        get_message_as!(client_rx, EditorMessageContents::Update);
+        send_response(&ide_tx_queue, 0, None).await;
+
+        // 1.  Changing an image the file shows refreshes it.
+        fs::write(test_dir.join("fig.png"), "A new figure.").unwrap();
+        let urls = get_message_as!(client_rx, EditorMessageContents::Refresh);
+        assert_eq!(urls.len(), 1);
+        assert!(urls[0].ends_with("/fig.png"));
+        send_response(&ide_tx_queue, 0, None).await;
+
+        // 2.  Changing an unrelated file does nothing, while changing the TOC
+        //     refreshes the sidebar.
+        fs::write(test_dir.join("unrelated.md"), "# Unrelated").unwrap();
+        fs::write(test_dir.join("toc.md"), "# Contents").unwrap();
+        let urls = get_message_as!(client_rx, EditorMessageContents::Refresh);
+        assert_eq!(urls.len(), 1);
+        assert!(urls[0].ends_with("/toc.md"));
+        send_response(&ide_tx_queue, 0, None).await;
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Open the file at `uri` in a new Client, returning this Client's
+    // connection ID and queues.
+    async fn open_client(
+        app: &impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
+        app_data: &web::Data<AppState>,
+        uri: &str,
+    ) -> (u32, Sender<EditorMessage>, Receiver<EditorMessage>) {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        assert!(test::call_service(app, req).await.status().is_success());
+        sleep(Duration::from_millis(10)).await;
+        let connection_id = *app_data.connection_id.lock().unwrap();
+        let je = app_data
+            .filewatcher_client_queues
+            .lock()
+            .unwrap()
+            .remove(&connection_id.to_string())
+            .unwrap();
+        (connection_id, je.from_websocket_tx, je.to_websocket_rx)
+    }
+
+    #[actix_web::test]
+    async fn test_websocket_session_1() {
+        configure_testing_logger();
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let app_data = make_app_data(IP_ADDRESS, IP_PORT, None);
+        let app = test::init_service(configure_app(App::new(), &app_data)).await;
+        let file_uri = format!("{}/test.py", test_dir.to_string_lossy());
+
+        // Open the same file in two Clients.
+        let (id1, tx1, mut rx1) =
+            open_client(&app, &app_data, &format!("/fw/fsb/{file_uri}")).await;
+        get_message_as!(rx1, EditorMessageContents::CurrentFile);
+        send_response(&tx1, 0, None).await;
+        let (id2, tx2, mut rx2) = open_client(
+            &app,
+            &app_data,
+            &format!("/fw/fsb/{file_uri}?user=Reviewer"),
+        )
+        .await;
+        get_message_as!(rx2, EditorMessageContents::CurrentFile);
+        send_response(&tx2, 0, None).await;
+
+        // 1.  Each Client learns who else is viewing this file.
+        assert_eq!(
+            get_message_as!(rx1, EditorMessageContents::Presence),
+            vec![ClientPresence {
+                connection_id: id2.to_string(),
+                name: "Reviewer".to_string()
+            }]
+        );
+        send_response(&tx1, 0, None).await;
+        assert_eq!(
+            get_message_as!(rx2, EditorMessageContents::Presence),
+            vec![ClientPresence {
+                connection_id: id1.to_string(),
+                name: format!("Client {id1}")
+            }]
+        );
+        send_response(&tx2, 0, None).await;
+
+        // Load the file in both Clients.
+        let mut versions = Vec::new();
+        for (id, tx, rx) in [(id1, &tx1, &mut rx1), (id2, &tx2, &mut rx2)] {
+            let req = test::TestRequest::get()
+                .uri(&format!("/fw/fsc/{id}/{file_uri}"))
+                .to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+            versions.push(get_message_as!(*rx, EditorMessageContents::Update).version);
+            send_response(tx, 0, None).await;
+        }
+
+        // 2.  Edits the first Client saves are sent to the second.
+        tx1.send(EditorMessage {
+            id: 0,
+            message: EditorMessageContents::Update(UpdateMessageContents {
+                path: None,
+                contents: Some(CodeChatForWebUpdate::Snapshot(CodeChatForWeb {
+                    metadata: SourceFileMetadata {
+                        mode: "python".to_string(),
+                        backlinks: Vec::new(),
+                    },
+                    source: CodeMirror {
+                        doc: "testing()\n".to_string(),
+                        doc_blocks: vec![],
+                    },
+                })),
+                base_version: Some(versions[0]),
+                version: 1.0,
+                cursor_position: None,
+                scroll_position: None,
+            }),
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            get_message_as2!(rx1, EditorMessageContents::Result),
+            (None, None)
+        );
+        let umc = get_message_as!(rx2, EditorMessageContents::Update);
+        assert_eq!(umc.base_version, Some(versions[1]));
+        assert_eq!(umc.version, 1.0);
+        assert!(matches!(umc.contents, Some(CodeChatForWebUpdate::Diff(_))));
+        send_response(&tx2, 0, None).await;
+
+        // 3.  When the second Client closes, the first learns it's alone.
+        tx2.send(EditorMessage {
+            id: 0,
+            message: EditorMessageContents::Closed,
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            get_message_as!(rx1, EditorMessageContents::Presence),
+            vec![]
+        );
+        send_response(&tx1, 0, None).await;
+
+        // Neither Client reports the saved edits when the file watcher sees
+        // them.
+        sleep(Duration::from_secs(3)).await;
+        assert!(rx1.try_recv().is_err());
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_websocket_concurrent_save_1() {
+        configure_testing_logger();
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let app_data = make_app_data(IP_ADDRESS, IP_PORT, None);
+        let app = test::init_service(configure_app(App::new(), &app_data)).await;
+        let file_path = test_dir.join("test.py");
+        let file_uri = format!("{}/test.py", test_dir.to_string_lossy());
+
+        // Open and load the same file in two Clients.
+        let mut clients = Vec::new();
+        for _ in 0..2 {
+            let (id, tx, mut rx) =
+                open_client(&app, &app_data, &format!("/fw/fsb/{file_uri}")).await;
+            get_message_as!(rx, EditorMessageContents::CurrentFile);
+            send_response(&tx, 0, None).await;
+            clients.push((id, tx, rx));
+        }
+        get_message_as!(clients[0].2, EditorMessageContents::Presence);
+        send_response(&clients[0].1, 0, None).await;
+        get_message_as!(clients[1].2, EditorMessageContents::Presence);
+        send_response(&clients[1].1, 0, None).await;
+        let mut loaded = Vec::new();
+        for (id, tx, rx) in &mut clients {
+            let req = test::TestRequest::get()
+                .uri(&format!("/fw/fsc/{id}/{file_uri}"))
+                .to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+            let umc = get_message_as!(*rx, EditorMessageContents::Update);
+            send_response(tx, 0, None).await;
+            loaded.push((
+                umc.version,
+                cast!(umc.contents.unwrap(), CodeChatForWebUpdate::Snapshot),
+            ));
+        }
+        // Both Clients load the same version.
+        assert_eq!(loaded[0].0, loaded[1].0);
+
+        // Both Clients save edits to different lines, based on the version
+        // they loaded, before either save is processed.
+        let edits = [
+            "a = 10\nx = 0\ny = 0\nz = 0\nb = 2\n",
+            "a = 1\nx = 0\ny = 0\nz = 0\nb = 20\n",
+        ];
+        let merged = "a = 10\nx = 0\ny = 0\nz = 0\nb = 20\n";
+        for (index, (_, tx, _)) in clients.iter().enumerate() {
+            let mut contents = loaded[index].1.clone();
+            contents.source.doc = edits[index].to_string();
+            tx.send(EditorMessage {
+                id: index as u32 + 1,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
+                    contents: Some(CodeChatForWebUpdate::Snapshot(contents)),
+                    base_version: Some(loaded[index].0),
+                    version: index as f64 + 1.0,
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            })
+            .await
+            .unwrap();
+        }
+
+        // Each save succeeds, and each Client ends up with both edits, whichever
+        // save was processed first. Track the contents the Server sent to each
+        // Client.
+        for (index, (_, tx, rx)) in clients.iter_mut().enumerate() {
+            let saved_version = index as f64 + 1.0;
+            let mut contents = loaded[index].1.clone();
+            let mut is_saved = false;
+            while !is_saved || contents.source.doc != merged {
+                match get_message(rx).await {
+                    EditorMessageContents::Update(umc) => {
+                        // If the Server merged this Client's edits, it sends
+                        // the merged contents before the result of the save.
+                        contents = umc
+                            .contents
+                            .unwrap()
+                            .into_contents(Some(&contents))
+                            .unwrap();
+                        send_response(tx, 0, None).await;
+                    }
+                    EditorMessageContents::Result(err, _) => {
+                        assert_eq!(err, None);
+                        is_saved = true;
+                        // If this Client's edits weren't merged, the Server
+                        // has its edits.
+                        if contents.source.doc != merged {
+                            contents.source.doc = edits[index].to_string();
+                        }
+                    }
+                    other => {
+                        panic!("Unexpected message {other:?} after saving version {saved_version}.")
+                    }
+                }
+            }
+        }
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), merged);
+
+        // Neither Client receives anything more when the file watcher sees
+        // these saves.
+        sleep(Duration::from_secs(3)).await;
+        for (_, _, rx) in &mut clients {
+            assert!(rx.try_recv().is_err());
+        }
+
+        check_logger_errors(0);
         // Report any errors produced when removing the temporary directory.
         temp_dir.close().unwrap();
     }
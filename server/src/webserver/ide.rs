@@ -0,0 +1,1925 @@
+/// Copyright (C) 2023 Bryan A. Jones.
+///
+/// This file is part of the CodeChat Editor. The CodeChat Editor is free
+/// software: you can redistribute it and/or modify it under the terms of the
+/// GNU General Public License as published by the Free Software Foundation,
+/// either version 3 of the License, or (at your option) any later version.
+///
+/// The CodeChat Editor is distributed in the hope that it will be useful, but
+/// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+/// or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+/// more details.
+///
+/// You should have received a copy of the GNU General Public License along with
+/// the CodeChat Editor. If not, see
+/// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+///
+/// # `ide.rs` -- Implement server-side functionality for IDEs
+///
+/// An IDE extension or editor plugin connects to the websocket at
+/// `/ide/ws-ide/{connection_id}`, then exchanges `EditorMessage`s with the
+/// Server, which translates and relays them to and from the Client. The IDE
+/// sends and receives the plain text of source files; see `Capabilities` for
+/// the optional parts of this protocol. The VSCode extension uses the same
+/// routes with the `/vsc` prefix in place of `/ide`.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+// ### Third-party
+use actix_web::{
+    error::{Error, ErrorBadRequest},
+    get, web, HttpRequest, HttpResponse,
+};
+use log::{error, info, warn};
+use open;
+use tokio::{select, sync::mpsc};
+
+// ### Local
+use super::{
+    client_framework_html, client_websocket, filesystem_endpoint, get_client_framework, is_stale,
+    path_to_url, send_response, serve_file, serve_file_from_disk, url_to_path, AppState,
+    EditorMessage, EditorMessageContents, ProcessingTaskHttpRequest, ProtocolInfo, ResultContents,
+    SharedContents, SimpleHttpResponse, UpdateMessageContents, VersionHistory, WebsocketQueues,
+    PROTOCOL_VERSION,
+};
+use crate::{
+    oneshot_send,
+    processing::{
+        codechat_for_web_to_source, new_version, source_to_codechat_for_web_string, CodeChatForWeb,
+        CodeChatForWebUpdate, CodeMirror, TranslationCache, TranslationResultsString,
+    },
+    queue_send,
+};
+
+// ## Code
+/// Define a websocket handler for the IDE. The route's prefix, `ide` or `vsc`,
+/// prefixes the URLs the Client uses for this connection.
+#[get("/{prefix:ide|vsc}/ws-ide/{connection_id}")]
+pub async fn ide_websocket(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    body: web::Payload,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let (prefix, connection_id_str) = path.into_inner();
+    let connection_id = web::Path::from(connection_id_str.clone());
+
+    // There are three cases for this `connection_id`:
+    //
+    // 1.  It hasn't been used before. In this case, create the appropriate
+    //     queues and start websocket and processing tasks.
+    // 2.  It's in use, but was disconnected. In this case, re-use the queues
+    //     and start the websocket task; the processing task is still running.
+    // 3.  It's in use by another IDE. This is an error, but I don't have a way
+    //     to detect it yet.
+    //
+    // Check case 3.
+    if app_state
+        .ide_connection_id
+        .lock()
+        .unwrap()
+        .contains(&connection_id_str)
+    {
+        let msg = format!("Connection ID {connection_id_str} already in use.");
+        error!("{msg}");
+        return Err(ErrorBadRequest(msg));
+    }
+
+    // Now case 2.
+    if app_state
+        .ide_queues
+        .lock()
+        .unwrap()
+        .contains_key(&connection_id_str)
+    {
+        return client_websocket(connection_id, req, body, app_state.ide_queues.clone()).await;
+    }
+
+    // Then this is case 1. Add the connection ID to the list of active
+    // connections.
+    let (from_ide_tx, mut from_ide_rx) = mpsc::channel(10);
+    let (to_ide_tx, to_ide_rx) = mpsc::channel(10);
+    assert!(app_state
+        .ide_queues
+        .lock()
+        .unwrap()
+        .insert(
+            connection_id_str.clone(),
+            WebsocketQueues {
+                from_websocket_tx: from_ide_tx,
+                to_websocket_rx: to_ide_rx,
+            },
+        )
+        .is_none());
+    let (from_client_tx, mut from_client_rx) = mpsc::channel(10);
+    let (to_client_tx, to_client_rx) = mpsc::channel(10);
+    assert!(app_state
+        .ide_client_queues
+        .lock()
+        .unwrap()
+        .insert(
+            connection_id_str.clone(),
+            WebsocketQueues {
+                from_websocket_tx: from_client_tx,
+                to_websocket_rx: to_client_rx,
+            },
+        )
+        .is_none());
+    app_state
+        .ide_connection_id
+        .lock()
+        .unwrap()
+        .insert(connection_id_str.clone());
+
+    let connection_id_task = connection_id_str;
+    let app_state_task = app_state.clone();
+    actix_rt::spawn(async move {
+        // Use a
+        // [labeled block expression](https://doc.rust-lang.org/reference/expressions/loop-expr.html#labelled-block-expressions)
+        // to provide a way to exit the current task.
+        'task: {
+            // Get the first message sent by the IDE.
+            let Some(message): std::option::Option<EditorMessage> = from_ide_rx.recv().await else {
+                error!("{}", "IDE websocket received no data.");
+                break 'task;
+            };
+
+            // Make sure it's the `Opened` message.
+            let EditorMessageContents::Opened(ide_type, protocol_info) = message.message else {
+                let msg = format!("Unexpected message {message:?}");
+                error!("{msg}");
+                send_response(&to_ide_tx, message.id, Some(msg)).await;
+
+                // Send a `Closed` message to shut down the websocket.
+                queue_send!(to_ide_tx.send(EditorMessage { id: 0, message: EditorMessageContents::Closed}), 'task);
+                break 'task;
+            };
+
+            // Ensure the IDE speaks this Server's version of the protocol. In
+            // either case, reply with the protocol this Server speaks.
+            if protocol_info.protocol_version != PROTOCOL_VERSION {
+                let msg = format!(
+                    "Unsupported protocol version {}; this Server supports version {PROTOCOL_VERSION}.",
+                    protocol_info.protocol_version
+                );
+                error!("{msg}");
+                queue_send!(to_ide_tx.send(EditorMessage {
+                    id: message.id,
+                    message: EditorMessageContents::Result(Some(msg), Some(ResultContents::Opened(ProtocolInfo::server())))
+                }), 'task);
+
+                // Close the connection.
+                queue_send!(to_ide_tx.send(EditorMessage { id: 0, message: EditorMessageContents::Closed}), 'task);
+                break 'task;
+            }
+            // The parts of the protocol which the IDE supports.
+            let ide_capabilities = protocol_info.capabilities;
+            // The prefix of URLs which the Client uses to load files.
+            let fs_prefix = format!("/{prefix}/fs");
+
+            info!("{} connected.", ide_type.name());
+            if ide_type.is_self_hosted() {
+                // Send a response (successful) to the `Opened` message.
+                queue_send!(to_ide_tx.send(EditorMessage {
+                    id: message.id,
+                    message: EditorMessageContents::Result(None, Some(ResultContents::Opened(ProtocolInfo::server())))
+                }), 'task);
+
+                // Send the HTML for the internal browser. Since it's
+                // not served by this server, provide the server's URL
+                // as the base for all relative URLs.
+                let client_html = match client_framework_html(
+                    &format!("{prefix}/ws-client"),
+                    &connection_id_task,
+                    false,
+                    Some(&format!("{}/", app_state_task.server_url)),
+                ) {
+                    Ok(html) => html,
+                    Err(err) => {
+                        error!("{err}");
+                        queue_send!(to_ide_tx.send(EditorMessage {
+                            id: 1,
+                            message: EditorMessageContents::Closed
+                        }), 'task);
+                        break 'task;
+                    }
+                };
+                queue_send!(to_ide_tx.send(EditorMessage {
+                    id: 0,
+                    message: EditorMessageContents::ClientHtml(client_html)
+                }), 'task);
+
+                // Wait for the response.
+                let Some(message): std::option::Option<EditorMessage> = from_ide_rx.recv().await
+                else {
+                    error!("{}", "IDE websocket received no data.");
+                    break 'task;
+                };
+
+                // Make sure it's the `Result` message.
+                if let Some(err) = match message.message {
+                    EditorMessageContents::Result(err, result_contents) => {
+                        if let Some(err_msg) = err {
+                            Some(format!("Error in ClientHtml: {err_msg}"))
+                        } else {
+                            result_contents
+                                .map(|contents| format!("Unexpected result contents {contents:?}."))
+                        }
+                    }
+                    _ => Some(format!("Unexpected message {message:?}")),
+                } {
+                    error!("{err}");
+                    // Send a `Closed` message.
+                    queue_send!(to_ide_tx.send(EditorMessage {
+                        id: 1,
+                        message: EditorMessageContents::Closed
+                    }), 'task);
+                    break 'task;
+                };
+            } else {
+                // Open the Client in an external browser.
+                if let Err(err) = open::that_detached(format!(
+                    "{}/{prefix}/cf/{connection_id_task}",
+                    app_state_task.server_url
+                )) {
+                    let msg = format!("Unable to open web browser: {err}");
+                    error!("{msg}");
+                    send_response(&to_ide_tx, message.id, Some(msg)).await;
+
+                    // Send a `Closed` message.
+                    queue_send!(to_ide_tx.send(EditorMessage{
+                        id: 0,
+                        message: EditorMessageContents::Closed
+                    }), 'task);
+                    break 'task;
+                }
+                // Send a response (successful) to the `Opened` message.
+                queue_send!(to_ide_tx.send(EditorMessage {
+                    id: message.id,
+                    message: EditorMessageContents::Result(None, Some(ResultContents::Opened(ProtocolInfo::server())))
+                }), 'task);
+            }
+
+            // All further messages are handled in the main loop.
+            //
+            // The current file being edited, as provided by the IDE or the
+            // Client.
+            let mut current_file: Option<PathBuf> = None;
+            // The previous translation of the current file, which allows
+            // translating each update from the IDE incrementally.
+            let mut translation_cache = TranslationCache::default();
+            // The version of the current file's contents.
+            let mut version = new_version();
+            // The contents of the current file shared with the IDE and with
+            // the Client, which allow sending updates as diffs.
+            let mut ide_contents = SharedContents::default();
+            let mut client_contents = SharedContents::default();
+            // Recent versions of the current file, used to merge concurrent
+            // edits from the IDE and the Client.
+            let mut history = VersionHistory::default();
+            // The ids of `Update` messages sent as diffs. If the recipient
+            // can't apply a diff, send a snapshot instead.
+            let mut pending_ide_diffs: HashSet<u32> = HashSet::new();
+            let mut pending_client_diffs: HashSet<u32> = HashSet::new();
+            // Assign each message this task sends a unique id.
+            let mut id: u32 = 0;
+            // Messages sent to the Client which await a `Result`. Each maps
+            // the id of the message sent to the Client to the id of the IDE
+            // message which produced it, or `None` if this message
+            // originated in this task.
+            let mut pending_client_messages: HashMap<u32, Option<u32>> = HashMap::new();
+            // Likewise, messages sent to the IDE which await a `Result`.
+            let mut pending_ide_messages: HashMap<u32, Option<u32>> = HashMap::new();
+            // HTTP requests awaiting the IDE's response to a `LoadFile`
+            // message, indexed by the id of that message.
+            let mut pending_load_files: HashMap<u32, ProcessingTaskHttpRequest> = HashMap::new();
+
+            // Create a queue for HTTP requests to communicate with this task.
+            let (from_http_tx, mut from_http_rx) = mpsc::channel(10);
+            app_state_task
+                .processing_task_queue_tx
+                .lock()
+                .unwrap()
+                .insert(connection_id_task.clone(), from_http_tx);
+            loop {
+                select! {
+                    // Look for messages from the IDE.
+                    Some(ide_message) = from_ide_rx.recv() => {
+                        match ide_message.message {
+                            // Handle messages that the IDE must not send.
+                            EditorMessageContents::Opened(..) | EditorMessageContents::LoadFile(_) | EditorMessageContents::ClientHtml(_) | EditorMessageContents::Refresh(_) | EditorMessageContents::Presence(_) => {
+                                let msg = "IDE must not send this message.";
+                                error!("{msg}");
+                                send_response(&to_ide_tx, ide_message.id, Some(msg.to_string())).await;
+                            },
+
+                            // Forward a `Closed` message to the Client, then
+                            // shut down.
+                            EditorMessageContents::Closed => {
+                                info!("IDE closed.");
+                                queue_send!(to_client_tx.send(ide_message));
+                                break;
+                            },
+
+                            // Pass this on to the Client, recording it so that
+                            // the Client's `Result` is routed back to the IDE.
+                            EditorMessageContents::RequestClose => {
+                                pending_client_messages.insert(id, Some(ide_message.id));
+                                queue_send!(to_client_tx.send(EditorMessage {
+                                    id,
+                                    message: ide_message.message
+                                }));
+                                id += 1;
+                            },
+
+                            // Route a `Result` (or a timeout) back to the
+                            // Client message which produced it.
+                            EditorMessageContents::Result(err, result_contents) => {
+                                // If this is a response to a `LoadFile`, use it
+                                // to complete the matching HTTP request.
+                                if let Some(http_request) = pending_load_files.remove(&ide_message.id) {
+                                    let is_current = current_file.as_deref() == Some(http_request.request_path.as_path());
+                                    let (simple_http_response, option_codechat_for_web) = match (err, result_contents) {
+                                        // The IDE provided the file's contents,
+                                        // which may contain unsaved edits.
+                                        (None, Some(ResultContents::LoadFile(load_file_result_contents))) => {
+                                            // If these aren't the contents the
+                                            // IDE last shared, they begin a new
+                                            // version.
+                                            if is_current && ide_contents.received.as_ref().map(|received| &received.source.doc) != Some(&load_file_result_contents.contents) {
+                                                ide_contents = SharedContents::default();
+                                                version = new_version();
+                                                history.clear();
+                                                history.push(version, load_file_result_contents.contents.clone());
+                                            }
+                                            serve_file(
+                                                &http_request.request_path,
+                                                &load_file_result_contents.contents,
+                                                http_request.is_toc,
+                                                is_current,
+                                                http_request.is_test_mode
+                                            ).await
+                                        },
+                                        // The IDE doesn't have this file loaded;
+                                        // fall back to the filesystem.
+                                        (Some(err_msg), _) if err_msg == "not loaded" => {
+                                            if is_current {
+                                                ide_contents = SharedContents::default();
+                                                version = new_version();
+                                                history.clear();
+                                            }
+                                            serve_file_from_disk(
+                                                &http_request,
+                                                current_file.as_deref().unwrap_or(Path::new(""))
+                                            ).await
+                                        },
+                                        (Some(err_msg), _) => (SimpleHttpResponse::Err(format!(
+                                            "<p>Unable to load file {:?}: {err_msg}.</p>", http_request.request_path
+                                        )), None),
+                                        (None, _) => (SimpleHttpResponse::Err(format!(
+                                            "<p>The IDE provided no contents for file {:?}.</p>", http_request.request_path
+                                        )), None),
+                                    };
+                                    // If this file is editable and is the
+                                    // current file, send an `Update`. The
+                                    // `simple_http_response` contains the
+                                    // Client, which needs a snapshot of the
+                                    // file's contents.
+                                    if let Some(codechat_for_web) = option_codechat_for_web {
+                                        client_contents = SharedContents::default();
+                                        pending_client_messages.insert(id, None);
+                                        queue_send!(to_client_tx.send(EditorMessage {
+                                            id,
+                                            message: EditorMessageContents::Update(UpdateMessageContents {
+                                                path: current_file.clone(),
+                                                contents: Some(client_contents.send(codechat_for_web)),
+                                                base_version: None,
+                                                version,
+                                                cursor_position: None,
+                                                scroll_position: None,
+                                            })
+                                        }));
+                                        id += 1;
+                                    }
+                                    oneshot_send!(http_request.response_queue.send(simple_http_response));
+                                    continue;
+                                }
+                                // If the IDE couldn't apply a diff, send a
+                                // snapshot instead.
+                                if pending_ide_diffs.remove(&ide_message.id) && err.is_some() {
+                                    if let Some(snapshot) = ide_contents.snapshot() {
+                                        let origin = pending_ide_messages.remove(&ide_message.id).flatten();
+                                        pending_ide_messages.insert(id, origin);
+                                        queue_send!(to_ide_tx.send(EditorMessage {
+                                            id,
+                                            message: EditorMessageContents::Update(UpdateMessageContents {
+                                                path: current_file.clone(),
+                                                contents: Some(snapshot),
+                                                base_version: None,
+                                                version,
+                                                cursor_position: None,
+                                                scroll_position: None,
+                                            })
+                                        }));
+                                        id += 1;
+                                        continue;
+                                    }
+                                }
+                                match pending_ide_messages.remove(&ide_message.id) {
+                                    Some(Some(client_id)) => {
+                                        queue_send!(to_client_tx.send(EditorMessage {
+                                            id: client_id,
+                                            message: EditorMessageContents::Result(err, result_contents)
+                                        }));
+                                    }
+                                    Some(None) => {
+                                        if let Some(err_msg) = err {
+                                            error!("Error in message {}: {err_msg}.", ide_message.id);
+                                        }
+                                    }
+                                    None => warn!("Unexpected IDE result for message id {}.", ide_message.id),
+                                }
+                            },
+
+                            // Translate the plain text of an IDE update to
+                            // the CodeChat Editor format, then send it to the
+                            // Client.
+                            EditorMessageContents::Update(update) => {
+                                let result = 'process: {
+                                    // First, see if this update refers to a
+                                    // different file. If so, tell the Client
+                                    // to load it before sending the update.
+                                    if let Some(path) = update.path {
+                                        if current_file.as_ref() != Some(&path) {
+                                            pending_client_messages.insert(id, None);
+                                            queue_send!(to_client_tx.send(EditorMessage {
+                                                id,
+                                                message: EditorMessageContents::CurrentFile(path_to_url(&fs_prefix, &connection_id_task, &path))
+                                            }), 'task);
+                                            id += 1;
+                                            current_file = Some(path);
+                                            ide_contents = SharedContents::default();
+                                            client_contents = SharedContents::default();
+                                            history.clear();
+                                        }
+                                    }
+                                    let base_version = version;
+                                    let contents = match update.contents {
+                                        None => None,
+                                        Some(codechat_for_web_update) => {
+                                            let Some(ref file_path) = current_file else {
+                                                break 'process Some("No current file.".to_string());
+                                            };
+                                            let codechat_for_web = match ide_contents.receive(version, update.base_version, codechat_for_web_update) {
+                                                Ok(codechat_for_web) => codechat_for_web,
+                                                Err(err) => break 'process Some(err),
+                                            };
+                                            // If the Client changed this file
+                                            // since the IDE's edits began,
+                                            // merge these changes.
+                                            let is_merged = is_stale(version, update.base_version);
+                                            let source = match history.merge(version, update.base_version, codechat_for_web.source.doc) {
+                                                Ok(source) => source,
+                                                Err(err) => break 'process Some(err),
+                                            };
+                                            version = if is_merged { new_version() } else { update.version };
+                                            history.push(version, source.clone());
+                                            if is_merged {
+                                                // The IDE doesn't have the
+                                                // merged contents; send them.
+                                                ide_contents = SharedContents::default();
+                                                let contents = ide_contents.send(CodeChatForWeb {
+                                                    metadata: codechat_for_web.metadata,
+                                                    source: CodeMirror {
+                                                        doc: source.clone(),
+                                                        doc_blocks: vec![],
+                                                    },
+                                                });
+                                                ide_contents.received = ide_contents.sent.clone();
+                                                pending_ide_messages.insert(id, None);
+                                                queue_send!(to_ide_tx.send(EditorMessage {
+                                                    id,
+                                                    message: EditorMessageContents::Update(UpdateMessageContents {
+                                                        path: current_file.clone(),
+                                                        contents: Some(contents),
+                                                        base_version: Some(update.version),
+                                                        version,
+                                                        cursor_position: None,
+                                                        scroll_position: None,
+                                                    })
+                                                }), 'task);
+                                                id += 1;
+                                            } else {
+                                                // The IDE displays the contents
+                                                // it sent.
+                                                ide_contents.sent = ide_contents.received.clone();
+                                            }
+                                            match source_to_codechat_for_web_string(&source, file_path, false, Some(&mut translation_cache)).0 {
+                                                TranslationResultsString::CodeChat(cc) => Some(client_contents.send(cc)),
+                                                TranslationResultsString::Err(err) => break 'process Some(format!("Unable to translate to CodeChat: {err}")),
+                                                // The Client displays files
+                                                // unknown to the CodeChat Editor
+                                                // as raw text, which requires no
+                                                // update.
+                                                TranslationResultsString::Unknown | TranslationResultsString::Toc(_) => None,
+                                            }
+                                        }
+                                    };
+                                    if matches!(contents, Some(CodeChatForWebUpdate::Diff(_))) {
+                                        pending_client_diffs.insert(id);
+                                    }
+                                    // Translate the IDE's cursor and scroll
+                                    // positions to the Client's coordinates,
+                                    // ignoring any the IDE doesn't support.
+                                    let (cursor_position, scroll_position) = match (history.get(version), &client_contents.sent) {
+                                        (Some(source), Some(sent)) => (
+                                            update.cursor_position.filter(|_| ide_capabilities.cursor_position).map(|cursor_position| cursor_position.to_code_mirror(source, &sent.source)),
+                                            update.scroll_position.filter(|_| ide_capabilities.scroll_position).map(|scroll_position| scroll_position.to_code_mirror(source, &sent.source)),
+                                        ),
+                                        _ => (None, None),
+                                    };
+                                    pending_client_messages.insert(id, Some(ide_message.id));
+                                    queue_send!(to_client_tx.send(EditorMessage {
+                                        id,
+                                        message: EditorMessageContents::Update(UpdateMessageContents {
+                                            path: current_file.clone(),
+                                            contents,
+                                            base_version: Some(base_version),
+                                            version,
+                                            cursor_position,
+                                            scroll_position,
+                                        })
+                                    }), 'task);
+                                    id += 1;
+                                    None
+                                };
+                                // Report any errors; otherwise, the Client's
+                                // `Result` will be forwarded to the IDE.
+                                if result.is_some() {
+                                    send_response(&to_ide_tx, ide_message.id, result).await;
+                                }
+                            }
+
+                            // Translate the path to a URL, then send it to the
+                            // Client.
+                            EditorMessageContents::CurrentFile(file_path) => {
+                                let file_path = PathBuf::from(file_path);
+                                pending_client_messages.insert(id, Some(ide_message.id));
+                                queue_send!(to_client_tx.send(EditorMessage {
+                                    id,
+                                    message: EditorMessageContents::CurrentFile(path_to_url(&fs_prefix, &connection_id_task, &file_path))
+                                }));
+                                id += 1;
+                                current_file = Some(file_path);
+                                ide_contents = SharedContents::default();
+                                client_contents = SharedContents::default();
+                                history.clear();
+                            }
+                        }
+                    }
+
+                    // Ask the IDE for the contents of a file requested by
+                    // the Client, so that any unsaved edits are served. The
+                    // IDE's `Result` completes this request.
+                    Some(http_request) = from_http_rx.recv() => {
+                        // If the IDE can't provide files, serve this file
+                        // from disk.
+                        if !ide_capabilities.load_file {
+                            if current_file.as_deref() == Some(http_request.request_path.as_path()) {
+                                ide_contents = SharedContents::default();
+                                version = new_version();
+                                history.clear();
+                            }
+                            let (simple_http_response, option_codechat_for_web) = serve_file_from_disk(
+                                &http_request,
+                                current_file.as_deref().unwrap_or(Path::new(""))
+                            ).await;
+                            if let Some(codechat_for_web) = option_codechat_for_web {
+                                client_contents = SharedContents::default();
+                                pending_client_messages.insert(id, None);
+                                queue_send!(to_client_tx.send(EditorMessage {
+                                    id,
+                                    message: EditorMessageContents::Update(UpdateMessageContents {
+                                        path: current_file.clone(),
+                                        contents: Some(client_contents.send(codechat_for_web)),
+                                        base_version: None,
+                                        version,
+                                        cursor_position: None,
+                                        scroll_position: None,
+                                    })
+                                }));
+                                id += 1;
+                            }
+                            oneshot_send!(http_request.response_queue.send(simple_http_response));
+                            continue;
+                        }
+                        queue_send!(to_ide_tx.send(EditorMessage {
+                            id,
+                            message: EditorMessageContents::LoadFile(http_request.request_path.clone())
+                        }));
+                        pending_load_files.insert(id, http_request);
+                        id += 1;
+                    }
+
+                    // Look for messages from the Client.
+                    Some(client_message) = from_client_rx.recv() => {
+                        match client_message.message {
+                            // Handle messages that the Client must not send.
+                            EditorMessageContents::Opened(..) | EditorMessageContents::RequestClose | EditorMessageContents::LoadFile(_) | EditorMessageContents::ClientHtml(_) | EditorMessageContents::Refresh(_) | EditorMessageContents::Presence(_) => {
+                                let msg = "Client must not send this message.";
+                                error!("{msg}");
+                                send_response(&to_client_tx, client_message.id, Some(msg.to_string())).await;
+                            },
+
+                            // Forward a `Closed` message to the IDE, then shut
+                            // down.
+                            EditorMessageContents::Closed => {
+                                info!("Client closed.");
+                                queue_send!(to_ide_tx.send(client_message));
+                                break;
+                            },
+
+                            // Route a `Result` (or a timeout) back to the IDE
+                            // message which produced it.
+                            EditorMessageContents::Result(err, result_contents) => {
+                                // If the Client couldn't apply a diff, send a
+                                // snapshot instead.
+                                if pending_client_diffs.remove(&client_message.id) && err.is_some() {
+                                    if let Some(snapshot) = client_contents.snapshot() {
+                                        let origin = pending_client_messages.remove(&client_message.id).flatten();
+                                        pending_client_messages.insert(id, origin);
+                                        queue_send!(to_client_tx.send(EditorMessage {
+                                            id,
+                                            message: EditorMessageContents::Update(UpdateMessageContents {
+                                                path: current_file.clone(),
+                                                contents: Some(snapshot),
+                                                base_version: None,
+                                                version,
+                                                cursor_position: None,
+                                                scroll_position: None,
+                                            })
+                                        }));
+                                        id += 1;
+                                        continue;
+                                    }
+                                }
+                                match pending_client_messages.remove(&client_message.id) {
+                                    Some(Some(ide_id)) => {
+                                        queue_send!(to_ide_tx.send(EditorMessage {
+                                            id: ide_id,
+                                            message: EditorMessageContents::Result(err, result_contents)
+                                        }));
+                                    }
+                                    Some(None) => {
+                                        if let Some(err_msg) = err {
+                                            error!("Error in message {}: {err_msg}.", client_message.id);
+                                        }
+                                    }
+                                    None => warn!("Unexpected Client result for message id {}.", client_message.id),
+                                }
+                            },
+
+                            // Translate the Client's edits back to source
+                            // code, then send it to the IDE.
+                            EditorMessageContents::Update(update) => {
+                                let result = 'process: {
+                                    let Some(ref file_path) = current_file else {
+                                        break 'process Some("No current file.".to_string());
+                                    };
+                                    let base_version = version;
+                                    let contents = match update.contents {
+                                        None => None,
+                                        Some(codechat_for_web_update) => {
+                                            let codechat_for_web = match client_contents.receive(version, update.base_version, codechat_for_web_update) {
+                                                Ok(codechat_for_web) => codechat_for_web,
+                                                Err(err) => break 'process Some(err),
+                                            };
+                                            let metadata = codechat_for_web.metadata.clone();
+                                            match codechat_for_web_to_source(codechat_for_web) {
+                                                Ok(source) => {
+                                                    // If the IDE changed this
+                                                    // file since the Client's
+                                                    // edits began, merge these
+                                                    // changes.
+                                                    let is_merged = is_stale(version, update.base_version);
+                                                    let source = match history.merge(version, update.base_version, source) {
+                                                        Ok(source) => source,
+                                                        Err(err) => break 'process Some(err),
+                                                    };
+                                                    version = if is_merged { new_version() } else { update.version };
+                                                    history.push(version, source.clone());
+                                                    // Translating the source
+                                                    // updates the anchor index
+                                                    // and provides the contents
+                                                    // the Client displays, so
+                                                    // that later updates to the
+                                                    // Client may be diffs.
+                                                    if let TranslationResultsString::CodeChat(cc) = source_to_codechat_for_web_string(&source, file_path, false, Some(&mut translation_cache)).0 {
+                                                        if is_merged {
+                                                            // The Client doesn't
+                                                            // have the merged
+                                                            // contents; send
+                                                            // them.
+                                                            client_contents = SharedContents::default();
+                                                            pending_client_messages.insert(id, None);
+                                                            queue_send!(to_client_tx.send(EditorMessage {
+                                                                id,
+                                                                message: EditorMessageContents::Update(UpdateMessageContents {
+                                                                    path: Some(file_path.clone()),
+                                                                    contents: Some(client_contents.send(cc)),
+                                                                    base_version: Some(update.version),
+                                                                    version,
+                                                                    cursor_position: None,
+                                                                    scroll_position: None,
+                                                                })
+                                                            }), 'task);
+                                                            id += 1;
+                                                        } else {
+                                                            client_contents.set_displayed(cc);
+                                                        }
+                                                    }
+                                                    // An IDE which can't apply
+                                                    // diffs receives snapshots.
+                                                    if !ide_capabilities.diffs {
+                                                        ide_contents.sent = None;
+                                                    }
+                                                    let contents = ide_contents.send(CodeChatForWeb {
+                                                        metadata,
+                                                        source: CodeMirror {
+                                                            doc: source,
+                                                            doc_blocks: vec![],
+                                                        },
+                                                    });
+                                                    // The IDE will send changes
+                                                    // relative to these contents.
+                                                    ide_contents.received = ide_contents.sent.clone();
+                                                    Some(contents)
+                                                }
+                                                Err(message) => break 'process Some(format!("Unable to translate to source: {message}")),
+                                            }
+                                        }
+                                    };
+                                    if matches!(contents, Some(CodeChatForWebUpdate::Diff(_))) {
+                                        pending_ide_diffs.insert(id);
+                                    }
+                                    // Translate the Client's cursor and
+                                    // scroll positions to the IDE's
+                                    // coordinates, if the IDE supports them.
+                                    let (cursor_position, scroll_position) = match (history.get(version), &client_contents.sent) {
+                                        (Some(source), Some(sent)) => (
+                                            update.cursor_position.filter(|_| ide_capabilities.cursor_position).map(|cursor_position| cursor_position.to_source(source, &sent.source)),
+                                            update.scroll_position.filter(|_| ide_capabilities.scroll_position).map(|scroll_position| scroll_position.to_source(source, &sent.source)),
+                                        ),
+                                        _ => (None, None),
+                                    };
+                                    pending_ide_messages.insert(id, Some(client_message.id));
+                                    queue_send!(to_ide_tx.send(EditorMessage {
+                                        id,
+                                        message: EditorMessageContents::Update(UpdateMessageContents {
+                                            path: Some(file_path.clone()),
+                                            contents,
+                                            base_version: Some(base_version),
+                                            version,
+                                            cursor_position,
+                                            scroll_position,
+                                        })
+                                    }), 'task);
+                                    id += 1;
+                                    None
+                                };
+                                if result.is_some() {
+                                    send_response(&to_client_tx, client_message.id, result).await;
+                                }
+                            }
+
+                            // Translate the URL to a path, then send it to the
+                            // IDE.
+                            EditorMessageContents::CurrentFile(url_string) => {
+                                match url_to_path(url_string, &[&prefix, "fs"]) {
+                                    Err(err) => send_response(&to_client_tx, client_message.id, Some(err)).await,
+                                    Ok(file_path) => {
+                                        pending_ide_messages.insert(id, Some(client_message.id));
+                                        queue_send!(to_ide_tx.send(EditorMessage {
+                                            id,
+                                            message: EditorMessageContents::CurrentFile(file_path.to_string_lossy().to_string())
+                                        }));
+                                        id += 1;
+                                        current_file = Some(file_path);
+                                        ide_contents = SharedContents::default();
+                                        client_contents = SharedContents::default();
+                                        history.clear();
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    else => break
+                }
+            }
+        }
+
+        // Remove this connection; its ID may now be reused.
+        app_state_task
+            .processing_task_queue_tx
+            .lock()
+            .unwrap()
+            .remove(&connection_id_task);
+        app_state_task
+            .ide_queues
+            .lock()
+            .unwrap()
+            .remove(&connection_id_task);
+        app_state_task
+            .ide_client_queues
+            .lock()
+            .unwrap()
+            .remove(&connection_id_task);
+        app_state_task
+            .ide_connection_id
+            .lock()
+            .unwrap()
+            .remove(&connection_id_task);
+        info!("IDE processing task exiting.");
+    });
+
+    // Move data between the IDE and the processing task via queues.
+    client_websocket(connection_id, req, body, app_state.ide_queues.clone()).await
+}
+
+/// Serve the Client framework for an IDE connection, for display in an
+/// external browser.
+#[get("/{prefix:ide|vsc}/cf/{connection_id}")]
+async fn ide_client_framework(path: web::Path<(String, String)>, req: HttpRequest) -> HttpResponse {
+    let (prefix, connection_id) = path.into_inner();
+    get_client_framework(&req, &format!("{prefix}/ws-client"), &connection_id)
+}
+
+/// Define a websocket handler for the CodeChat Editor Client.
+#[get("/{prefix:ide|vsc}/ws-client/{connection_id}")]
+pub async fn ide_client_websocket(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    body: web::Payload,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    client_websocket(
+        web::Path::from(path.into_inner().1),
+        req,
+        body,
+        app_state.ide_client_queues.clone(),
+    )
+    .await
+}
+
+/// Respond to requests for the filesystem. The processing task obtains the
+/// file's contents from the IDE, so that unsaved edits are served.
+#[get("/{prefix:ide|vsc}/fs/{connection_id}/{file_path:.*}")]
+async fn serve_ide_fs(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let (_, connection_id, file_path) = path.into_inner();
+    filesystem_endpoint(
+        web::Path::from((connection_id, file_path)),
+        &req,
+        &app_state,
+    )
+    .await
+}
+
+// ## Tests
+#[cfg(test)]
+mod test {
+    use std::{net::TcpStream, thread, time::Duration};
+
+    use assertables::{
+        assert_contains, assert_contains_as_result, assert_starts_with,
+        assert_starts_with_as_result,
+    };
+    use futures_util::{SinkExt, StreamExt};
+    use lazy_static::lazy_static;
+    use minreq;
+    use tokio::{
+        io::{AsyncRead, AsyncWrite},
+        task,
+    };
+    use tokio_tungstenite::{
+        connect_async, tungstenite::http::StatusCode, tungstenite::protocol::Message,
+        WebSocketStream,
+    };
+
+    use super::super::{
+        path_to_url, run_server, Capabilities, EditorMessage, EditorMessageContents, IdeType,
+        LoadFileResultContents, ProtocolInfo, ResultContents, IP_ADDRESS, IP_PORT,
+    };
+    use crate::{
+        cast, cast2, prep_test_dir,
+        processing::{
+            source_to_codechat_for_web, CodeChatForWeb, CodeChatForWebUpdate, CodeMirror,
+            CodeMirrorPosition, CursorPosition, LineScrollPosition, ScrollPosition, Selection,
+            SourceFileMetadata, SourcePosition, TranslationResults,
+        },
+        test_utils::{check_logger_errors, configure_testing_logger},
+        webserver::UpdateMessageContents,
+    };
+
+    lazy_static! {
+        // Run a single webserver for all tests. Each test runs in its own
+        // runtime, so run the server in a separate thread with its own runtime;
+        // otherwise, the server would stop when the test which started it
+        // finishes.
+        static ref webserver_handle: thread::JoinHandle<std::io::Result<()>> = {
+            let handle = thread::spawn(|| {
+                actix_rt::System::new().block_on(run_server(IP_ADDRESS, IP_PORT, None))
+            });
+            // Wait until the server accepts connections.
+            while TcpStream::connect((IP_ADDRESS, IP_PORT)).is_err() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            handle
+        };
+    }
+
+    // Send a message via a websocket.
+    async fn send_message<S: AsyncRead + AsyncWrite + Unpin>(
+        ws_stream: &mut WebSocketStream<S>,
+        message: &EditorMessage,
+    ) {
+        ws_stream
+            .send(Message::Text(serde_json::to_string(message).unwrap()))
+            .await
+            .unwrap();
+    }
+
+    // Read a message from a websocket.
+    async fn read_message<S: AsyncRead + AsyncWrite + Unpin>(
+        ws_stream: &mut WebSocketStream<S>,
+    ) -> EditorMessage {
+        let msg = ws_stream.next().await.unwrap().unwrap();
+        serde_json::from_str(&msg.into_text().unwrap()).unwrap()
+    }
+
+    // Test incorrect inputs: two connections with the same ID, sending the
+    // wrong first message.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket1() {
+        configure_testing_logger();
+        // Ensure the webserver is running.
+        let _ = &*webserver_handle;
+
+        // Connect to the VSCode IDE websocket.
+        let (mut ws_stream, _) = connect_async(format!(
+            "ws://{IP_ADDRESS}:{IP_PORT}/vsc/ws-ide/test-connection-id1"
+        ))
+        .await
+        .expect("Failed to connect");
+
+        // Start a second connection; verify that it fails.
+        let err = connect_async(format!(
+            "ws://{IP_ADDRESS}:{IP_PORT}/vsc/ws-ide/test-connection-id1"
+        ))
+        .await
+        .expect_err("Should fail to connect");
+        let response = cast!(err, tokio_tungstenite::tungstenite::Error::Http);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Note: we can't check the logs, since the server runs in a separate
+        // thread. Changing the logger to log across threads means we get logs
+        // from other tests (which run in parallel by default). The benefit of
+        // running all tests single-threaded plus fixing the logger is low.
+        //
+        // Send a message that's not an `Opened` message.
+        send_message(
+            &mut ws_stream,
+            &EditorMessage {
+                id: 0,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
+                    contents: None,
+                    base_version: None,
+                    version: 0.0,
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+
+        // Get the response. It should be an error.
+        let em = read_message(&mut ws_stream).await;
+        let result = cast2!(em.message, EditorMessageContents::Result);
+
+        assert_starts_with!(cast!(&result.0, Option::Some), "Unexpected message");
+
+        // Next, expect the websocket to be closed.
+        let err = &ws_stream.next().await.unwrap().unwrap();
+        assert_eq!(*err, Message::Close(None));
+
+        check_logger_errors(0);
+    }
+
+    // Test opening the Client in an external browser.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket2() {
+        configure_testing_logger();
+        // Ensure the webserver is running.
+        let _ = &*webserver_handle;
+
+        // Connect to the VSCode IDE websocket.
+        let (mut ws_stream, _) = connect_async(format!(
+            "ws://{IP_ADDRESS}:{IP_PORT}/vsc/ws-ide/test-connection-id2"
+        ))
+        .await
+        .expect("Failed to connect");
+
+        // Send the `Opened` message.
+        send_message(
+            &mut ws_stream,
+            &EditorMessage {
+                id: 0,
+                message: EditorMessageContents::Opened(
+                    IdeType::VSCode(false),
+                    ProtocolInfo::server(),
+                ),
+            },
+        )
+        .await;
+
+        // Get the response. It should be success, reporting the Server's
+        // protocol.
+        let em = read_message(&mut ws_stream).await;
+        assert_eq!(
+            cast2!(em.message, EditorMessageContents::Result),
+            (None, Some(ResultContents::Opened(ProtocolInfo::server())))
+        );
+
+        // The browser opens the Client framework for this connection.
+        let response = minreq::get(format!(
+            "http://{IP_ADDRESS}:{IP_PORT}/vsc/cf/test-connection-id2"
+        ))
+        .send()
+        .unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_contains!(
+            response.as_str().unwrap(),
+            r#"page_init("vsc/ws-client/test-connection-id2", false)"#
+        );
+
+        check_logger_errors(0);
+    }
+
+    // Test opening the Client in the VSCode browser.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket3() {
+        configure_testing_logger();
+        let (temp_dir, test_dir) = prep_test_dir!();
+        // Ensure the webserver is running.
+        let _ = &*webserver_handle;
+
+        // Connect to the VSCode IDE websocket.
+        let (mut ws_stream_ide, _) = connect_async(format!(
+            "ws://{IP_ADDRESS}:{IP_PORT}/vsc/ws-ide/test-connection-id3"
+        ))
+        .await
+        .expect("Failed to connect");
+
+        // Send the `Opened` message.
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: 0,
+                message: EditorMessageContents::Opened(
+                    IdeType::VSCode(true),
+                    ProtocolInfo::server(),
+                ),
+            },
+        )
+        .await;
+
+        // Get the response. It should be success, reporting the Server's
+        // protocol.
+        let em = read_message(&mut ws_stream_ide).await;
+        assert_eq!(
+            cast2!(em.message, EditorMessageContents::Result),
+            (None, Some(ResultContents::Opened(ProtocolInfo::server())))
+        );
+
+        // Next, wait for the next message -- the HTML.
+        let em = read_message(&mut ws_stream_ide).await;
+        let client_html = cast!(em.message, EditorMessageContents::ClientHtml);
+        assert_contains!(
+            client_html,
+            r#"page_init("vsc/ws-client/test-connection-id3", false)"#
+        );
+        assert_contains!(
+            client_html,
+            &format!(r#"<base href="http://{IP_ADDRESS}:{IP_PORT}/">"#)
+        );
+
+        // Send a success response to this message.
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+
+        // Fetch a non-existent file and verify the response returns an error.
+        // Since `minreq` blocks, run it in another thread while this test
+        // plays the role of the IDE.
+        let mut none_py = test_dir.clone();
+        none_py.push("none.py");
+        let none_py_url = format!(
+            "http://{IP_ADDRESS}:{IP_PORT}{}",
+            path_to_url("/vsc/fs", "test-connection-id3", &none_py)
+        );
+        let http_task =
+            task::spawn_blocking(move || minreq::get(none_py_url).send().unwrap().status_code);
+        // The server asks the IDE for this file; the IDE doesn't have it
+        // loaded, so the server looks for it on disk instead.
+        let em = read_message(&mut ws_stream_ide).await;
+        assert_eq!(cast!(em.message, EditorMessageContents::LoadFile), none_py);
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(Some("not loaded".to_string()), None),
+            },
+        )
+        .await;
+        assert_eq!(http_task.await.unwrap(), 404);
+
+        // Create a websocket to emulate the client.
+        let (mut ws_stream_client, _) = connect_async(format!(
+            "ws://{IP_ADDRESS}:{IP_PORT}/vsc/ws-client/test-connection-id3"
+        ))
+        .await
+        .expect("Failed to connect");
+
+        // Send an `Update` message with a file to edit.
+        let mut test_py = test_dir.clone();
+        test_py.push("test.py");
+        let test_py = test_py.canonicalize().unwrap();
+        let source = "# Test\nprint('Hello, world!')\n";
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: 1,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: Some(test_py.clone()),
+                    contents: Some(CodeChatForWebUpdate::Snapshot(CodeChatForWeb {
+                        metadata: SourceFileMetadata {
+                            mode: "python".to_string(),
+                            backlinks: Vec::new(),
+                        },
+                        source: CodeMirror {
+                            doc: source.to_string(),
+                            doc_blocks: vec![],
+                        },
+                    })),
+                    base_version: None,
+                    version: 1.0,
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+
+        // This should become a `CurrentFile` to load the correct URL, then an
+        // `Update` with the actual file contents.
+        let em = read_message(&mut ws_stream_client).await;
+        assert_eq!(
+            cast!(em.message, EditorMessageContents::CurrentFile),
+            path_to_url("/vsc/fs", "test-connection-id3", &test_py)
+        );
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_client).await;
+        let codechat_for_web = cast!(
+            source_to_codechat_for_web(source, "py", false, false),
+            TranslationResults::CodeChat
+        );
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        // The server's initial version is random, but the Client must receive
+        // it to detect stale updates.
+        let base_version = umc.base_version;
+        assert!(base_version.is_some());
+        assert_eq!(
+            umc,
+            UpdateMessageContents {
+                path: Some(test_py.clone()),
+                contents: Some(CodeChatForWebUpdate::Snapshot(codechat_for_web.clone())),
+                base_version,
+                version: 1.0,
+                cursor_position: None,
+                scroll_position: None,
+            }
+        );
+        let codechat_for_web_ide = codechat_for_web.clone();
+
+        // The Client's response should be routed back to the IDE, using the
+        // IDE's message id.
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_stream_ide).await,
+            EditorMessage {
+                id: 1,
+                message: EditorMessageContents::Result(None, None)
+            }
+        );
+
+        // Send an edit from the Client; the IDE should receive a diff to the
+        // source it sent. The Client sends doc blocks as Markdown, not HTML,
+        // so its first update is a snapshot.
+        let mut client_codechat_for_web = codechat_for_web.clone();
+        client_codechat_for_web.source.doc_blocks[0].4 = "Test\n".to_string();
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: 5,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
+                    contents: Some(CodeChatForWebUpdate::Snapshot(client_codechat_for_web)),
+                    base_version: Some(1.0),
+                    version: 2.0,
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_ide).await;
+        let ide_source = CodeChatForWeb {
+            metadata: SourceFileMetadata {
+                mode: "python".to_string(),
+                backlinks: Vec::new(),
+            },
+            source: CodeMirror {
+                doc: source.to_string(),
+                doc_blocks: vec![],
+            },
+        };
+        assert_eq!(
+            cast!(em.message, EditorMessageContents::Update),
+            UpdateMessageContents {
+                path: Some(test_py.clone()),
+                contents: Some(CodeChatForWebUpdate::new(
+                    Some(&ide_source),
+                    ide_source.clone()
+                )),
+                base_version: Some(1.0),
+                version: 2.0,
+                cursor_position: None,
+                scroll_position: None,
+            }
+        );
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_stream_client).await,
+            EditorMessage {
+                id: 5,
+                message: EditorMessageContents::Result(None, None)
+            }
+        );
+
+        // Fetch the current file; the IDE provides its contents, which
+        // produces a snapshot for the Client at the current version.
+        let test_py_url = format!(
+            "http://{IP_ADDRESS}:{IP_PORT}{}",
+            path_to_url("/vsc/fs", "test-connection-id3", &test_py)
+        );
+        let http_task = task::spawn_blocking(move || minreq::get(test_py_url).send().unwrap());
+        let em = read_message(&mut ws_stream_ide).await;
+        assert_eq!(cast!(em.message, EditorMessageContents::LoadFile), test_py);
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(
+                    None,
+                    Some(ResultContents::LoadFile(LoadFileResultContents {
+                        file_path: test_py.clone(),
+                        contents: source.to_string(),
+                    })),
+                ),
+            },
+        )
+        .await;
+        let response = http_task.await.unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_contains!(response.as_str().unwrap(), "CodeChat-body");
+        let em = read_message(&mut ws_stream_client).await;
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        assert_eq!(
+            umc.contents,
+            Some(CodeChatForWebUpdate::Snapshot(codechat_for_web_ide.clone()))
+        );
+        assert_eq!(umc.version, 2.0);
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+
+        // Send an edit from the IDE as a diff; the Client should receive a
+        // diff.
+        let source2 = format!("{source}# More\n");
+        let ide_source2 = CodeChatForWeb {
+            metadata: SourceFileMetadata {
+                mode: "python".to_string(),
+                backlinks: Vec::new(),
+            },
+            source: CodeMirror {
+                doc: source2.clone(),
+                doc_blocks: vec![],
+            },
+        };
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: 2,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
+                    contents: Some(CodeChatForWebUpdate::new(
+                        Some(&ide_source),
+                        ide_source2.clone(),
+                    )),
+                    base_version: Some(2.0),
+                    version: 3.0,
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+        let codechat_for_web2 = cast!(
+            source_to_codechat_for_web(&source2, "py", false, false),
+            TranslationResults::CodeChat
+        );
+        let em = read_message(&mut ws_stream_client).await;
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        assert_eq!(umc.base_version, Some(2.0));
+        assert_eq!(umc.version, 3.0);
+        assert_eq!(
+            umc.contents,
+            Some(CodeChatForWebUpdate::new(
+                Some(&codechat_for_web_ide),
+                codechat_for_web2.clone()
+            ))
+        );
+        assert!(matches!(umc.contents, Some(CodeChatForWebUpdate::Diff(_))));
+
+        // If the Client can't apply this diff, it receives a snapshot instead.
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(Some("Out of sync".to_string()), None),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_client).await;
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        assert_eq!(umc.version, 3.0);
+        assert_eq!(
+            umc.contents,
+            Some(CodeChatForWebUpdate::Snapshot(codechat_for_web2))
+        );
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_stream_ide).await,
+            EditorMessage {
+                id: 2,
+                message: EditorMessageContents::Result(None, None)
+            }
+        );
+
+        // A diff from the IDE which doesn't apply to the current version
+        // produces an error.
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: 3,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
+                    contents: Some(CodeChatForWebUpdate::new(
+                        Some(&ide_source),
+                        ide_source.clone(),
+                    )),
+                    base_version: Some(2.0),
+                    version: 4.0,
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_ide).await;
+        assert_eq!(em.id, 3);
+        let result = cast2!(em.message, EditorMessageContents::Result);
+        assert_starts_with!(cast!(&result.0, Option::Some), "Out of sync");
+
+        // Send an edit from the Client based on the version before the IDE's
+        // last edit. The server merges both edits, sending the merged
+        // contents to the Client and a diff to the IDE.
+        let mut client_codechat_for_web = codechat_for_web.clone();
+        client_codechat_for_web.source.doc_blocks[0].4 = "Test 2\n".to_string();
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: 7,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
+                    contents: Some(CodeChatForWebUpdate::Snapshot(client_codechat_for_web)),
+                    base_version: Some(2.0),
+                    version: 5.0,
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+        let merged_source = "# Test 2\nprint('Hello, world!')\n# More\n".to_string();
+        let em = read_message(&mut ws_stream_client).await;
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        assert_eq!(umc.base_version, Some(5.0));
+        let merged_version = umc.version;
+        assert_eq!(
+            umc.contents,
+            Some(CodeChatForWebUpdate::Snapshot(cast!(
+                source_to_codechat_for_web(&merged_source, "py", false, false),
+                TranslationResults::CodeChat
+            )))
+        );
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_ide).await;
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        assert_eq!(umc.base_version, Some(3.0));
+        assert_eq!(umc.version, merged_version);
+        assert_eq!(
+            umc.contents
+                .unwrap()
+                .into_contents(Some(&ide_source2))
+                .unwrap()
+                .source
+                .doc,
+            merged_source
+        );
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_stream_client).await,
+            EditorMessage {
+                id: 7,
+                message: EditorMessageContents::Result(None, None)
+            }
+        );
+
+        // An edit from the IDE based on the same version which changes the
+        // same lines conflicts.
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: 4,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
+                    contents: Some(CodeChatForWebUpdate::Snapshot(CodeChatForWeb {
+                        metadata: SourceFileMetadata {
+                            mode: "python".to_string(),
+                            backlinks: Vec::new(),
+                        },
+                        source: CodeMirror {
+                            doc: "# Test 3\nprint('Hello, world!')\n# More\n".to_string(),
+                            doc_blocks: vec![],
+                        },
+                    })),
+                    base_version: Some(3.0),
+                    version: 6.0,
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_ide).await;
+        assert_eq!(em.id, 4);
+        let result = cast2!(em.message, EditorMessageContents::Result);
+        assert_starts_with!(cast!(&result.0, Option::Some), "Conflict:");
+
+        // A selection from the IDE is translated to the Client's coordinates.
+        let source_selection = CursorPosition::Source(Selection {
+            anchor: SourcePosition { line: 0, column: 4 },
+            head: SourcePosition { line: 1, column: 6 },
+        });
+        let code_mirror_selection = CursorPosition::CodeMirror(Selection {
+            anchor: CodeMirrorPosition::DocBlock {
+                index: 0,
+                line: 0,
+                column: 2,
+            },
+            head: CodeMirrorPosition::Code(7),
+        });
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: 5,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
+                    contents: None,
+                    base_version: Some(merged_version),
+                    version: merged_version,
+                    cursor_position: Some(source_selection),
+                    scroll_position: Some(ScrollPosition::Source(LineScrollPosition {
+                        line: 0,
+                        offset: 0.5,
+                    })),
+                }),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_client).await;
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        assert_eq!(umc.cursor_position, Some(code_mirror_selection));
+        // The first line is a one-line doc block.
+        assert_eq!(
+            umc.scroll_position,
+            Some(ScrollPosition::CodeMirror(LineScrollPosition {
+                line: 0,
+                offset: 0.5
+            }))
+        );
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        assert_eq!(read_message(&mut ws_stream_ide).await.id, 5);
+
+        // Likewise, a selection from the Client is translated to the IDE's
+        // coordinates.
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: 8,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
+                    contents: None,
+                    base_version: Some(merged_version),
+                    version: merged_version,
+                    cursor_position: Some(code_mirror_selection),
+                    scroll_position: Some(ScrollPosition::CodeMirror(LineScrollPosition {
+                        line: 1,
+                        offset: 0.25,
+                    })),
+                }),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_ide).await;
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        assert_eq!(umc.cursor_position, Some(source_selection));
+        assert_eq!(
+            umc.scroll_position,
+            Some(ScrollPosition::Source(LineScrollPosition {
+                line: 1,
+                offset: 0.25
+            }))
+        );
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        assert_eq!(read_message(&mut ws_stream_client).await.id, 8);
+
+        // Follow a link in the Client; the IDE should receive a path.
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: 6,
+                message: EditorMessageContents::CurrentFile(format!(
+                    "http://{IP_ADDRESS}:{IP_PORT}{}",
+                    path_to_url("/vsc/fs", "test-connection-id3", &test_py)
+                )),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_ide).await;
+        assert_eq!(
+            cast!(em.message, EditorMessageContents::CurrentFile),
+            test_py.to_string_lossy()
+        );
+
+        // Don't reply; the resulting timeout should be reported to the
+        // Client.
+        let em = read_message(&mut ws_stream_client).await;
+        assert_eq!(em.id, 6);
+        let result = cast2!(em.message, EditorMessageContents::Result);
+        assert_starts_with!(cast!(&result.0, Option::Some), "Timeout");
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Test an IDE which speaks a different version of the protocol.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket4() {
+        configure_testing_logger();
+        // Ensure the webserver is running.
+        let _ = &*webserver_handle;
+
+        // Connect to the VSCode IDE websocket.
+        let (mut ws_stream, _) = connect_async(format!(
+            "ws://{IP_ADDRESS}:{IP_PORT}/vsc/ws-ide/test-connection-id4"
+        ))
+        .await
+        .expect("Failed to connect");
+
+        // Send an `Opened` message with an unsupported protocol version.
+        let mut protocol_info = ProtocolInfo::server();
+        protocol_info.protocol_version += 1;
+        send_message(
+            &mut ws_stream,
+            &EditorMessage {
+                id: 0,
+                message: EditorMessageContents::Opened(IdeType::VSCode(true), protocol_info),
+            },
+        )
+        .await;
+
+        // The response should be an error which reports the Server's
+        // protocol.
+        let em = read_message(&mut ws_stream).await;
+        let (err, result_contents) = cast2!(em.message, EditorMessageContents::Result);
+        assert_starts_with!(cast!(&err, Option::Some), "Unsupported protocol version");
+        assert_eq!(
+            result_contents,
+            Some(ResultContents::Opened(ProtocolInfo::server()))
+        );
+
+        // Next, expect the websocket to be closed.
+        let err = &ws_stream.next().await.unwrap().unwrap();
+        assert_eq!(*err, Message::Close(None));
+
+        check_logger_errors(0);
+    }
+
+    // Test an IDE other than VSCode which supports none of the optional parts
+    // of the protocol, using the IDE-neutral routes.
+    #[actix_web::test]
+    async fn test_ide_websocket_other_ide() {
+        configure_testing_logger();
+        let (temp_dir, test_dir) = prep_test_dir!();
+        // Ensure the webserver is running.
+        let _ = &*webserver_handle;
+
+        // Connect to the IDE websocket, then open the Client.
+        let (mut ws_stream_ide, _) = connect_async(format!(
+            "ws://{IP_ADDRESS}:{IP_PORT}/ide/ws-ide/test-connection-id5"
+        ))
+        .await
+        .expect("Failed to connect");
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: 0,
+                message: EditorMessageContents::Opened(
+                    IdeType::Other {
+                        name: "Neovim".to_string(),
+                        is_self_hosted: true,
+                    },
+                    ProtocolInfo {
+                        protocol_version: ProtocolInfo::server().protocol_version,
+                        capabilities: Capabilities {
+                            diffs: false,
+                            cursor_position: false,
+                            scroll_position: false,
+                            load_file: false,
+                        },
+                    },
+                ),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_ide).await;
+        assert_eq!(
+            cast2!(em.message, EditorMessageContents::Result),
+            (None, Some(ResultContents::Opened(ProtocolInfo::server())))
+        );
+        let em = read_message(&mut ws_stream_ide).await;
+        let client_html = cast!(em.message, EditorMessageContents::ClientHtml);
+        assert_contains!(
+            client_html,
+            r#"page_init("ide/ws-client/test-connection-id5", false)"#
+        );
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        let (mut ws_stream_client, _) = connect_async(format!(
+            "ws://{IP_ADDRESS}:{IP_PORT}/ide/ws-client/test-connection-id5"
+        ))
+        .await
+        .expect("Failed to connect");
+
+        // Select a file to edit.
+        let mut test_py = test_dir.clone();
+        test_py.push("test.py");
+        let test_py = test_py.canonicalize().unwrap();
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: 1,
+                message: EditorMessageContents::CurrentFile(test_py.to_string_lossy().to_string()),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_client).await;
+        assert_eq!(
+            cast!(em.message, EditorMessageContents::CurrentFile),
+            path_to_url("/ide/fs", "test-connection-id5", &test_py)
+        );
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        assert_eq!(read_message(&mut ws_stream_ide).await.id, 1);
+
+        // Fetch this file. Since the IDE can't provide it, the Server reads
+        // it from disk without sending a `LoadFile`.
+        let test_py_url = format!(
+            "http://{IP_ADDRESS}:{IP_PORT}{}",
+            path_to_url("/ide/fs", "test-connection-id5", &test_py)
+        );
+        let response = task::spawn_blocking(move || minreq::get(test_py_url).send().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status_code, 200);
+        let em = read_message(&mut ws_stream_client).await;
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        let mut codechat_for_web = cast!(cast!(umc.contents, Some), CodeChatForWebUpdate::Snapshot);
+        let mut version = umc.version;
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+
+        // Send two edits from the Client. The IDE receives each as a
+        // snapshot, without the cursor or scroll position.
+        for (client_id, contents) in [(2, "Test\n"), (3, "Tested\n")] {
+            codechat_for_web.source.doc_blocks[0].4 = contents.to_string();
+            send_message(
+                &mut ws_stream_client,
+                &EditorMessage {
+                    id: client_id,
+                    message: EditorMessageContents::Update(UpdateMessageContents {
+                        path: None,
+                        contents: Some(CodeChatForWebUpdate::Snapshot(codechat_for_web.clone())),
+                        base_version: Some(version),
+                        version: version + 1.0,
+                        cursor_position: Some(CursorPosition::CodeMirror(Selection {
+                            anchor: CodeMirrorPosition::Code(0),
+                            head: CodeMirrorPosition::Code(0),
+                        })),
+                        scroll_position: Some(ScrollPosition::CodeMirror(LineScrollPosition {
+                            line: 0,
+                            offset: 0.0,
+                        })),
+                    }),
+                },
+            )
+            .await;
+            version += 1.0;
+            let em = read_message(&mut ws_stream_ide).await;
+            let umc = cast!(em.message, EditorMessageContents::Update);
+            assert!(matches!(
+                umc.contents,
+                Some(CodeChatForWebUpdate::Snapshot(_))
+            ));
+            assert_eq!(umc.cursor_position, None);
+            assert_eq!(umc.scroll_position, None);
+            send_message(
+                &mut ws_stream_ide,
+                &EditorMessage {
+                    id: em.id,
+                    message: EditorMessageContents::Result(None, None),
+                },
+            )
+            .await;
+            assert_eq!(read_message(&mut ws_stream_client).await.id, client_id);
+        }
+
+        // Likewise, the Client doesn't receive the IDE's cursor or scroll
+        // position.
+        send_message(
+            &mut ws_stream_ide,
+            &EditorMessage {
+                id: 2,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    path: None,
+                    contents: None,
+                    base_version: Some(version),
+                    version,
+                    cursor_position: Some(CursorPosition::Source(Selection {
+                        anchor: SourcePosition { line: 0, column: 0 },
+                        head: SourcePosition { line: 0, column: 2 },
+                    })),
+                    scroll_position: Some(ScrollPosition::Source(LineScrollPosition {
+                        line: 0,
+                        offset: 0.5,
+                    })),
+                }),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_stream_client).await;
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        assert_eq!(umc.cursor_position, None);
+        assert_eq!(umc.scroll_position, None);
+        send_message(
+            &mut ws_stream_client,
+            &EditorMessage {
+                id: em.id,
+                message: EditorMessageContents::Result(None, None),
+            },
+        )
+        .await;
+        assert_eq!(read_message(&mut ws_stream_ide).await.id, 2);
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+/// Copyright (C) 2023 Bryan A. Jones.
+///
+/// This file is part of the CodeChat Editor. The CodeChat Editor is free
+/// software: you can redistribute it and/or modify it under the terms of the
+/// GNU General Public License as published by the Free Software Foundation,
+/// either version 3 of the License, or (at your option) any later version.
+///
+/// The CodeChat Editor is distributed in the hope that it will be useful, but
+/// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+/// or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+/// more details.
+///
+/// You should have received a copy of the GNU General Public License along with
+/// the CodeChat Editor. If not, see
+/// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+///
+/// # `schema.rs` -- Describe the IDE protocol using JSON Schema
+///
+/// IDE extensions exchange `EditorMessage`s with the Server as JSON. This
+/// module produces a [JSON Schema](https://json-schema.org/) for these
+/// messages, so that extensions written in other languages have a contract to
+/// develop and validate against. The schema is derived (using
+/// [schemars](https://docs.rs/schemars)) from the same types and `serde`
+/// attributes which produce this JSON; therefore, it changes along with them.
+// ## Imports
+//
+// ### Third-party
+use schemars::{generate::SchemaSettings, transform::RecursiveTransform, Schema};
+use serde_json::Value;
+
+// ### Local
+use super::{EditorMessage, PROTOCOL_VERSION};
+
+// ## Code
+/// Return the JSON Schema for an `EditorMessage`.
+pub fn protocol_schema() -> Value {
+    let mut schema = SchemaSettings::draft2020_12()
+        .with_transform(RecursiveTransform(deny_unknown_properties))
+        .into_generator()
+        .into_root_schema_for::<EditorMessage>();
+    schema.insert("title".to_string(), "CodeChat Editor protocol".into());
+    schema.insert(
+        "description".to_string(),
+        format!("A message exchanged between an IDE, the CodeChat Editor Server, and the CodeChat Editor Client, using version {PROTOCOL_VERSION} of the protocol.").into(),
+    );
+    schema.to_value()
+}
+
+/// A struct serializes only its own fields; therefore, reject any other
+/// property of an object.
+fn deny_unknown_properties(schema: &mut Schema) {
+    if schema.get("properties").is_some() {
+        schema.insert("additionalProperties".to_string(), false.into());
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs, path::PathBuf};
+
+    use jsonschema::Validator;
+    use serde_json::{json, Value};
+
+    use super::{
+        super::{
+            Capabilities, ClientPresence, EditorMessage, EditorMessageContents, IdeType,
+            LoadFileResultContents, ProtocolInfo, ResultContents, UpdateMessageContents,
+        },
+        protocol_schema,
+    };
+    use crate::processing::{
+        Backlink, CodeChatForWeb, CodeChatForWebDiff, CodeChatForWebUpdate, CodeMirror,
+        CodeMirrorPosition, CursorPosition, DocBlockDiff, LineScrollPosition, ScrollPosition,
+        Selection, SourceFileMetadata, SourcePosition, StringDiff,
+    };
+
+    fn message(message: EditorMessageContents) -> Value {
+        serde_json::to_value(EditorMessage { id: 1, message }).unwrap()
+    }
+
+    // Return the name of the variant of `EditorMessageContents` in a
+    // serialized `EditorMessage`.
+    fn variant_name(value: &Value) -> &str {
+        match &value["message"] {
+            Value::String(name) => name,
+            Value::Object(variant) => variant.keys().next().unwrap(),
+            _ => panic!("Unexpected message {value}."),
+        }
+    }
+
+    // Return the names of the variants of `EditorMessageContents` in the
+    // schema: unit variants are strings; the others are objects with one
+    // required property.
+    fn schema_variant_names(schema: &Value) -> HashSet<&str> {
+        schema["$defs"]["EditorMessageContents"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|variant| {
+                variant
+                    .get("enum")
+                    .or(variant.get("required"))
+                    .into_iter()
+                    .chain(variant.get("const"))
+            })
+            .flat_map(|names| match names {
+                Value::Array(names) => names.iter().collect(),
+                name => vec![name],
+            })
+            .map(|name| name.as_str().unwrap())
+            .collect()
+    }
+
+    // Verify that every message validates against the schema.
+    #[test]
+    fn test_protocol_schema_1() {
+        let schema = protocol_schema();
+        let metadata = SourceFileMetadata {
+            mode: "python".to_string(),
+            backlinks: vec![Backlink {
+                anchor: None,
+                file: "a.md".to_string(),
+                source_anchor: Some("intro".to_string()),
+                pre_snippet: "See ".to_string(),
+                post_snippet: "here".to_string(),
+            }],
+        };
+        let doc_block = (
+            0,
+            1,
+            "".to_string(),
+            "#".to_string(),
+            "Test".to_string(),
+            "".to_string(),
+        );
+        let update = |contents, cursor_position, scroll_position| UpdateMessageContents {
+            path: Some(PathBuf::from("test.py")),
+            contents: Some(contents),
+            base_version: Some(0.5),
+            version: 0.25,
+            cursor_position: Some(cursor_position),
+            scroll_position: Some(scroll_position),
+        };
+        let messages = [
+            EditorMessageContents::Update(update(
+                CodeChatForWebUpdate::Snapshot(CodeChatForWeb {
+                    metadata: metadata.clone(),
+                    source: CodeMirror {
+                        doc: "\n".to_string(),
+                        doc_blocks: vec![doc_block.clone()],
+                    },
+                }),
+                CursorPosition::Source(Selection {
+                    anchor: SourcePosition { line: 0, column: 1 },
+                    head: SourcePosition { line: 2, column: 3 },
+                }),
+                ScrollPosition::Source(LineScrollPosition {
+                    line: 1,
+                    offset: 0.5,
+                }),
+            )),
+            EditorMessageContents::Update(update(
+                CodeChatForWebUpdate::Diff(CodeChatForWebDiff {
+                    metadata: SourceFileMetadata {
+                        mode: "python".to_string(),
+                        backlinks: vec![],
+                    },
+                    doc: vec![StringDiff {
+                        from: 0,
+                        to: 1,
+                        insert: "x".to_string(),
+                    }],
+                    doc_blocks: vec![
+                        DocBlockDiff::Insert(0, doc_block.clone()),
+                        DocBlockDiff::Delete(1),
+                        DocBlockDiff::Replace(0, doc_block),
+                    ],
+                }),
+                CursorPosition::CodeMirror(Selection {
+                    anchor: CodeMirrorPosition::Code(0),
+                    head: CodeMirrorPosition::DocBlock {
+                        index: 0,
+                        line: 1,
+                        column: 2,
+                    },
+                }),
+                ScrollPosition::CodeMirror(LineScrollPosition {
+                    line: 0,
+                    offset: 0.0,
+                }),
+            )),
+            EditorMessageContents::Update(UpdateMessageContents {
+                path: None,
+                contents: None,
+                base_version: None,
+                version: 0.0,
+                cursor_position: None,
+                scroll_position: None,
+            }),
+            EditorMessageContents::CurrentFile("test.py".to_string()),
+            EditorMessageContents::Opened(
+                IdeType::VSCode(true),
+                ProtocolInfo {
+                    protocol_version: 1,
+                    capabilities: Capabilities {
+                        diffs: false,
+                        cursor_position: true,
+                        scroll_position: false,
+                        load_file: true,
+                    },
+                },
+            ),
+            EditorMessageContents::Opened(
+                IdeType::Other {
+                    name: "Neovim".to_string(),
+                    is_self_hosted: false,
+                },
+                ProtocolInfo::server(),
+            ),
+            EditorMessageContents::RequestClose,
+            EditorMessageContents::LoadFile(PathBuf::from("test.py")),
+            EditorMessageContents::ClientHtml("<p>Test</p>".to_string()),
+            EditorMessageContents::Refresh(vec!["/fw/fsc/1/fig.png".to_string()]),
+            EditorMessageContents::Presence(vec![ClientPresence {
+                connection_id: "1".to_string(),
+                name: "Client 1".to_string(),
+            }]),
+            EditorMessageContents::Closed,
+            EditorMessageContents::Result(None, None),
+            EditorMessageContents::Result(
+                Some("Unsupported".to_string()),
+                Some(ResultContents::Opened(ProtocolInfo::server())),
+            ),
+            EditorMessageContents::Result(
+                None,
+                Some(ResultContents::LoadFile(LoadFileResultContents {
+                    file_path: PathBuf::from("test.py"),
+                    contents: "".to_string(),
+                })),
+            ),
+        ];
+        let validator = Validator::new(&schema).unwrap();
+        let values: Vec<Value> = messages.into_iter().map(message).collect();
+        for value in &values {
+            assert!(validator.is_valid(value), "{value}");
+        }
+        // Verify that the messages include every variant of
+        // `EditorMessageContents`.
+        assert_eq!(
+            values.iter().map(variant_name).collect::<HashSet<_>>(),
+            schema_variant_names(&schema)
+        );
+
+        // Verify that invalid messages don't validate.
+        for value in [
+            json!({ "id": 1, "message": { "Opened": { "VSCode": true } } }),
+            json!({ "id": 1, "message": { "Opened": [{ "Other": { "name": "Neovim" } }, null] } }),
+            json!({ "id": 1, "message": "Update" }),
+            json!({ "id": -1, "message": "Closed" }),
+            json!({ "message": "Closed" }),
+            json!({ "id": 1, "message": { "Closed": null, "RequestClose": null } }),
+            json!({ "id": 1, "message": { "Result": [null] } }),
+            json!({ "id": 1, "message": { "Update": { "path": null } } }),
+            json!({ "id": 1, "message": "Closed", "connection_id": "1" }),
+            json!({ "id": 1, "message": { "Presence": [{ "connection_id": "1", "name": "Client 1", "color": "red" }] } }),
+        ] {
+            assert!(!validator.is_valid(&value), "{value}");
+        }
+    }
+
+    // Verify that the published schema is up to date.
+    #[test]
+    fn test_protocol_schema_2() {
+        let published = fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../docs/protocol.schema.json"
+        ))
+        .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&published).unwrap(),
+            protocol_schema(),
+            "Regenerate docs/protocol.schema.json using `codechat-editor-server schema`."
+        );
+    }
+}
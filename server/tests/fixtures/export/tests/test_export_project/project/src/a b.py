@@ -0,0 +1,5 @@
+# See the [notes](../notes.md) and [data](data.txt).
+# Also [external](https://example.com/x.py).
+if a < b:
+    # Indented.
+    a = b
@@ -0,0 +1,5 @@
+# # Setup
+#
+# See [b](b.md#top), [missing](missing.py), [the TOC](../toc.md), and
+# [nowhere](../toc.md#nowhere).
+x = 1
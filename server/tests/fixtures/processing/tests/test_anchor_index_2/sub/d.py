@@ -0,0 +1,3 @@
+# <a id="fn"></a>A function.
+def fn():
+    pass
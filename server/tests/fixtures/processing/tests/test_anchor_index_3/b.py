@@ -0,0 +1,2 @@
+# ## Beta
+x = 1
@@ -0,0 +1,5 @@
+a = 1
+x = 0
+y = 0
+z = 0
+b = 2
@@ -0,0 +1,4 @@
+# Code with a figure:
+#
+# ![A figure](fig.png)
+print("test")
@@ -0,0 +1,2 @@
+# Test
+print('Hello, world!')